@@ -22,7 +22,7 @@ pub fn embedded_queries(args: proc_macro::TokenStream, original: proc_macro::Tok
     gen.into()
 }
 
-#[proc_macro_derive(PersistentEmbedded, attributes(index))]
+#[proc_macro_derive(PersistentEmbedded, attributes(index, persistent))]
 pub fn persistent_embedded(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let parsed: DeriveInput = syn::parse(input).unwrap();
 
@@ -30,7 +30,7 @@ pub fn persistent_embedded(input: proc_macro::TokenStream) -> proc_macro::TokenS
     gen.into()
 }
 
-#[proc_macro_derive(Persistent, attributes(index))]
+#[proc_macro_derive(Persistent, attributes(index, persistent))]
 pub fn persistent(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let parsed: DeriveInput = syn::parse(input).unwrap();
 
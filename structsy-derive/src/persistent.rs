@@ -14,10 +14,40 @@ pub struct ProjectionInfo {
 }
 
 #[derive(FromDeriveInput, Debug)]
-#[darling(attributes(index))]
+#[darling(attributes(persistent))]
 pub struct PersistentInfo {
     ident: Ident,
+    generics: syn::Generics,
     data: Data<PersistentEnum, PersistentAttr>,
+    #[darling(default)]
+    name: Option<String>,
+    #[darling(default, multiple, rename = "index")]
+    computed_indexes: Vec<ComputedIndexAttr>,
+}
+
+/// An index on a value computed from the whole struct, declared with
+/// `#[persistent(index(name = "...", expr = "...", ty = "..."))]`
+#[derive(FromMeta, Debug)]
+struct ComputedIndexAttr {
+    name: String,
+    expr: String,
+    ty: String,
+    #[darling(default)]
+    mode: Option<IndexMode>,
+}
+
+/// An approximate nearest neighbour index on a vector field, declared with
+/// `#[index(vector(dim = ..., metric = "..."))]`
+#[derive(FromMeta, Debug, Clone)]
+struct VectorAttr {
+    dim: u32,
+    #[darling(default)]
+    metric: Option<String>,
+}
+impl VectorAttr {
+    fn metric(&self) -> String {
+        self.metric.clone().unwrap_or_else(|| "cosine".to_string())
+    }
 }
 
 #[derive(FromMeta, Debug, Clone, PartialEq)]
@@ -35,7 +65,89 @@ impl Default for IndexMode {
 #[derive(FromVariant, Debug)]
 struct PersistentEnum {
     ident: Ident,
-    fields: Fields<syn::Type>,
+    fields: Fields<VariantField>,
+}
+
+#[derive(FromField, Debug)]
+struct VariantField {
+    ident: Option<Ident>,
+    ty: syn::Type,
+}
+
+enum VariantKind {
+    Unit,
+    Value(Box<Type>),
+    Tuple(Vec<Type>),
+    Struct(Vec<(Ident, Type)>),
+}
+
+struct VariantInfo {
+    ident: Ident,
+    index: u32,
+    kind: VariantKind,
+}
+
+impl VariantInfo {
+    /// Name of the variant fields as stored in the description, tuple fields are named by position
+    fn fields(&self) -> Vec<(String, TokenStream, Type)> {
+        match &self.kind {
+            VariantKind::Tuple(types) => types
+                .iter()
+                .enumerate()
+                .map(|(pos, ty)| {
+                    let binding = Ident::new(&format!("v{}", pos), Span::call_site());
+                    (pos.to_string(), quote! {#binding}, ty.clone())
+                })
+                .collect(),
+            VariantKind::Struct(fields) => fields
+                .iter()
+                .map(|(ident, ty)| (ident.to_string(), quote! {#ident}, ty.clone()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn variant_infos(variants: &[PersistentEnum]) -> Vec<VariantInfo> {
+    variants
+        .iter()
+        .enumerate()
+        .map(|(pos, vt)| {
+            let fields = &vt.fields.fields;
+            let kind = match vt.fields.style {
+                Style::Unit => VariantKind::Unit,
+                Style::Tuple if fields.is_empty() => VariantKind::Unit,
+                Style::Tuple if fields.len() == 1 => VariantKind::Value(Box::new(fields[0].ty.clone())),
+                Style::Tuple => VariantKind::Tuple(fields.iter().map(|f| f.ty.clone()).collect()),
+                Style::Struct => VariantKind::Struct(
+                    fields
+                        .iter()
+                        .map(|f| (f.ident.clone().expect("named field"), f.ty.clone()))
+                        .collect(),
+                ),
+            };
+            VariantInfo {
+                ident: vt.ident.clone(),
+                index: pos as u32,
+                kind,
+            }
+        })
+        .collect()
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i != 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
 }
 
 #[derive(FromField, Debug)]
@@ -45,26 +157,49 @@ struct PersistentAttr {
     ty: syn::Type,
     #[darling(default)]
     mode: Option<IndexMode>,
+    #[darling(default)]
+    when: Option<String>,
+    #[darling(default)]
+    fulltext: bool,
+    #[darling(default)]
+    stem: Option<String>,
+    #[darling(default)]
+    vector: Option<VectorAttr>,
+    #[darling(default)]
+    geo: bool,
 }
 
 #[derive(FromField, Debug)]
+#[darling(attributes(projection))]
 struct ProjectionAttr {
     ident: Option<Ident>,
     ty: syn::Type,
+    /// The field holds the id of the projected record, declared with `#[projection(id)]`
+    #[darling(default)]
+    id: bool,
 }
 
 #[derive(Clone, Debug)]
 struct FieldInfo {
     name: Ident,
+    full_ty: syn::Type,
     ty: Ident,
     template_ty: Option<Ident>,
     sub_template_ty: Option<Ident>,
     index_mode: Option<IndexMode>,
+    /// The boolean field and the value it must have for the record to be indexed
+    index_when: Option<(Ident, bool)>,
+    fulltext: bool,
+    /// The stemming language of the full-text index
+    stem: Option<String>,
+    vector: Option<VectorAttr>,
+    geo: bool,
 }
 impl ProjectionInfo {
     fn field_infos(&self, fields: &Fields<ProjectionAttr>) -> Vec<FieldInfo> {
         fields
             .iter()
+            .filter(|f| !f.id)
             .filter_map(|f| {
                 let field = f.ident.clone().unwrap();
                 let st = sub_type(&f.ty);
@@ -72,15 +207,29 @@ impl ProjectionInfo {
                 let subsub = st.iter().filter_map(|x| sub_type(x)).find_map(get_type_ident);
                 get_type_ident(&f.ty).map(|ty| FieldInfo {
                     name: field,
+                    full_ty: f.ty.clone(),
                     ty,
                     template_ty: sub,
                     sub_template_ty: subsub,
                     index_mode: None,
+                    index_when: None,
+                    fulltext: false,
+                    stem: None,
+                    vector: None,
+                    geo: false,
                 })
             })
             .collect()
     }
 
+    fn id_fields(&self, fields: &Fields<ProjectionAttr>) -> Vec<Ident> {
+        fields
+            .iter()
+            .filter(|f| f.id)
+            .map(|f| f.ident.clone().unwrap())
+            .collect()
+    }
+
     pub fn to_tokens(&self) -> TokenStream {
         let name = &self.ident;
         let mut target = None;
@@ -103,17 +252,40 @@ impl ProjectionInfo {
         match &self.data {
             Data::Struct(data) => {
                 let fields = self.field_infos(data);
-                let ser = projection_tokens(&fields);
+                let ids = self.id_fields(data);
                 let target_ident = Ident::new(&target.expect("missing projection attributed"), Span::call_site());
+                let projection = if ids.is_empty() {
+                    projection_tokens(&fields, &ids)
+                } else {
+                    let message = format!(
+                        "the projection {} holds the id of the record, it can be only queried",
+                        name
+                    );
+                    quote! {
+                        panic!(#message)
+                    }
+                };
+                let with_id = if ids.is_empty() {
+                    quote! {}
+                } else {
+                    let ser = projection_tokens(&fields, &ids);
+                    quote! {
+                        fn projection_with_id(id: &structsy::Ref<#target_ident>, source: &#target_ident) -> Self {
+                            #ser
+                        }
+                    }
+                };
+                let index = index_projection_tokens(&target_ident, &fields, &ids);
                 quote! {
 
                 impl structsy::internal::Projection<#target_ident> for #name {
 
                     fn projection(source:& #target_ident) -> Self {
-                        use structsy::internal::Projection;
-                        #ser
+                        #projection
                     }
 
+                    #with_id
+                    #index
                 }
 
                 }
@@ -134,29 +306,77 @@ impl PersistentInfo {
                 let st = sub_type(&f.ty);
                 let sub = st.iter().find_map(|x| get_type_ident(*x));
                 let subsub = st.iter().filter_map(|x| sub_type(x)).find_map(get_type_ident);
+                let index_when = f.when.as_ref().map(|when| parse_index_when(&field, when));
                 get_type_ident(&f.ty).map(|ty| FieldInfo {
                     name: field,
+                    full_ty: f.ty.clone(),
                     ty,
                     template_ty: sub,
                     sub_template_ty: subsub,
                     index_mode: f.mode.clone(),
+                    index_when,
+                    fulltext: f.fulltext,
+                    stem: f.stem.clone(),
+                    vector: f.vector.clone(),
+                    geo: f.geo,
                 })
             })
             .collect()
     }
 
+    fn persistent_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.ident.to_string())
+    }
+
+    /// The name expression, for generic types includes the names of the type parameters
+    fn name_tokens(&self) -> TokenStream {
+        let string_name = self.persistent_name();
+        let params = self.type_params();
+        if params.is_empty() {
+            quote! { #string_name }
+        } else {
+            quote! {
+                structsy::internal::generic_name(#string_name, &[
+                    #( <#params as structsy::internal::SupportedType>::resolve() ),*
+                ])
+            }
+        }
+    }
+
+    fn type_params(&self) -> Vec<Ident> {
+        self.generics.type_params().map(|p| p.ident.clone()).collect()
+    }
+
+    /// The generics of the type with the bounds needed to persist the type parameters
+    fn bounded_generics(&self) -> syn::Generics {
+        let mut generics = self.generics.clone();
+        for param in generics.type_params_mut() {
+            param
+                .bounds
+                .push(syn::parse_quote!(structsy::internal::PersistentEmbedded));
+            param.bounds.push(syn::parse_quote!(structsy::internal::SupportedType));
+        }
+        generics
+    }
+
     pub fn to_tokens(&self) -> TokenStream {
         let name = &self.ident;
-        let string_name = name.to_string();
+        let string_name = self.name_tokens();
+        let generics = self.bounded_generics();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
         match &self.data {
             Data::Struct(data) => {
                 let fields = self.field_infos(data);
-                let (desc, ser) = serialization_tokens(name, &fields);
-                let indexes = indexes_tokens(name, &fields);
+                let (desc, ser) = serialization_tokens(name, &string_name, &fields, &self.computed_indexes);
+                let indexes = indexes_tokens(&string_name, &fields, &self.type_params(), &self.computed_indexes);
+                let computed = computed_value_tokens(&self.computed_indexes);
+                let fulltext = fulltext_tokens(&fields);
+                let vector = vector_tokens(&fields);
+                let geo = geo_tokens(&fields);
                 let filters = filter_tokens(&fields);
                 quote! {
 
-                impl structsy::internal::Persistent for #name {
+                impl #impl_generics structsy::internal::Persistent for #name #ty_generics #where_clause {
 
                     fn get_name() -> &'static str {
                         #string_name
@@ -166,18 +386,30 @@ impl PersistentInfo {
                     #ser
 
                     #indexes
+                    #computed
+                    #fulltext
+                    #vector
+                    #geo
                 }
 
-                impl #name {
+                impl #impl_generics #name #ty_generics #where_clause {
                     #filters
                 }
                 }
             }
             Data::Enum(variants) => {
-                let (desc, ser) = enum_serialization_tokens(name, variants);
+                if !self.computed_indexes.is_empty() {
+                    panic!("computed indexes not supported for enums");
+                }
+                let (desc, ser) = enum_serialization_tokens(name, &string_name, variants);
+                let filters = enum_filter_tokens(name, variants);
 
                 quote! {
-                impl structsy::internal::Persistent for #name {
+                impl #impl_generics #name #ty_generics #where_clause {
+                    #filters
+                }
+
+                impl #impl_generics structsy::internal::Persistent for #name #ty_generics #where_clause {
 
                     #desc
                     #ser
@@ -204,117 +436,174 @@ impl PersistentInfo {
     }
 
     pub fn to_embedded_tokens(&self) -> TokenStream {
+        if !self.computed_indexes.is_empty() {
+            panic!("computed indexes not supported for Persistent Embedded types");
+        }
         let name = &self.ident;
+        let string_name = self.name_tokens();
+        let generics = self.bounded_generics();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
         match &self.data {
             Data::Struct(data) => {
                 let fields = self.field_infos(data);
-                let (desc, ser) = serialization_tokens(name, &fields);
+                let (desc, ser) = serialization_tokens(name, &string_name, &fields, &[]);
                 let filters = filter_tokens(&fields);
-
-                for f in fields {
-                    if f.index_mode.is_some() {
-                        panic!("indexing not supported for Persistent Embedded structs");
-                    }
-                }
+                let indexes = nested_indexes_tokens(&fields, &self.type_params());
 
                 quote! {
-                    impl structsy::internal::EmbeddedDescription for #name {
+                    impl #impl_generics structsy::internal::EmbeddedDescription for #name #ty_generics #where_clause {
                         #desc
                     }
-                    impl structsy::internal::PersistentEmbedded for #name {
+                    impl #impl_generics structsy::internal::PersistentEmbedded for #name #ty_generics #where_clause {
                         #ser
+                        #indexes
                     }
 
-                    impl #name {
+                    impl #impl_generics #name #ty_generics #where_clause {
                         #filters
                     }
                 }
             }
             Data::Enum(variants) => {
-                let (desc, ser) = enum_serialization_tokens(name, variants);
+                let (desc, ser) = enum_serialization_tokens(name, &string_name, variants);
+                let filters = enum_filter_tokens(name, variants);
+                let positions = variant_infos(variants).into_iter().map(|vt| {
+                    let ident = vt.ident;
+                    let index = vt.index;
+                    match vt.kind {
+                        VariantKind::Unit => quote! { #name::#ident => #index, },
+                        VariantKind::Tuple(_) | VariantKind::Value(_) => quote! { #name::#ident(..) => #index, },
+                        VariantKind::Struct(_) => quote! { #name::#ident{..} => #index, },
+                    }
+                });
 
                 quote! {
-                    impl structsy::internal::EmbeddedDescription for #name {
+                    impl #impl_generics structsy::internal::EmbeddedDescription for #name #ty_generics #where_clause {
                         #desc
+
+                        fn variant_position(&self) -> Option<u32> {
+                            Some(match self {
+                                #( #positions )*
+                            })
+                        }
+                    }
+                    impl #impl_generics structsy::internal::IndexableValue for #name #ty_generics #where_clause {
+                        type Key = u32;
+                        fn puts<P: structsy::Persistent>(&self, tx: &mut dyn structsy::Sytx, name: &str, field_path: &[&str], id: &structsy::Ref<P>) -> structsy::SRes<()> {
+                            use structsy::internal::{EmbeddedDescription, IndexableValue};
+                            self.variant_position().unwrap_or_default().puts(tx, name, field_path, id)
+                        }
+                        fn removes<P: structsy::Persistent>(&self, tx: &mut dyn structsy::Sytx, name: &str, field_path: &[&str], id: &structsy::Ref<P>) -> structsy::SRes<()> {
+                            use structsy::internal::{EmbeddedDescription, IndexableValue};
+                            self.variant_position().unwrap_or_default().removes(tx, name, field_path, id)
+                        }
                     }
-                    impl structsy::internal::PersistentEmbedded for #name {
+                    impl #impl_generics structsy::internal::PersistentEmbedded for #name #ty_generics #where_clause {
                         #ser
                     }
+
+                    impl #impl_generics #name #ty_generics #where_clause {
+                        #filters
+                    }
                 }
             }
         }
     }
 }
-fn enum_serialization_tokens(name: &Ident, variants: &[PersistentEnum]) -> (TokenStream, TokenStream) {
-    let enum_name = name.to_string();
-    let variants_data = variants
-        .iter()
-        .enumerate()
-        .map(|(pos, vt)| {
-            let index = pos as u32;
-            let tt = match vt.fields.style {
-                Style::Tuple => {
-                    if vt.fields.fields.len() == 1 {
-                        match &vt.fields.fields[0] {
-                            Type::Path(p) => Some(p.clone()),
-                            _ => panic!("Supported only named types as enums values"),
-                        }
-                    } else if vt.fields.fields.is_empty() {
-                        None
-                    } else {
-                        panic!("Tuples with multiple values not supported")
-                    }
-                }
-                Style::Unit => None,
-                _ => panic!("Supported only named types as enums values"),
-            };
-            (vt.ident.clone(), index, tt)
-        })
-        .collect::<Vec<_>>();
+fn enum_serialization_tokens(
+    name: &Ident,
+    enum_name: &TokenStream,
+    variants: &[PersistentEnum],
+) -> (TokenStream, TokenStream) {
+    let variants_data = variant_infos(variants);
 
-    let variants_meta = variants_data.iter().map(|(ident, index, tt)| {
-        let vt_name = ident.to_string();
-        if let Some(t) = tt {
-            quote! {
-                structsy::internal::VariantDescription::new_value::<#t>(#vt_name, #index),
-            }
-        } else {
-            quote! {
+    let variants_meta = variants_data.iter().map(|vt| {
+        let vt_name = vt.ident.to_string();
+        let index = vt.index;
+        match &vt.kind {
+            VariantKind::Unit => quote! {
                 structsy::internal::VariantDescription::new(#vt_name, #index),
+            },
+            VariantKind::Value(t) => quote! {
+                structsy::internal::VariantDescription::new_value::<#t>(#vt_name, #index),
+            },
+            _ => {
+                let fields = vt.fields().into_iter().enumerate().map(|(pos, (field_name, _, ty))| {
+                    let pos = pos as u32;
+                    quote! {
+                        structsy::internal::FieldDescription::new::<#ty>(#pos, #field_name, None),
+                    }
+                });
+                quote! {
+                    structsy::internal::VariantDescription::new_fields(#vt_name, #index, &[#( #fields )*]),
+                }
             }
         }
     });
 
     let variants_write = variants_data
         .iter()
-        .map(|(ident, index, tt)| {
-            if let Some(_t) = tt {
-                quote! {
+        .map(|vt| {
+            let ident = &vt.ident;
+            let index = vt.index;
+            let bindings = vt
+                .fields()
+                .into_iter()
+                .map(|(_, binding, _)| binding)
+                .collect::<Vec<_>>();
+            match &vt.kind {
+                VariantKind::Unit => quote! {
+                   #name::#ident => #index.write(write)?,
+                },
+                VariantKind::Value(_) => quote! {
                    #name::#ident(v) => {
                         #index.write(write)?;
                         v.write(write)?;
                    }
-                }
-            } else {
-                quote! {
-                   #name::#ident => #index.write(write)?,
-                }
+                },
+                VariantKind::Tuple(_) => quote! {
+                   #name::#ident(#( #bindings ),*) => {
+                        #index.write(write)?;
+                        #( #bindings.write(write)?; )*
+                   }
+                },
+                VariantKind::Struct(_) => quote! {
+                   #name::#ident{#( #bindings ),*} => {
+                        #index.write(write)?;
+                        #( #bindings.write(write)?; )*
+                   }
+                },
             }
         })
         .collect::<Vec<_>>();
 
     let variants_read = variants_data
         .iter()
-        .map(|(ident, index, tt)| {
-            if let Some(t) = tt {
-                quote! {
-                   #index => #name::#ident(#t::read(read)?),
-                }
-            } else {
-                quote! {
+        .map(|vt| {
+            let ident = &vt.ident;
+            let index = vt.index;
+            let bindings = vt
+                .fields()
+                .into_iter()
+                .map(|(_, binding, _)| binding)
+                .collect::<Vec<_>>();
+            match &vt.kind {
+                VariantKind::Unit => quote! {
                    #index => #name::#ident,
-                }
+                },
+                VariantKind::Value(_) => quote! {
+                   #index => #name::#ident(PersistentEmbedded::read(read)?),
+                },
+                VariantKind::Tuple(_) => quote! {
+                   #index => {
+                        #( let #bindings = PersistentEmbedded::read(read)?; )*
+                        #name::#ident(#( #bindings ),*)
+                   }
+                },
+                VariantKind::Struct(_) => quote! {
+                   #index => #name::#ident{#( #bindings: PersistentEmbedded::read(read)? ),*},
+                },
             }
         })
         .collect::<Vec<_>>();
@@ -337,7 +626,7 @@ fn enum_serialization_tokens(name: &Ident, variants: &[PersistentEnum]) -> (Toke
                 Ok(())
             }
 
-            fn read(read:&mut std::io::Read) -> structsy::SRes<#name> {
+            fn read(read:&mut std::io::Read) -> structsy::SRes<Self> {
                 use structsy::internal::PersistentEmbedded;
                 Ok(match u32::read(read)? {
                     #( #variants_read )*
@@ -348,7 +637,12 @@ fn enum_serialization_tokens(name: &Ident, variants: &[PersistentEnum]) -> (Toke
     (desc, ser)
 }
 
-fn serialization_tokens(name: &Ident, fields: &[FieldInfo]) -> (TokenStream, TokenStream) {
+fn serialization_tokens(
+    name: &Ident,
+    struct_name: &TokenStream,
+    fields: &[FieldInfo],
+    computed: &[ComputedIndexAttr],
+) -> (TokenStream, TokenStream) {
     let fields_info = fields.iter().enumerate().map(|(position, field)| {
         let pos = position as u32;
         let indexed = translate_option_mode(&field.index_mode);
@@ -357,24 +651,25 @@ fn serialization_tokens(name: &Ident, fields: &[FieldInfo]) -> (TokenStream, Tok
         let read_fill = quote! {
             #field_ident,
         };
-        let ty = field.ty.clone();
-        let desc = match (field.template_ty.clone(), field.sub_template_ty.clone()) {
-            (Some(x), Some(z)) => {
-                quote! {
-                    structsy::internal::FieldDescription::new::<#ty<#x<#z>>>(#pos,#field_name,#indexed),
-                }
-            }
-            (Some(x), None) => {
-                quote! {
-                    structsy::internal::FieldDescription::new::<#ty<#x>>(#pos,#field_name,#indexed),
-                }
-            }
-            (None, None) => {
-                quote! {
-                    structsy::internal::FieldDescription::new::<#ty>(#pos,#field_name,#indexed),
-                }
-            }
-            (None, Some(_x)) => panic!(""),
+        let ty = &field.full_ty;
+        let condition = field.index_when.as_ref().map(|(condition, value)| {
+            let condition = condition.to_string();
+            quote! { .with_index_condition(#condition, #value) }
+        });
+        let fulltext = if field.fulltext {
+            let stem = option_str_tokens(&field.stem);
+            Some(quote! { .with_fulltext(#stem) })
+        } else {
+            None
+        };
+        let vector = field.vector.as_ref().map(|vector| {
+            let dim = vector.dim;
+            let metric = vector.metric();
+            quote! { .with_vector(#dim, #metric) }
+        });
+        let geo = if field.geo { Some(quote! { .with_geo() }) } else { None };
+        let desc = quote! {
+            structsy::internal::FieldDescription::new::<#ty>(#pos,#field_name,#indexed) #condition #fulltext #vector #geo,
         };
 
         let write = quote! {
@@ -392,7 +687,27 @@ fn serialization_tokens(name: &Ident, fields: &[FieldInfo]) -> (TokenStream, Tok
     let (fields_meta, fields_write): (Vec<TokenStream>, Vec<TokenStream>) = fields_meta_write.into_iter().unzip();
     let (fields_read, fields_construct): (Vec<TokenStream>, Vec<TokenStream>) = fields_read_fill.into_iter().unzip();
 
-    let struct_name = name.to_string();
+    // The values of the computed indexes are stored after the fields, so they are available
+    // also to the raw records
+    let (computed_meta, computed_write): (Vec<TokenStream>, Vec<TokenStream>) = computed
+        .iter()
+        .map(|index| {
+            let (expr, ty) = parse_computed_index(index);
+            let index_name = &index.name;
+            let mode = translate_mode(&index.mode.clone().unwrap_or_default());
+            let desc = quote! {
+                .with_computed_index(structsy::internal::ComputedIndexDescription::new::<#ty>(#index_name, Some(#mode)))
+            };
+            let write = quote! {
+                {
+                    let value: #ty = #expr;
+                    value.write(write)?;
+                }
+            };
+            (desc, write)
+        })
+        .unzip();
+
     let desc = quote! {
             fn get_description() -> structsy::internal::Description {
                 let fields  = [
@@ -400,6 +715,7 @@ fn serialization_tokens(name: &Ident, fields: &[FieldInfo]) -> (TokenStream, Tok
                 ];
                 structsy::internal::Description::Struct(
                     structsy::internal::StructDescription::new(#struct_name,&fields)
+                    #( #computed_meta )*
                 )
             }
     };
@@ -407,10 +723,11 @@ fn serialization_tokens(name: &Ident, fields: &[FieldInfo]) -> (TokenStream, Tok
             fn write(&self,write:&mut std::io::Write) -> structsy::SRes<()> {
                 use structsy::internal::PersistentEmbedded;
                 #( #fields_write )*
+                #( #computed_write )*
                 Ok(())
             }
 
-            fn read(read:&mut std::io::Read) -> structsy::SRes<#name> {
+            fn read(read:&mut std::io::Read) -> structsy::SRes<Self> {
                 use structsy::internal::PersistentEmbedded;
                 #( #fields_read )*
                 Ok(#name {
@@ -421,53 +738,505 @@ fn serialization_tokens(name: &Ident, fields: &[FieldInfo]) -> (TokenStream, Tok
     (desc, serialization)
 }
 
-fn indexes_tokens(name: &Ident, fields: &[FieldInfo]) -> TokenStream {
+fn check_indexed_fields(fields: &[FieldInfo], type_params: &[Ident]) {
     let only_indexed: Vec<FieldInfo> = fields.iter().filter(|f| f.index_mode.is_some()).cloned().collect();
-
-    let snippets = only_indexed.iter().map(|f| {
-        let index_name = format!("{}.{}", name, f.name);
-        let field = f.name.clone();
-        let field_name = field.to_string();
-        let t_name = name.to_string();
-        let mode = translate_mode(f.index_mode.as_ref().unwrap());
-        let index_type = match (f.template_ty.clone(), f.sub_template_ty.clone()) {
-            (Some(_), Some(s1)) => s1,
-            (Some(s), None) => s,
-            _ => f.ty.clone(),
-        };
-        let declare = quote! {
-            structsy::internal::declare_index::<#index_type>(db,#index_name,#mode)?;
-        };
-        let put = quote! {
-            self.#field.puts(tx, #t_name, &[#field_name], id)?;
-        };
-        let remove = quote! {
-            self.#field.removes(tx, #t_name, &[#field_name], id)?;
+    for f in &only_indexed {
+        if type_params.contains(&f.ty) {
+            panic!("indexing not supported for generic fields");
+        }
+        if f.ty == "BTreeMap" || f.ty == "HashMap" {
+            panic!("indexing not supported for map fields");
+        }
+        // Indexed `Vec<u8>` fields keep the index by element
+        let array_u8 = match &f.full_ty {
+            syn::Type::Array(a) => get_type_ident(&a.elem).map(|t| t == "u8").unwrap_or(false),
+            _ => false,
         };
-        (declare, (put, remove))
-    });
-    let (index_declare, index_put_remove): (Vec<TokenStream>, Vec<(TokenStream, TokenStream)>) = snippets.unzip();
-    let (index_put, index_remove): (Vec<TokenStream>, Vec<TokenStream>) = index_put_remove.into_iter().unzip();
+        if array_u8 || f.ty == "Bytes" {
+            panic!("indexing not supported for byte fields");
+        }
+        if f.ty == "Array" {
+            panic!("indexing not supported for fixed array fields");
+        }
+        if f.ty == "Blob" || f.template_ty.as_ref().map(|t| t == "Blob").unwrap_or(false) {
+            panic!("indexing not supported for blob fields");
+        }
+    }
+    for f in fields {
+        if f.stem.is_some() && !f.fulltext {
+            panic!("the stem of field '{}' requires a fulltext index", f.name);
+        }
+        let is_string = |t: &Option<Ident>| t.as_ref().map(|t| t == "String").unwrap_or(false);
+        if f.fulltext && !(f.ty == "String" || (f.ty == "Option" && is_string(&f.template_ty))) {
+            panic!("fulltext index supported only for String fields");
+        }
+        if let Some(vector) = &f.vector {
+            let is_f32 = |t: &Option<Ident>| t.as_ref().map(|t| t == "f32").unwrap_or(false);
+            let vec_f32 = f.ty == "Vec" && is_f32(&f.template_ty);
+            let option_vec_f32 = f.ty == "Option"
+                && f.template_ty.as_ref().map(|t| t == "Vec").unwrap_or(false)
+                && is_f32(&f.sub_template_ty);
+            if !(vec_f32 || option_vec_f32) {
+                panic!("vector index supported only for Vec<f32> and Option<Vec<f32>> fields");
+            }
+            if vector.dim == 0 {
+                panic!(
+                    "the vector index of field '{}' requires a dimension greater than zero",
+                    f.name
+                );
+            }
+            if !["cosine", "euclidean", "dot"].contains(&vector.metric().as_str()) {
+                panic!(
+                    "the vector index of field '{}' supports only the metrics cosine, euclidean and dot",
+                    f.name
+                );
+            }
+        }
+        let is_geo_point = |t: &Option<Ident>| t.as_ref().map(|t| t == "GeoPoint").unwrap_or(false);
+        if f.geo && !(f.ty == "GeoPoint" || (f.ty == "Option" && is_geo_point(&f.template_ty))) {
+            panic!("geo index supported only for GeoPoint fields");
+        }
+        if let Some((condition, _)) = &f.index_when {
+            if f.index_mode.is_none() {
+                panic!("the when condition of field '{}' requires an index mode", f.name);
+            }
+            if !fields.iter().any(|c| &c.name == condition && c.ty == "bool") {
+                panic!("the when condition of field '{}' must refer to a bool field", f.name);
+            }
+        }
+    }
+}
 
-    let indexes = quote! {
+/// Parse the condition of a partial index, supported only in the forms `self.field` and `!self.field`
+fn parse_index_when(field: &Ident, when: &str) -> (Ident, bool) {
+    let fail = || -> ! {
+        panic!(
+            "the when condition of field '{}' must be `self.<field>` or `!self.<field>`",
+            field
+        )
+    };
+    let expr = syn::parse_str::<syn::Expr>(when).unwrap_or_else(|_| fail());
+    let (expr, value) = match expr {
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Not(_),
+            expr,
+            ..
+        }) => (*expr, false),
+        expr => (expr, true),
+    };
+    match expr {
+        syn::Expr::Field(syn::ExprField {
+            base,
+            member: syn::Member::Named(condition),
+            ..
+        }) if matches!(&*base, syn::Expr::Path(p) if p.path.is_ident("self")) => (condition, value),
+        _ => fail(),
+    }
+}
+
+/// Declare, put and remove statements for the indexes of the fields and of the embedded structs
+/// they hold, nested ones build the index path from the `path` of the containing field
+fn index_snippets(
+    type_name: &TokenStream,
+    fields: &[FieldInfo],
+    nested: bool,
+) -> Vec<(TokenStream, TokenStream, TokenStream)> {
+    fields
+        .iter()
+        .map(|f| {
+            let field = f.name.clone();
+            let field_name = field.to_string();
+            let ty = &f.full_ty;
+            let field_path = if nested {
+                quote! {
+                    let mut field_path = path.to_vec();
+                    field_path.push(#field_name);
+                }
+            } else {
+                quote! {
+                    let field_path = [#field_name];
+                }
+            };
+            let (declare, put, remove) = if let Some(mode) = &f.index_mode {
+                let mode = translate_mode(mode);
+                let mut put = quote! {
+                    self.#field.puts(tx, #type_name, &field_path, id)?;
+                };
+                let mut remove = quote! {
+                    self.#field.removes(tx, #type_name, &field_path, id)?;
+                };
+                if let Some((condition, value)) = &f.index_when {
+                    let condition = if *value {
+                        quote! { self.#condition }
+                    } else {
+                        quote! { !self.#condition }
+                    };
+                    put = quote! { if #condition { #put } };
+                    remove = quote! { if #condition { #remove } };
+                }
+                (
+                    quote! {
+                        let index_name = structsy::internal::index_name(#type_name, &field_path);
+                        structsy::internal::declare_index::<<#ty as structsy::internal::IndexableValue>::Key>(db,&index_name,#mode)?;
+                    },
+                    put,
+                    remove,
+                )
+            } else {
+                (quote! {}, quote! {}, quote! {})
+            };
+            let (fulltext_declare, fulltext_put, fulltext_remove) = if f.fulltext {
+                let stem = option_str_tokens(&f.stem);
+                (
+                    quote! {
+                        structsy::internal::declare_fulltext_index(db, #type_name, #field_name, #stem)?;
+                    },
+                    quote! {
+                        let fulltext_name = structsy::internal::fulltext_field_name(#field_name);
+                        structsy::internal::index_tokens(&self.#field, #stem)?.puts(tx, #type_name, &[&fulltext_name], id)?;
+                    },
+                    quote! {
+                        let fulltext_name = structsy::internal::fulltext_field_name(#field_name);
+                        structsy::internal::index_tokens(&self.#field, #stem)?.removes(tx, #type_name, &[&fulltext_name], id)?;
+                    },
+                )
+            } else {
+                (quote! {}, quote! {}, quote! {})
+            };
+            let (vector_declare, vector_put, vector_remove) = if let Some(vector) = &f.vector {
+                let dim = vector.dim;
+                let metric = vector.metric();
+                (
+                    quote! {
+                        structsy::internal::declare_vector_index(db, #type_name, #field_name, #dim, #metric)?;
+                    },
+                    quote! {
+                        structsy::internal::put_vector(tx, #type_name, #field_name, #dim, #metric, &self.#field, id)?;
+                    },
+                    quote! {
+                        structsy::internal::remove_vector(tx, #type_name, #field_name, #metric, &self.#field, id)?;
+                    },
+                )
+            } else {
+                (quote! {}, quote! {}, quote! {})
+            };
+            let (geo_declare, geo_put, geo_remove) = if f.geo {
+                (
+                    quote! {
+                        structsy::internal::declare_geo_index(db, #type_name, #field_name)?;
+                    },
+                    quote! {
+                        structsy::internal::put_geo(tx, #type_name, #field_name, &self.#field, id)?;
+                    },
+                    quote! {
+                        structsy::internal::remove_geo(tx, #type_name, #field_name, &self.#field, id)?;
+                    },
+                )
+            } else {
+                (quote! {}, quote! {}, quote! {})
+            };
+            (
+                quote! {
+                    {
+                        #field_path
+                        #declare
+                        #fulltext_declare
+                        #vector_declare
+                        #geo_declare
+                        <#ty as structsy::internal::PersistentEmbedded>::declare_nested_indexes(db, #type_name, &field_path)?;
+                    }
+                },
+                quote! {
+                    {
+                        #field_path
+                        #put
+                        #fulltext_put
+                        #vector_put
+                        #geo_put
+                        self.#field.put_nested_indexes(tx, #type_name, &field_path, id)?;
+                    }
+                },
+                quote! {
+                    {
+                        #field_path
+                        #remove
+                        #fulltext_remove
+                        #vector_remove
+                        #geo_remove
+                        self.#field.remove_nested_indexes(tx, #type_name, &field_path, id)?;
+                    }
+                },
+            )
+        })
+        .collect()
+}
+
+fn indexes_tokens(
+    name: &TokenStream,
+    fields: &[FieldInfo],
+    type_params: &[Ident],
+    computed: &[ComputedIndexAttr],
+) -> TokenStream {
+    check_indexed_fields(fields, type_params);
+    let mut snippets = index_snippets(name, fields, false);
+    snippets.extend(computed_index_snippets(name, fields, computed));
+    let index_declare = snippets.iter().map(|(declare, _, _)| declare);
+    let index_put = snippets.iter().map(|(_, put, _)| put);
+    let index_remove = snippets.iter().map(|(_, _, remove)| remove);
+
+    quote! {
             fn declare(db:&mut structsy::Sytx)-> structsy::SRes<()> {
                 #( #index_declare )*
                 Ok(())
             }
 
             fn put_indexes(&self, tx:&mut structsy::Sytx, id:&structsy::Ref<Self>) -> structsy::SRes<()> {
-                use structsy::internal::IndexableValue;
+                use structsy::internal::{IndexableValue, PersistentEmbedded};
                 #( #index_put )*
                 Ok(())
             }
 
             fn remove_indexes(&self, tx:&mut structsy::Sytx, id:&structsy::Ref<Self>) -> structsy::SRes<()> {
-                use structsy::internal::IndexableValue;
+                use structsy::internal::{IndexableValue, PersistentEmbedded};
                 #( #index_remove )*
                 Ok(())
             }
-    };
-    indexes
+    }
+}
+
+fn parse_computed_index(index: &ComputedIndexAttr) -> (syn::Expr, syn::Type) {
+    let expr = syn::parse_str::<syn::Expr>(&index.expr)
+        .unwrap_or_else(|e| panic!("invalid expr for computed index '{}': {}", index.name, e));
+    let ty = syn::parse_str::<syn::Type>(&index.ty)
+        .unwrap_or_else(|e| panic!("invalid ty for computed index '{}': {}", index.name, e));
+    (expr, ty)
+}
+
+fn computed_index_snippets(
+    type_name: &TokenStream,
+    fields: &[FieldInfo],
+    computed: &[ComputedIndexAttr],
+) -> Vec<(TokenStream, TokenStream, TokenStream)> {
+    computed
+        .iter()
+        .map(|index| {
+            if fields.iter().any(|f| f.name == index.name) {
+                panic!("computed index '{}' has the same name of a field", index.name);
+            }
+            let (expr, ty) = parse_computed_index(index);
+            let index_name = &index.name;
+            let mode = translate_mode(&index.mode.clone().unwrap_or_default());
+            (
+                quote! {
+                    let index_name = structsy::internal::index_name(#type_name, &[#index_name]);
+                    structsy::internal::declare_index::<<#ty as structsy::internal::IndexableValue>::Key>(db,&index_name,#mode)?;
+                },
+                quote! {
+                    {
+                        let value: #ty = #expr;
+                        value.puts(tx, #type_name, &[#index_name], id)?;
+                    }
+                },
+                quote! {
+                    {
+                        let value: #ty = #expr;
+                        value.removes(tx, #type_name, &[#index_name], id)?;
+                    }
+                },
+            )
+        })
+        .collect()
+}
+
+fn computed_value_tokens(computed: &[ComputedIndexAttr]) -> TokenStream {
+    if computed.is_empty() {
+        return quote! {};
+    }
+    let values = computed.iter().map(|index| {
+        let (expr, ty) = parse_computed_index(index);
+        let index_name = &index.name;
+        quote! {
+            #index_name => {
+                let value: #ty = #expr;
+                Some(Box::new(value))
+            }
+        }
+    });
+    quote! {
+        fn computed_index_value(&self, name: &str) -> Option<Box<dyn std::any::Any>> {
+            match name {
+                #( #values )*
+                _ => None,
+            }
+        }
+    }
+}
+
+fn option_str_tokens(value: &Option<String>) -> TokenStream {
+    match value {
+        Some(v) => quote! { Some(#v) },
+        None => quote! { None },
+    }
+}
+
+/// A `&'static str` with the value of the `name` expression, computed once by the generated code
+/// so the names of the indexes come from the helpers of structsy
+fn static_name(name: TokenStream) -> TokenStream {
+    quote! {
+        {
+            static NAME: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+            NAME.get_or_init(|| #name).as_str()
+        }
+    }
+}
+
+/// The tokens of the full-text indexed fields used for rank the searches
+fn fulltext_tokens(fields: &[FieldInfo]) -> TokenStream {
+    let fulltext: Vec<&FieldInfo> = fields.iter().filter(|f| f.fulltext).collect();
+    if fulltext.is_empty() {
+        return quote! {};
+    }
+    let tokens = fulltext.iter().map(|f| {
+        let field = &f.name;
+        let field_name = field.to_string();
+        let stem = option_str_tokens(&f.stem);
+        quote! {
+            #field_name => structsy::internal::FullTextValue::text(&self.#field)
+                .and_then(|text| structsy::internal::tokenize(text, #stem).ok()),
+        }
+    });
+    let indexes = fulltext.iter().map(|f| {
+        let field_name = f.name.to_string();
+        let fulltext_name = static_name(quote! { structsy::internal::fulltext_field_name(#field_name) });
+        let stem = option_str_tokens(&f.stem);
+        quote! {
+            #field_name => Some((#fulltext_name, #stem)),
+        }
+    });
+    quote! {
+        fn fulltext_tokens(&self, field: &str) -> Option<Vec<String>> {
+            match field {
+                #( #tokens )*
+                _ => None,
+            }
+        }
+        fn fulltext_index(field: &str) -> Option<(&'static str, Option<&'static str>)> {
+            match field {
+                #( #indexes )*
+                _ => None,
+            }
+        }
+    }
+}
+
+/// The vectors of the vector indexed fields used for rank the nearest records
+fn vector_tokens(fields: &[FieldInfo]) -> TokenStream {
+    let vector: Vec<&FieldInfo> = fields.iter().filter(|f| f.vector.is_some()).collect();
+    if vector.is_empty() {
+        return quote! {};
+    }
+    let values = vector.iter().map(|f| {
+        let field = &f.name;
+        let field_name = field.to_string();
+        quote! {
+            #field_name => structsy::internal::VectorValue::vector(&self.#field),
+        }
+    });
+    let indexes = vector.iter().map(|f| {
+        let field_name = f.name.to_string();
+        let attr = f.vector.as_ref().unwrap();
+        let dim = attr.dim;
+        let metric = attr.metric();
+        quote! {
+            #field_name => Some((#dim, #metric)),
+        }
+    });
+    quote! {
+        fn vector_value(&self, field: &str) -> Option<&[f32]> {
+            match field {
+                #( #values )*
+                _ => None,
+            }
+        }
+        fn vector_index(field: &str) -> Option<(u32, &'static str)> {
+            match field {
+                #( #indexes )*
+                _ => None,
+            }
+        }
+    }
+}
+
+/// The points of the geo indexed fields used for check the searched area and rank by distance
+fn geo_tokens(fields: &[FieldInfo]) -> TokenStream {
+    let geo: Vec<&FieldInfo> = fields.iter().filter(|f| f.geo).collect();
+    if geo.is_empty() {
+        return quote! {};
+    }
+    let values = geo.iter().map(|f| {
+        let field = &f.name;
+        let field_name = field.to_string();
+        quote! {
+            #field_name => structsy::internal::GeoValue::point(&self.#field),
+        }
+    });
+    let indexes = geo.iter().map(|f| {
+        let field_name = f.name.to_string();
+        let geo_name = static_name(quote! { structsy::internal::geo_field_name(#field_name) });
+        quote! {
+            #field_name => Some(#geo_name),
+        }
+    });
+    quote! {
+        fn geo_value(&self, field: &str) -> Option<&structsy::GeoPoint> {
+            match field {
+                #( #values )*
+                _ => None,
+            }
+        }
+        fn geo_index(field: &str) -> Option<&'static str> {
+            match field {
+                #( #indexes )*
+                _ => None,
+            }
+        }
+    }
+}
+
+fn nested_indexes_tokens(fields: &[FieldInfo], type_params: &[Ident]) -> TokenStream {
+    check_indexed_fields(fields, type_params);
+    if fields.iter().any(|f| f.fulltext) {
+        panic!("fulltext index not supported on embedded structs");
+    }
+    if fields.iter().any(|f| f.vector.is_some()) {
+        panic!("vector index not supported on embedded structs");
+    }
+    if fields.iter().any(|f| f.geo) {
+        panic!("geo index not supported on embedded structs");
+    }
+    let snippets = index_snippets(&quote! { type_name }, fields, true);
+    let index_declare = snippets.iter().map(|(declare, _, _)| declare);
+    let index_put = snippets.iter().map(|(_, put, _)| put);
+    let index_remove = snippets.iter().map(|(_, _, remove)| remove);
+
+    quote! {
+            fn declare_nested_indexes(db: &mut dyn structsy::Sytx, type_name: &str, path: &[&str]) -> structsy::SRes<()> {
+                #( #index_declare )*
+                Ok(())
+            }
+
+            fn put_nested_indexes<P: structsy::Persistent>(&self, tx: &mut dyn structsy::Sytx, type_name: &str, path: &[&str], id: &structsy::Ref<P>) -> structsy::SRes<()> {
+                use structsy::internal::{IndexableValue, PersistentEmbedded};
+                #( #index_put )*
+                Ok(())
+            }
+
+            fn remove_nested_indexes<P: structsy::Persistent>(&self, tx: &mut dyn structsy::Sytx, type_name: &str, path: &[&str], id: &structsy::Ref<P>) -> structsy::SRes<()> {
+                use structsy::internal::{IndexableValue, PersistentEmbedded};
+                #( #index_remove )*
+                Ok(())
+            }
+    }
 }
 
 fn sub_type(t: &Type) -> Option<&Type> {
@@ -521,10 +1290,11 @@ fn translate_mode(mode: &IndexMode) -> TokenStream {
     }
 }
 fn get_type_ident(ty: &syn::Type) -> Option<Ident> {
-    if let Path(ref path) = ty {
-        Some(path.clone().path.segments.iter().last().unwrap().ident.clone())
-    } else {
-        None
+    match ty {
+        Path(ref path) => Some(path.clone().path.segments.iter().last().unwrap().ident.clone()),
+        syn::Type::Array(_) => Some(Ident::new("Array", Span::call_site())),
+        syn::Type::Tuple(_) => Some(Ident::new("Tuple", Span::call_site())),
+        _ => None,
     }
 }
 
@@ -533,15 +1303,7 @@ fn filter_tokens(fields: &[FieldInfo]) -> TokenStream {
         .iter()
         .map(|field| {
             let field_ident = field.name.clone();
-            let t = field.ty.clone();
-            let ty = match (field.template_ty.clone(), field.sub_template_ty.clone()) {
-                (Some(x), Some(z)) => {
-                    quote! {#t<#x<#z>>}
-                }
-                (Some(x), None) => quote! {#t<#x>},
-                (None, None) => quote! {#t},
-                (None, Some(_x)) => panic!(""),
-            };
+            let ty = &field.full_ty;
             let field_name = field.name.to_string();
             let meta_method = Ident::new(&format!("field_{}", &field_name), Span::call_site());
             quote! {
@@ -557,13 +1319,71 @@ fn filter_tokens(fields: &[FieldInfo]) -> TokenStream {
     }
 }
 
-fn projection_tokens(fields: &[FieldInfo]) -> TokenStream {
+fn enum_filter_tokens(name: &Ident, variants: &[PersistentEnum]) -> TokenStream {
+    let methods: Vec<TokenStream> = variant_infos(variants)
+        .iter()
+        .flat_map(|vt| {
+            let ident = &vt.ident;
+            let variant_name = vt.ident.to_string();
+            let tuple = matches!(vt.kind, VariantKind::Tuple(_));
+            let fields = vt.fields();
+            let bindings = fields.iter().map(|(_, binding, _)| binding.clone()).collect::<Vec<_>>();
+            let index = vt.index;
+            let variant_method = Ident::new(&format!("variant_{}", snake_case(&variant_name)), Span::call_site());
+            let mut methods = vec![quote! {
+                pub fn #variant_method() -> structsy::Variant<Self> {
+                    structsy::Variant::new(#index, #variant_name)
+                }
+            }];
+            if let VariantKind::Value(ty) = &vt.kind {
+                let value_method = Ident::new(&format!("field_{}", snake_case(&variant_name)), Span::call_site());
+                methods.push(quote! {
+                    #[allow(unreachable_patterns)]
+                    pub fn #value_method() -> structsy::internal::Field<Self,#ty> {
+                        structsy::internal::Field::new_variant(#variant_name, |x| match x {
+                            #name::#ident(v) => Some(v),
+                            _ => None,
+                        })
+                    }
+                });
+            }
+            methods.extend(fields.iter().map(|(field_name, binding, ty)| {
+                let meta_method = Ident::new(
+                    &format!("field_{}_{}", snake_case(&variant_name), field_name),
+                    Span::call_site(),
+                );
+                let full_name = format!("{}.{}", variant_name, field_name);
+                let pattern = if tuple {
+                    quote! { #name::#ident(#( #bindings ),*) }
+                } else {
+                    quote! { #name::#ident{ #binding, .. } }
+                };
+                quote! {
+                    #[allow(unused_variables, unreachable_patterns)]
+                    pub fn #meta_method() -> structsy::internal::Field<Self,#ty> {
+                        structsy::internal::Field::new_variant(#full_name, |x| match x {
+                            #pattern => Some(#binding),
+                            _ => None,
+                        })
+                    }
+                }
+            }));
+            methods
+        })
+        .collect();
+
+    quote! {
+        #( #methods )*
+    }
+}
+
+fn projection_tokens(fields: &[FieldInfo], ids: &[Ident]) -> TokenStream {
     let fields_info: Vec<TokenStream> = fields
         .iter()
         .map(|field| {
             let field_ident = field.name.clone();
             quote! {
-                #field_ident: Projection::projection(&source.#field_ident),
+                #field_ident: structsy::internal::Projection::projection(&source.#field_ident),
             }
         })
         .collect();
@@ -571,6 +1391,46 @@ fn projection_tokens(fields: &[FieldInfo]) -> TokenStream {
     quote! {
         Self {
             #( #fields_info )*
+            #( #ids: id.clone(), )*
+        }
+    }
+}
+
+/// The fields read by the projection, and when the only one is of a type used as key of the
+/// indexes the build of the projection from the value read from the index of the field
+fn index_projection_tokens(target: &Ident, fields: &[FieldInfo], ids: &[Ident]) -> TokenStream {
+    let names = fields.iter().map(|f| f.name.to_string());
+    let projected = quote! {
+        fn projected_fields() -> Option<Vec<&'static str>> {
+            Some(vec![#( #names ),*])
+        }
+    };
+    let key_types = [
+        "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128", "f32", "f64", "bool", "String",
+    ];
+    match fields {
+        [field] if field.template_ty.is_none() && key_types.iter().any(|t| field.ty == t) => {
+            let field_ident = &field.name;
+            let ty = &field.full_ty;
+            quote! {
+                #projected
+                fn from_index(id: &structsy::Ref<#target>, value: &dyn std::any::Any) -> Option<Self> {
+                    Some(Self {
+                        #field_ident: value.downcast_ref::<#ty>()?.clone(),
+                        #( #ids: id.clone(), )*
+                    })
+                }
+            }
         }
+        // A projection of only the id does not need any value
+        [] => quote! {
+            #projected
+            fn from_index(id: &structsy::Ref<#target>, _value: &dyn std::any::Any) -> Option<Self> {
+                Some(Self {
+                    #( #ids: id.clone(), )*
+                })
+            }
+        },
+        _ => projected,
     }
 }
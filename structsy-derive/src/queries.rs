@@ -50,6 +50,10 @@ fn extract_fields(s: &Signature) -> Vec<Operation> {
             } else {
                 Some((t.ident.to_string(), None))
             }
+        } else if let Type::Tuple(_) = &*f.ty {
+            Some(("tuple".to_owned(), None))
+        } else if let Type::Array(_) = &*f.ty {
+            Some(("array".to_owned(), None))
         } else if let Type::Reference(t) = &*f.ty {
             if let Type::Path(nt) = &*t.elem {
                 let last = nt.path.segments.last().unwrap().ident.to_string();
@@ -5,8 +5,101 @@ use crate::{
     queries::{SnapshotQuery, StructsyQuery},
     Order, Persistent, PersistentEmbedded, Ref,
 };
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::marker::PhantomData;
 use std::ops::RangeBounds;
 
+/// Query argument to match the byte fields that start with the provided bytes
+///
+/// # Example
+/// ```
+/// use structsy::{Prefix, SRes, Structsy};
+/// use structsy_derive::{queries, Persistent};
+/// #[derive(Persistent)]
+/// struct Image {
+///     data: Vec<u8>,
+/// }
+///
+/// #[queries(Image)]
+/// trait ImageQuery {
+///     fn by_data_prefix(self, data: Prefix<Vec<u8>>) -> Self;
+/// }
+/// # fn example() -> SRes<()> {
+/// let stry = Structsy::open("path/to/file.stry")?;
+/// stry.define::<Image>()?;
+/// let png = stry.query::<Image>().by_data_prefix(Prefix(vec![0x89, b'P', b'N', b'G']));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Prefix<T>(pub T);
+
+/// Query argument to match the enum fields of a specific variant, without providing the variant values,
+/// the variants are generated by the derive as `variant_<name>()` methods of the enum
+///
+/// # Example
+/// ```
+/// use structsy::{SRes, Structsy, Variant};
+/// use structsy_derive::{queries, Persistent, PersistentEmbedded};
+/// #[derive(PersistentEmbedded, PartialEq)]
+/// enum Status {
+///     Pending,
+///     Shipped(String),
+/// }
+/// #[derive(Persistent)]
+/// struct Order {
+///     #[index(mode = "cluster")]
+///     status: Status,
+/// }
+///
+/// #[queries(Order)]
+/// trait OrderQuery {
+///     fn by_status(self, status: Variant<Status>) -> Self;
+/// }
+/// # fn example() -> SRes<()> {
+/// let stry = Structsy::open("path/to/file.stry")?;
+/// stry.define::<Order>()?;
+/// let shipped = stry.query::<Order>().by_status(Status::variant_shipped());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Variant<E> {
+    position: u32,
+    name: &'static str,
+    phantom: PhantomData<E>,
+}
+
+impl<E> Variant<E> {
+    pub fn new(position: u32, name: &'static str) -> Self {
+        Variant {
+            position,
+            name,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<E> Clone for Variant<E> {
+    fn clone(&self) -> Self {
+        Variant::new(self.position, self.name)
+    }
+}
+
+impl<E> std::fmt::Debug for Variant<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Variant:{}", self.name)
+    }
+}
+
 pub trait EqualAction<X> {
     fn equal(self, value: X);
 }
@@ -73,6 +166,114 @@ where
     }
 }
 
+impl<T, K, V> EqualAction<K> for (Field<T, BTreeMap<K, V>>, &mut FilterBuilder<T>)
+where
+    T: 'static,
+    K: PersistentEmbedded + SolveQueryValue + ValueCompare + Ord + 'static,
+    V: PersistentEmbedded + ValueCompare + 'static,
+{
+    #[inline]
+    fn equal(self, key: K) {
+        self.1.cond_contains_key(self.0, key);
+    }
+}
+impl<T, V> EqualAction<&str> for (Field<T, BTreeMap<String, V>>, &mut FilterBuilder<T>)
+where
+    T: 'static,
+    V: PersistentEmbedded + ValueCompare + 'static,
+{
+    #[inline]
+    fn equal(self, key: &str) {
+        self.1.cond_contains_key(self.0, key.to_string());
+    }
+}
+impl<T, K, V> EqualAction<(K, V)> for (Field<T, BTreeMap<K, V>>, &mut FilterBuilder<T>)
+where
+    T: 'static,
+    K: PersistentEmbedded + SolveQueryValue + ValueCompare + Ord + 'static,
+    V: PersistentEmbedded + SolveQueryValue + ValueCompare + 'static,
+{
+    #[inline]
+    fn equal(self, (key, value): (K, V)) {
+        self.1.cond_key_equal(self.0, key, value);
+    }
+}
+
+impl<T, K, V> EqualAction<K> for (Field<T, HashMap<K, V>>, &mut FilterBuilder<T>)
+where
+    T: 'static,
+    K: PersistentEmbedded + SolveQueryValue + ValueCompare + Eq + Hash + 'static,
+    V: PersistentEmbedded + ValueCompare + 'static,
+{
+    #[inline]
+    fn equal(self, key: K) {
+        self.1.cond_contains_key(self.0, key);
+    }
+}
+impl<T, V> EqualAction<&str> for (Field<T, HashMap<String, V>>, &mut FilterBuilder<T>)
+where
+    T: 'static,
+    V: PersistentEmbedded + ValueCompare + 'static,
+{
+    #[inline]
+    fn equal(self, key: &str) {
+        self.1.cond_contains_key(self.0, key.to_string());
+    }
+}
+impl<T, K, V> EqualAction<(K, V)> for (Field<T, HashMap<K, V>>, &mut FilterBuilder<T>)
+where
+    T: 'static,
+    K: PersistentEmbedded + SolveQueryValue + ValueCompare + Eq + Hash + 'static,
+    V: PersistentEmbedded + SolveQueryValue + ValueCompare + 'static,
+{
+    #[inline]
+    fn equal(self, (key, value): (K, V)) {
+        self.1.cond_key_equal(self.0, key, value);
+    }
+}
+
+impl<T, E> EqualAction<Variant<E>> for (Field<T, E>, &mut FilterBuilder<T>)
+where
+    T: 'static,
+    E: EmbeddedDescription + PartialEq + 'static,
+{
+    #[inline]
+    fn equal(self, value: Variant<E>) {
+        self.1.cond_variant(self.0, value);
+    }
+}
+
+impl<T> EqualAction<Prefix<Vec<u8>>> for (Field<T, Vec<u8>>, &mut FilterBuilder<T>)
+where
+    T: 'static,
+{
+    #[inline]
+    fn equal(self, value: Prefix<Vec<u8>>) {
+        self.1.cond_prefix(self.0, value.0);
+    }
+}
+
+impl<T, const N: usize> EqualAction<Prefix<Vec<u8>>> for (Field<T, [u8; N]>, &mut FilterBuilder<T>)
+where
+    T: 'static,
+{
+    #[inline]
+    fn equal(self, value: Prefix<Vec<u8>>) {
+        self.1.cond_prefix(self.0, value.0);
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<T> EqualAction<Prefix<Vec<u8>>> for (Field<T, bytes::Bytes>, &mut FilterBuilder<T>)
+where
+    T: 'static,
+{
+    #[inline]
+    fn equal(self, value: Prefix<Vec<u8>>) {
+        self.1.cond_prefix(self.0, value.0);
+    }
+}
+
 pub trait RangeAction<X> {
     fn range(self, value: impl RangeBounds<X>);
 }
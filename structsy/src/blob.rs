@@ -0,0 +1,327 @@
+//! Large binary contents stored in a dedicated segment outside of the records
+//!
+use crate::{desc::Description, format::PersistentEmbedded, record::Record, SRes, StructsyError};
+use persy::{Persy, PersyId, Transaction, ValueMode};
+use std::io::{Cursor, Read, Write};
+
+pub(crate) const BLOB_SEGMENT_NAME: &str = "__#blobs";
+const PENDING_BLOBS_INDEX: &str = "__#pending_blobs";
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Handle to a large binary content stored in a dedicated segment, the record
+/// keeps only the handle so scan and filters do not load the content.
+///
+/// A blob is written with a [`BlobWriter`] created from a transaction and read back
+/// in streaming with a [`BlobReader`], the content is removed when the record holding
+/// the handle is deleted or updated to not hold it anymore, and on commit if the handle
+/// was not stored in any record of the transaction.
+///
+/// # Example
+/// ```
+/// use structsy::{Blob, Structsy, StructsyTx};
+/// use structsy_derive::Persistent;
+/// use std::io::{Read, Write};
+/// #[derive(Persistent)]
+/// struct Picture {
+///     name: String,
+///     content: Blob,
+/// }
+/// # use structsy::SRes;
+/// # fn example() -> SRes<()> {
+/// let stry = Structsy::open("path/to/file.stry")?;
+/// stry.define::<Picture>()?;
+/// let mut tx = stry.begin()?;
+/// let mut writer = tx.create_blob()?;
+/// writer.write_all(&[0; 1024])?;
+/// let content = writer.finish()?;
+/// let id = tx.insert(&Picture { name: "black".to_string(), content })?;
+/// tx.commit()?;
+///
+/// let picture = stry.read(&id)?.unwrap();
+/// let mut data = Vec::new();
+/// stry.read_blob(&picture.content)?.unwrap().read_to_end(&mut data)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Blob {
+    pub(crate) id: PersyId,
+}
+
+impl PersistentEmbedded for Blob {
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        format!("{}", self.id).write(write)
+    }
+    fn read(read: &mut dyn Read) -> SRes<Blob> {
+        let s_id = String::read(read)?;
+        Ok(Blob { id: s_id.parse()? })
+    }
+}
+
+struct BlobHead {
+    len: u64,
+    chunks: Vec<PersyId>,
+}
+
+impl BlobHead {
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        self.len.write(write)?;
+        let chunks = self.chunks.iter().map(|c| format!("{}", c)).collect::<Vec<String>>();
+        chunks.write(write)
+    }
+    fn read(read: &mut dyn Read) -> SRes<BlobHead> {
+        let len = u64::read(read)?;
+        let chunks = Vec::<String>::read(read)?
+            .iter()
+            .map(|c| Ok(c.parse()?))
+            .collect::<SRes<Vec<PersyId>>>()?;
+        Ok(BlobHead { len, chunks })
+    }
+}
+
+/// Streaming writer of a blob content, the content is stored in chunks while written
+/// and [`finish`] returns the handle to store in a record.
+///
+/// The chunks already stored are removed if the writer is dropped or [`abort`]ed
+/// before [`finish`].
+///
+/// [`finish`]: #method.finish
+/// [`abort`]: #method.abort
+pub struct BlobWriter<'a> {
+    trans: &'a mut Transaction,
+    buffer: Vec<u8>,
+    chunks: Vec<PersyId>,
+    len: u64,
+}
+
+impl<'a> BlobWriter<'a> {
+    pub(crate) fn new(trans: &'a mut Transaction) -> Self {
+        BlobWriter {
+            trans,
+            buffer: Vec::new(),
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+
+    fn store_chunk(&mut self, size: usize) -> SRes<()> {
+        let id = self.trans.insert(BLOB_SEGMENT_NAME, &self.buffer[..size])?;
+        self.chunks.push(id);
+        self.buffer.drain(..size);
+        Ok(())
+    }
+
+    /// Store the remaining content and return the handle of the blob
+    pub fn finish(mut self) -> SRes<Blob> {
+        if !self.buffer.is_empty() {
+            self.store_chunk(self.buffer.len())?;
+        }
+        let head = BlobHead {
+            len: self.len,
+            chunks: std::mem::take(&mut self.chunks),
+        };
+        let mut buff = Vec::new();
+        head.write(&mut buff)?;
+        let id = self.trans.insert(BLOB_SEGMENT_NAME, &buff)?;
+        self.trans.put::<PersyId, PersyId>(PENDING_BLOBS_INDEX, id, id)?;
+        Ok(Blob { id })
+    }
+
+    /// Discard the blob removing the content already stored
+    pub fn abort(mut self) -> SRes<()> {
+        self.remove_chunks()
+    }
+
+    fn remove_chunks(&mut self) -> SRes<()> {
+        for chunk in std::mem::take(&mut self.chunks) {
+            self.trans.delete(BLOB_SEGMENT_NAME, &chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Drop for BlobWriter<'a> {
+    fn drop(&mut self) {
+        // Errors can't be reported from drop, the changes of the transaction are anyway
+        // discarded if it is not committed
+        let _ = self.remove_chunks();
+    }
+}
+
+impl<'a> Write for BlobWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        self.len += buf.len() as u64;
+        while self.buffer.len() >= CHUNK_SIZE {
+            self.store_chunk(CHUNK_SIZE).map_err(to_io_error)?;
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+pub(crate) enum ChunkSource<'a> {
+    Persy(Persy),
+    Snapshot(persy::Snapshot),
+    Tx(&'a mut Transaction),
+}
+
+impl<'a> ChunkSource<'a> {
+    fn read(&mut self, id: &PersyId) -> SRes<Option<Vec<u8>>> {
+        Ok(match self {
+            ChunkSource::Persy(p) => p.read(BLOB_SEGMENT_NAME, id)?,
+            ChunkSource::Snapshot(s) => s.read(BLOB_SEGMENT_NAME, id)?,
+            ChunkSource::Tx(t) => t.read(BLOB_SEGMENT_NAME, id)?,
+        })
+    }
+}
+
+/// Streaming reader of a blob content, the chunks are loaded only while read.
+pub struct BlobReader<'a> {
+    source: ChunkSource<'a>,
+    len: u64,
+    chunks: std::vec::IntoIter<PersyId>,
+    current: Cursor<Vec<u8>>,
+}
+
+impl<'a> BlobReader<'a> {
+    pub(crate) fn open(mut source: ChunkSource<'a>, blob: &Blob) -> SRes<Option<Self>> {
+        if let Some(data) = source.read(&blob.id)? {
+            let head = BlobHead::read(&mut Cursor::new(data))?;
+            Ok(Some(BlobReader {
+                source,
+                len: head.len,
+                chunks: head.chunks.into_iter(),
+                current: Cursor::new(Vec::new()),
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The size in bytes of the whole blob content
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// True if the blob has no content
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<'a> Read for BlobReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            if let Some(id) = self.chunks.next() {
+                let chunk = self.source.read(&id).map_err(to_io_error)?;
+                let chunk = chunk
+                    .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "missing blob chunk"))?;
+                self.current = Cursor::new(chunk);
+            } else {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+fn to_io_error(err: StructsyError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err)
+}
+
+pub(crate) fn delete_blob(trans: &mut Transaction, blob: &Blob) -> SRes<()> {
+    if let Some(data) = trans.read(BLOB_SEGMENT_NAME, &blob.id)? {
+        let head = BlobHead::read(&mut Cursor::new(data))?;
+        for chunk in head.chunks {
+            trans.delete(BLOB_SEGMENT_NAME, &chunk)?;
+        }
+        trans.delete(BLOB_SEGMENT_NAME, &blob.id)?;
+    }
+    Ok(())
+}
+
+/// Delete the blobs held by the old record that are not held by the new one
+pub(crate) fn remove_unused_blobs(trans: &mut Transaction, old: &Record, new: Option<&Record>) -> SRes<()> {
+    let mut old_blobs = Vec::new();
+    old.collect_blobs(&mut old_blobs)?;
+    remove_dropped_blobs(trans, &old_blobs, new)
+}
+
+/// Delete the blobs collected from an old record that are not held by the new one
+pub(crate) fn remove_dropped_blobs(trans: &mut Transaction, old_blobs: &[Blob], new: Option<&Record>) -> SRes<()> {
+    let mut new_blobs = Vec::new();
+    if let Some(rec) = new {
+        rec.collect_blobs(&mut new_blobs)?;
+    }
+    for blob in old_blobs.iter().filter(|b| !new_blobs.contains(b)) {
+        delete_blob(trans, blob)?;
+    }
+    Ok(())
+}
+
+/// Same as [`remove_unused_blobs`] starting from the serialized content of the records
+pub(crate) fn remove_unused_blobs_raw(
+    trans: &mut Transaction,
+    desc: &Description,
+    segment: &str,
+    id: &PersyId,
+    new: Option<&[u8]>,
+) -> SRes<()> {
+    if let Some(data) = trans.read(segment, id)? {
+        let old = Record::read(&mut Cursor::new(data), desc)?;
+        let new = new.map(|d| Record::read(&mut Cursor::new(d), desc)).transpose()?;
+        remove_unused_blobs(trans, &old, new.as_ref())?;
+    }
+    Ok(())
+}
+
+/// Mark the blobs held by a record as stored, so they are kept on commit
+pub(crate) fn attach_blobs(trans: &mut Transaction, record: &Record) -> SRes<()> {
+    let mut blobs = Vec::new();
+    record.collect_blobs(&mut blobs)?;
+    for blob in blobs {
+        trans.remove::<PersyId, PersyId>(PENDING_BLOBS_INDEX, blob.id, None)?;
+    }
+    Ok(())
+}
+
+/// Same as [`attach_blobs`] starting from the serialized content of the record
+pub(crate) fn attach_blobs_raw(trans: &mut Transaction, desc: &Description, data: &[u8]) -> SRes<()> {
+    let record = Record::read(&mut Cursor::new(data), desc)?;
+    attach_blobs(trans, &record)
+}
+
+/// Delete the blobs created in the transaction that are not stored in any record
+pub(crate) fn remove_pending_blobs(trans: &mut Transaction) -> SRes<()> {
+    let pending = trans
+        .range::<PersyId, PersyId, _>(PENDING_BLOBS_INDEX, ..)?
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    for id in pending {
+        delete_blob(trans, &Blob { id })?;
+        trans.remove::<PersyId, PersyId>(PENDING_BLOBS_INDEX, id, None)?;
+    }
+    Ok(())
+}
+
+/// Create the segment of the blobs and the index of the pending ones, if not there yet, they are
+/// created on the first definition of a type with blobs or on the first blob created
+pub(crate) fn init_blob_segment(persy: &Persy) -> SRes<()> {
+    if !persy.exists_segment(BLOB_SEGMENT_NAME)? {
+        let mut tx = persy.begin()?;
+        tx.create_segment(BLOB_SEGMENT_NAME)?;
+        tx.prepare()?.commit()?;
+    }
+    if !persy.exists_index(PENDING_BLOBS_INDEX)? {
+        let mut tx = persy.begin()?;
+        tx.create_index::<PersyId, PersyId>(PENDING_BLOBS_INDEX, ValueMode::Replace)?;
+        tx.prepare()?.commit()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+//! Date and time types of the `chrono` and `time` crates, stored as integers that keep
+//! the same order of the values so they can be indexed and queried by range.
+//!
+//! Instants are stored as nanoseconds since the unix epoch in UTC, dates as days since
+//! the unix epoch and durations as nanoseconds, so both crates share the same layout.
+//!
+use crate::{
+    desc::{SimpleType, SimpleValueType},
+    keyed::{impl_keyed_type, invalid_value as invalid, KeyedValue},
+    projection::Projection,
+    record::SimpleValue,
+    PersistentEmbedded, SRes,
+};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// Conversion of a date/time type from its order preserving integer key, that is also the stored value
+trait TimeValue: KeyedValue {
+    fn from_key(key: Self::Key) -> SRes<Self>;
+}
+
+/// Implements persistence, index and query traits of a date/time type,
+/// `$simple` is the value type variant, `$kt` the key type and `$key` its query variant
+macro_rules! impl_time_type {
+    ($t:ty, $simple:ident, $kt:ty, $key:ident) => {
+        impl PersistentEmbedded for $t {
+            fn write(&self, write: &mut dyn Write) -> SRes<()> {
+                self.to_key().write(write)
+            }
+            fn read(read: &mut dyn Read) -> SRes<Self> {
+                <$t>::from_key(<$kt>::read(read)?)
+            }
+        }
+
+        impl SimpleType for $t {
+            fn resolve() -> SimpleValueType {
+                SimpleValueType::$simple
+            }
+            fn new(self) -> SRes<SimpleValue> {
+                Ok(SimpleValue::$simple(self.to_key()))
+            }
+        }
+
+        impl Projection<$t> for $t {
+            fn projection(source: &$t) -> Self {
+                *source
+            }
+        }
+
+        impl_keyed_type!($t, $kt, $key);
+    };
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_types {
+    use super::*;
+    use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+
+    /// Days from the first of January of year 1 to the unix epoch
+    const UNIX_EPOCH_DAYS_FROM_CE: i32 = 719_163;
+
+    impl KeyedValue for DateTime<Utc> {
+        type Key = i128;
+        fn to_key(&self) -> i128 {
+            self.timestamp() as i128 * NANOS_PER_SEC + self.timestamp_subsec_nanos() as i128
+        }
+    }
+    impl TimeValue for DateTime<Utc> {
+        fn from_key(key: i128) -> SRes<Self> {
+            let secs = i64::try_from(key.div_euclid(NANOS_PER_SEC)).map_err(|_| invalid("DateTime", key))?;
+            let nanos = key.rem_euclid(NANOS_PER_SEC) as u32;
+            DateTime::from_timestamp(secs, nanos).ok_or_else(|| invalid("DateTime", key))
+        }
+    }
+
+    impl KeyedValue for NaiveDate {
+        type Key = i32;
+        fn to_key(&self) -> i32 {
+            self.num_days_from_ce() - UNIX_EPOCH_DAYS_FROM_CE
+        }
+    }
+    impl TimeValue for NaiveDate {
+        fn from_key(key: i32) -> SRes<Self> {
+            key.checked_add(UNIX_EPOCH_DAYS_FROM_CE)
+                .and_then(NaiveDate::from_num_days_from_ce_opt)
+                .ok_or_else(|| invalid("Date", key))
+        }
+    }
+
+    impl KeyedValue for Duration {
+        type Key = i128;
+        fn to_key(&self) -> i128 {
+            self.num_seconds() as i128 * NANOS_PER_SEC + self.subsec_nanos() as i128
+        }
+    }
+    impl TimeValue for Duration {
+        fn from_key(key: i128) -> SRes<Self> {
+            let secs = i64::try_from(key.div_euclid(NANOS_PER_SEC)).map_err(|_| invalid("Duration", key))?;
+            let nanos = key.rem_euclid(NANOS_PER_SEC) as u32;
+            Duration::new(secs, nanos).ok_or_else(|| invalid("Duration", key))
+        }
+    }
+
+    impl_time_type!(DateTime<Utc>, DateTime, i128, I128);
+    impl_time_type!(NaiveDate, Date, i32, I32);
+    impl_time_type!(Duration, Duration, i128, I128);
+}
+
+#[cfg(feature = "time")]
+mod time_types {
+    use super::*;
+    use time::{Date, Duration, OffsetDateTime};
+
+    /// Julian day number of the unix epoch
+    const UNIX_EPOCH_JULIAN_DAY: i32 = 2_440_588;
+
+    /// The offset is not stored, the value is read back in UTC
+    impl KeyedValue for OffsetDateTime {
+        type Key = i128;
+        fn to_key(&self) -> i128 {
+            self.unix_timestamp_nanos()
+        }
+    }
+    impl TimeValue for OffsetDateTime {
+        fn from_key(key: i128) -> SRes<Self> {
+            OffsetDateTime::from_unix_timestamp_nanos(key).map_err(|_| invalid("DateTime", key))
+        }
+    }
+
+    impl KeyedValue for Date {
+        type Key = i32;
+        fn to_key(&self) -> i32 {
+            self.to_julian_day() - UNIX_EPOCH_JULIAN_DAY
+        }
+    }
+    impl TimeValue for Date {
+        fn from_key(key: i32) -> SRes<Self> {
+            key.checked_add(UNIX_EPOCH_JULIAN_DAY)
+                .and_then(|day| Date::from_julian_day(day).ok())
+                .ok_or_else(|| invalid("Date", key))
+        }
+    }
+
+    impl KeyedValue for Duration {
+        type Key = i128;
+        fn to_key(&self) -> i128 {
+            self.whole_nanoseconds()
+        }
+    }
+    impl TimeValue for Duration {
+        fn from_key(key: i128) -> SRes<Self> {
+            let secs = i64::try_from(key / NANOS_PER_SEC).map_err(|_| invalid("Duration", key))?;
+            Ok(Duration::new(secs, (key % NANOS_PER_SEC) as i32))
+        }
+    }
+
+    impl_time_type!(OffsetDateTime, DateTime, i128, I128);
+    impl_time_type!(Date, Date, i32, I32);
+    impl_time_type!(Duration, Duration, i128, I128);
+}
@@ -1,19 +1,24 @@
 use crate::{
+    blob::init_blob_segment,
     filter_builder::query_model::{
         OptionRangeQueryValue, OptionVecRangeQueryValue, RangeQueryValue, VecRangeQueryValue,
     },
     filter_builder::Reader,
     format::PersistentEmbedded,
+    fulltext::{check_fulltext, fulltext_field_name},
+    geo::geo_field_name,
     internal::{EmbeddedDescription, Persistent},
     record::{Record, SimpleValue, Value},
     structsy::{StructsyImpl, INTERNAL_SEGMENT_NAME},
-    OwnedSytx, Ref, SRes, StructsyTx, Sytx,
+    vector::{check_vector_index, create_vector_index, drop_vector_index, rename_vector_index, vector_field_name},
+    Blob, OwnedSytx, Ref, SRes, StructsyError, StructsyTx, Sytx,
 };
 use data_encoding::BASE32_DNSSEC;
 use persy::{IndexType, PersyId, Transaction, ValueMode};
+use std::collections::{BTreeMap, HashMap};
 use std::io::{Cursor, Read, Write};
 use std::ops::Bound;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 
 pub struct StructDescriptionBuilder {
     desc: StructDescription,
@@ -24,6 +29,7 @@ impl StructDescriptionBuilder {
             desc: StructDescription {
                 name: name.to_string(),
                 fields: Vec::new(),
+                computed_indexes: Vec::new(),
             },
         }
     }
@@ -33,11 +39,24 @@ impl StructDescriptionBuilder {
             name,
             field_type,
             indexed,
+            index_condition: None,
+            fulltext: None,
+            vector: None,
+            geo: false,
         };
         self.desc.fields.push(field);
         self
     }
 
+    pub fn add_computed_index(mut self, name: String, value_type: ValueType, indexed: Option<ValueMode>) -> Self {
+        self.desc.computed_indexes.push(ComputedIndexDescription {
+            name,
+            value_type,
+            indexed,
+        });
+        self
+    }
+
     pub fn build(self) -> Description {
         Description::Struct(self.desc)
     }
@@ -60,6 +79,18 @@ impl EnumDescriptionBuilder {
             position,
             name: name.to_owned(),
             ty: value_type,
+            fields: Vec::new(),
+        };
+        self.en.variants.push(var);
+        self
+    }
+
+    pub fn add_fields_variant(mut self, position: u32, name: &str, fields: Vec<FieldDescription>) -> Self {
+        let var = VariantDescription {
+            position,
+            name: name.to_owned(),
+            ty: None,
+            fields,
         };
         self.en.variants.push(var);
         self
@@ -81,18 +112,27 @@ impl ValueTypeBuilder {
         }
     }
     pub fn option(st: SimpleValueType) -> Self {
+        Self::option_of(ValueType::Value(st))
+    }
+    pub fn array(st: SimpleValueType) -> Self {
+        Self::array_of(ValueType::Value(st))
+    }
+    pub fn option_array(st: SimpleValueType) -> Self {
+        Self::option_of(ValueType::Array(Box::new(ValueType::Value(st))))
+    }
+    pub fn option_of(value: ValueType) -> Self {
         Self {
-            t: Some(ValueType::Option(st)),
+            t: Some(ValueType::Option(Box::new(value))),
         }
     }
-    pub fn array(st: SimpleValueType) -> Self {
+    pub fn array_of(value: ValueType) -> Self {
         Self {
-            t: Some(ValueType::Array(st)),
+            t: Some(ValueType::Array(Box::new(value))),
         }
     }
-    pub fn option_array(st: SimpleValueType) -> Self {
+    pub fn map(key: SimpleValueType, value: SimpleValueType) -> Self {
         Self {
-            t: Some(ValueType::OptionArray(st)),
+            t: Some(ValueType::Map(key, value)),
         }
     }
     pub fn build(self) -> ValueType {
@@ -122,6 +162,14 @@ impl SimpleValueTypeBuilder {
                 "F64" => SimpleValueType::F64,
                 "Bool" => SimpleValueType::Bool,
                 "String" => SimpleValueType::String,
+                "Bytes" => SimpleValueType::Bytes,
+                "Blob" => SimpleValueType::Blob,
+                "DateTime" => SimpleValueType::DateTime,
+                "Date" => SimpleValueType::Date,
+                "Duration" => SimpleValueType::Duration,
+                "Char" => SimpleValueType::Char,
+                "Uuid" => SimpleValueType::Uuid,
+                "Decimal" => SimpleValueType::Decimal,
                 _ => {
                     if name.starts_with("Ref") {
                         panic!("use ref method for reference case");
@@ -144,6 +192,16 @@ impl SimpleValueTypeBuilder {
             t: Some(SimpleValueType::Ref(name)),
         }
     }
+    pub fn tuple(types: Vec<SimpleValueType>) -> Self {
+        Self {
+            t: Some(SimpleValueType::Tuple(types)),
+        }
+    }
+    pub fn fixed_array(st: SimpleValueType, len: u32) -> Self {
+        Self {
+            t: Some(SimpleValueType::FixedArray(Box::new(st), len)),
+        }
+    }
     pub fn build(self) -> SimpleValueType {
         self.t.expect("there is a type")
     }
@@ -168,38 +226,91 @@ pub enum SimpleValueType {
     String,
     Ref(String),
     Embedded(Description),
+    Bytes,
+    Blob,
+    /// Instant in UTC stored as nanoseconds since the unix epoch
+    DateTime,
+    /// Calendar date stored as days since the unix epoch
+    Date,
+    /// Signed duration stored as nanoseconds
+    Duration,
+    Char,
+    Uuid,
+    /// Decimal number stored as mantissa and scale
+    Decimal,
+    /// Tuple of values, indexed with a composite key of all the values
+    Tuple(Vec<SimpleValueType>),
+    /// Array of values with a fixed length
+    FixedArray(Box<SimpleValueType>, u32),
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ValueType {
     Value(SimpleValueType),
-    Option(SimpleValueType),
-    Array(SimpleValueType),
-    OptionArray(SimpleValueType),
+    Option(Box<ValueType>),
+    Array(Box<ValueType>),
+    Map(SimpleValueType, SimpleValueType),
 }
 
 impl ValueType {
-    pub(crate) fn index_score(&self, reader: &mut Reader, index_name: &str) -> SRes<usize> {
+    /// Name of the type used in the names of generic types instantiations
+    pub(crate) fn type_name(&self) -> String {
         match self {
-            ValueType::Value(v) => v.index_score(reader, index_name),
-            ValueType::Array(v) => v.index_score(reader, index_name),
-            ValueType::Option(v) => v.index_score(reader, index_name),
-            ValueType::OptionArray(v) => v.index_score(reader, index_name),
+            ValueType::Value(v) => v.type_name(),
+            ValueType::Option(v) => format!("Option<{}>", v.type_name()),
+            ValueType::Array(v) => format!("Vec<{}>", v.type_name()),
+            ValueType::Map(k, v) => format!("Map<{},{}>", k.type_name(), v.type_name()),
+        }
+    }
+
+    /// The simple type of the values inside options and arrays, `None` for maps
+    pub(crate) fn leaf(&self) -> Option<&SimpleValueType> {
+        match self {
+            ValueType::Value(v) => Some(v),
+            ValueType::Option(v) => v.leaf(),
+            ValueType::Array(v) => v.leaf(),
+            ValueType::Map(_, _) => None,
+        }
+    }
+
+    pub(crate) fn index_score(&self, reader: &mut Reader, index_name: &str) -> SRes<usize> {
+        match self.leaf() {
+            Some(v) => v.index_score(reader, index_name),
+            None => Ok(usize::MAX),
         }
     }
 
     pub(crate) fn default_range(&self) -> RangeQueryValue {
+        let leaf = self.leaf().expect("map fields are not indexable");
         match self {
             ValueType::Value(v) => v.default_range(),
-            ValueType::Array(v) => RangeQueryValue::Vec(v.default_range_vec()),
-            ValueType::Option(v) => RangeQueryValue::Option(v.default_range_option()),
-            ValueType::OptionArray(v) => RangeQueryValue::OptionVec(v.default_range_option_vec()),
+            ValueType::Array(_) => RangeQueryValue::Vec(leaf.default_range_vec()),
+            ValueType::Option(v) if matches!(**v, ValueType::Value(_)) => {
+                RangeQueryValue::Option(leaf.default_range_option())
+            }
+            ValueType::Option(_) => RangeQueryValue::OptionVec(leaf.default_range_option_vec()),
+            ValueType::Map(_, _) => unreachable!("map fields are not indexable"),
         }
     }
 }
 
 impl SimpleValueType {
+    fn type_name(&self) -> String {
+        match self {
+            SimpleValueType::Ref(t) => format!("Ref<{}>", t),
+            SimpleValueType::Embedded(t) => t.get_name(),
+            SimpleValueType::Tuple(types) => {
+                format!(
+                    "({})",
+                    types.iter().map(|t| t.type_name()).collect::<Vec<_>>().join(",")
+                )
+            }
+            SimpleValueType::FixedArray(t, len) => format!("[{};{}]", t.type_name(), len),
+            other => other.to_string(),
+        }
+    }
+
     fn read(read: &mut dyn Read) -> SRes<SimpleValueType> {
         let sv = u8::read(read)?;
         Ok(match sv {
@@ -225,6 +336,26 @@ impl SimpleValueType {
                 let s = Description::read(read)?;
                 SimpleValueType::Embedded(s)
             }
+            17 => SimpleValueType::Bytes,
+            18 => SimpleValueType::Blob,
+            19 => SimpleValueType::DateTime,
+            20 => SimpleValueType::Date,
+            21 => SimpleValueType::Duration,
+            22 => SimpleValueType::Char,
+            23 => SimpleValueType::Uuid,
+            24 => SimpleValueType::Decimal,
+            25 => {
+                let len = u32::read(read)?;
+                let mut types = Vec::new();
+                for _ in 0..len {
+                    types.push(SimpleValueType::read(read)?);
+                }
+                SimpleValueType::Tuple(types)
+            }
+            26 => {
+                let t = SimpleValueType::read(read)?;
+                SimpleValueType::FixedArray(Box::new(t), u32::read(read)?)
+            }
             _ => panic!("error on de-serialization"),
         })
     }
@@ -252,6 +383,26 @@ impl SimpleValueType {
                 u8::write(&16, write)?;
                 t.write(write)?;
             }
+            SimpleValueType::Bytes => u8::write(&17, write)?,
+            SimpleValueType::Blob => u8::write(&18, write)?,
+            SimpleValueType::DateTime => u8::write(&19, write)?,
+            SimpleValueType::Date => u8::write(&20, write)?,
+            SimpleValueType::Duration => u8::write(&21, write)?,
+            SimpleValueType::Char => u8::write(&22, write)?,
+            SimpleValueType::Uuid => u8::write(&23, write)?,
+            SimpleValueType::Decimal => u8::write(&24, write)?,
+            SimpleValueType::Tuple(types) => {
+                u8::write(&25, write)?;
+                u32::write(&(types.len() as u32), write)?;
+                for t in types {
+                    t.write(write)?;
+                }
+            }
+            SimpleValueType::FixedArray(t, len) => {
+                u8::write(&26, write)?;
+                t.write(write)?;
+                u32::write(len, write)?;
+            }
         }
         Ok(())
     }
@@ -281,7 +432,71 @@ impl SimpleValueType {
             SimpleValueType::Ref(_) => {
                 create_index::<PersyId>(tx, type_name, name, value_mode)?;
             }
+            SimpleValueType::Embedded(Description::Enum(_)) => create_index::<u32>(tx, type_name, name, value_mode)?,
             SimpleValueType::Embedded(_v) => (),
+            SimpleValueType::DateTime => create_index::<i128>(tx, type_name, name, value_mode)?,
+            SimpleValueType::Date => create_index::<i32>(tx, type_name, name, value_mode)?,
+            SimpleValueType::Duration => create_index::<i128>(tx, type_name, name, value_mode)?,
+            SimpleValueType::Char => create_index::<u32>(tx, type_name, name, value_mode)?,
+            SimpleValueType::Uuid => create_index::<u128>(tx, type_name, name, value_mode)?,
+            SimpleValueType::Decimal => create_index::<String>(tx, type_name, name, value_mode)?,
+            SimpleValueType::Tuple(_) if self.is_key_part() => create_index::<String>(tx, type_name, name, value_mode)?,
+            SimpleValueType::Bytes
+            | SimpleValueType::Blob
+            | SimpleValueType::Tuple(_)
+            | SimpleValueType::FixedArray(..) => {
+                return Err(StructsyError::TypeError(format!(
+                    "field '{}' of '{}' of type {} can not be indexed",
+                    name, type_name, self
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn rename_index(
+        &self,
+        tx: &mut persy::Transaction,
+        old_type: &str,
+        new_type: &str,
+        name: &str,
+        value_mode: ValueMode,
+    ) -> SRes<()> {
+        match self {
+            SimpleValueType::U8 => rename_index::<u8>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::U16 => rename_index::<u16>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::U32 => rename_index::<u32>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::U64 => rename_index::<u64>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::U128 => rename_index::<u128>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::I8 => rename_index::<i8>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::I16 => rename_index::<i16>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::I32 => rename_index::<i32>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::I64 => rename_index::<i64>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::I128 => rename_index::<i128>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::F32 => rename_index::<f32>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::F64 => rename_index::<f64>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::Bool => (),
+            SimpleValueType::String => rename_index::<String>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::Ref(_) => {
+                rename_index::<PersyId>(tx, old_type, new_type, name, value_mode)?;
+            }
+            SimpleValueType::Embedded(Description::Enum(_)) => {
+                rename_index::<u32>(tx, old_type, new_type, name, value_mode)?
+            }
+            SimpleValueType::Embedded(_v) => (),
+            SimpleValueType::Bytes => (),
+            SimpleValueType::Blob => (),
+            SimpleValueType::DateTime => rename_index::<i128>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::Date => rename_index::<i32>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::Duration => rename_index::<i128>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::Char => rename_index::<u32>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::Uuid => rename_index::<u128>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::Decimal => rename_index::<String>(tx, old_type, new_type, name, value_mode)?,
+            SimpleValueType::Tuple(_) if self.is_key_part() => {
+                rename_index::<String>(tx, old_type, new_type, name, value_mode)?
+            }
+            SimpleValueType::Tuple(_) => (),
+            SimpleValueType::FixedArray(..) => (),
         }
         Ok(())
     }
@@ -303,7 +518,18 @@ impl SimpleValueType {
             SimpleValueType::Bool => Ok(usize::MAX),
             SimpleValueType::String => String::finder().score(reader, index_name, None),
             SimpleValueType::Ref(_) => Ok(usize::MAX),
+            SimpleValueType::Embedded(Description::Enum(_)) => u32::finder().score(reader, index_name, None),
             SimpleValueType::Embedded(_v) => Ok(usize::MAX),
+            SimpleValueType::Bytes => Ok(usize::MAX),
+            SimpleValueType::Blob => Ok(usize::MAX),
+            SimpleValueType::DateTime => i128::finder().score(reader, index_name, None),
+            SimpleValueType::Date => i32::finder().score(reader, index_name, None),
+            SimpleValueType::Duration => i128::finder().score(reader, index_name, None),
+            SimpleValueType::Char => u32::finder().score(reader, index_name, None),
+            SimpleValueType::Uuid => u128::finder().score(reader, index_name, None),
+            SimpleValueType::Decimal => String::finder().score(reader, index_name, None),
+            SimpleValueType::Tuple(_) => String::finder().score(reader, index_name, None),
+            SimpleValueType::FixedArray(..) => Ok(usize::MAX),
         }
     }
 
@@ -325,6 +551,16 @@ impl SimpleValueType {
             SimpleValueType::String => RangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
             SimpleValueType::Ref(_) => RangeQueryValue::Ref((Bound::Unbounded, Bound::Unbounded)),
             SimpleValueType::Embedded(_v) => RangeQueryValue::Embedded((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::DateTime => RangeQueryValue::I128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Date => RangeQueryValue::I32((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Duration => RangeQueryValue::I128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Char => RangeQueryValue::U32((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Uuid => RangeQueryValue::U128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Decimal => RangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Tuple(_) => RangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Bytes => unreachable!("byte fields are not indexable"),
+            SimpleValueType::Blob => unreachable!("blob fields are not indexable"),
+            SimpleValueType::FixedArray(..) => unreachable!("fixed array fields are not indexable"),
         }
     }
 
@@ -346,6 +582,16 @@ impl SimpleValueType {
             SimpleValueType::String => OptionVecRangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
             SimpleValueType::Ref(_) => OptionVecRangeQueryValue::Ref((Bound::Unbounded, Bound::Unbounded)),
             SimpleValueType::Embedded(_v) => OptionVecRangeQueryValue::Embedded((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::DateTime => OptionVecRangeQueryValue::I128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Date => OptionVecRangeQueryValue::I32((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Duration => OptionVecRangeQueryValue::I128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Char => OptionVecRangeQueryValue::U32((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Uuid => OptionVecRangeQueryValue::U128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Decimal => OptionVecRangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Tuple(_) => OptionVecRangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Bytes => unreachable!("byte fields are not indexable"),
+            SimpleValueType::Blob => unreachable!("blob fields are not indexable"),
+            SimpleValueType::FixedArray(..) => unreachable!("fixed array fields are not indexable"),
         }
     }
 
@@ -367,6 +613,16 @@ impl SimpleValueType {
             SimpleValueType::String => OptionRangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
             SimpleValueType::Ref(_) => OptionRangeQueryValue::Ref((Bound::Unbounded, Bound::Unbounded)),
             SimpleValueType::Embedded(_v) => OptionRangeQueryValue::Embedded((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::DateTime => OptionRangeQueryValue::I128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Date => OptionRangeQueryValue::I32((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Duration => OptionRangeQueryValue::I128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Char => OptionRangeQueryValue::U32((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Uuid => OptionRangeQueryValue::U128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Decimal => OptionRangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Tuple(_) => OptionRangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Bytes => unreachable!("byte fields are not indexable"),
+            SimpleValueType::Blob => unreachable!("blob fields are not indexable"),
+            SimpleValueType::FixedArray(..) => unreachable!("fixed array fields are not indexable"),
         }
     }
 
@@ -388,6 +644,49 @@ impl SimpleValueType {
             SimpleValueType::String => VecRangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
             SimpleValueType::Ref(_) => VecRangeQueryValue::Ref((Bound::Unbounded, Bound::Unbounded)),
             SimpleValueType::Embedded(_v) => VecRangeQueryValue::Embedded((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::DateTime => VecRangeQueryValue::I128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Date => VecRangeQueryValue::I32((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Duration => VecRangeQueryValue::I128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Char => VecRangeQueryValue::U32((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Uuid => VecRangeQueryValue::U128((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Decimal => VecRangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Tuple(_) => VecRangeQueryValue::String((Bound::Unbounded, Bound::Unbounded)),
+            SimpleValueType::Bytes => unreachable!("byte fields are not indexable"),
+            SimpleValueType::Blob => unreachable!("blob fields are not indexable"),
+            SimpleValueType::FixedArray(..) => unreachable!("fixed array fields are not indexable"),
+        }
+    }
+
+    /// Check if the values of the type can be part of the composite key of a tuple
+    fn is_key_part(&self) -> bool {
+        match self {
+            SimpleValueType::Ref(_)
+            | SimpleValueType::Embedded(_)
+            | SimpleValueType::Bytes
+            | SimpleValueType::Blob
+            | SimpleValueType::FixedArray(..) => false,
+            SimpleValueType::Tuple(types) => types.iter().all(SimpleValueType::is_key_part),
+            _ => true,
+        }
+    }
+
+    fn refers(&self, name: &str) -> bool {
+        match self {
+            SimpleValueType::Ref(t) => t == name,
+            SimpleValueType::Embedded(t) => t.refers(name),
+            SimpleValueType::Tuple(types) => types.iter().any(|t| t.refers(name)),
+            SimpleValueType::FixedArray(t, _) => t.refers(name),
+            _ => false,
+        }
+    }
+
+    fn has_blobs(&self) -> bool {
+        match self {
+            SimpleValueType::Blob => true,
+            SimpleValueType::Embedded(t) => t.has_blobs(),
+            SimpleValueType::Tuple(types) => types.iter().any(SimpleValueType::has_blobs),
+            SimpleValueType::FixedArray(t, _) => t.has_blobs(),
+            _ => false,
         }
     }
 
@@ -402,12 +701,21 @@ impl SimpleValueType {
                 }
             }
             SimpleValueType::Embedded(t) => t.remap_refer(old, new),
+            SimpleValueType::Tuple(types) => {
+                let mut changed = false;
+                for t in types {
+                    changed |= t.remap_refer(old, new);
+                }
+                changed
+            }
+            SimpleValueType::FixedArray(t, _) => t.remap_refer(old, new),
             _ => false,
         }
     }
 }
 
-pub(crate) fn index_name(type_name: &str, field_path: &[&str]) -> String {
+/// Name of the index of the field at `field_path` of the type `type_name`
+pub fn index_name(type_name: &str, field_path: &[&str]) -> String {
     format!("{}.{}", type_name, field_path.join("."))
 }
 
@@ -415,13 +723,42 @@ fn create_index<T: IndexType>(tx: &mut Transaction, type_name: &str, name: &str,
     tx.create_index::<T, PersyId>(&index_name(type_name, &[name]), value_mode)?;
     Ok(())
 }
+
+fn rename_index<T: IndexType>(
+    tx: &mut Transaction,
+    old_type: &str,
+    new_type: &str,
+    name: &str,
+    value_mode: ValueMode,
+) -> SRes<()> {
+    let old_name = index_name(old_type, &[name]);
+    let new_name = index_name(new_type, &[name]);
+    tx.create_index::<T, PersyId>(&new_name, value_mode)?;
+    let entries = tx
+        .range::<T, PersyId, _>(&old_name, ..)?
+        .map(|(k, v)| (k, v.into_iter().collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    for (k, ids) in entries {
+        for id in ids {
+            tx.put::<T, PersyId>(&new_name, k.clone(), id)?;
+        }
+    }
+    tx.drop_index(&old_name)?;
+    Ok(())
+}
 impl std::fmt::Display for ValueType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ValueType::Value(s) => write!(f, "Value<{}>", s),
-            ValueType::Option(v) => write!(f, "Option<{}>", v),
-            ValueType::Array(v) => write!(f, "Array<{}>", v),
-            ValueType::OptionArray(v) => write!(f, "OptionArray<{}>", v),
+            ValueType::Option(v) => match &**v {
+                ValueType::Value(s) => write!(f, "Option<{}>", s),
+                other => write!(f, "Option<{}>", other),
+            },
+            ValueType::Array(v) => match &**v {
+                ValueType::Value(s) => write!(f, "Array<{}>", s),
+                other => write!(f, "Array<{}>", other),
+            },
+            ValueType::Map(k, v) => write!(f, "Map<{},{}>", k, v),
         }
     }
 }
@@ -436,7 +773,7 @@ impl std::fmt::Display for SimpleValueType {
             SimpleValueType::I8 => write!(f, "I8"),
             SimpleValueType::I16 => write!(f, "I16"),
             SimpleValueType::I32 => write!(f, "I32"),
-            SimpleValueType::I64 => write!(f, "I63"),
+            SimpleValueType::I64 => write!(f, "I64"),
             SimpleValueType::I128 => write!(f, "I128"),
             SimpleValueType::F32 => write!(f, "F32"),
             SimpleValueType::F64 => write!(f, "F64"),
@@ -444,18 +781,93 @@ impl std::fmt::Display for SimpleValueType {
             SimpleValueType::String => write!(f, "String"),
             SimpleValueType::Ref(t) => write!(f, "Ref#{}", t),
             SimpleValueType::Embedded(t) => write!(f, "Embedded#{}", t.get_name()),
+            SimpleValueType::Bytes => write!(f, "Bytes"),
+            SimpleValueType::Blob => write!(f, "Blob"),
+            SimpleValueType::DateTime => write!(f, "DateTime"),
+            SimpleValueType::Date => write!(f, "Date"),
+            SimpleValueType::Duration => write!(f, "Duration"),
+            SimpleValueType::Char => write!(f, "Char"),
+            SimpleValueType::Uuid => write!(f, "Uuid"),
+            SimpleValueType::Decimal => write!(f, "Decimal"),
+            SimpleValueType::Tuple(types) => {
+                write!(f, "(")?;
+                for (pos, t) in types.iter().enumerate() {
+                    if pos > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", t)?;
+                }
+                write!(f, ")")
+            }
+            SimpleValueType::FixedArray(t, len) => write!(f, "[{};{}]", t, len),
         }
     }
 }
 
+/// Name of an instantiation of a generic persistent type, like `Wrapper<Customer>`, built
+/// from the names of the type parameters and kept for the whole process life
+pub fn generic_name(name: &str, params: &[ValueType]) -> &'static str {
+    static NAMES: OnceLock<Mutex<HashMap<String, &'static str>>> = OnceLock::new();
+    let params = params.iter().map(|p| p.type_name()).collect::<Vec<_>>();
+    let full_name = format!("{}<{}>", name, params.join(","));
+    let mut names = NAMES
+        .get_or_init(Default::default)
+        .lock()
+        .expect("generic names lock not poisoned");
+    names
+        .entry(full_name)
+        .or_insert_with_key(|key| Box::leak(key.clone().into_boxed_str()))
+}
+
 pub trait SupportedType {
     fn resolve() -> ValueType;
     fn new(self) -> SRes<Value>;
+    #[doc(hidden)]
+    fn resolve_vec() -> ValueType {
+        ValueType::Array(Box::new(Self::resolve()))
+    }
+    #[doc(hidden)]
+    fn new_vec(vec: Vec<Self>) -> SRes<Value>
+    where
+        Self: Sized,
+    {
+        Ok(Value::Array(
+            vec.into_iter().map(|v| v.new()).collect::<SRes<Vec<Value>>>()?,
+        ))
+    }
 }
 
 pub trait SimpleType {
     fn resolve() -> SimpleValueType;
     fn new(self) -> SRes<SimpleValue>;
+    #[doc(hidden)]
+    fn resolve_vec() -> ValueType {
+        ValueType::Array(Box::new(ValueType::Value(Self::resolve())))
+    }
+    #[doc(hidden)]
+    fn new_vec(vec: Vec<Self>) -> SRes<Value>
+    where
+        Self: Sized,
+    {
+        Ok(Value::Array(
+            vec.into_iter()
+                .map(|v| Ok(Value::Value(v.new()?)))
+                .collect::<SRes<Vec<Value>>>()?,
+        ))
+    }
+    #[doc(hidden)]
+    fn resolve_fixed_array(len: usize) -> SimpleValueType {
+        SimpleValueType::FixedArray(Box::new(Self::resolve()), len as u32)
+    }
+    #[doc(hidden)]
+    fn new_fixed_array(vec: Vec<Self>) -> SRes<SimpleValue>
+    where
+        Self: Sized,
+    {
+        Ok(SimpleValue::FixedArray(
+            vec.into_iter().map(|v| v.new()).collect::<SRes<Vec<SimpleValue>>>()?,
+        ))
+    }
 }
 
 macro_rules! impl_field_type {
@@ -471,7 +883,26 @@ macro_rules! impl_field_type {
     };
 }
 
-impl_field_type!(u8, U8);
+impl SimpleType for u8 {
+    fn resolve() -> SimpleValueType {
+        SimpleValueType::U8
+    }
+    fn new(self) -> SRes<SimpleValue> {
+        Ok(SimpleValue::U8(self))
+    }
+    fn resolve_vec() -> ValueType {
+        ValueType::Value(SimpleValueType::Bytes)
+    }
+    fn new_vec(vec: Vec<u8>) -> SRes<Value> {
+        Ok(Value::Value(SimpleValue::Bytes(vec)))
+    }
+    fn resolve_fixed_array(_len: usize) -> SimpleValueType {
+        SimpleValueType::Bytes
+    }
+    fn new_fixed_array(vec: Vec<u8>) -> SRes<SimpleValue> {
+        Ok(SimpleValue::Bytes(vec))
+    }
+}
 impl_field_type!(u16, U16);
 impl_field_type!(u32, U32);
 impl_field_type!(u64, U64);
@@ -485,6 +916,73 @@ impl_field_type!(f32, F32);
 impl_field_type!(f64, F64);
 impl_field_type!(bool, Bool);
 impl_field_type!(String, String);
+impl_field_type!(char, Char);
+
+impl SimpleType for Blob {
+    fn resolve() -> SimpleValueType {
+        SimpleValueType::Blob
+    }
+    fn new(self) -> SRes<SimpleValue> {
+        Ok(SimpleValue::Blob(format!("{}", self.id)))
+    }
+}
+
+impl<T: SimpleType, const N: usize> SimpleType for [T; N] {
+    fn resolve() -> SimpleValueType {
+        T::resolve_fixed_array(N)
+    }
+    fn new(self) -> SRes<SimpleValue> {
+        T::new_fixed_array(Vec::from(self))
+    }
+}
+
+macro_rules! impl_tuple_type {
+    ($($t:ident $pos:tt),+) => {
+        impl<$($t: SimpleType),+> SimpleType for ($($t,)+) {
+            fn resolve() -> SimpleValueType {
+                SimpleValueType::Tuple(vec![$($t::resolve()),+])
+            }
+            fn new(self) -> SRes<SimpleValue> {
+                Ok(SimpleValue::Tuple(vec![$(self.$pos.new()?),+]))
+            }
+        }
+    };
+}
+impl_tuple_type!(A 0, B 1);
+impl_tuple_type!(A 0, B 1, C 2);
+impl_tuple_type!(A 0, B 1, C 2, D 3);
+impl_tuple_type!(A 0, B 1, C 2, D 3, E 4);
+impl_tuple_type!(A 0, B 1, C 2, D 3, E 4, F 5);
+
+#[cfg(feature = "uuid")]
+impl SimpleType for uuid::Uuid {
+    fn resolve() -> SimpleValueType {
+        SimpleValueType::Uuid
+    }
+    fn new(self) -> SRes<SimpleValue> {
+        Ok(SimpleValue::Uuid(self.as_u128()))
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl SimpleType for rust_decimal::Decimal {
+    fn resolve() -> SimpleValueType {
+        SimpleValueType::Decimal
+    }
+    fn new(self) -> SRes<SimpleValue> {
+        Ok(SimpleValue::Decimal(self.mantissa(), self.scale()))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl SimpleType for bytes::Bytes {
+    fn resolve() -> SimpleValueType {
+        SimpleValueType::Bytes
+    }
+    fn new(self) -> SRes<SimpleValue> {
+        Ok(SimpleValue::Bytes(self.to_vec()))
+    }
+}
 
 impl<T: Persistent> SimpleType for Ref<T> {
     fn resolve() -> SimpleValueType {
@@ -515,36 +1013,54 @@ impl<T: SimpleType> SupportedType for T {
     fn new(self) -> SRes<Value> {
         Ok(Value::Value(self.new()?))
     }
+    fn resolve_vec() -> ValueType {
+        <T as SimpleType>::resolve_vec()
+    }
+    fn new_vec(vec: Vec<Self>) -> SRes<Value> {
+        <T as SimpleType>::new_vec(vec)
+    }
+}
+
+impl<T: SupportedType> SupportedType for Option<T> {
+    fn resolve() -> ValueType {
+        ValueType::Option(Box::new(T::resolve()))
+    }
+    fn new(self) -> SRes<Value> {
+        Ok(Value::Option(self.map(|v| v.new().map(Box::new)).transpose()?))
+    }
 }
 
-impl<T: SimpleType> SupportedType for Option<T> {
+impl<T: SupportedType> SupportedType for Vec<T> {
     fn resolve() -> ValueType {
-        ValueType::Option(T::resolve())
+        T::resolve_vec()
     }
     fn new(self) -> SRes<Value> {
-        Ok(Value::Option(self.map(|v| v.new()).transpose()?))
+        T::new_vec(self)
     }
 }
 
-impl<T: SimpleType> SupportedType for Option<Vec<T>> {
+impl<K: SimpleType, V: SimpleType> SupportedType for BTreeMap<K, V> {
     fn resolve() -> ValueType {
-        ValueType::OptionArray(T::resolve())
+        ValueType::Map(K::resolve(), V::resolve())
     }
     fn new(self) -> SRes<Value> {
-        Ok(Value::OptionArray(
-            self.map(|vec| vec.into_iter().map(|v| v.new()).collect::<SRes<Vec<SimpleValue>>>())
-                .transpose()?,
+        Ok(Value::Map(
+            self.into_iter()
+                .map(|(k, v)| Ok((k.new()?, v.new()?)))
+                .collect::<SRes<Vec<(SimpleValue, SimpleValue)>>>()?,
         ))
     }
 }
 
-impl<T: SimpleType> SupportedType for Vec<T> {
+impl<K: SimpleType, V: SimpleType> SupportedType for HashMap<K, V> {
     fn resolve() -> ValueType {
-        ValueType::Array(T::resolve())
+        ValueType::Map(K::resolve(), V::resolve())
     }
     fn new(self) -> SRes<Value> {
-        Ok(Value::Array(
-            self.into_iter().map(|v| v.new()).collect::<SRes<Vec<SimpleValue>>>()?,
+        Ok(Value::Map(
+            self.into_iter()
+                .map(|(k, v)| Ok((k.new()?, v.new()?)))
+                .collect::<SRes<Vec<(SimpleValue, SimpleValue)>>>()?,
         ))
     }
 }
@@ -558,39 +1074,111 @@ impl ValueType {
         let t = u8::read(read)?;
         Ok(match t {
             1 => ValueType::Value(SimpleValueType::read(read)?),
-            2 => ValueType::Option(SimpleValueType::read(read)?),
-            3 => ValueType::Array(SimpleValueType::read(read)?),
-            4 => ValueType::OptionArray(SimpleValueType::read(read)?),
+            2 => ValueType::Option(Box::new(ValueType::Value(SimpleValueType::read(read)?))),
+            3 => ValueType::Array(Box::new(ValueType::Value(SimpleValueType::read(read)?))),
+            4 => {
+                let array = ValueType::Array(Box::new(ValueType::Value(SimpleValueType::read(read)?)));
+                ValueType::Option(Box::new(array))
+            }
+            5 => ValueType::Map(SimpleValueType::read(read)?, SimpleValueType::read(read)?),
+            6 => ValueType::Option(Box::new(ValueType::read(read)?)),
+            7 => ValueType::Array(Box::new(ValueType::read(read)?)),
             _ => panic!("invalid value"),
         })
     }
+
+    /// Byte arrays were stored as arrays of `U8` before the `Bytes` type, the data layout
+    /// is the same so the old descriptions can be read as `Bytes`
+    fn normalize_bytes(self) -> ValueType {
+        match self {
+            ValueType::Array(t) if *t == ValueType::Value(SimpleValueType::U8) => {
+                ValueType::Value(SimpleValueType::Bytes)
+            }
+            ValueType::Array(t) => ValueType::Array(Box::new(t.normalize_bytes())),
+            ValueType::Option(t) => ValueType::Option(Box::new(t.normalize_bytes())),
+            other => other,
+        }
+    }
+
+    /// Indexed byte arrays keep the description as arrays of `U8`, indexed by element as before
+    /// the `Bytes` type
+    pub(crate) fn indexed_bytes(self) -> ValueType {
+        match self {
+            ValueType::Value(SimpleValueType::Bytes) => {
+                ValueType::Array(Box::new(ValueType::Value(SimpleValueType::U8)))
+            }
+            ValueType::Option(t) => ValueType::Option(Box::new(t.indexed_bytes())),
+            other => other,
+        }
+    }
+
+    /// The single level forms keep the tags used before nested options and arrays,
+    /// deeper nesting is written with its own tags followed by the inner type
     fn write(&self, write: &mut dyn Write) -> SRes<()> {
         match self {
             ValueType::Value(t) => {
                 u8::write(&1, write)?;
                 t.write(write)?;
             }
-            ValueType::Option(t) => {
-                u8::write(&2, write)?;
-                t.write(write)?;
-            }
-            ValueType::Array(t) => {
-                u8::write(&3, write)?;
-                t.write(write)?;
-            }
-            ValueType::OptionArray(t) => {
-                u8::write(&4, write)?;
-                t.write(write)?;
+            ValueType::Option(t) => match &**t {
+                ValueType::Value(t) => {
+                    u8::write(&2, write)?;
+                    t.write(write)?;
+                }
+                ValueType::Array(a) if matches!(**a, ValueType::Value(_)) => {
+                    u8::write(&4, write)?;
+                    a.leaf().expect("array of values").write(write)?;
+                }
+                other => {
+                    u8::write(&6, write)?;
+                    other.write(write)?;
+                }
+            },
+            ValueType::Array(t) => match &**t {
+                ValueType::Value(t) => {
+                    u8::write(&3, write)?;
+                    t.write(write)?;
+                }
+                other => {
+                    u8::write(&7, write)?;
+                    other.write(write)?;
+                }
+            },
+            ValueType::Map(k, v) => {
+                u8::write(&5, write)?;
+                k.write(write)?;
+                v.write(write)?;
             }
         }
         Ok(())
     }
-    fn remap_refer(&mut self, new: &str, old: &str) -> bool {
+    fn refers(&self, name: &str) -> bool {
         match self {
-            ValueType::Array(ref mut t) => t.remap_refer(new, old),
-            ValueType::Option(ref mut t) => t.remap_refer(new, old),
-            ValueType::OptionArray(ref mut t) => t.remap_refer(new, old),
-            ValueType::Value(ref mut t) => t.remap_refer(new, old),
+            ValueType::Array(t) => t.refers(name),
+            ValueType::Option(t) => t.refers(name),
+            ValueType::Value(t) => t.refers(name),
+            ValueType::Map(k, v) => k.refers(name) || v.refers(name),
+        }
+    }
+
+    fn has_blobs(&self) -> bool {
+        match self {
+            ValueType::Array(t) => t.has_blobs(),
+            ValueType::Option(t) => t.has_blobs(),
+            ValueType::Value(t) => t.has_blobs(),
+            ValueType::Map(k, v) => k.has_blobs() || v.has_blobs(),
+        }
+    }
+
+    fn remap_refer(&mut self, old: &str, new: &str) -> bool {
+        match self {
+            ValueType::Array(ref mut t) => t.remap_refer(old, new),
+            ValueType::Option(ref mut t) => t.remap_refer(old, new),
+            ValueType::Value(ref mut t) => t.remap_refer(old, new),
+            ValueType::Map(ref mut k, ref mut v) => {
+                let key = k.remap_refer(old, new);
+                v.remap_refer(old, new) || key
+            }
         }
     }
 
@@ -601,11 +1189,26 @@ impl ValueType {
         name: &str,
         value_mode: ValueMode,
     ) -> SRes<()> {
-        match self {
-            ValueType::Value(t) => t.create_index(tx, type_name, name, value_mode),
-            ValueType::Option(t) => t.create_index(tx, type_name, name, value_mode),
-            ValueType::Array(t) => t.create_index(tx, type_name, name, value_mode),
-            ValueType::OptionArray(t) => t.create_index(tx, type_name, name, value_mode),
+        match self.leaf() {
+            Some(t) => t.create_index(tx, type_name, name, value_mode),
+            None => Err(StructsyError::TypeError(format!(
+                "field '{}' of '{}' is a map and can not be indexed",
+                name, type_name
+            ))),
+        }
+    }
+
+    pub(crate) fn rename_index(
+        &self,
+        tx: &mut persy::Transaction,
+        old_type: &str,
+        new_type: &str,
+        name: &str,
+        value_mode: ValueMode,
+    ) -> SRes<()> {
+        match self.leaf() {
+            Some(t) => t.rename_index(tx, old_type, new_type, name, value_mode),
+            None => Ok(()),
         }
     }
 }
@@ -645,6 +1248,56 @@ pub(crate) fn value_mode_deserialize<'de, D: serde::Deserializer<'de>>(
     }
 }
 
+/// Condition of a partial index, only the records where the boolean field has the given value
+/// are put in the index of the field
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexCondition {
+    pub(crate) field: String,
+    pub(crate) value: bool,
+}
+
+impl IndexCondition {
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn value(&self) -> bool {
+        self.value
+    }
+}
+
+/// Full-text index of a string field, with the optional stemming language of the tokens
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FullTextIndex {
+    pub(crate) stem: Option<String>,
+}
+
+impl FullTextIndex {
+    pub fn stem(&self) -> Option<&str> {
+        self.stem.as_deref()
+    }
+}
+
+/// Approximate nearest neighbour index of a vector field, with the dimension of the vectors
+/// and the metric used to compare them
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VectorIndex {
+    pub(crate) dim: u32,
+    pub(crate) metric: String,
+}
+
+impl VectorIndex {
+    pub fn dim(&self) -> u32 {
+        self.dim
+    }
+    pub fn metric(&self) -> &str {
+        &self.metric
+    }
+}
+
 /// Field metadata for internal use
 #[derive(PartialEq, Eq, Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -657,51 +1310,175 @@ pub struct FieldDescription {
         serde(serialize_with = "value_mode_serialize", deserialize_with = "value_mode_deserialize")
     )]
     pub(crate) indexed: Option<ValueMode>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) index_condition: Option<IndexCondition>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) fulltext: Option<FullTextIndex>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) vector: Option<VectorIndex>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) geo: bool,
 }
 
+/// Flag set on the index mode byte when the index condition follows
+const INDEX_CONDITION_FLAG: u8 = 0x10;
+/// Flag set on the index mode byte when the full-text index definition follows
+const FULLTEXT_FLAG: u8 = 0x20;
+/// Flag set on the index mode byte when the vector index definition follows
+const VECTOR_FLAG: u8 = 0x40;
+/// Flag set on the index mode byte when the field has a geo index
+const GEO_FLAG: u8 = 0x80;
+
 impl FieldDescription {
     pub fn new<T: SupportedType>(position: u32, name: &str, indexed: Option<ValueMode>) -> FieldDescription {
+        let field_type = ValueType::resolve::<T>();
+        let field_type = if indexed.is_some() {
+            field_type.indexed_bytes()
+        } else {
+            field_type
+        };
         FieldDescription {
             position,
             name: name.to_string(),
-            field_type: ValueType::resolve::<T>(),
+            field_type,
             indexed,
+            index_condition: None,
+            fulltext: None,
+            vector: None,
+            geo: false,
         }
     }
+
+    /// Make the index of this field partial, indexing only the records where the boolean
+    /// `field` of the same struct is equal to `value`
+    pub fn with_index_condition(mut self, field: &str, value: bool) -> FieldDescription {
+        self.index_condition = Some(IndexCondition {
+            field: field.to_string(),
+            value,
+        });
+        self
+    }
+
+    /// Add a full-text index on this field, with the tokens optionally reduced to the stem of
+    /// the `stem` language
+    pub fn with_fulltext(mut self, stem: Option<&str>) -> FieldDescription {
+        self.fulltext = Some(FullTextIndex {
+            stem: stem.map(|s| s.to_string()),
+        });
+        self
+    }
+
+    /// Add a vector index on this field, for vectors of dimension `dim` compared with `metric`
+    pub fn with_vector(mut self, dim: u32, metric: &str) -> FieldDescription {
+        self.vector = Some(VectorIndex {
+            dim,
+            metric: metric.to_string(),
+        });
+        self
+    }
+
+    /// Add a geo index on this field, for search the points in an area
+    pub fn with_geo(mut self) -> FieldDescription {
+        self.geo = true;
+        self
+    }
+
     fn read(read: &mut dyn Read) -> SRes<FieldDescription> {
         let position = u32::read(read)?;
         let name = String::read(read)?;
         let field_type = ValueType::read(read)?;
         let indexed_value = u8::read(read)?;
-        let indexed = match indexed_value {
+        let index_condition = if indexed_value & INDEX_CONDITION_FLAG != 0 {
+            let field = String::read(read)?;
+            let value = bool::read(read)?;
+            Some(IndexCondition { field, value })
+        } else {
+            None
+        };
+        let fulltext = if indexed_value & FULLTEXT_FLAG != 0 {
+            let stem = Option::<String>::read(read)?;
+            Some(FullTextIndex { stem })
+        } else {
+            None
+        };
+        let vector = if indexed_value & VECTOR_FLAG != 0 {
+            let dim = u32::read(read)?;
+            let metric = String::read(read)?;
+            Some(VectorIndex { dim, metric })
+        } else {
+            None
+        };
+        let geo = indexed_value & GEO_FLAG != 0;
+        let indexed = match indexed_value & !(INDEX_CONDITION_FLAG | FULLTEXT_FLAG | VECTOR_FLAG | GEO_FLAG) {
             0 => None,
             1 => Some(ValueMode::Cluster),
             2 => Some(ValueMode::Exclusive),
             3 => Some(ValueMode::Replace),
             _ => panic!("index type reading failure"),
         };
+        let field_type = if indexed.is_none() && fulltext.is_none() {
+            field_type.normalize_bytes()
+        } else {
+            field_type
+        };
         Ok(FieldDescription {
             position,
             name,
             field_type,
             indexed,
+            index_condition,
+            fulltext,
+            vector,
+            geo,
         })
     }
     fn write(&self, write: &mut dyn Write) -> SRes<()> {
         self.position.write(write)?;
         self.name.write(write)?;
         self.field_type.write(write)?;
-        match self.indexed {
-            None => u8::write(&0, write)?,
-            Some(ValueMode::Cluster) => u8::write(&1, write)?,
-            Some(ValueMode::Exclusive) => u8::write(&2, write)?,
-            Some(ValueMode::Replace) => u8::write(&3, write)?,
+        let mut mode: u8 = match self.indexed {
+            None => 0,
+            Some(ValueMode::Cluster) => 1,
+            Some(ValueMode::Exclusive) => 2,
+            Some(ValueMode::Replace) => 3,
+        };
+        if self.index_condition.is_some() {
+            mode |= INDEX_CONDITION_FLAG;
+        }
+        if self.fulltext.is_some() {
+            mode |= FULLTEXT_FLAG;
+        }
+        if self.vector.is_some() {
+            mode |= VECTOR_FLAG;
+        }
+        if self.geo {
+            mode |= GEO_FLAG;
+        }
+        u8::write(&mode, write)?;
+        if let Some(condition) = &self.index_condition {
+            condition.field.write(write)?;
+            condition.value.write(write)?;
+        }
+        if let Some(fulltext) = &self.fulltext {
+            fulltext.stem.write(write)?;
+        }
+        if let Some(vector) = &self.vector {
+            vector.dim.write(write)?;
+            vector.metric.write(write)?;
         }
         Ok(())
     }
 
-    fn remap_refer(&mut self, new: &str, old: &str) -> bool {
-        self.field_type.remap_refer(new, old)
+    fn refers(&self, name: &str) -> bool {
+        self.field_type.refers(name)
+    }
+
+    fn has_blobs(&self) -> bool {
+        self.field_type.has_blobs()
+    }
+
+    fn remap_refer(&mut self, old: &str, new: &str) -> bool {
+        self.field_type.remap_refer(old, new)
     }
 
     pub fn name(&self) -> &str {
@@ -717,12 +1494,7 @@ impl FieldDescription {
     }
 
     pub fn get_field_type_description(&self) -> Option<&Description> {
-        if let SimpleValueType::Embedded(d) = match &self.field_type {
-            ValueType::Value(v) => v,
-            ValueType::Array(v) => v,
-            ValueType::Option(v) => v,
-            ValueType::OptionArray(v) => v,
-        } {
+        if let Some(SimpleValueType::Embedded(d)) = self.field_type.leaf() {
             Some(d)
         } else {
             None
@@ -733,14 +1505,218 @@ impl FieldDescription {
         &self.indexed
     }
 
+    pub fn index_condition(&self) -> Option<&IndexCondition> {
+        self.index_condition.as_ref()
+    }
+
+    pub fn fulltext(&self) -> Option<&FullTextIndex> {
+        self.fulltext.as_ref()
+    }
+
+    pub fn vector(&self) -> Option<&VectorIndex> {
+        self.vector.as_ref()
+    }
+
+    pub fn geo(&self) -> bool {
+        self.geo
+    }
+
+    /// Check if this field has the same indexes of the other field, so its indexes can be kept
+    pub(crate) fn same_indexes(&self, other: &FieldDescription) -> bool {
+        self.indexed == other.indexed
+            && self.index_condition == other.index_condition
+            && self.fulltext == other.fulltext
+            && self.vector == other.vector
+            && self.geo == other.geo
+    }
+
+    /// Indexes of this field and of the fields of the embedded structs it holds, together with
+    /// the dotted path of the indexed field
+    fn indexes(&self) -> Vec<(String, FieldIndex<'_>)> {
+        let mut indexes = Vec::new();
+        self.collect_indexes(&self.name, &mut indexes);
+        indexes
+    }
+
+    fn collect_indexes<'a>(&'a self, path: &str, indexes: &mut Vec<(String, FieldIndex<'a>)>) {
+        if let Some(mode) = &self.indexed {
+            indexes.push((path.to_owned(), FieldIndex::Value(self, mode)));
+        }
+        if let Some(fulltext) = &self.fulltext {
+            indexes.push((path.to_owned(), FieldIndex::FullText(fulltext)));
+        }
+        if let Some(vector) = &self.vector {
+            indexes.push((path.to_owned(), FieldIndex::Vector(vector)));
+        }
+        if self.geo {
+            indexes.push((path.to_owned(), FieldIndex::Geo));
+        }
+        if let Some(Description::Struct(s)) = self.get_field_type_description() {
+            for field in s.fields() {
+                field.collect_indexes(&format!("{}.{}", path, field.name), indexes);
+            }
+        }
+    }
+
+    /// Check if this field or any field of the embedded structs it holds is indexed
+    pub(crate) fn has_indexes(&self) -> bool {
+        !self.indexes().is_empty()
+    }
+
+    pub(crate) fn create_index(&self, tx: &mut Transaction, type_name: &str) -> SRes<()> {
+        for (path, index) in self.indexes() {
+            match index {
+                FieldIndex::Value(field, mode) => field.field_type.create_index(tx, type_name, &path, mode.clone())?,
+                FieldIndex::FullText(fulltext) => {
+                    check_fulltext(fulltext.stem())?;
+                    create_index::<String>(tx, type_name, &fulltext_field_name(&path), ValueMode::Cluster)?
+                }
+                FieldIndex::Vector(vector) => {
+                    check_vector_index(vector.dim(), vector.metric())?;
+                    create_vector_index(tx, &index_name(type_name, &[&vector_field_name(&path)]))?
+                }
+                FieldIndex::Geo => create_index::<u64>(tx, type_name, &geo_field_name(&path), ValueMode::Cluster)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn rename_index(&self, tx: &mut Transaction, old_type: &str, new_type: &str) -> SRes<()> {
+        for (path, index) in self.indexes() {
+            match index {
+                FieldIndex::Value(field, mode) => {
+                    field
+                        .field_type
+                        .rename_index(tx, old_type, new_type, &path, mode.clone())?
+                }
+                FieldIndex::FullText(_) => {
+                    rename_index::<String>(tx, old_type, new_type, &fulltext_field_name(&path), ValueMode::Cluster)?
+                }
+                FieldIndex::Vector(_) => {
+                    let field = vector_field_name(&path);
+                    rename_vector_index(tx, &index_name(old_type, &[&field]), &index_name(new_type, &[&field]))?
+                }
+                FieldIndex::Geo => {
+                    rename_index::<u64>(tx, old_type, new_type, &geo_field_name(&path), ValueMode::Cluster)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn drop_index(&self, tx: &mut Transaction, type_name: &str) -> SRes<()> {
+        for (path, index) in self.indexes() {
+            match index {
+                FieldIndex::Value(_, _) => tx.drop_index(&index_name(type_name, &[&path]))?,
+                FieldIndex::FullText(_) => tx.drop_index(&index_name(type_name, &[&fulltext_field_name(&path)]))?,
+                FieldIndex::Vector(_) => drop_vector_index(tx, &index_name(type_name, &[&vector_field_name(&path)]))?,
+                FieldIndex::Geo => tx.drop_index(&index_name(type_name, &[&geo_field_name(&path)]))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Kind of an index of a field, the value index keeps the field description for its type
+enum FieldIndex<'a> {
+    Value(&'a FieldDescription, &'a ValueMode),
+    FullText(&'a FullTextIndex),
+    Vector(&'a VectorIndex),
+    Geo,
+}
+
+/// Index on a value computed from the whole struct, the values are stored after the fields of
+/// the records so they can be indexed also without the source code
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComputedIndexDescription {
+    pub(crate) name: String,
+    pub(crate) value_type: ValueType,
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "value_mode_serialize", deserialize_with = "value_mode_deserialize")
+    )]
+    pub(crate) indexed: Option<ValueMode>,
+}
+
+impl ComputedIndexDescription {
+    pub fn new<T: SupportedType>(name: &str, indexed: Option<ValueMode>) -> ComputedIndexDescription {
+        ComputedIndexDescription {
+            name: name.to_string(),
+            value_type: ValueType::resolve::<T>().indexed_bytes(),
+            indexed,
+        }
+    }
+
+    fn read(read: &mut dyn Read) -> SRes<ComputedIndexDescription> {
+        let name = String::read(read)?;
+        let value_type = ValueType::read(read)?;
+        let indexed = match u8::read(read)? {
+            0 => None,
+            1 => Some(ValueMode::Cluster),
+            2 => Some(ValueMode::Exclusive),
+            3 => Some(ValueMode::Replace),
+            _ => panic!("index type reading failure"),
+        };
+        Ok(ComputedIndexDescription {
+            name,
+            value_type,
+            indexed,
+        })
+    }
+
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        self.name.write(write)?;
+        self.value_type.write(write)?;
+        let mode: u8 = match self.indexed {
+            None => 0,
+            Some(ValueMode::Cluster) => 1,
+            Some(ValueMode::Exclusive) => 2,
+            Some(ValueMode::Replace) => 3,
+        };
+        mode.write(write)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value_type(&self) -> &ValueType {
+        &self.value_type
+    }
+
+    pub fn indexed(&self) -> &Option<ValueMode> {
+        &self.indexed
+    }
+
     pub(crate) fn create_index(&self, tx: &mut Transaction, type_name: &str) -> SRes<()> {
-        if let Some(t) = &self.indexed {
-            self.field_type.create_index(tx, type_name, &self.name, t.clone())?;
+        if let Some(mode) = &self.indexed {
+            self.value_type.create_index(tx, type_name, &self.name, mode.clone())?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn rename_index(&self, tx: &mut Transaction, old_type: &str, new_type: &str) -> SRes<()> {
+        if let Some(mode) = &self.indexed {
+            self.value_type
+                .rename_index(tx, old_type, new_type, &self.name, mode.clone())?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn drop_index(&self, tx: &mut Transaction, type_name: &str) -> SRes<()> {
+        if self.indexed.is_some() {
+            tx.drop_index(&index_name(type_name, &[&self.name]))?;
         }
         Ok(())
     }
 }
 
+/// Tags of the optional values stored after the fixed part of a definition, the versions that
+/// do not know them ignore the trailing values
+const INDEX_BUILD_TAG: u8 = 1;
+const COMPUTED_INDEXES_TAG: u8 = 2;
+
 #[derive(Clone)]
 pub struct InternalDescription {
     pub desc: Description,
@@ -748,36 +1724,72 @@ pub struct InternalDescription {
     pub id: PersyId,
     segment_name: String,
     migration_started: bool,
+    /// Field of the index in build by a `create_index`
+    index_build: Option<String>,
 }
 
 #[derive(Clone)]
 pub(crate) struct DefinitionInfo {
     segment_name: String,
+    blobs_desc: Option<Description>,
 }
 
 impl DefinitionInfo {
     pub(crate) fn segment_name(&self) -> &str {
         &self.segment_name
     }
+
+    /// The description to use for read the blob handles, present only if the type has blobs
+    pub(crate) fn blobs_desc(&self) -> Option<&Description> {
+        self.blobs_desc.as_ref()
+    }
 }
 
 impl InternalDescription {
     pub(crate) fn info(&self) -> DefinitionInfo {
         DefinitionInfo {
             segment_name: self.segment_name.clone(),
+            blobs_desc: if self.desc.has_blobs() {
+                Some(self.desc.clone())
+            } else {
+                None
+            },
         }
     }
 
     pub fn read(id: PersyId, read: &mut dyn Read) -> SRes<Self> {
-        let desc = Description::read(read)?;
+        let mut desc = Description::read(read)?;
         let segment_name = String::read(read)?;
         let migration_started = bool::read(read)?;
+        let mut index_build = None;
+        let mut trailing = Vec::new();
+        read.read_to_end(&mut trailing)?;
+        let len = trailing.len() as u64;
+        let mut trailing = Cursor::new(trailing);
+        while trailing.position() < len {
+            match u8::read(&mut trailing)? {
+                INDEX_BUILD_TAG => index_build = Some(String::read(&mut trailing)?),
+                COMPUTED_INDEXES_TAG => {
+                    let count = u32::read(&mut trailing)?;
+                    let mut computed = Vec::new();
+                    for _ in 0..count {
+                        computed.push(ComputedIndexDescription::read(&mut trailing)?);
+                    }
+                    if let Description::Struct(s) = &mut desc {
+                        s.computed_indexes = computed;
+                    }
+                }
+                // Values of newer versions
+                _ => break,
+            }
+        }
         Ok(InternalDescription {
             desc,
             checked: false,
             id,
             segment_name,
             migration_started,
+            index_build,
         })
     }
 
@@ -792,6 +1804,10 @@ impl InternalDescription {
         desc.write(&mut buff)?;
         segment_name.write(&mut buff)?;
         false.write(&mut buff)?;
+        write_computed_indexes(&desc, &mut buff)?;
+        if desc.has_blobs() {
+            init_blob_segment(&structsy.persy)?;
+        }
         let mut tx = structsy.begin()?;
         let id = tx.trans.insert(INTERNAL_SEGMENT_NAME, &buff)?;
         tx.trans.create_segment(&segment_name)?;
@@ -803,6 +1819,7 @@ impl InternalDescription {
             id,
             segment_name,
             migration_started: false,
+            index_build: None,
         })
     }
 
@@ -823,11 +1840,55 @@ impl InternalDescription {
         self.migration_started
     }
 
-    pub(crate) fn update(&self, st: &Arc<StructsyImpl>) -> SRes<()> {
-        let mut tx = st.begin()?;
-        self.update_tx(&mut tx)?;
-        tx.commit()?;
-        Ok(())
+    pub(crate) fn start_index_build(&mut self, field: &str) {
+        self.index_build = Some(field.to_owned());
+    }
+
+    /// The field of the index in build, if an index build is in progress
+    pub(crate) fn index_build(&self) -> Option<&str> {
+        self.index_build.as_deref()
+    }
+
+    pub(crate) fn finish_index_build(&mut self) {
+        self.index_build = None;
+    }
+
+    pub(crate) fn field_index(&self, field: &str) -> SRes<Option<ValueMode>> {
+        match &self.desc {
+            Description::Struct(s) => {
+                if let Some(f) = s.get_field(field) {
+                    Ok(f.indexed.clone())
+                } else if let Some(c) = s.get_computed_index(field) {
+                    Ok(c.indexed.clone())
+                } else {
+                    Err(StructsyError::ValueChangeError(format!(
+                        "field with name '{}' not found",
+                        field
+                    )))
+                }
+            }
+            Description::Enum(_) => Err(StructsyError::TypeError("Expected a struct definition".to_owned())),
+        }
+    }
+
+    pub(crate) fn set_field_index(&mut self, field: &str, mode: Option<ValueMode>) -> SRes<()> {
+        match &mut self.desc {
+            Description::Struct(s) => {
+                if let Some(f) = s.fields.iter_mut().find(|f| f.name == field) {
+                    f.indexed = mode;
+                    Ok(())
+                } else if let Some(c) = s.computed_indexes.iter_mut().find(|c| c.name == field) {
+                    c.indexed = mode;
+                    Ok(())
+                } else {
+                    Err(StructsyError::ValueChangeError(format!(
+                        "field with name '{}' not found",
+                        field
+                    )))
+                }
+            }
+            Description::Enum(_) => Err(StructsyError::TypeError("Expected a struct definition".to_owned())),
+        }
     }
 
     pub(crate) fn update_tx(&self, tx: &mut dyn Sytx) -> SRes<()> {
@@ -835,13 +1896,18 @@ impl InternalDescription {
         self.desc.write(&mut buff)?;
         self.segment_name.write(&mut buff)?;
         self.migration_started.write(&mut buff)?;
+        if let Some(field) = &self.index_build {
+            INDEX_BUILD_TAG.write(&mut buff)?;
+            field.write(&mut buff)?;
+        }
+        write_computed_indexes(&self.desc, &mut buff)?;
         tx.tx().trans.update(INTERNAL_SEGMENT_NAME, &self.id, &buff)?;
         Ok(())
     }
 
-    pub(crate) fn migrate<T: Persistent>(&mut self, tx: &mut dyn Sytx) -> SRes<()> {
+    pub(crate) fn migrate(&mut self, desc: Description, tx: &mut dyn Sytx) -> SRes<()> {
         self.migration_started = false;
-        self.desc = T::get_description();
+        self.desc = desc;
         self.update_tx(tx)?;
         Ok(())
     }
@@ -849,6 +1915,21 @@ impl InternalDescription {
     pub(crate) fn remap_refer(&mut self, old: &str, new: &str) -> bool {
         self.desc.remap_refer(old, new)
     }
+
+    pub(crate) fn rename(&mut self, tx: &mut Transaction, new: &str) -> SRes<()> {
+        let old = self.desc.get_name();
+        if let Description::Struct(s) = &self.desc {
+            for field in s.fields() {
+                field.rename_index(tx, &old, new)?;
+            }
+            for computed in s.computed_indexes() {
+                computed.rename_index(tx, &old, new)?;
+            }
+        }
+        self.desc.set_name(new);
+        self.checked = false;
+        Ok(())
+    }
 }
 
 /// Struct metadata for internal use
@@ -857,6 +1938,10 @@ impl InternalDescription {
 pub struct StructDescription {
     pub(crate) name: String,
     pub(crate) fields: Vec<FieldDescription>,
+    /// Stored in the definition after the description, not written by [`write`](#method.write)
+    /// to keep the format of the description readable by the versions that do not know them
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) computed_indexes: Vec<ComputedIndexDescription>,
 }
 
 impl StructDescription {
@@ -864,8 +1949,15 @@ impl StructDescription {
         StructDescription {
             name: name.to_string(),
             fields: Vec::from(fields),
+            computed_indexes: Vec::new(),
         }
     }
+
+    /// Add an index on a value computed from the whole struct
+    pub fn with_computed_index(mut self, computed: ComputedIndexDescription) -> StructDescription {
+        self.computed_indexes.push(computed);
+        self
+    }
     pub fn read(read: &mut dyn Read) -> SRes<StructDescription> {
         let name = String::read(read)?;
         let n_fields = u32::read(read)?;
@@ -873,7 +1965,11 @@ impl StructDescription {
         for _ in 0..n_fields {
             fields.push(FieldDescription::read(read)?);
         }
-        Ok(StructDescription { name, fields })
+        Ok(StructDescription {
+            name,
+            fields,
+            computed_indexes: Vec::new(),
+        })
     }
     pub fn write(&self, write: &mut dyn Write) -> SRes<()> {
         self.name.write(write)?;
@@ -884,6 +1980,14 @@ impl StructDescription {
         Ok(())
     }
 
+    pub(crate) fn refers(&self, name: &str) -> bool {
+        self.fields.iter().any(|f| f.refers(name))
+    }
+
+    fn has_blobs(&self) -> bool {
+        self.fields.iter().any(|f| f.has_blobs())
+    }
+
     pub(crate) fn remap_refer(&mut self, old: &str, new: &str) -> bool {
         let mut changed = false;
         for f in &mut self.fields {
@@ -906,12 +2010,46 @@ impl StructDescription {
         self.fields.iter()
     }
 
+    pub fn computed_indexes(&self) -> impl std::iter::Iterator<Item = &ComputedIndexDescription> {
+        self.computed_indexes.iter()
+    }
+
+    pub(crate) fn get_computed_index(&self, name: &str) -> Option<&ComputedIndexDescription> {
+        self.computed_indexes.iter().find(|c| c.name == name)
+    }
+
     pub(crate) fn raw_define(&self, tx: &mut Transaction) -> SRes<()> {
         for field in &self.fields {
             field.create_index(tx, &self.name)?;
         }
+        for computed in &self.computed_indexes {
+            computed.create_index(tx, &self.name)?;
+        }
         Ok(())
     }
+
+    pub(crate) fn drop_indexes(&self, tx: &mut Transaction) -> SRes<()> {
+        for field in &self.fields {
+            field.drop_index(tx, &self.name)?;
+        }
+        for computed in &self.computed_indexes {
+            computed.drop_index(tx, &self.name)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_computed_indexes(desc: &Description, write: &mut dyn Write) -> SRes<()> {
+    if let Description::Struct(s) = desc {
+        if !s.computed_indexes.is_empty() {
+            COMPUTED_INDEXES_TAG.write(write)?;
+            (s.computed_indexes.len() as u32).write(write)?;
+            for computed in &s.computed_indexes {
+                computed.write(write)?;
+            }
+        }
+    }
+    Ok(())
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -920,6 +2058,7 @@ pub struct VariantDescription {
     pub(crate) position: u32,
     pub(crate) name: String,
     pub(crate) ty: Option<ValueType>,
+    pub(crate) fields: Vec<FieldDescription>,
 }
 
 impl VariantDescription {
@@ -928,6 +2067,7 @@ impl VariantDescription {
             name: name.to_string(),
             position,
             ty: None,
+            fields: Vec::new(),
         }
     }
     pub fn new_value<T: SupportedType>(name: &str, position: u32) -> Self {
@@ -935,35 +2075,75 @@ impl VariantDescription {
             name: name.to_string(),
             position,
             ty: Some(ValueType::resolve::<T>()),
+            fields: Vec::new(),
+        }
+    }
+    /// Variant with multiple fields, the fields of tuple variants are named by position "0", "1", ...
+    pub fn new_fields(name: &str, position: u32, fields: &[FieldDescription]) -> Self {
+        Self {
+            name: name.to_string(),
+            position,
+            ty: None,
+            fields: fields.to_vec(),
         }
     }
     fn write(&self, write: &mut dyn Write) -> SRes<()> {
         self.name.write(write)?;
         self.position.write(write)?;
         if let Some(ty) = &self.ty {
-            true.write(write)?;
+            u8::write(&1, write)?;
             ty.write(write)?;
+        } else if !self.fields.is_empty() {
+            u8::write(&2, write)?;
+            (self.fields.len() as u32).write(write)?;
+            for f in &self.fields {
+                f.write(write)?;
+            }
         } else {
-            false.write(write)?;
+            u8::write(&0, write)?;
         }
         Ok(())
     }
     fn read(read: &mut dyn Read) -> SRes<VariantDescription> {
         let name = String::read(read)?;
         let position = u32::read(read)?;
-        let ty = if bool::read(read)? {
-            Some(ValueType::read(read)?)
-        } else {
-            None
-        };
-        Ok(Self { name, position, ty })
+        let mut ty = None;
+        let mut fields = Vec::new();
+        match u8::read(read)? {
+            0 => {}
+            1 => ty = Some(ValueType::read(read)?.normalize_bytes()),
+            2 => {
+                let len = u32::read(read)?;
+                for _ in 0..len {
+                    fields.push(FieldDescription::read(read)?);
+                }
+            }
+            _ => panic!("variant type reading failure"),
+        }
+        Ok(Self {
+            name,
+            position,
+            ty,
+            fields,
+        })
     }
-    fn remap_refer(&mut self, new: &str, old: &str) -> bool {
+    fn refers(&self, name: &str) -> bool {
+        self.ty.as_ref().map(|t| t.refers(name)).unwrap_or(false) || self.fields.iter().any(|f| f.refers(name))
+    }
+
+    fn has_blobs(&self) -> bool {
+        self.ty.as_ref().map(|t| t.has_blobs()).unwrap_or(false) || self.fields.iter().any(|f| f.has_blobs())
+    }
+
+    fn remap_refer(&mut self, old: &str, new: &str) -> bool {
+        let mut remapped = false;
         if let Some(ref mut r) = self.ty {
-            r.remap_refer(new, old)
-        } else {
-            false
+            remapped |= r.remap_refer(old, new);
         }
+        for f in &mut self.fields {
+            remapped |= f.remap_refer(old, new);
+        }
+        remapped
     }
 
     pub fn name(&self) -> &str {
@@ -977,6 +2157,15 @@ impl VariantDescription {
     pub fn value_type(&self) -> &Option<ValueType> {
         &self.ty
     }
+
+    /// The fields of a struct-like or multi-field tuple variant, empty for the other variants
+    pub fn fields(&self) -> impl std::iter::Iterator<Item = &FieldDescription> {
+        self.fields.iter()
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<&FieldDescription> {
+        self.fields.iter().find(|f| f.name == name)
+    }
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -1015,7 +2204,15 @@ impl EnumDescription {
         self.name.clone()
     }
 
-    fn remap_refer(&mut self, new: &str, old: &str) -> bool {
+    fn refers(&self, name: &str) -> bool {
+        self.variants.iter().any(|v| v.refers(name))
+    }
+
+    fn has_blobs(&self) -> bool {
+        self.variants.iter().any(|v| v.has_blobs())
+    }
+
+    fn remap_refer(&mut self, old: &str, new: &str) -> bool {
         let mut changed = false;
         for v in &mut self.variants {
             if v.remap_refer(old, new) {
@@ -1035,6 +2232,10 @@ impl EnumDescription {
     pub(crate) fn raw_define(&self, _tx: &mut Transaction) -> SRes<()> {
         Ok(())
     }
+
+    pub(crate) fn drop_indexes(&self, _tx: &mut Transaction) -> SRes<()> {
+        Ok(())
+    }
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -1052,6 +2253,29 @@ impl Description {
         }
     }
 
+    pub(crate) fn set_name(&mut self, name: &str) {
+        match self {
+            Description::Struct(s) => s.name = name.to_owned(),
+            Description::Enum(e) => e.name = name.to_owned(),
+        }
+    }
+
+    /// Check if the description contains a reference to the type with the provided name
+    pub fn refers(&self, name: &str) -> bool {
+        match self {
+            Description::Struct(s) => s.refers(name),
+            Description::Enum(e) => e.refers(name),
+        }
+    }
+
+    /// Check if the description contains blob fields, also inside embedded types
+    pub fn has_blobs(&self) -> bool {
+        match self {
+            Description::Struct(s) => s.has_blobs(),
+            Description::Enum(e) => e.has_blobs(),
+        }
+    }
+
     pub fn remap_refer(&mut self, old: &str, new: &str) -> bool {
         match self {
             Description::Struct(s) => s.remap_refer(old, new),
@@ -1087,4 +2311,11 @@ impl Description {
             Description::Enum(e) => e.raw_define(tx),
         }
     }
+
+    pub(crate) fn drop_indexes(&self, tx: &mut Transaction) -> SRes<()> {
+        match self {
+            Description::Struct(s) => s.drop_indexes(tx),
+            Description::Enum(e) => e.drop_indexes(tx),
+        }
+    }
 }
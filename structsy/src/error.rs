@@ -7,6 +7,8 @@ pub enum StructsyError {
     PersyError(PersyError),
     StructAlreadyDefined(String),
     StructNotDefined(String),
+    StructDescriptionMismatch(String),
+    StructReferenced(String, String),
     IOError,
     PoisonedLock,
     MigrationNotSupported(String),
@@ -41,7 +43,15 @@ impl Display for StructsyError {
         match self {
             StructsyError::PersyError(pe) => write!(f, "Persy Error: {}", pe),
             StructsyError::StructAlreadyDefined(name) => write!(f, "Struct with name '{}' already defined ", name),
-            StructsyError::StructNotDefined(name) => writeln!(f, "Struct with name '{}', already defined", name),
+            StructsyError::StructNotDefined(name) => writeln!(f, "Struct with name '{}' not defined", name),
+            StructsyError::StructDescriptionMismatch(name) => writeln!(
+                f,
+                "Struct with name '{}' do not match the stored definition, check the schema diff",
+                name
+            ),
+            StructsyError::StructReferenced(name, by) => {
+                writeln!(f, "Struct with name '{}' is referenced by '{}'", name, by)
+            }
             StructsyError::IOError => writeln!(f, "IOError"),
             StructsyError::PoisonedLock => writeln!(f, "PoisonedLock"),
             StructsyError::MigrationNotSupported(name) => writeln!(f, "Migration of Struct '{}' not supported", name),
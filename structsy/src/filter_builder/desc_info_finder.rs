@@ -4,9 +4,10 @@ use super::{
     reader::{Reader, ReaderIterator},
 };
 use crate::{
-    desc::{index_name, Description},
+    desc::{index_name, Description, ValueType},
     format::PersistentEmbedded,
-    index::{RangeInstanceIter, RangeIter},
+    index::{RangeInstanceIter, RangeIter, RangeProjectionIter},
+    projection::QueryProjection,
     Order, Persistent, Ref, SRes,
 };
 
@@ -74,6 +75,47 @@ fn map_finder<'a, P: Persistent + 'static, K: PersistentEmbedded + 'static>(
     }
 }
 
+/// The projections read from the keys of the index in the range
+pub(crate) fn index_find_projection<'a, P: QueryProjection<T> + 'a, T: Persistent + 'static>(
+    reader: Reader<'a>,
+    index_name: &str,
+    range: RangeQueryValue,
+    order: Order,
+) -> SRes<Box<dyn ReaderIterator<Item = P> + 'a>> {
+    match range {
+        RangeQueryValue::U8(b) => map_projection(order, u8::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::U16(b) => map_projection(order, u16::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::U32(b) => map_projection(order, u32::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::U64(b) => map_projection(order, u64::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::U128(b) => map_projection(order, u128::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::I8(b) => map_projection(order, i8::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::I16(b) => map_projection(order, i16::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::I32(b) => map_projection(order, i32::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::I64(b) => map_projection(order, i64::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::I128(b) => map_projection(order, i128::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::F32(b) => map_projection(order, f32::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::F64(b) => map_projection(order, f64::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::Bool(b) => map_projection(order, bool::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::String(b) => map_projection(order, String::finder().find_range(reader, index_name, b)?),
+        RangeQueryValue::Vec(_) => unreachable!("wrong value in the range"),
+        RangeQueryValue::Option(_) => unreachable!("wrong value in the range"),
+        RangeQueryValue::OptionVec(_) => unreachable!("wrong value in the range"),
+        RangeQueryValue::Ref(_) => unreachable!("wrong value in the range"),
+        RangeQueryValue::Embedded(_) => unreachable!("wrong value in the range"),
+    }
+}
+fn map_projection<'a, P: QueryProjection<T> + 'a, T: Persistent + 'static, K: PersistentEmbedded + 'static>(
+    order: Order,
+    iter: RangeIter<'a, K>,
+) -> SRes<Box<dyn ReaderIterator<Item = P> + 'a>> {
+    let found = RangeProjectionIter::new(iter);
+    if Order::Desc == order {
+        Ok(Box::new(found.reader_rev()))
+    } else {
+        Ok(Box::new(found))
+    }
+}
+
 fn support_index(val: &Option<RangeQueryValue>) -> bool {
     match val {
         Some(RangeQueryValue::OptionVec(_)) => false,
@@ -112,15 +154,24 @@ impl<'a> InfoFinder for Reader<'a> {
                 }
             }
             if let Some(field) = last_field {
+                if let Some(Description::Enum(_)) = field.get_field_type_description() {
+                    // Enum indexes are keyed by variant position, only a variant lookup can use them
+                    if !matches!(range, Some(RangeQueryValue::U32(_))) {
+                        return None;
+                    }
+                }
                 if let Some(_) = field.indexed() {
                     let index_name = index_name(type_name, &field_path.field_path_names_str());
-                    Some(IndexInfo::new(
-                        field_path.to_owned(),
-                        index_name,
-                        range,
-                        mode,
-                        field.field_type.clone(),
-                    ))
+                    let info = IndexInfo::new(field_path.to_owned(), index_name, range, mode, field.field_type.clone());
+                    if let Some(condition) = field.index_condition() {
+                        // The condition field is a sibling of the indexed field
+                        let mut condition_path = field_path.field_path_names();
+                        condition_path.pop();
+                        condition_path.push(condition.field().to_owned());
+                        Some(info.with_condition(condition_path, condition.value()))
+                    } else {
+                        Some(info)
+                    }
                 } else {
                     None
                 }
@@ -131,6 +182,28 @@ impl<'a> InfoFinder for Reader<'a> {
             None
         }
     }
+    fn find_computed_index(
+        &self,
+        type_name: &str,
+        field_path: &FieldPathPlan,
+        range: RangeQueryValue,
+        value_type: &ValueType,
+    ) -> Option<IndexInfo> {
+        let index_name = index_name(type_name, &field_path.field_path_names_str());
+        let persy = &self.structsy().structsy_impl.persy;
+        if persy.exists_index(&index_name).unwrap_or(false) {
+            Some(IndexInfo::new(
+                field_path.to_owned(),
+                index_name,
+                Some(range),
+                Order::Asc,
+                value_type.clone(),
+            ))
+        } else {
+            None
+        }
+    }
+
     fn score_index(&mut self, index: &IndexInfo) -> SRes<usize> {
         if let Some(bounds) = index.index_range.clone() {
             index_score(self, &index.index_name, bounds)
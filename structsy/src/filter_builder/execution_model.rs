@@ -2,23 +2,38 @@ use crate::{
     filter_builder::{
         fields_holder::{CompareOperations, IntoCompareOperations, RefOperations},
         plan_model::{
-            FieldPathPlan, FilterByPlan, FilterFieldPlanItem, FilterPlan, FilterPlanItem, FilterPlanMode,
+            FieldPathPlan, FilterByPlan, FilterFieldPlanItem, FilterPlan, FilterPlanItem, FilterPlanMode, GeoCover,
             OrderPlanItem, OrdersPlan, QueryPlan, QueryValuePlan, Source,
         },
-        query_model::RangeQueryValue,
+        query_model::{Nearest, RangeQueryValue},
         reader::{Reader, ReaderIterator},
     },
-    Order, Persistent, Ref, SRes,
+    geo::GeoArea,
+    projection::QueryProjection,
+    vector::{search, EF_SEARCH},
+    Order, Persistent, PersistentEmbedded, Ref, SRes,
 };
-use std::{cmp::Ordering, rc::Rc};
+use persy::PersyId;
+use std::{cmp::Ordering, collections::HashSet, ops::Bound, rc::Rc};
 
 fn start<'a, T: Persistent + 'static>(
     source: Source,
+    filter: Option<FilterExecutionGroup<T>>,
     reader: Reader<'a>,
 ) -> SRes<Box<dyn ReaderIterator<Item = (Ref<T>, T)> + 'a>> {
-    Ok(match source {
+    let iter: Box<dyn ReaderIterator<Item = (Ref<T>, T)> + 'a> = match source {
         Source::Index(index) => reader.find_range_from_info(index)?,
         Source::Scan(_scan) => Box::new(reader.scan()?),
+        Source::Nearest(nearest) => {
+            // The nearest that match the filter are read here, so the errors of the search are returned
+            return Ok(Box::new(NearestSource::new(nearest, reader)?.find(filter.as_ref())?));
+        }
+        Source::Geo(cover) => Box::new(read_geo_cover(cover, reader)?),
+    };
+    Ok(if let Some(filter) = filter {
+        Box::new(FilterExecution { source: iter, filter })
+    } else {
+        iter
     })
 }
 
@@ -47,8 +62,15 @@ fn filter_by_to_execution<T>(
         FilterByPlan::Equal(v) => FilterExecutionByPlan::Equal(v),
         FilterByPlan::Is(v) => FilterExecutionByPlan::Is(v),
         FilterByPlan::Contains(v) => FilterExecutionByPlan::Contains(v),
+        FilterByPlan::ContainsKey(k) => FilterExecutionByPlan::ContainsKey(k),
+        FilterByPlan::Prefix(v) => FilterExecutionByPlan::Prefix(v),
+        FilterByPlan::Variant(v) => FilterExecutionByPlan::Variant(v),
+        FilterByPlan::KeyEqual(k, v) => FilterExecutionByPlan::KeyEqual(k, v),
         FilterByPlan::Range(v) => FilterExecutionByPlan::Range(v),
         FilterByPlan::RangeIs(v) => FilterExecutionByPlan::RangeIs(v),
+        FilterByPlan::IndexRange(v, _) => FilterExecutionByPlan::Range(v),
+        FilterByPlan::FullText(v) => FilterExecutionByPlan::Range(v),
+        FilterByPlan::Geo(area) => FilterExecutionByPlan::Geo(area),
         FilterByPlan::RangeContains(v) => FilterExecutionByPlan::RangeContains(v),
         FilterByPlan::LoadAndEqual(v) => {
             FilterExecutionByPlan::LoadAndEqual(filter_by_query_to_execution(v, field, access))
@@ -131,14 +153,13 @@ pub(crate) fn execute<'a, T: Persistent + 'static>(
         //This is not used for now because the projections are based on code generation
         //and do not have algorithms in them yet
         projections: _projections,
+        nearest,
     } = plan;
 
-    let iter = start::<T>(source, reader)?;
-    let iter = if let Some(f) = filter {
-        Box::new(FilterExecution {
-            source: iter,
-            filter: filter_plan_to_execution(f, fields.clone()),
-        })
+    let filter = filter.map(|f| filter_plan_to_execution(f, fields.clone()));
+    let iter = start::<T>(source, filter, reader)?;
+    let iter = if let Some(nearest) = nearest {
+        Box::new(NearestRank::new(iter, nearest))
     } else {
         iter
     };
@@ -155,6 +176,46 @@ pub(crate) fn execute<'a, T: Persistent + 'static>(
     Ok(iter)
 }
 
+/// Execute a plan that reads the projections from the keys of the index of the source, the index
+/// solves alone the filter and the orders of the plan, or from the ids of all the records when
+/// the projection holds only the id
+pub(crate) fn execute_index_only<'a, P: QueryProjection<T> + 'a, T: Persistent + 'static>(
+    plan: QueryPlan,
+    reader: Reader<'a>,
+) -> SRes<Box<dyn ReaderIterator<Item = P> + 'a>> {
+    match plan.source {
+        Source::Index(index) => reader.find_projection_from_info(index),
+        // A projection without fields is always built from the id alone
+        Source::Scan(_) => Ok(Box::new(ProjectionExecution {
+            source: Box::new(reader.scan_refs::<T>()?),
+            project: |id| P::from_index(&id, &()),
+        })),
+        _ => unreachable!("index only projections read from an index or from the ids"),
+    }
+}
+
+/// Projections of the items of a source, the items that can not be projected are skipped
+pub(crate) struct ProjectionExecution<'a, X, P> {
+    pub(crate) source: Box<dyn ReaderIterator<Item = X> + 'a>,
+    pub(crate) project: fn(X) -> Option<P>,
+}
+impl<'b, X, P> ReaderIterator for ProjectionExecution<'b, X, P> {
+    fn reader<'a>(&'a mut self) -> Reader<'a> {
+        self.source.reader()
+    }
+}
+impl<'a, X, P> Iterator for ProjectionExecution<'a, X, P> {
+    type Item = P;
+    fn next(&mut self) -> Option<Self::Item> {
+        for item in &mut self.source {
+            if let Some(projection) = (self.project)(item) {
+                return Some(projection);
+            }
+        }
+        None
+    }
+}
+
 struct FilterExecution<'a, T> {
     source: Box<dyn ReaderIterator<Item = (Ref<T>, T)> + 'a>,
     filter: FilterExecutionGroup<T>,
@@ -226,9 +287,14 @@ enum FilterExecutionByPlan {
     Equal(QueryValuePlan),
     Contains(QueryValuePlan),
     Is(QueryValuePlan),
+    ContainsKey(QueryValuePlan),
+    KeyEqual(QueryValuePlan, QueryValuePlan),
+    Prefix(QueryValuePlan),
+    Variant(u32),
     Range(RangeQueryValue),
     RangeContains(RangeQueryValue),
     RangeIs(RangeQueryValue),
+    Geo(GeoArea),
     LoadAndEqual(LoadExecution),
     LoadAndContains(LoadExecution),
     LoadAndIs(LoadExecution),
@@ -248,9 +314,14 @@ impl<T> FilterCheck<T> for FilterExecutionField<T> {
             FilterExecutionByPlan::Equal(value) => self.field.equals(rec, value.clone()),
             FilterExecutionByPlan::Contains(value) => self.field.contains(rec, value.clone()),
             FilterExecutionByPlan::Is(value) => self.field.is(rec, value.clone()),
+            FilterExecutionByPlan::ContainsKey(key) => self.field.contains_key(rec, key.clone()),
+            FilterExecutionByPlan::KeyEqual(key, value) => self.field.key_equals(rec, key.clone(), value.clone()),
+            FilterExecutionByPlan::Prefix(value) => self.field.starts_with(rec, value.clone()),
+            FilterExecutionByPlan::Variant(position) => self.field.is_variant(rec, *position),
             FilterExecutionByPlan::Range(value) => self.field.range(rec, value.clone()),
             FilterExecutionByPlan::RangeContains(value) => self.field.range_contains(rec, value.clone()),
             FilterExecutionByPlan::RangeIs(value) => self.field.range_is(rec, value.clone()),
+            FilterExecutionByPlan::Geo(area) => self.field.within(rec, area),
             FilterExecutionByPlan::LoadAndEqual(value) => self.field.query_equals(rec, &*value.ops, reader),
             FilterExecutionByPlan::LoadAndContains(value) => self.field.query_contains(rec, &*value.ops, reader),
             FilterExecutionByPlan::LoadAndIs(value) => self.field.query_is(rec, &*value.ops, reader),
@@ -283,9 +354,15 @@ impl<'a, T> Accumulator<'a, T> {
             buffer: Default::default(),
         }
     }
-    fn order_item(&self, first: &T, second: &T) -> Ordering {
-        for order in &self.orders {
-            let ord = order.compare.compare(&first, &second);
+    fn ranks(&self, item: &T) -> Vec<Option<usize>> {
+        self.orders.iter().map(|order| order.compare.rank(item)).collect()
+    }
+    fn order_item(&self, first: &(Vec<Option<usize>>, T), second: &(Vec<Option<usize>>, T)) -> Ordering {
+        for (pos, order) in self.orders.iter().enumerate() {
+            let ord = match (first.0[pos], second.0[pos]) {
+                (Some(first_rank), Some(second_rank)) => first_rank.cmp(&second_rank),
+                _ => order.compare.compare(&first.1, &second.1),
+            };
             let ord = match order.order {
                 Order::Asc => ord,
                 Order::Desc => match ord {
@@ -312,16 +389,183 @@ impl<'a, T: 'static> Iterator for Accumulator<'a, T> {
         if let Some(iter) = &mut self.buffer {
             iter.next()
         } else {
-            let mut buffer = Vec::<(Ref<T>, T)>::new();
-            while let Some(item) = self.source.next() {
-                let index = match buffer.binary_search_by(|(_, e)| self.order_item(e, &item.1)) {
+            // The ranks are computed once for record, not for every comparison
+            let mut buffer = Vec::<(Ref<T>, (Vec<Option<usize>>, T))>::new();
+            while let Some((id, rec)) = self.source.next() {
+                let item = (self.ranks(&rec), rec);
+                let index = match buffer.binary_search_by(|(_, e)| self.order_item(e, &item)) {
                     Ok(index) => index,
                     Err(index) => index,
                 };
-                buffer.insert(index, item);
+                buffer.insert(index, (id, item));
             }
-            self.buffer = Some(Box::new(buffer.into_iter()));
+            self.buffer = Some(Box::new(buffer.into_iter().map(|(id, (_, rec))| (id, rec))));
             self.buffer.as_mut().unwrap().next()
         }
     }
 }
+
+/// Records found with the vector index from the nearest, when all the candidates are consumed
+/// the search is repeated with a candidate list twice as wide for provide the next ones
+struct NearestSource<'a> {
+    reader: Reader<'a>,
+    nearest: Nearest,
+    ef: usize,
+    seen: HashSet<PersyId>,
+    candidates: std::vec::IntoIter<PersyId>,
+    exhausted: bool,
+}
+impl<'a> NearestSource<'a> {
+    fn new(nearest: Nearest, reader: Reader<'a>) -> SRes<Self> {
+        let mut source = Self {
+            reader,
+            ef: nearest.k.max(EF_SEARCH),
+            nearest,
+            seen: HashSet::new(),
+            candidates: Vec::new().into_iter(),
+            exhausted: false,
+        };
+        source.search_candidates()?;
+        Ok(source)
+    }
+
+    fn search_candidates(&mut self) -> SRes<()> {
+        let Nearest {
+            index_name,
+            metric,
+            vector,
+            ..
+        } = &self.nearest;
+        let found = search(&mut self.reader, index_name, *metric, vector, self.ef)?;
+        self.exhausted = found.len() < self.ef;
+        self.ef *= 2;
+        let seen = &mut self.seen;
+        self.candidates = found
+            .into_iter()
+            .map(|(id, _)| id)
+            .filter(|id| seen.insert(*id))
+            .collect::<Vec<_>>()
+            .into_iter();
+        Ok(())
+    }
+
+    fn next_record<T: Persistent>(&mut self) -> SRes<Option<(Ref<T>, T)>> {
+        loop {
+            if let Some(id) = self.candidates.next() {
+                let id = Ref::new(id);
+                if let Some(rec) = self.reader.read(&id)? {
+                    return Ok(Some((id, rec)));
+                }
+            } else if self.exhausted {
+                return Ok(None);
+            } else {
+                self.search_candidates()?;
+            }
+        }
+    }
+
+    /// Read the first `k` candidates that match the filter
+    fn find<T: Persistent>(mut self, filter: Option<&FilterExecutionGroup<T>>) -> SRes<Found<'a, T>> {
+        let mut records = Vec::new();
+        while records.len() < self.nearest.k {
+            match self.next_record::<T>()? {
+                Some((id, rec)) => {
+                    if filter.map_or(true, |f| f.check(&rec, &mut self.reader.reborrow())) {
+                        records.push((id, rec));
+                    }
+                }
+                None => break,
+            }
+        }
+        Ok(Found {
+            reader: self.reader,
+            records: records.into_iter(),
+        })
+    }
+}
+
+/// Records already read by the source of the query
+struct Found<'a, T> {
+    reader: Reader<'a>,
+    records: std::vec::IntoIter<(Ref<T>, T)>,
+}
+impl<'b, T> ReaderIterator for Found<'b, T> {
+    fn reader<'a>(&'a mut self) -> Reader<'a> {
+        self.reader.reborrow()
+    }
+}
+impl<'a, T> Iterator for Found<'a, T> {
+    type Item = (Ref<T>, T);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records.next()
+    }
+}
+
+/// Keep the `k` records nearest to the vector of the query, from the nearest
+struct NearestRank<'a, T> {
+    source: Box<dyn ReaderIterator<Item = (Ref<T>, T)> + 'a>,
+    nearest: Nearest,
+    buffer: Option<std::vec::IntoIter<(Ref<T>, T)>>,
+}
+impl<'a, T> NearestRank<'a, T> {
+    fn new(source: Box<dyn ReaderIterator<Item = (Ref<T>, T)> + 'a>, nearest: Nearest) -> Self {
+        Self {
+            source,
+            nearest,
+            buffer: None,
+        }
+    }
+}
+impl<'b, T: Persistent> ReaderIterator for NearestRank<'b, T> {
+    fn reader<'a>(&'a mut self) -> Reader<'a> {
+        self.source.reader()
+    }
+}
+impl<'a, T: Persistent> Iterator for NearestRank<'a, T> {
+    type Item = (Ref<T>, T);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_none() {
+            let Nearest {
+                field,
+                metric,
+                vector,
+                k,
+                ..
+            } = &self.nearest;
+            let mut buffer = Vec::<(f32, (Ref<T>, T))>::new();
+            for (id, rec) in &mut self.source {
+                let distance = match rec.vector_value(field) {
+                    Some(value) => metric.distance(vector, value),
+                    None => continue,
+                };
+                let index = buffer.partition_point(|(d, _)| *d <= distance);
+                if index < *k {
+                    buffer.insert(index, (distance, (id, rec)));
+                    buffer.truncate(*k);
+                }
+            }
+            self.buffer = Some(buffer.into_iter().map(|(_, item)| item).collect::<Vec<_>>().into_iter());
+        }
+        self.buffer.as_mut().and_then(|b| b.next())
+    }
+}
+
+/// Read the records in the ranges of cells of the cover of a geo index, the records are read
+/// here so the errors of the reads are returned
+fn read_geo_cover<'a, T: Persistent>(cover: GeoCover, mut reader: Reader<'a>) -> SRes<Found<'a, T>> {
+    let mut records = Vec::new();
+    for (start, end) in cover.ranges {
+        let range = (Bound::Included(start), Bound::Included(end));
+        let found = u64::finder().find_range(reader.reborrow(), &cover.index_name, range)?;
+        for id in found.map(|(_, id)| id).collect::<Vec<_>>() {
+            let id = Ref::new(id);
+            if let Some(rec) = reader.read(&id)? {
+                records.push((id, rec));
+            }
+        }
+    }
+    Ok(Found {
+        reader,
+        records: records.into_iter(),
+    })
+}
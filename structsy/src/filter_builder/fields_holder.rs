@@ -6,10 +6,12 @@ use crate::{
         reader::Reader,
         value_compare::{ValueCompare, ValueRange},
     },
+    fulltext::term_frequency,
+    geo::{GeoArea, GeoPoint},
     internal::{Field, FieldInfo},
     Persistent, Ref,
 };
-use std::{cmp::Ordering, collections::HashMap, rc::Rc};
+use std::{cmp::Ordering, collections::HashMap, marker::PhantomData, ops::RangeBounds, rc::Rc};
 
 struct FieldValueRef<T, X>(Field<T, Ref<X>>, FieldsHolder<X>);
 struct FieldValueVecRef<T, X>(Field<T, Vec<Ref<X>>>, FieldsHolder<X>);
@@ -213,6 +215,35 @@ impl<T: 'static> FieldsHolder<T> {
             Entry::Occupied(mut o) => o.get_mut().replace_simple_range(Rc::new(FieldValueRange(field))),
         }
     }
+    pub(crate) fn add_computed_index<K: ValueRange + 'static>(&mut self, index: ComputedIndex<T, K>)
+    where
+        T: Persistent,
+    {
+        self.fields
+            .insert(index.name.to_owned(), TypedField::<T>::simple_range(Rc::new(index)));
+    }
+    pub(crate) fn add_fulltext(&mut self, field: FullTextField<T>)
+    where
+        T: Persistent,
+    {
+        self.fields
+            .insert(field.name.to_owned(), TypedField::<T>::simple_range(Rc::new(field)));
+    }
+    /// Add the field of a geo index, the one with the origin for rank the records by distance
+    /// replaces the others
+    pub(crate) fn add_geo(&mut self, field: GeoField<T>)
+    where
+        T: Persistent,
+    {
+        let name = field.name.to_owned();
+        if field.origin.is_some() {
+            self.fields.insert(name, TypedField::<T>::simple_range(Rc::new(field)));
+        } else {
+            self.fields
+                .entry(name)
+                .or_insert_with(|| TypedField::<T>::simple_range(Rc::new(field)));
+        }
+    }
     pub(crate) fn add_nested_field<V: 'static>(&mut self, field: Field<T, V>, holder: FieldsHolder<V>) {
         use std::collections::hash_map::Entry;
         match self.fields.entry(field.name().to_owned()) {
@@ -253,13 +284,25 @@ pub(crate) trait CompareOperations<T> {
     fn equals(&self, t: &T, value: QueryValuePlan) -> bool;
     fn contains(&self, t: &T, value: QueryValuePlan) -> bool;
     fn is(&self, t: &T, value: QueryValuePlan) -> bool;
+    fn contains_key(&self, t: &T, key: QueryValuePlan) -> bool;
+    fn key_equals(&self, t: &T, key: QueryValuePlan, value: QueryValuePlan) -> bool;
+    fn starts_with(&self, t: &T, value: QueryValuePlan) -> bool;
+    fn is_variant(&self, t: &T, position: u32) -> bool;
     fn range(&self, t: &T, value: RangeQueryValue) -> bool;
     fn range_contains(&self, t: &T, value: RangeQueryValue) -> bool;
     fn range_is(&self, t: &T, value: RangeQueryValue) -> bool;
+    /// Check if the point of the field is in the area, only fields with a geo index have a point
+    fn within(&self, _t: &T, _area: &GeoArea) -> bool {
+        false
+    }
     fn query_equals(&self, t: &T, value: &dyn RefOperations, reader: &mut Reader) -> bool;
     fn query_contains(&self, t: &T, value: &dyn RefOperations, reader: &mut Reader) -> bool;
     fn query_is(&self, t: &T, value: &dyn RefOperations, reader: &mut Reader) -> bool;
     fn compare(&self, first: &T, second: &T) -> Ordering;
+    /// Rank of the record computed once before sorting, `None` if the records are sorted by `compare`
+    fn rank(&self, _t: &T) -> Option<usize> {
+        None
+    }
 }
 
 pub(crate) trait RefOperations {
@@ -271,14 +314,26 @@ pub(crate) trait RefBuildOperations<T>: CompareOperations<T> {
 
 impl<T, V: ValueCompare> CompareOperations<T> for FieldValueCompare<T, V> {
     fn equals(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).equals(value)
+        self.0.get(t).is_some_and(|v| v.equals(value))
     }
 
     fn contains(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).contains_value(value)
+        self.0.get(t).is_some_and(|v| v.contains_value(value))
     }
     fn is(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).is(value)
+        self.0.get(t).is_some_and(|v| v.is(value))
+    }
+    fn contains_key(&self, t: &T, key: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.contains_key(key))
+    }
+    fn key_equals(&self, t: &T, key: QueryValuePlan, value: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.key_equals(key, value))
+    }
+    fn starts_with(&self, t: &T, value: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.starts_with(value))
+    }
+    fn is_variant(&self, t: &T, position: u32) -> bool {
+        self.0.get(t).is_some_and(|v| v.is_variant(position))
     }
 
     fn range(&self, _t: &T, _value: RangeQueryValue) -> bool {
@@ -307,28 +362,308 @@ impl<T, V: ValueCompare> CompareOperations<T> for FieldValueCompare<T, V> {
     }
 }
 
+/// A value computed from the whole record and stored in an index, instead of read from a field
+pub(crate) struct ComputedIndex<T, K> {
+    name: &'static str,
+    phantom: PhantomData<(T, K)>,
+}
+impl<T, K> ComputedIndex<T, K> {
+    pub(crate) fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            phantom: PhantomData,
+        }
+    }
+}
+impl<T, K> Clone for ComputedIndex<T, K> {
+    fn clone(&self) -> Self {
+        Self::new(self.name)
+    }
+}
+impl<T, K> std::fmt::Debug for ComputedIndex<T, K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComputedIndex").field("name", &self.name).finish()
+    }
+}
+impl<T, K> FieldInfo for ComputedIndex<T, K> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+impl<T: Persistent, K: 'static> ComputedIndex<T, K> {
+    fn get(&self, t: &T) -> Option<Box<K>> {
+        t.computed_index_value(self.name).and_then(|v| v.downcast::<K>().ok())
+    }
+}
+
+impl<T: Persistent, K: ValueRange + 'static> CompareOperations<T> for ComputedIndex<T, K> {
+    fn equals(&self, t: &T, value: QueryValuePlan) -> bool {
+        self.get(t).is_some_and(|v| v.equals(value))
+    }
+    fn contains(&self, t: &T, value: QueryValuePlan) -> bool {
+        self.get(t).is_some_and(|v| v.contains_value(value))
+    }
+    fn is(&self, t: &T, value: QueryValuePlan) -> bool {
+        self.get(t).is_some_and(|v| v.is(value))
+    }
+    fn contains_key(&self, _t: &T, _key: QueryValuePlan) -> bool {
+        false
+    }
+    fn key_equals(&self, _t: &T, _key: QueryValuePlan, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn starts_with(&self, _t: &T, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn is_variant(&self, _t: &T, _position: u32) -> bool {
+        false
+    }
+    fn range(&self, t: &T, value: RangeQueryValue) -> bool {
+        self.get(t).is_some_and(|v| v.range(value))
+    }
+    fn range_contains(&self, t: &T, value: RangeQueryValue) -> bool {
+        self.get(t).is_some_and(|v| v.range_contains(value))
+    }
+    fn range_is(&self, t: &T, value: RangeQueryValue) -> bool {
+        self.get(t).is_some_and(|v| v.range_is(value))
+    }
+    fn query_equals(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
+        false
+    }
+    fn query_contains(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
+        false
+    }
+    fn query_is(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
+        false
+    }
+    fn compare(&self, first: &T, second: &T) -> Ordering {
+        match (self.get(first), self.get(second)) {
+            (Some(f), Some(s)) => f.sort_compare(&s),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+/// The tokens of a field with a full-text index, compared by the frequency of the searched terms
+pub(crate) struct FullTextField<T> {
+    name: &'static str,
+    field: &'static str,
+    terms: Vec<String>,
+    phantom: PhantomData<T>,
+}
+impl<T> FullTextField<T> {
+    pub(crate) fn new(name: &'static str, field: &'static str, terms: Vec<String>) -> Self {
+        Self {
+            name,
+            field,
+            terms,
+            phantom: PhantomData,
+        }
+    }
+}
+impl<T> Clone for FullTextField<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.name, self.field, self.terms.clone())
+    }
+}
+impl<T> std::fmt::Debug for FullTextField<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FullTextField")
+            .field("name", &self.name)
+            .field("terms", &self.terms)
+            .finish()
+    }
+}
+impl<T> FieldInfo for FullTextField<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+impl<T: Persistent> FullTextField<T> {
+    fn score(&self, t: &T) -> usize {
+        t.fulltext_tokens(self.field)
+            .map(|tokens| term_frequency(&tokens, &self.terms))
+            .unwrap_or(0)
+    }
+}
+
+impl<T: Persistent> CompareOperations<T> for FullTextField<T> {
+    fn equals(&self, _t: &T, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn contains(&self, _t: &T, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn is(&self, _t: &T, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn contains_key(&self, _t: &T, _key: QueryValuePlan) -> bool {
+        false
+    }
+    fn key_equals(&self, _t: &T, _key: QueryValuePlan, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn starts_with(&self, _t: &T, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn is_variant(&self, _t: &T, _position: u32) -> bool {
+        false
+    }
+    fn range(&self, t: &T, value: RangeQueryValue) -> bool {
+        if let (Some(tokens), RangeQueryValue::String(range)) = (t.fulltext_tokens(self.field), value) {
+            tokens.iter().any(|token| range.contains(token))
+        } else {
+            false
+        }
+    }
+    fn range_contains(&self, _t: &T, _value: RangeQueryValue) -> bool {
+        false
+    }
+    fn range_is(&self, _t: &T, _value: RangeQueryValue) -> bool {
+        false
+    }
+    fn query_equals(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
+        false
+    }
+    fn query_contains(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
+        false
+    }
+    fn query_is(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
+        false
+    }
+    fn compare(&self, first: &T, second: &T) -> Ordering {
+        self.score(first).cmp(&self.score(second))
+    }
+    fn rank(&self, t: &T) -> Option<usize> {
+        Some(self.score(t))
+    }
+}
+
+/// The point of a field with a geo index, compared by the distance from the origin
+pub(crate) struct GeoField<T> {
+    name: &'static str,
+    field: &'static str,
+    origin: Option<GeoPoint>,
+    phantom: PhantomData<T>,
+}
+impl<T> GeoField<T> {
+    pub(crate) fn new(name: &'static str, field: &'static str, origin: Option<GeoPoint>) -> Self {
+        Self {
+            name,
+            field,
+            origin,
+            phantom: PhantomData,
+        }
+    }
+}
+impl<T> std::fmt::Debug for GeoField<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GeoField")
+            .field("name", &self.name)
+            .field("origin", &self.origin)
+            .finish()
+    }
+}
+impl<T> FieldInfo for GeoField<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+impl<T: Persistent> GeoField<T> {
+    fn distance(&self, t: &T) -> f64 {
+        match (t.geo_value(self.field), &self.origin) {
+            (Some(point), Some(origin)) => origin.distance(point),
+            _ => f64::MAX,
+        }
+    }
+}
+
+impl<T: Persistent> CompareOperations<T> for GeoField<T> {
+    fn equals(&self, _t: &T, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn contains(&self, _t: &T, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn is(&self, _t: &T, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn contains_key(&self, _t: &T, _key: QueryValuePlan) -> bool {
+        false
+    }
+    fn key_equals(&self, _t: &T, _key: QueryValuePlan, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn starts_with(&self, _t: &T, _value: QueryValuePlan) -> bool {
+        false
+    }
+    fn is_variant(&self, _t: &T, _position: u32) -> bool {
+        false
+    }
+    fn range(&self, _t: &T, _value: RangeQueryValue) -> bool {
+        false
+    }
+    fn range_contains(&self, _t: &T, _value: RangeQueryValue) -> bool {
+        false
+    }
+    fn range_is(&self, _t: &T, _value: RangeQueryValue) -> bool {
+        false
+    }
+    fn within(&self, t: &T, area: &GeoArea) -> bool {
+        t.geo_value(self.field).is_some_and(|point| area.contains(point))
+    }
+    fn query_equals(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
+        false
+    }
+    fn query_contains(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
+        false
+    }
+    fn query_is(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
+        false
+    }
+    fn compare(&self, first: &T, second: &T) -> Ordering {
+        self.distance(first)
+            .partial_cmp(&self.distance(second))
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
 impl<T, V: ValueRange> CompareOperations<T> for FieldValueRange<T, V> {
     fn equals(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).equals(value)
+        self.0.get(t).is_some_and(|v| v.equals(value))
     }
 
     fn contains(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).contains_value(value)
+        self.0.get(t).is_some_and(|v| v.contains_value(value))
     }
     fn is(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).is(value)
+        self.0.get(t).is_some_and(|v| v.is(value))
+    }
+    fn contains_key(&self, t: &T, key: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.contains_key(key))
+    }
+    fn key_equals(&self, t: &T, key: QueryValuePlan, value: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.key_equals(key, value))
+    }
+    fn starts_with(&self, t: &T, value: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.starts_with(value))
+    }
+    fn is_variant(&self, t: &T, position: u32) -> bool {
+        self.0.get(t).is_some_and(|v| v.is_variant(position))
     }
 
     fn range(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range(value)
+        self.0.get(t).is_some_and(|v| v.range(value))
     }
 
     fn range_contains(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range_contains(value)
+        self.0.get(t).is_some_and(|v| v.range_contains(value))
     }
 
     fn range_is(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range_is(value)
+        self.0.get(t).is_some_and(|v| v.range_is(value))
     }
 
     fn query_equals(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
@@ -342,36 +677,48 @@ impl<T, V: ValueRange> CompareOperations<T> for FieldValueRange<T, V> {
     }
 
     fn compare(&self, first: &T, second: &T) -> Ordering {
-        ((self.0.access)(first)).sort_compare((self.0.access)(second))
+        self.0.compare(first, second, |f, s| f.sort_compare(s))
     }
 }
 
 impl<T, X: Persistent> CompareOperations<T> for FieldValueRef<T, X> {
     fn equals(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).equals(value)
+        self.0.get(t).is_some_and(|v| v.equals(value))
     }
 
     fn contains(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).contains_value(value)
+        self.0.get(t).is_some_and(|v| v.contains_value(value))
     }
     fn is(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).is(value)
+        self.0.get(t).is_some_and(|v| v.is(value))
+    }
+    fn contains_key(&self, t: &T, key: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.contains_key(key))
+    }
+    fn key_equals(&self, t: &T, key: QueryValuePlan, value: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.key_equals(key, value))
+    }
+    fn starts_with(&self, t: &T, value: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.starts_with(value))
+    }
+    fn is_variant(&self, t: &T, position: u32) -> bool {
+        self.0.get(t).is_some_and(|v| v.is_variant(position))
     }
 
     fn range(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range(value)
+        self.0.get(t).is_some_and(|v| v.range(value))
     }
 
     fn range_contains(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range_contains(value)
+        self.0.get(t).is_some_and(|v| v.range_contains(value))
     }
 
     fn range_is(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range_is(value)
+        self.0.get(t).is_some_and(|v| v.range_is(value))
     }
 
     fn query_equals(&self, t: &T, value: &dyn RefOperations, reader: &mut Reader) -> bool {
-        value.equals(RawRef::from((self.0.access)(t)), reader)
+        self.0.get(t).is_some_and(|r| value.equals(RawRef::from(r), reader))
     }
     fn query_contains(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
         false
@@ -380,39 +727,51 @@ impl<T, X: Persistent> CompareOperations<T> for FieldValueRef<T, X> {
         false
     }
     fn compare(&self, first: &T, second: &T) -> Ordering {
-        ((self.0.access)(first)).sort_compare((self.0.access)(second))
+        self.0.compare(first, second, |f, s| f.sort_compare(s))
     }
 }
 
 impl<T, X: Persistent> CompareOperations<T> for FieldValueVecRef<T, X> {
     fn equals(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).equals(value)
+        self.0.get(t).is_some_and(|v| v.equals(value))
     }
 
     fn contains(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).contains_value(value)
+        self.0.get(t).is_some_and(|v| v.contains_value(value))
     }
     fn is(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).is(value)
+        self.0.get(t).is_some_and(|v| v.is(value))
+    }
+    fn contains_key(&self, t: &T, key: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.contains_key(key))
+    }
+    fn key_equals(&self, t: &T, key: QueryValuePlan, value: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.key_equals(key, value))
+    }
+    fn starts_with(&self, t: &T, value: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.starts_with(value))
+    }
+    fn is_variant(&self, t: &T, position: u32) -> bool {
+        self.0.get(t).is_some_and(|v| v.is_variant(position))
     }
 
     fn range(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range(value)
+        self.0.get(t).is_some_and(|v| v.range(value))
     }
 
     fn range_contains(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range_contains(value)
+        self.0.get(t).is_some_and(|v| v.range_contains(value))
     }
 
     fn range_is(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range_is(value)
+        self.0.get(t).is_some_and(|v| v.range_is(value))
     }
 
     fn query_equals(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
         false
     }
     fn query_contains(&self, t: &T, value: &dyn RefOperations, reader: &mut Reader) -> bool {
-        for r in (self.0.access)(t) {
+        for r in self.0.get(t).into_iter().flatten() {
             if value.equals(RawRef::from(r), reader) {
                 return true;
             }
@@ -423,32 +782,44 @@ impl<T, X: Persistent> CompareOperations<T> for FieldValueVecRef<T, X> {
         false
     }
     fn compare(&self, first: &T, second: &T) -> Ordering {
-        ((self.0.access)(first)).sort_compare((self.0.access)(second))
+        self.0.compare(first, second, |f, s| f.sort_compare(s))
     }
 }
 
 impl<T, X: Persistent> CompareOperations<T> for FieldValueOptionRef<T, X> {
     fn equals(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).equals(value)
+        self.0.get(t).is_some_and(|v| v.equals(value))
     }
 
     fn contains(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).contains_value(value)
+        self.0.get(t).is_some_and(|v| v.contains_value(value))
     }
     fn is(&self, t: &T, value: QueryValuePlan) -> bool {
-        (self.0.access)(t).is(value)
+        self.0.get(t).is_some_and(|v| v.is(value))
+    }
+    fn contains_key(&self, t: &T, key: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.contains_key(key))
+    }
+    fn key_equals(&self, t: &T, key: QueryValuePlan, value: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.key_equals(key, value))
+    }
+    fn starts_with(&self, t: &T, value: QueryValuePlan) -> bool {
+        self.0.get(t).is_some_and(|v| v.starts_with(value))
+    }
+    fn is_variant(&self, t: &T, position: u32) -> bool {
+        self.0.get(t).is_some_and(|v| v.is_variant(position))
     }
 
     fn range(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range(value)
+        self.0.get(t).is_some_and(|v| v.range(value))
     }
 
     fn range_contains(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range_contains(value)
+        self.0.get(t).is_some_and(|v| v.range_contains(value))
     }
 
     fn range_is(&self, t: &T, value: RangeQueryValue) -> bool {
-        (self.0.access)(t).range_is(value)
+        self.0.get(t).is_some_and(|v| v.range_is(value))
     }
 
     fn query_equals(&self, _t: &T, _value: &dyn RefOperations, _reader: &mut Reader) -> bool {
@@ -458,14 +829,14 @@ impl<T, X: Persistent> CompareOperations<T> for FieldValueOptionRef<T, X> {
         false
     }
     fn query_is(&self, t: &T, value: &dyn RefOperations, reader: &mut Reader) -> bool {
-        if let Some(r) = (self.0.access)(t) {
+        if let Some(Some(r)) = self.0.get(t) {
             value.equals(RawRef::from(r), reader)
         } else {
             false
         }
     }
     fn compare(&self, first: &T, second: &T) -> Ordering {
-        ((self.0.access)(first)).sort_compare((self.0.access)(second))
+        self.0.compare(first, second, |f, s| f.sort_compare(s))
     }
 }
 
@@ -600,34 +971,49 @@ impl<T: 'static> IntoCompareOperations<T> for FieldsHolder<T> {
 
 impl<T, V> CompareOperations<T> for PathStep<T, V> {
     fn equals(&self, t: &T, value: QueryValuePlan) -> bool {
-        self.next.equals((self.field.access)(t), value)
+        self.field.get(t).is_some_and(|v| self.next.equals(v, value))
     }
     fn contains(&self, t: &T, value: QueryValuePlan) -> bool {
-        self.next.contains((self.field.access)(t), value)
+        self.field.get(t).is_some_and(|v| self.next.contains(v, value))
     }
     fn is(&self, t: &T, value: QueryValuePlan) -> bool {
-        self.next.is((self.field.access)(t), value)
+        self.field.get(t).is_some_and(|v| self.next.is(v, value))
+    }
+    fn contains_key(&self, t: &T, key: QueryValuePlan) -> bool {
+        self.field.get(t).is_some_and(|v| self.next.contains_key(v, key))
+    }
+    fn key_equals(&self, t: &T, key: QueryValuePlan, value: QueryValuePlan) -> bool {
+        self.field.get(t).is_some_and(|v| self.next.key_equals(v, key, value))
+    }
+    fn starts_with(&self, t: &T, value: QueryValuePlan) -> bool {
+        self.field.get(t).is_some_and(|v| self.next.starts_with(v, value))
+    }
+    fn is_variant(&self, t: &T, position: u32) -> bool {
+        self.field.get(t).is_some_and(|v| self.next.is_variant(v, position))
     }
     fn range(&self, t: &T, value: RangeQueryValue) -> bool {
-        self.next.range((self.field.access)(t), value)
+        self.field.get(t).is_some_and(|v| self.next.range(v, value))
     }
     fn range_contains(&self, t: &T, value: RangeQueryValue) -> bool {
-        self.next.range_contains((self.field.access)(t), value)
+        self.field.get(t).is_some_and(|v| self.next.range_contains(v, value))
     }
     fn range_is(&self, t: &T, value: RangeQueryValue) -> bool {
-        self.next.range_is((self.field.access)(t), value)
+        self.field.get(t).is_some_and(|v| self.next.range_is(v, value))
     }
     fn query_equals(&self, t: &T, value: &dyn RefOperations, reader: &mut Reader) -> bool {
-        self.next.query_equals((self.field.access)(t), value, reader)
+        self.field
+            .get(t)
+            .is_some_and(|v| self.next.query_equals(v, value, reader))
     }
     fn query_contains(&self, t: &T, value: &dyn RefOperations, reader: &mut Reader) -> bool {
-        self.next.query_contains((self.field.access)(t), value, reader)
+        self.field
+            .get(t)
+            .is_some_and(|v| self.next.query_contains(v, value, reader))
     }
     fn query_is(&self, t: &T, value: &dyn RefOperations, reader: &mut Reader) -> bool {
-        self.next.query_is((self.field.access)(t), value, reader)
+        self.field.get(t).is_some_and(|v| self.next.query_is(v, value, reader))
     }
     fn compare(&self, first: &T, second: &T) -> Ordering {
-        self.next
-            .compare((self.field.access)(first), (self.field.access)(second))
+        self.field.compare(first, second, |f, s| self.next.compare(f, s))
     }
 }
@@ -1,14 +1,22 @@
 use crate::{
+    desc::index_name,
     filter_builder::{
-        execution_model::execute,
-        fields_holder::FieldsHolder,
+        execution_model::{execute, execute_index_only, ProjectionExecution},
+        fields_holder::{ComputedIndex, FieldsHolder, FullTextField, GeoField},
         plan_model::plan_from_query,
-        query_model::{FilterHolder, FilterMode, Orders as OrdersModel, Query, SolveQueryRange, SolveQueryValue},
+        query_model::{
+            FilterHolder, FilterMode, Nearest, Orders as OrdersModel, Projection as ProjectionModel, Query,
+            SolveQueryRange, SolveQueryValue,
+        },
         reader::{Reader, ReaderIterator},
         ValueCompare, ValueRange,
     },
-    internal::Field,
-    Order, Persistent, PersistentEmbedded, Ref,
+    fulltext::tokenize,
+    geo::{GeoArea, GeoPoint, GeoRect},
+    internal::{EmbeddedDescription, Field, Projection, SupportedType},
+    projection::{Projected, QueryProjection},
+    vector::{vector_field_name, Metric},
+    Order, Persistent, PersistentEmbedded, Ref, SRes, StructsyError, Variant,
 };
 use std::{
     ops::{Bound, RangeBounds},
@@ -19,6 +27,20 @@ pub struct FilterBuilder<T> {
     filters: FilterHolder,
     fields: FieldsHolder<T>,
     orders: Vec<OrdersModel>,
+    nearest: Option<Nearest>,
+    /// The first invalid condition added, the query fails with it when executed
+    error: Option<String>,
+}
+impl<T> Clone for FilterBuilder<T> {
+    fn clone(&self) -> Self {
+        FilterBuilder {
+            filters: self.filters.clone(),
+            fields: self.fields.clone(),
+            orders: self.orders.clone(),
+            nearest: self.nearest.clone(),
+            error: self.error.clone(),
+        }
+    }
 }
 impl<T> Default for FilterBuilder<T> {
     fn default() -> Self {
@@ -32,6 +54,8 @@ impl<T> FilterBuilder<T> {
             filters: FilterHolder::new(FilterMode::And),
             fields: Default::default(),
             orders: Vec::new(),
+            nearest: None,
+            error: None,
         }
     }
 }
@@ -47,13 +71,63 @@ impl<'a, T> Iterator for ToIter<'a, T> {
 }
 
 impl<T: Persistent + 'static> FilterBuilder<T> {
-    pub fn finish<'a>(self, mut reader_inst: Reader<'a>) -> Box<dyn Iterator<Item = (Ref<T>, T)> + 'a> {
-        let query = Query::new(T::get_name(), self.filters, self.orders, Vec::new());
-        let plan = plan_from_query(query, &mut reader_inst).unwrap();
-        let iter = execute(plan, Rc::new(self.fields), reader_inst);
-        Box::new(ToIter {
-            read_iterator: iter.unwrap(),
-        })
+    pub fn finish<'a>(self, reader_inst: Reader<'a>) -> Box<dyn Iterator<Item = (Ref<T>, T)> + 'a> {
+        self.try_finish(reader_inst).unwrap()
+    }
+
+    /// Execute the query, failing if a condition of the query is invalid
+    pub(crate) fn try_finish<'a>(
+        self,
+        mut reader_inst: Reader<'a>,
+    ) -> SRes<Box<dyn Iterator<Item = (Ref<T>, T)> + 'a>> {
+        self.check()?;
+        let query = Query::new(T::get_name(), self.filters, self.orders, None, self.nearest);
+        let plan = plan_from_query(query, &mut reader_inst)?;
+        let iter = execute(plan, Rc::new(self.fields), reader_inst)?;
+        Ok(Box::new(ToIter { read_iterator: iter }))
+    }
+
+    /// Execute the query and project the found records, reading the projected values straight
+    /// from an index when it covers the projection, and only the ids when the projection holds
+    /// only the id
+    pub fn finish_projection<'a, P: Projection<T> + 'a>(
+        self,
+        reader_inst: Reader<'a>,
+    ) -> Box<dyn Iterator<Item = P> + 'a> {
+        let iter = self.try_finish_projection::<Projected<P>>(reader_inst).unwrap();
+        Box::new(iter.map(|p| p.0))
+    }
+
+    /// Execute the query and return only the ids of the found records
+    pub(crate) fn finish_refs<'a>(self, reader_inst: Reader<'a>) -> Box<dyn Iterator<Item = Ref<T>> + 'a> {
+        self.try_finish_projection(reader_inst).unwrap()
+    }
+
+    /// Execute the query and return only the ids of the found records, giving access to the
+    /// reader of the query while iterating
+    pub(crate) fn try_finish_refs<'a>(
+        self,
+        reader_inst: Reader<'a>,
+    ) -> SRes<Box<dyn ReaderIterator<Item = Ref<T>> + 'a>> {
+        self.try_finish_projection(reader_inst)
+    }
+
+    fn try_finish_projection<'a, P: QueryProjection<T> + 'a>(
+        self,
+        mut reader_inst: Reader<'a>,
+    ) -> SRes<Box<dyn ReaderIterator<Item = P> + 'a>> {
+        self.check()?;
+        let projections = P::projected_fields().map(|fields| fields.into_iter().map(ProjectionModel::new).collect());
+        let query = Query::new(T::get_name(), self.filters, self.orders, projections, self.nearest);
+        let plan = plan_from_query(query, &mut reader_inst)?;
+        if plan.projections.as_ref().is_some_and(|p| p.index_only) {
+            execute_index_only(plan, reader_inst)
+        } else {
+            Ok(Box::new(ProjectionExecution {
+                source: execute(plan, Rc::new(self.fields), reader_inst)?,
+                project: |(id, rec)| Some(P::from_record(&id, &rec)),
+            }))
+        }
     }
 }
 
@@ -97,6 +171,43 @@ impl<T: 'static> FilterBuilder<T> {
         self.fields.add_field(field.clone());
     }
 
+    pub fn cond_contains_key<M, K>(&mut self, field: Field<T, M>, key: K)
+    where
+        M: ValueCompare + 'static,
+        K: SolveQueryValue + 'static,
+    {
+        self.filters.add_field_contains_key(Rc::new(field.clone()), key);
+        self.fields.add_field(field.clone());
+    }
+
+    pub fn cond_key_equal<M, K, V>(&mut self, field: Field<T, M>, key: K, value: V)
+    where
+        M: ValueCompare + 'static,
+        K: SolveQueryValue + 'static,
+        V: SolveQueryValue + 'static,
+    {
+        self.filters.add_field_key_equal(Rc::new(field.clone()), key, value);
+        self.fields.add_field(field.clone());
+    }
+
+    pub fn cond_prefix<V, P>(&mut self, field: Field<T, V>, prefix: P)
+    where
+        V: ValueCompare + 'static,
+        P: SolveQueryValue + 'static,
+    {
+        self.filters.add_field_prefix(Rc::new(field.clone()), prefix);
+        self.fields.add_field(field.clone());
+    }
+
+    pub fn cond_variant<V>(&mut self, field: Field<T, V>, variant: Variant<V>)
+    where
+        V: EmbeddedDescription + PartialEq + 'static,
+    {
+        self.filters
+            .add_field_variant(Rc::new(field.clone()), variant.position());
+        self.fields.add_field(field.clone());
+    }
+
     pub fn cond_range<V, R: RangeBounds<V>>(&mut self, field: Field<T, V>, range: R)
     where
         V: ValueRange + SolveQueryRange + Clone + 'static,
@@ -106,6 +217,110 @@ impl<T: 'static> FilterBuilder<T> {
         self.fields.add_field_ord(field.clone());
     }
 
+    pub fn cond_index_range<K, R: RangeBounds<K>>(&mut self, name: &'static str, range: R)
+    where
+        T: Persistent,
+        K: ValueRange + SolveQueryRange + SupportedType + Clone + 'static,
+    {
+        let index = ComputedIndex::<T, K>::new(name);
+        self.filters
+            .add_index_range(Rc::new(index.clone()), (&range.start_bound(), &range.end_bound()));
+        self.fields.add_computed_index(index);
+    }
+
+    /// Search the terms in the full-text index of the field, every term has to be in the field
+    /// and the results are ranked by the frequency of the terms, a text without words matches nothing
+    pub fn cond_search(&mut self, field: &'static str, terms: &str)
+    where
+        T: Persistent,
+    {
+        let (name, stem) = match T::fulltext_index(field) {
+            Some(index) => index,
+            None => return self.invalid(format!("the field '{}' does not have a fulltext index", field)),
+        };
+        let terms = match tokenize(terms, stem) {
+            Ok(terms) => terms,
+            Err(e) => return self.invalid(error_message(e)),
+        };
+        // A text without words do not match any record, no token is empty
+        let empty = [String::new()];
+        let required = if terms.is_empty() { &empty[..] } else { &terms[..] };
+        for term in required {
+            self.filters
+                .add_fulltext_term(Rc::new(FullTextField::<T>::new(name, field, Vec::new())), term);
+        }
+        let rank = FullTextField::<T>::new(name, field, terms);
+        self.orders
+            .push(OrdersModel::new_field(Rc::new(rank.clone()), Order::Desc));
+        self.fields.add_fulltext(rank);
+    }
+
+    /// Keep only the `k` records with the vector of the field nearest to `vector`, from the nearest,
+    /// found with the vector index of the field together with the other conditions
+    pub fn cond_nearest(&mut self, field: &'static str, vector: &[f32], k: usize)
+    where
+        T: Persistent,
+    {
+        let (dim, metric) = match T::vector_index(field) {
+            Some(index) => index,
+            None => return self.invalid(format!("the field '{}' does not have a vector index", field)),
+        };
+        if vector.len() != dim as usize {
+            return self.invalid(format!(
+                "the vector index of the field '{}' has dimension {} not {}",
+                field,
+                dim,
+                vector.len()
+            ));
+        }
+        let metric = match Metric::from_name(metric) {
+            Ok(metric) => metric,
+            Err(e) => return self.invalid(error_message(e)),
+        };
+        self.merge_nearest(Some(Nearest {
+            field,
+            index_name: index_name(T::get_name(), &[&vector_field_name(field)]),
+            metric,
+            vector: vector.to_vec(),
+            k,
+        }));
+    }
+
+    /// Keep only the records with the point of the field inside the rectangle, found with the geo
+    /// index of the field
+    pub fn cond_within_bbox(&mut self, field: &'static str, rect: GeoRect)
+    where
+        T: Persistent,
+    {
+        self.cond_geo(field, GeoArea::Rect(rect), None);
+    }
+
+    /// Keep only the records with the point of the field within `meters` from `center`, found with
+    /// the geo index of the field and ranked by the distance from the center
+    pub fn cond_within_radius(&mut self, field: &'static str, center: GeoPoint, meters: f64)
+    where
+        T: Persistent,
+    {
+        self.cond_geo(field, GeoArea::Radius(center, meters), Some(center));
+    }
+
+    fn cond_geo(&mut self, field: &'static str, area: GeoArea, origin: Option<GeoPoint>)
+    where
+        T: Persistent,
+    {
+        let name = match T::geo_index(field) {
+            Some(name) => name,
+            None => return self.invalid(format!("the field '{}' does not have a geo index", field)),
+        };
+        self.filters
+            .add_geo(Rc::new(GeoField::<T>::new(name, field, None)), area);
+        if origin.is_some() {
+            let rank = GeoField::<T>::new(name, field, origin);
+            self.orders.push(OrdersModel::new_field(Rc::new(rank), Order::Asc));
+        }
+        self.fields.add_geo(GeoField::new(name, field, origin));
+    }
+
     pub fn cond_range_contains<V, R: RangeBounds<V>>(&mut self, field: Field<T, Vec<V>>, range: R)
     where
         V: ValueRange + SolveQueryRange + Clone + PartialOrd + 'static,
@@ -134,7 +349,11 @@ impl<T: 'static> FilterBuilder<T> {
             filters: filter,
             orders,
             fields: fields_holder,
+            nearest,
+            error,
         } = filter;
+        self.merge_error(error);
+        self.no_nearest(nearest, field.name);
 
         self.fields.add_nested_field(field.clone(), fields_holder);
         self.filters.add_field_embedded(Rc::new(field.clone()), filter);
@@ -150,7 +369,11 @@ impl<T: 'static> FilterBuilder<T> {
             filters: filter,
             fields: fields_holder,
             orders,
+            nearest,
+            error,
         } = query;
+        self.merge_error(error);
+        self.no_nearest(nearest, field.name);
         self.fields.add_field_ref(field.clone(), fields_holder.clone());
         self.filters.add_field_ref_query_equal(Rc::new(field.clone()), filter);
         self.orders
@@ -165,7 +388,11 @@ impl<T: 'static> FilterBuilder<T> {
             filters,
             fields,
             orders,
+            nearest,
+            error,
         } = query;
+        self.merge_error(error);
+        self.no_nearest(nearest, field.name);
         self.fields.add_field_vec_ref(field.clone(), fields.clone());
         self.filters
             .add_field_ref_query_contains(Rc::new(field.clone()), filters);
@@ -181,7 +408,11 @@ impl<T: 'static> FilterBuilder<T> {
             filters,
             fields,
             orders,
+            nearest,
+            error,
         } = query;
+        self.merge_error(error);
+        self.no_nearest(nearest, field.name);
         self.fields.add_field_option_ref(field.clone(), fields.clone());
         self.orders
             .push(OrdersModel::new_query_is(Rc::new(field.clone()), orders));
@@ -193,7 +424,11 @@ impl<T: 'static> FilterBuilder<T> {
             mut filters,
             orders,
             fields,
+            nearest,
+            error,
         } = builder;
+        self.merge_error(error);
+        self.no_nearest(nearest, "or");
         filters.mode = FilterMode::Or;
         self.fields.merge(fields);
         self.filters.add_group(filters);
@@ -205,7 +440,11 @@ impl<T: 'static> FilterBuilder<T> {
             mut filters,
             fields,
             orders,
+            nearest,
+            error,
         } = builder;
+        self.merge_error(error);
+        self.merge_nearest(nearest);
         filters.mode = FilterMode::And;
         self.fields.merge(fields);
         self.filters.add_group(filters);
@@ -217,7 +456,11 @@ impl<T: 'static> FilterBuilder<T> {
             mut filters,
             fields,
             orders,
+            nearest,
+            error,
         } = filters;
+        self.merge_error(error);
+        self.merge_nearest(nearest);
         filters.mode = FilterMode::And;
         self.fields.merge(fields);
         self.filters.add_group(filters);
@@ -229,16 +472,73 @@ impl<T: 'static> FilterBuilder<T> {
             mut filters,
             fields,
             orders,
+            nearest,
+            error,
         } = builder;
+        self.merge_error(error);
+        self.no_nearest(nearest, "not");
         filters.mode = FilterMode::Not;
         self.filters.add_group(filters);
         self.fields.merge(fields);
         self.orders.extend(orders);
     }
 
+    /// Move the nearest of a group joined in and to this builder, the nearest limits the whole query
+    fn merge_nearest(&mut self, nearest: Option<Nearest>) {
+        if let Some(nearest) = nearest {
+            if let Some(current) = &self.nearest {
+                let error = format!(
+                    "the nearest of the field '{}' conflicts with the nearest of the field '{}'",
+                    nearest.field, current.field
+                );
+                return self.invalid(error);
+            }
+            self.nearest = Some(nearest);
+        }
+    }
+
+    /// The nearest limits the records of the whole query, it cannot be nested in a condition
+    fn no_nearest(&mut self, nearest: Option<Nearest>, condition: &str) {
+        if let Some(nearest) = nearest {
+            self.invalid(format!(
+                "the nearest of the field '{}' is not supported inside '{}'",
+                nearest.field, condition
+            ));
+        }
+    }
+
+    /// Keep the first invalid condition, reported when the query is executed
+    fn invalid(&mut self, error: String) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    /// Keep the invalid condition of a group joined to this builder
+    fn merge_error(&mut self, error: Option<String>) {
+        if let Some(error) = error {
+            self.invalid(error);
+        }
+    }
+
+    fn check(&self) -> SRes<()> {
+        match &self.error {
+            Some(error) => Err(StructsyError::TypeError(error.clone())),
+            None => Ok(()),
+        }
+    }
+
     pub fn order<V: ValueRange + Ord + 'static>(&mut self, field: Field<T, V>, order: Order) {
         self.orders
             .push(OrdersModel::new_field(Rc::new(field.clone()), order.clone()));
         self.fields.add_field_ord(field.clone());
     }
 }
+
+/// The message of an error found checking a condition
+fn error_message(error: StructsyError) -> String {
+    match error {
+        StructsyError::TypeError(message) => message,
+        error => error.to_string(),
+    }
+}
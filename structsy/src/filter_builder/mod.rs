@@ -8,6 +8,7 @@ mod reader;
 mod value_compare;
 
 pub use filter_builder::FilterBuilder;
+pub(crate) use plan_model::QueryValuePlan;
 pub(crate) use query_model::{SolveQueryRange, SolveQueryValue};
 pub(crate) use reader::{Reader, ReaderIterator};
-pub(crate) use value_compare::{ValueCompare, ValueRange};
+pub(crate) use value_compare::{map_option_vec, ValueCompare, ValueRange};
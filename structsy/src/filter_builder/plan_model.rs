@@ -1,14 +1,15 @@
 use crate::{
-    desc::ValueType,
+    desc::{SimpleValueType, ValueType},
     error::SRes,
     filter_builder::query_model::{
-        FieldOrder, FilterFieldItem, FilterItem, FilterMode, FilterType, Orders, OrdersFilters, Projection, Query,
-        QueryValue, RangeQueryValue, SimpleQueryValue,
+        FieldOrder, FilterFieldItem, FilterItem, FilterMode, FilterType, Nearest, Orders, OrdersFilters, Projection,
+        Query, QueryValue, RangeQueryValue, SimpleQueryValue,
     },
+    geo::GeoArea,
     internal::FieldInfo,
     Order,
 };
-use std::rc::Rc;
+use std::{ops::Bound, rc::Rc};
 
 use super::query_model::{FieldNestedOrders, FilterHolder};
 
@@ -44,6 +45,15 @@ pub(crate) struct TypeSource {
 pub(crate) enum Source {
     Index(IndexInfo),
     Scan(TypeSource),
+    /// The candidates found with the vector index, from the nearest
+    Nearest(Nearest),
+    /// The records in the cells of a geo index that cover an area
+    Geo(GeoCover),
+}
+
+pub(crate) struct GeoCover {
+    pub(crate) index_name: String,
+    pub(crate) ranges: Vec<(u64, u64)>,
 }
 
 pub(crate) struct FilterFieldPlanItem {
@@ -70,13 +80,79 @@ pub(crate) struct FilterPlan {
 }
 
 impl FilterPlan {
+    fn requires_bool(&self, path: &[String], value: bool) -> bool {
+        if self.mode != FilterPlanMode::And {
+            return false;
+        }
+        self.filters.iter().any(|filter| match filter {
+            FilterPlanItem::Field(f) => {
+                f.field.field_path_names() == path
+                    && matches!(
+                        &f.filter_by,
+                        FilterByPlan::Equal(QueryValuePlan::Single(SimpleQueryValue::Bool(v))) if *v == value
+                    )
+            }
+            FilterPlanItem::Group(_) => false,
+        })
+    }
+
+    /// The cells of the geo index of the first area required by the filter
+    fn find_geo_cover(&self, type_name: &str, info_finder: &dyn InfoFinder) -> Option<GeoCover> {
+        if self.mode != FilterPlanMode::And {
+            return None;
+        }
+        self.filters.iter().find_map(|filter| match filter {
+            FilterPlanItem::Field(FilterFieldPlanItem {
+                field,
+                filter_by: FilterByPlan::Geo(area),
+            }) => {
+                let range = RangeQueryValue::U64((Bound::Unbounded, Bound::Unbounded));
+                info_finder
+                    .find_computed_index(type_name, field, range, &ValueType::resolve::<u64>())
+                    .map(|info| GeoCover {
+                        index_name: info.index_name,
+                        ranges: area.cover(),
+                    })
+            }
+            _ => None,
+        })
+    }
+
+    /// If the only condition of the filter is the range of the index
+    fn solved_by(&self, index: &IndexInfo) -> bool {
+        match self.filters.as_slice() {
+            [FilterPlanItem::Field(f)] => {
+                self.mode == FilterPlanMode::And
+                    && f.field.field_path_names() == index.field_path_names()
+                    && matches!(
+                        f.filter_by,
+                        FilterByPlan::Equal(QueryValuePlan::Single(_)) | FilterByPlan::Range(_)
+                    )
+            }
+            _ => false,
+        }
+    }
+
     fn find_possible_indexes(&self, type_name: &str, info_finder: &dyn InfoFinder) -> Vec<IndexInfo> {
         let mut vec = Vec::new();
         match self.mode {
             FilterPlanMode::And => {
                 for filter in &self.filters {
                     if let FilterPlanItem::Field(f) = filter {
-                        if let Some(range) = f.filter_by.solve_range() {
+                        if let FilterByPlan::IndexRange(range, value_type) = &f.filter_by {
+                            let index = info_finder.find_computed_index(type_name, &f.field, range.clone(), value_type);
+                            if let Some(info) = index {
+                                vec.push(info);
+                            }
+                        } else if let FilterByPlan::FullText(range) = &f.filter_by {
+                            let string_type = ValueType::resolve::<String>();
+                            let index =
+                                info_finder.find_computed_index(type_name, &f.field, range.clone(), &string_type);
+                            if let Some(info) = index {
+                                // The records of a term are not in the order of the search rank
+                                vec.push(info.unordered());
+                            }
+                        } else if let Some(range) = f.filter_by.solve_range() {
                             if let Some(info) = info_finder.find_index(&type_name, &f.field, Some(range), Order::Asc) {
                                 vec.push(info);
                             }
@@ -113,6 +189,8 @@ pub enum QueryValuePlan {
     Option(Option<SimpleQueryValue>),
     Array(Vec<SimpleQueryValue>),
     OptionArray(Option<Vec<SimpleQueryValue>>),
+    NestedOption(Option<Box<QueryValuePlan>>),
+    NestedArray(Vec<QueryValuePlan>),
 }
 
 impl QueryValuePlan {
@@ -122,6 +200,8 @@ impl QueryValuePlan {
             QueryValue::Option(s) => QueryValuePlan::Option(s),
             QueryValue::Vec(v) => QueryValuePlan::Array(v),
             QueryValue::OptionVec(s) => QueryValuePlan::OptionArray(s),
+            QueryValue::NestedOption(s) => QueryValuePlan::NestedOption(s.map(|v| Box::new(Self::translate(*v)))),
+            QueryValue::NestedVec(v) => QueryValuePlan::NestedArray(v.into_iter().map(Self::translate).collect()),
         }
     }
     fn to_range(&self) -> Option<RangeQueryValue> {
@@ -137,6 +217,8 @@ impl QueryValuePlan {
             }
             QueryValuePlan::Array(_) => None,
             QueryValuePlan::OptionArray(_) => None,
+            QueryValuePlan::NestedOption(_) => None,
+            QueryValuePlan::NestedArray(_) => None,
         }
     }
 }
@@ -145,9 +227,16 @@ pub(crate) enum FilterByPlan {
     Equal(QueryValuePlan),
     Contains(QueryValuePlan),
     Is(QueryValuePlan),
+    ContainsKey(QueryValuePlan),
+    KeyEqual(QueryValuePlan, QueryValuePlan),
+    Prefix(QueryValuePlan),
+    Variant(u32),
     Range(RangeQueryValue),
     RangeContains(RangeQueryValue),
     RangeIs(RangeQueryValue),
+    IndexRange(RangeQueryValue, Box<ValueType>),
+    FullText(RangeQueryValue),
+    Geo(GeoArea),
     LoadAndEqual(FilterPlan),
     LoadAndContains(FilterPlan),
     LoadAndIs(FilterPlan),
@@ -158,9 +247,19 @@ impl FilterByPlan {
             Self::Equal(e) => e.to_range(),
             Self::Contains(e) => e.to_range(),
             Self::Is(e) => e.to_range(),
+            Self::ContainsKey(_) => None,
+            Self::KeyEqual(_, _) => None,
+            Self::Prefix(_) => None,
+            Self::Variant(position) => Some(RangeQueryValue::U32((
+                Bound::Included(*position),
+                Bound::Included(*position),
+            ))),
             Self::Range(e) => Some(e.clone()),
             Self::RangeContains(e) => Some(e.clone()),
             Self::RangeIs(e) => Some(e.clone()),
+            Self::IndexRange(e, _) => Some(e.clone()),
+            Self::FullText(_) => None,
+            Self::Geo(_) => None,
             Self::LoadAndEqual(_) => None,
             Self::LoadAndContains(_) => None,
             Self::LoadAndIs(_) => None,
@@ -246,6 +345,9 @@ impl OrdersPlan {
         vec
     }
     fn consider_index(&mut self, index: &IndexInfo) {
+        if !index.ordered {
+            return;
+        }
         if self.orders.len() == 1 {
             self.orders.retain(|o| match o {
                 OrderPlanItem::Field(f) => {
@@ -275,11 +377,15 @@ pub(crate) struct QueryPlan {
     pub(crate) filter: Option<FilterPlan>,
     pub(crate) orders: Option<OrdersPlan>,
     pub(crate) projections: Option<ProjectionsPlan>,
+    /// Keep only the nearest records, ranked by their distance
+    pub(crate) nearest: Option<Nearest>,
 }
 
 pub(crate) struct ProjectionsPlan {
     #[allow(unused)]
     projections: Vec<ProjectionPlan>,
+    /// The projected values are read from the index of the source, without load the records
+    pub(crate) index_only: bool,
 }
 pub(crate) struct ProjectionPlan {
     #[allow(unused)]
@@ -319,9 +425,19 @@ fn rationalize_filters_deep(
                     FilterType::Equal(val) => Some(FilterByPlan::Equal(QueryValuePlan::translate(val))),
                     FilterType::Contains(val) => Some(FilterByPlan::Contains(QueryValuePlan::translate(val))),
                     FilterType::Is(val) => Some(FilterByPlan::Is(QueryValuePlan::translate(val))),
+                    FilterType::ContainsKey(key) => Some(FilterByPlan::ContainsKey(QueryValuePlan::translate(key))),
+                    FilterType::KeyEqual(key, val) => Some(FilterByPlan::KeyEqual(
+                        QueryValuePlan::translate(key),
+                        QueryValuePlan::translate(val),
+                    )),
+                    FilterType::Prefix(value) => Some(FilterByPlan::Prefix(QueryValuePlan::translate(value))),
+                    FilterType::Variant(position) => Some(FilterByPlan::Variant(position)),
                     FilterType::Range(bound) => Some(FilterByPlan::Range(bound)),
                     FilterType::RangeContains(bound) => Some(FilterByPlan::RangeContains(bound)),
                     FilterType::RangeIs(bound) => Some(FilterByPlan::RangeIs(bound)),
+                    FilterType::IndexRange(bound, value_type) => Some(FilterByPlan::IndexRange(bound, value_type)),
+                    FilterType::FullText(bound) => Some(FilterByPlan::FullText(bound)),
+                    FilterType::Geo(area) => Some(FilterByPlan::Geo(area)),
                     FilterType::Embedded(x) => {
                         flat_or_deep_filter(x, parent_mode, f_path.clone(), elements);
                         None
@@ -407,6 +523,10 @@ pub(crate) struct IndexInfo {
     pub(crate) index_range: Option<RangeQueryValue>,
     pub(crate) ordering_mode: Order,
     pub(crate) value_type: ValueType,
+    /// Path and value of the boolean field required by a partial index
+    pub(crate) condition: Option<(Vec<String>, bool)>,
+    /// If the index iterates the records in the order of the indexed field
+    pub(crate) ordered: bool,
 }
 impl IndexInfo {
     pub(crate) fn new(
@@ -422,11 +542,39 @@ impl IndexInfo {
             index_range,
             ordering_mode,
             value_type,
+            condition: None,
+            ordered: true,
         }
     }
+    pub(crate) fn unordered(mut self) -> IndexInfo {
+        self.ordered = false;
+        self
+    }
+    pub(crate) fn with_condition(mut self, field_path: Vec<String>, value: bool) -> IndexInfo {
+        self.condition = Some((field_path, value));
+        self
+    }
     fn field_path_names(&self) -> Vec<String> {
         self.field_path.field_path_names()
     }
+    /// If every record has in the index exactly one key equal to the value of the field
+    fn holds_values(&self) -> bool {
+        use SimpleValueType::*;
+        self.condition.is_none()
+            && self.ordered
+            && matches!(
+                &self.value_type,
+                ValueType::Value(U8 | U16 | U32 | U64 | U128 | I8 | I16 | I32 | I64 | I128 | F32 | F64 | Bool | String)
+            )
+    }
+    /// A partial index can be used only if the filter requires the same condition of the index
+    fn usable_with(&self, filter: Option<&FilterPlan>) -> bool {
+        if let Some((path, value)) = &self.condition {
+            filter.is_some_and(|f| f.requires_bool(path, *value))
+        } else {
+            true
+        }
+    }
 }
 
 pub(crate) trait InfoFinder {
@@ -437,9 +585,21 @@ pub(crate) trait InfoFinder {
         range: Option<RangeQueryValue>,
         mode: Order,
     ) -> Option<IndexInfo>;
+    fn find_computed_index(
+        &self,
+        type_name: &str,
+        field_path: &FieldPathPlan,
+        range: RangeQueryValue,
+        value_type: &ValueType,
+    ) -> Option<IndexInfo>;
     fn score_index(&mut self, index: &IndexInfo) -> SRes<usize>;
 }
 
+fn retain_usable(mut indexes: Vec<IndexInfo>, filter: Option<&FilterPlan>) -> Vec<IndexInfo> {
+    indexes.retain(|index| index.usable_with(filter));
+    indexes
+}
+
 fn choose_index(
     mut filter_indexes: Option<Vec<IndexInfo>>,
     mut orders_indexes: Option<Vec<IndexInfo>>,
@@ -447,8 +607,10 @@ fn choose_index(
 ) -> Option<IndexInfo> {
     if let Some(index_info) = orders_indexes.as_mut().map(|v| v.pop()).flatten() {
         if let Some(fi) = filter_indexes {
-            for filter in fi {
+            for mut filter in fi {
                 if index_info.field_path_names() == filter.field_path_names() {
+                    // The range of the filter read in the direction of the order
+                    filter.ordering_mode = index_info.ordering_mode;
                     return Some(filter);
                 }
             }
@@ -462,16 +624,72 @@ fn choose_index(
     }
 }
 
-fn rationalize_projections(projections: Vec<Projection>) -> Option<ProjectionsPlan> {
-    if projections.is_empty() {
-        None
+/// Plan the projections, when the only projected field is the one of an index that solves alone
+/// the filter and the orders the projections are read from the index, that becomes the source
+/// also of a query without filter, when no field is projected only the ids are read
+fn rationalize_projections(
+    projections: Option<Vec<Projection>>,
+    type_name: &str,
+    source: &mut Source,
+    filter: Option<&FilterPlan>,
+    orders: Option<&OrdersPlan>,
+    nearest: Option<&Nearest>,
+    info_finder: &dyn InfoFinder,
+) -> Option<ProjectionsPlan> {
+    let projections = projections?;
+    // The records must not be ranked or sorted after read from the source
+    let ordered = nearest.is_none() && orders.map_or(true, |o| o.orders.is_empty());
+    let index_only = match (projections.as_slice(), &*source) {
+        ([], Source::Index(index)) if ordered => index.holds_values() && filter.map_or(true, |f| f.solved_by(index)),
+        ([], Source::Scan(_)) => ordered && filter.is_none(),
+        ([projection], Source::Index(index)) if ordered => {
+            index.field_path_names() == [projection.field]
+                && index.holds_values()
+                && filter.map_or(true, |f| f.solved_by(index))
+        }
+        ([projection], Source::Scan(_)) if ordered && filter.is_none() => {
+            let mut path = FieldPathPlan::new();
+            path.push(Rc::new(Projection::new(projection.field)));
+            let index = info_finder
+                .find_index(type_name, &path, None, Order::Asc)
+                .filter(IndexInfo::holds_values);
+            if let Some(index) = index {
+                *source = Source::Index(index);
+                true
+            } else {
+                false
+            }
+        }
+        _ => false,
+    };
+    Some(ProjectionsPlan {
+        projections: projections
+            .into_iter()
+            .map(|prj| ProjectionPlan {
+                field: prj.field.to_owned(),
+            })
+            .collect(),
+        index_only,
+    })
+}
+
+/// Max number of records estimated from the filter index for rank all of them by the exact
+/// distance, with more records the candidates from the vector index are filtered instead
+const NEAREST_PREFILTER_LIMIT: usize = 1000;
+
+/// Source of a nearest query, the records selected by a selective filter index are ranked by
+/// the exact distance, otherwise the candidates of the vector index are filtered until enough pass
+fn nearest_source(
+    nearest: &Nearest,
+    filter_indexes: Option<Vec<IndexInfo>>,
+    info_finder: &mut dyn InfoFinder,
+) -> Source {
+    let index = choose_index(filter_indexes, None, info_finder)
+        .filter(|idx| info_finder.score_index(idx).unwrap_or(usize::MAX) <= NEAREST_PREFILTER_LIMIT);
+    if let Some(idx) = index {
+        Source::Index(idx)
     } else {
-        Some(ProjectionsPlan {
-            projections: projections
-                .into_iter()
-                .map(|prj| ProjectionPlan { field: prj.field })
-                .collect(),
-        })
+        Source::Nearest(nearest.clone())
     }
 }
 
@@ -480,11 +698,11 @@ pub(crate) fn plan_from_query(query: Query, info_finder: &mut dyn InfoFinder) ->
         type_name,
         projections,
         orders_filter: OrdersFilters { filter, orders },
+        nearest,
     } = query;
 
     let filter = rationalize_filters(filter);
     let mut orders = rationalize_orders(orders);
-    let projections = rationalize_projections(projections);
 
     // The found index need to have inside the criteria for iterate trough them
     let filter_indexes = if let Some(f) = &filter {
@@ -498,45 +716,112 @@ pub(crate) fn plan_from_query(query: Query, info_finder: &mut dyn InfoFinder) ->
     } else {
         None
     };
-
-    let index = choose_index(filter_indexes, orders_indexes, info_finder);
-    if let Some(idx) = index {
+    let filter_indexes = filter_indexes.map(|v| retain_usable(v, filter.as_ref()));
+    let orders_indexes = orders_indexes.map(|v| retain_usable(v, filter.as_ref()));
+    let mut source = if let Some(nearest) = &nearest {
+        nearest_source(nearest, filter_indexes, info_finder)
+    } else if let Some(cover) = filter.as_ref().and_then(|f| f.find_geo_cover(&type_name, info_finder)) {
+        // An area is usually far more selective than the other conditions
+        Source::Geo(cover)
+    } else if let Some(idx) = choose_index(filter_indexes, orders_indexes, info_finder) {
         if let Some(orders) = &mut orders {
             orders.consider_index(&idx);
         }
-        Ok(QueryPlan {
-            source: Source::Index(idx),
-            filter,
-            orders,
-            projections,
-        })
+        Source::Index(idx)
     } else {
-        Ok(QueryPlan {
-            source: Source::Scan(TypeSource { name: type_name }),
-            filter,
-            orders,
-            projections,
+        Source::Scan(TypeSource {
+            name: type_name.clone(),
         })
-    }
+    };
+    let projections = rationalize_projections(
+        projections,
+        &type_name,
+        &mut source,
+        filter.as_ref(),
+        orders.as_ref(),
+        nearest.as_ref(),
+        info_finder,
+    );
+    Ok(QueryPlan {
+        source,
+        filter,
+        orders,
+        projections,
+        nearest,
+    })
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::{rationalize_filters, rationalize_orders};
+    use super::{plan_from_query, rationalize_filters, rationalize_orders, IndexInfo, InfoFinder};
     use crate::{
+        desc::ValueType,
         filter_builder::{
-            plan_model::{FilterPlanItem, FilterPlanMode, OrderPlanItem},
-            query_model::{FilterHolder, FilterMode, Orders},
+            plan_model::{FieldPathPlan, FilterPlanItem, FilterPlanMode, OrderPlanItem, Source},
+            query_model::{FilterHolder, FilterMode, Orders, Projection, Query, RangeQueryValue},
         },
         internal::Field,
-        Order,
+        Order, SRes,
     };
     use std::rc::Rc;
     struct Test {}
     fn tf(name: &'static str) -> Rc<Field<Test, u8>> {
         Rc::new(Field::<Test, u8>::new(name, |_| unreachable!()))
     }
+
+    /// Finder of the only index on the field `size`
+    struct SizeIndex {}
+    impl InfoFinder for SizeIndex {
+        fn find_index(
+            &self,
+            type_name: &str,
+            field_path: &FieldPathPlan,
+            range: Option<RangeQueryValue>,
+            mode: Order,
+        ) -> Option<IndexInfo> {
+            if field_path.field_path_names() == ["size"] {
+                let name = format!("{}.size", type_name);
+                Some(IndexInfo::new(
+                    field_path.clone(),
+                    name,
+                    range,
+                    mode,
+                    ValueType::resolve::<u8>(),
+                ))
+            } else {
+                None
+            }
+        }
+        fn find_computed_index(
+            &self,
+            _type_name: &str,
+            _field_path: &FieldPathPlan,
+            _range: RangeQueryValue,
+            _value_type: &ValueType,
+        ) -> Option<IndexInfo> {
+            None
+        }
+        fn score_index(&mut self, _index: &IndexInfo) -> SRes<usize> {
+            Ok(1)
+        }
+    }
+
+    fn index_only(filter: FilterHolder, orders: Vec<Orders>, field: &'static str) -> bool {
+        index_only_fields(filter, orders, vec![field])
+    }
+
+    fn index_only_fields(filter: FilterHolder, orders: Vec<Orders>, fields: Vec<&'static str>) -> bool {
+        let with_fields = !fields.is_empty();
+        let projections = fields.into_iter().map(Projection::new).collect();
+        let query = Query::new("Test", filter, orders, Some(projections), None);
+        let plan = plan_from_query(query, &mut SizeIndex {}).unwrap();
+        let index_only = plan.projections.unwrap().index_only;
+        if index_only && with_fields {
+            assert!(matches!(plan.source, Source::Index(_)));
+        }
+        index_only
+    }
     #[test]
     fn test_filter_rationalize_collapse() {
         let mut fh = FilterHolder::new(FilterMode::And);
@@ -612,4 +897,53 @@ mod tests {
             _ => panic!("expected load equal"),
         }
     }
+
+    #[test]
+    fn index_only_projections() {
+        assert!(index_only(FilterHolder::new(FilterMode::And), Vec::new(), "size"));
+        assert!(!index_only(FilterHolder::new(FilterMode::And), Vec::new(), "name"));
+
+        let mut fh = FilterHolder::new(FilterMode::And);
+        fh.add_field_equal(tf("size"), 10);
+        assert!(index_only(fh, Vec::new(), "size"));
+        let mut fh = FilterHolder::new(FilterMode::And);
+        fh.add_field_equal(tf("size"), 10);
+        fh.add_field_equal(tf("name"), 20);
+        assert!(!index_only(fh, Vec::new(), "size"));
+        let mut fh = FilterHolder::new(FilterMode::Or);
+        fh.add_field_equal(tf("size"), 10);
+        assert!(!index_only(fh, Vec::new(), "size"));
+
+        let orders = vec![Orders::new_field(tf("size"), Order::Desc)];
+        assert!(index_only(FilterHolder::new(FilterMode::And), orders, "size"));
+        let orders = vec![Orders::new_field(tf("name"), Order::Asc)];
+        assert!(!index_only(FilterHolder::new(FilterMode::And), orders, "size"));
+    }
+
+    #[test]
+    fn index_only_refs() {
+        assert!(index_only_fields(
+            FilterHolder::new(FilterMode::And),
+            Vec::new(),
+            Vec::new()
+        ));
+        let mut fh = FilterHolder::new(FilterMode::And);
+        fh.add_field_equal(tf("size"), 10);
+        assert!(index_only_fields(fh, Vec::new(), Vec::new()));
+        let mut fh = FilterHolder::new(FilterMode::And);
+        fh.add_field_equal(tf("name"), 10);
+        assert!(!index_only_fields(fh, Vec::new(), Vec::new()));
+        let orders = vec![Orders::new_field(tf("size"), Order::Desc)];
+        assert!(index_only_fields(
+            FilterHolder::new(FilterMode::And),
+            orders,
+            Vec::new()
+        ));
+        let orders = vec![Orders::new_field(tf("name"), Order::Asc)];
+        assert!(!index_only_fields(
+            FilterHolder::new(FilterMode::And),
+            orders,
+            Vec::new()
+        ));
+    }
 }
@@ -1,6 +1,9 @@
 use crate::{
+    desc::ValueType,
     error::SRes,
-    internal::{EmbeddedDescription, FieldInfo},
+    geo::GeoArea,
+    internal::{EmbeddedDescription, FieldInfo, SupportedType},
+    vector::Metric,
     Order, Persistent, Ref,
 };
 use persy::PersyId;
@@ -10,6 +13,7 @@ use std::{fmt::Debug, ops::Bound, rc::Rc};
 pub trait MyEq {
     fn my_eq(&self, other: &dyn MyEq) -> bool;
     fn gen_ref(&self) -> &dyn Any;
+    fn variant_position(&self) -> Option<u32>;
 }
 
 impl<T: EmbeddedDescription + PartialEq + 'static> MyEq for T {
@@ -23,12 +27,16 @@ impl<T: EmbeddedDescription + PartialEq + 'static> MyEq for T {
     fn gen_ref(&self) -> &dyn Any {
         self
     }
+    fn variant_position(&self) -> Option<u32> {
+        EmbeddedDescription::variant_position(self)
+    }
 }
 
 pub trait MyOrd {
     fn my_cmp(&self, other: &dyn MyOrd) -> Option<Ordering>;
     fn my_eq(&self, other: &dyn MyOrd) -> bool;
     fn gen_ref(&self) -> &dyn Any;
+    fn variant_position(&self) -> Option<u32>;
 }
 
 impl PartialEq<EmbValue> for dyn MyOrd {
@@ -65,6 +73,9 @@ impl<T: MyOrd + 'static> MyOrd for Option<T> {
     fn gen_ref(&self) -> &dyn Any {
         self
     }
+    fn variant_position(&self) -> Option<u32> {
+        None
+    }
 }
 
 impl<T: EmbeddedDescription + PartialOrd + 'static> MyOrd for T {
@@ -85,6 +96,9 @@ impl<T: EmbeddedDescription + PartialOrd + 'static> MyOrd for T {
     fn gen_ref(&self) -> &dyn Any {
         self
     }
+    fn variant_position(&self) -> Option<u32> {
+        EmbeddedDescription::variant_position(self)
+    }
 }
 
 impl PartialEq for EmbValue {
@@ -152,6 +166,13 @@ impl EmbValue {
     pub(crate) fn new_ord<T: EmbeddedDescription + PartialOrd + 'static>(t: T) -> Self {
         Self::OrdType(Rc::new(t))
     }
+    /// Position of the variant when the value is an enum
+    pub(crate) fn variant_position(&self) -> Option<u32> {
+        match self {
+            Self::OrdType(r) => r.variant_position(),
+            Self::EqType(e) => e.variant_position(),
+        }
+    }
 }
 
 impl Debug for EmbValue {
@@ -237,7 +258,12 @@ impl SimpleQueryValue {
             }
             SimpleQueryValue::Ref(v) => RangeQueryValue::Ref((Bound::Included(v.clone()), Bound::Included(v.clone()))),
             SimpleQueryValue::Embedded(v) => {
-                RangeQueryValue::Embedded((Bound::Included(v.clone()), Bound::Included(v.clone())))
+                // Enums are indexed by variant, the equality is then checked on the loaded records
+                if let Some(position) = v.variant_position() {
+                    RangeQueryValue::U32((Bound::Included(position), Bound::Included(position)))
+                } else {
+                    RangeQueryValue::Embedded((Bound::Included(v.clone()), Bound::Included(v.clone())))
+                }
             }
         }
     }
@@ -597,6 +623,33 @@ impl<T: EmbeddedDescription + PartialOrd + Clone + 'static> SolveRangeQueryValue
 
 pub trait SolveQueryValue {
     fn new(self) -> SRes<QueryValue>;
+    #[doc(hidden)]
+    fn new_option(value: Option<Self>) -> SRes<QueryValue>
+    where
+        Self: Sized,
+    {
+        Ok(QueryValue::NestedOption(
+            value.map(|v| v.new().map(Box::new)).transpose()?,
+        ))
+    }
+    #[doc(hidden)]
+    fn new_vec(values: Vec<Self>) -> SRes<QueryValue>
+    where
+        Self: Sized,
+    {
+        Ok(QueryValue::NestedVec(
+            values.into_iter().map(|v| v.new()).collect::<SRes<Vec<QueryValue>>>()?,
+        ))
+    }
+    #[doc(hidden)]
+    fn new_option_vec(values: Option<Vec<Self>>) -> SRes<QueryValue>
+    where
+        Self: Sized,
+    {
+        Ok(QueryValue::NestedOption(
+            values.map(|v| Self::new_vec(v).map(Box::new)).transpose()?,
+        ))
+    }
 }
 pub trait SolveQueryRange {
     fn range(val: (&Bound<&Self>, &Bound<&Self>)) -> RangeQueryValue;
@@ -606,6 +659,28 @@ impl<T: SolveSimpleQueryValue> SolveQueryValue for T {
     fn new(self) -> SRes<QueryValue> {
         Ok(QueryValue::Single(self.new()?))
     }
+    fn new_option(value: Option<Self>) -> SRes<QueryValue> {
+        Ok(QueryValue::Option(value.map(|v| v.new()).transpose()?))
+    }
+    fn new_vec(values: Vec<Self>) -> SRes<QueryValue> {
+        Ok(QueryValue::Vec(
+            values
+                .into_iter()
+                .map(|v| v.new())
+                .collect::<SRes<Vec<SimpleQueryValue>>>()?,
+        ))
+    }
+    fn new_option_vec(values: Option<Vec<Self>>) -> SRes<QueryValue> {
+        Ok(QueryValue::OptionVec(
+            values
+                .map(|vec| {
+                    vec.into_iter()
+                        .map(|v| v.new())
+                        .collect::<SRes<Vec<SimpleQueryValue>>>()
+                })
+                .transpose()?,
+        ))
+    }
 }
 impl<T: SolveRangeQueryValue> SolveQueryRange for T {
     fn range(val: (&Bound<&Self>, &Bound<&Self>)) -> RangeQueryValue {
@@ -613,9 +688,9 @@ impl<T: SolveRangeQueryValue> SolveQueryRange for T {
     }
 }
 
-impl<T: SolveSimpleQueryValue> SolveQueryValue for Option<T> {
+impl<T: SolveQueryValue> SolveQueryValue for Option<T> {
     fn new(self) -> SRes<QueryValue> {
-        Ok(QueryValue::Option(self.map(|v| v.new()).transpose()?))
+        T::new_option(self)
     }
 }
 impl<T: SolveRangeQueryValue> SolveQueryRange for Option<T> {
@@ -624,31 +699,18 @@ impl<T: SolveRangeQueryValue> SolveQueryRange for Option<T> {
     }
 }
 
-impl<T: SolveSimpleQueryValue> SolveQueryValue for Option<Vec<T>> {
-    fn new(self) -> SRes<QueryValue> {
-        Ok(QueryValue::OptionVec(
-            self.map(|vec| {
-                vec.into_iter()
-                    .map(|v| v.new())
-                    .collect::<SRes<Vec<SimpleQueryValue>>>()
-            })
-            .transpose()?,
-        ))
-    }
-}
 impl<T: SolveRangeQueryValue> SolveQueryRange for Option<Vec<T>> {
     fn range(val: (&Bound<&Self>, &Bound<&Self>)) -> RangeQueryValue {
         RangeQueryValue::OptionVec(SolveRangeQueryValue::range_option_vec(val))
     }
 }
 
-impl<T: SolveSimpleQueryValue> SolveQueryValue for Vec<T> {
+impl<T: SolveQueryValue> SolveQueryValue for Vec<T> {
     fn new(self) -> SRes<QueryValue> {
-        Ok(QueryValue::Vec(
-            self.into_iter()
-                .map(|v| v.new())
-                .collect::<SRes<Vec<SimpleQueryValue>>>()?,
-        ))
+        T::new_vec(self)
+    }
+    fn new_option(value: Option<Self>) -> SRes<QueryValue> {
+        T::new_option_vec(value)
     }
 }
 impl<T: SolveRangeQueryValue> SolveQueryRange for Vec<T> {
@@ -657,27 +719,42 @@ impl<T: SolveRangeQueryValue> SolveQueryRange for Vec<T> {
     }
 }
 
-#[derive(Debug)]
+impl<const N: usize> SolveQueryValue for [u8; N] {
+    fn new(self) -> SRes<QueryValue> {
+        Ok(QueryValue::Vec(self.iter().map(|v| SimpleQueryValue::U8(*v)).collect()))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl SolveQueryValue for bytes::Bytes {
+    fn new(self) -> SRes<QueryValue> {
+        Ok(QueryValue::Vec(self.iter().map(|v| SimpleQueryValue::U8(*v)).collect()))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum QueryValue {
     Single(SimpleQueryValue),
     Option(Option<SimpleQueryValue>),
     OptionVec(Option<Vec<SimpleQueryValue>>),
     Vec(Vec<SimpleQueryValue>),
+    NestedOption(Option<Box<QueryValue>>),
+    NestedVec(Vec<QueryValue>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct FilterFieldItem {
     pub(crate) field: Rc<dyn FieldInfo>,
     pub(crate) filter_type: FilterType,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum FilterItem {
     Field(FilterFieldItem),
     Group(FilterHolder),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct FilterHolder {
     pub(crate) filters: Vec<FilterItem>,
     pub(crate) mode: FilterMode,
@@ -707,6 +784,35 @@ impl FilterHolder {
             filter_type: FilterType::Contains(value.new().unwrap()),
         }))
     }
+    pub(crate) fn add_field_contains_key<K: SolveQueryValue>(&mut self, field: Rc<dyn FieldInfo>, key: K) {
+        self.filters.push(FilterItem::Field(FilterFieldItem {
+            field,
+            filter_type: FilterType::ContainsKey(key.new().unwrap()),
+        }))
+    }
+    pub(crate) fn add_field_prefix<V: SolveQueryValue>(&mut self, field: Rc<dyn FieldInfo>, value: V) {
+        self.filters.push(FilterItem::Field(FilterFieldItem {
+            field,
+            filter_type: FilterType::Prefix(value.new().unwrap()),
+        }))
+    }
+    pub(crate) fn add_field_variant(&mut self, field: Rc<dyn FieldInfo>, position: u32) {
+        self.filters.push(FilterItem::Field(FilterFieldItem {
+            field,
+            filter_type: FilterType::Variant(position),
+        }))
+    }
+    pub(crate) fn add_field_key_equal<K: SolveQueryValue, V: SolveQueryValue>(
+        &mut self,
+        field: Rc<dyn FieldInfo>,
+        key: K,
+        value: V,
+    ) {
+        self.filters.push(FilterItem::Field(FilterFieldItem {
+            field,
+            filter_type: FilterType::KeyEqual(key.new().unwrap(), value.new().unwrap()),
+        }))
+    }
 
     pub(crate) fn add_field_range<T: SolveQueryRange + Clone>(
         &mut self,
@@ -719,6 +825,31 @@ impl FilterHolder {
         }))
     }
 
+    pub(crate) fn add_index_range<T: SolveQueryRange + SupportedType + Clone>(
+        &mut self,
+        field: Rc<dyn FieldInfo>,
+        range: (&Bound<&T>, &Bound<&T>),
+    ) {
+        self.filters.push(FilterItem::Field(FilterFieldItem {
+            field,
+            filter_type: FilterType::IndexRange(SolveQueryRange::range(range), Box::new(T::resolve())),
+        }))
+    }
+
+    pub(crate) fn add_geo(&mut self, field: Rc<dyn FieldInfo>, area: GeoArea) {
+        self.filters.push(FilterItem::Field(FilterFieldItem {
+            field,
+            filter_type: FilterType::Geo(area),
+        }))
+    }
+
+    pub(crate) fn add_fulltext_term(&mut self, field: Rc<dyn FieldInfo>, term: &String) {
+        self.filters.push(FilterItem::Field(FilterFieldItem {
+            field,
+            filter_type: FilterType::FullText(SolveQueryRange::range((&Bound::Included(term), &Bound::Included(term)))),
+        }))
+    }
+
     pub(crate) fn add_field_range_is<T: SolveQueryRange + Clone>(
         &mut self,
         field: Rc<dyn FieldInfo>,
@@ -771,28 +902,35 @@ impl FilterHolder {
     }
 }
 
-#[derive(Ord, Eq, PartialEq, PartialOrd, Debug)]
+#[derive(Ord, Eq, PartialEq, PartialOrd, Debug, Clone)]
 pub(crate) enum FilterMode {
     And,
     Or,
     Not,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum FilterType {
     Equal(QueryValue),
     Contains(QueryValue),
     Is(QueryValue),
+    ContainsKey(QueryValue),
+    KeyEqual(QueryValue, QueryValue),
+    Prefix(QueryValue),
+    Variant(u32),
     Range(RangeQueryValue),
     RangeContains(RangeQueryValue),
     RangeIs(RangeQueryValue),
+    IndexRange(RangeQueryValue, Box<ValueType>),
+    FullText(RangeQueryValue),
+    Geo(GeoArea),
     Embedded(FilterHolder),
     QueryEqual(FilterHolder),
     QueryContains(FilterHolder),
     QueryIs(FilterHolder),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct OrdersFilters {
     pub(crate) orders: Vec<Orders>,
     pub(crate) filter: FilterHolder,
@@ -803,28 +941,58 @@ impl OrdersFilters {
     }
 }
 
+/// Request of the `k` records with the vector of a field nearest to `vector`
+#[derive(Debug, Clone)]
+pub(crate) struct Nearest {
+    pub(crate) field: &'static str,
+    pub(crate) index_name: String,
+    pub(crate) metric: Metric,
+    pub(crate) vector: Vec<f32>,
+    pub(crate) k: usize,
+}
+
 #[derive(Debug)]
 pub(crate) struct Query {
     pub(crate) type_name: String,
-    pub(crate) projections: Vec<Projection>,
+    /// The fields read by the projection of the query, `None` if it returns the whole records
+    pub(crate) projections: Option<Vec<Projection>>,
     pub(crate) orders_filter: OrdersFilters,
+    pub(crate) nearest: Option<Nearest>,
 }
 
 impl Query {
-    pub fn new(type_name: &str, filter: FilterHolder, orders: Vec<Orders>, projections: Vec<Projection>) -> Self {
+    pub fn new(
+        type_name: &str,
+        filter: FilterHolder,
+        orders: Vec<Orders>,
+        projections: Option<Vec<Projection>>,
+        nearest: Option<Nearest>,
+    ) -> Self {
         Self {
             type_name: type_name.to_owned(),
             orders_filter: OrdersFilters::new(filter, orders),
             projections,
+            nearest,
         }
     }
 }
 
+/// A field of the source read by a projection
 #[derive(Debug)]
 pub(crate) struct Projection {
-    pub(crate) field: String,
+    pub(crate) field: &'static str,
 }
-#[derive(Debug)]
+impl Projection {
+    pub(crate) fn new(field: &'static str) -> Projection {
+        Projection { field }
+    }
+}
+impl FieldInfo for Projection {
+    fn name(&self) -> &'static str {
+        self.field
+    }
+}
+#[derive(Debug, Clone)]
 pub(crate) enum Orders {
     Field(FieldOrder),
     Embeeded(FieldNestedOrders),
@@ -855,12 +1023,12 @@ impl Orders {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct FieldOrder {
     pub(crate) field: Rc<dyn FieldInfo>,
     pub(crate) mode: Order,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct FieldNestedOrders {
     pub(crate) field: Rc<dyn FieldInfo>,
     pub(crate) orders: Vec<Orders>,
@@ -1,10 +1,15 @@
 use crate::{
-    filter_builder::{desc_info_finder::index_find_range, plan_model::IndexInfo},
+    filter_builder::{
+        desc_info_finder::{index_find_projection, index_find_range},
+        plan_model::IndexInfo,
+    },
+    projection::QueryProjection,
     snapshot::{SnapshotIterator, SnapshotRecordIter},
-    structsy::RecordIter,
+    structsy::{RecordIter, StructsyImpl},
     transaction::{raw_tx_scan, TxRecordIter},
     Persistent, Ref, RefSytx, SRes, Snapshot, Structsy, StructsyTx,
 };
+use std::{marker::PhantomData, sync::Arc};
 
 pub trait ReaderIterator: Iterator {
     fn reader<'a>(&'a mut self) -> Reader<'a>;
@@ -35,6 +40,50 @@ impl<'a, T: Persistent> ReaderIterator for ScanIter<'a, T> {
     }
 }
 
+enum SegmentRefs<'a> {
+    Structsy((persy::SegmentIter, Structsy)),
+    Snapshot((persy::SnapshotSegmentIter, Snapshot)),
+    Tx((persy::TxSegmentIter<'a>, Arc<StructsyImpl>, &'a mut bool)),
+}
+
+/// Ids of the records of a segment, read without deserialize the records
+pub(crate) struct ScanRefsIter<'a, T> {
+    iter: SegmentRefs<'a>,
+    marker: PhantomData<T>,
+}
+impl<'a, T> ScanRefsIter<'a, T> {
+    fn new(iter: SegmentRefs<'a>) -> Self {
+        Self {
+            iter,
+            marker: PhantomData,
+        }
+    }
+}
+impl<'a, T: Persistent> Iterator for ScanRefsIter<'a, T> {
+    type Item = Ref<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = match &mut self.iter {
+            SegmentRefs::Structsy((it, _)) => it.next(),
+            SegmentRefs::Snapshot((it, _)) => it.next(),
+            SegmentRefs::Tx((it, _, _)) => it.next(),
+        };
+        next.map(|(id, _)| Ref::new(id))
+    }
+}
+impl<'a, T: Persistent> ReaderIterator for ScanRefsIter<'a, T> {
+    fn reader<'b>(&'b mut self) -> Reader<'b> {
+        match &mut self.iter {
+            SegmentRefs::Structsy((_, st)) => Reader::Structsy(st.clone()),
+            SegmentRefs::Snapshot((_, snap)) => Reader::Snapshot(snap.clone()),
+            SegmentRefs::Tx((it, structsy_impl, created_blobs)) => Reader::Tx(RefSytx {
+                structsy_impl: structsy_impl.clone(),
+                trans: it.tx(),
+                created_blobs,
+            }),
+        }
+    }
+}
+
 pub enum Reader<'a> {
     Structsy(Structsy),
     Snapshot(Snapshot),
@@ -53,7 +102,11 @@ impl<'a> Reader<'a> {
         match self {
             Reader::Structsy(st) => Ok(ScanIter::Structsy((st.scan::<T>()?, st.clone()))),
             Reader::Snapshot(snap) => Ok(ScanIter::Snapshot(snap.scan::<T>()?)),
-            Reader::Tx(RefSytx { structsy_impl, trans }) => Ok(ScanIter::Tx(raw_tx_scan(structsy_impl, trans)?)),
+            Reader::Tx(RefSytx {
+                structsy_impl,
+                trans,
+                created_blobs,
+            }) => Ok(ScanIter::Tx(raw_tx_scan(structsy_impl, trans, created_blobs)?)),
         }
     }
 
@@ -69,6 +122,57 @@ impl<'a> Reader<'a> {
         )
     }
 
+    /// The ids of all the records of the type, without deserialize them
+    pub(crate) fn scan_refs<T: Persistent>(self) -> SRes<ScanRefsIter<'a, T>> {
+        Ok(match self {
+            Reader::Structsy(st) => {
+                let def = st.structsy_impl.check_defined::<T>()?;
+                let iter = st.structsy_impl.persy.scan(def.segment_name())?;
+                ScanRefsIter::new(SegmentRefs::Structsy((iter, st)))
+            }
+            Reader::Snapshot(snap) => {
+                let def = snap.structsy_impl.check_defined::<T>()?;
+                let iter = snap.ps.scan(def.segment_name())?;
+                ScanRefsIter::new(SegmentRefs::Snapshot((iter, snap)))
+            }
+            Reader::Tx(RefSytx {
+                structsy_impl,
+                trans,
+                created_blobs,
+            }) => {
+                let def = structsy_impl.check_defined::<T>()?;
+                let iter = trans.scan(def.segment_name())?;
+                ScanRefsIter::new(SegmentRefs::Tx((iter, structsy_impl, created_blobs)))
+            }
+        })
+    }
+
+    /// The projections read from the keys of the index, without load the records
+    pub(crate) fn find_projection_from_info<P: QueryProjection<T> + 'a, T: Persistent + 'static>(
+        self,
+        info: IndexInfo,
+    ) -> SRes<Box<dyn ReaderIterator<Item = P> + 'a>> {
+        index_find_projection(
+            self,
+            &info.index_name,
+            info.index_range.unwrap_or(info.value_type.default_range()),
+            info.ordering_mode,
+        )
+    }
+
+    /// A reader on the same source borrowed from this one
+    pub(crate) fn reborrow(&mut self) -> Reader<'_> {
+        match self {
+            Reader::Structsy(st) => Reader::Structsy(st.clone()),
+            Reader::Snapshot(snap) => Reader::Snapshot(snap.clone()),
+            Reader::Tx(tx) => Reader::Tx(RefSytx {
+                structsy_impl: tx.structsy_impl.clone(),
+                trans: tx.trans,
+                created_blobs: tx.created_blobs,
+            }),
+        }
+    }
+
     pub(crate) fn structsy(&self) -> Structsy {
         match self {
             Reader::Structsy(st) => st.clone(),
@@ -10,6 +10,7 @@ use crate::{
     Ref,
 };
 use std::cmp::{min, Ordering};
+use std::collections::{BTreeMap, HashMap};
 use std::ops::{Bound, RangeBounds};
 
 use super::query_model::{MyEq, MyOrd};
@@ -18,6 +19,22 @@ pub trait ValueCompare {
     fn equals(&self, value: QueryValuePlan) -> bool;
     fn contains_value(&self, value: QueryValuePlan) -> bool;
     fn is(&self, value: QueryValuePlan) -> bool;
+    fn contains_key(&self, _key: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn key_equals(&self, _key: QueryValuePlan, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn starts_with(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn is_variant(&self, _position: u32) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
 }
 pub trait ValueRange: ValueCompare {
     type RangeType;
@@ -59,6 +76,10 @@ impl<T: ValueCompare> ValueCompare for Option<T> {
             (QueryValuePlan::Option(None), None) => true,
             (QueryValuePlan::Option(Some(_)), None) => false,
             (QueryValuePlan::Option(None), Some(_)) => false,
+            (QueryValuePlan::OptionArray(Some(v)), Some(ov)) => ov.equals(QueryValuePlan::Array(v)),
+            (QueryValuePlan::OptionArray(v), ov) => v.is_none() && ov.is_none(),
+            (QueryValuePlan::NestedOption(Some(v)), Some(ov)) => ov.equals(*v),
+            (QueryValuePlan::NestedOption(v), ov) => v.is_none() && ov.is_none(),
             _ => {
                 debug_assert!(false, "should never match a wrong type");
                 false
@@ -95,6 +116,12 @@ impl<T: ValueCompare> ValueCompare for Vec<T> {
                     false
                 }
             }
+            QueryValuePlan::NestedArray(v) => {
+                self.len() == v.len()
+                    && v.into_iter()
+                        .zip(self.iter())
+                        .all(|(first, second)| second.equals(first))
+            }
             _ => {
                 debug_assert!(false, "should never match a wrong type");
                 false
@@ -113,6 +140,124 @@ impl<T: ValueCompare> ValueCompare for Vec<T> {
         debug_assert!(false, "should never call wrong action");
         false
     }
+    fn starts_with(&self, value: QueryValuePlan) -> bool {
+        match value {
+            QueryValuePlan::Array(v) => {
+                v.len() <= self.len()
+                    && v.iter()
+                        .zip(self.iter())
+                        .all(|(first, second)| second.equals(QueryValuePlan::Single(first.clone())))
+            }
+            _ => {
+                debug_assert!(false, "should never match a wrong type");
+                false
+            }
+        }
+    }
+}
+
+fn bytes_equals(bytes: &[u8], value: QueryValuePlan) -> bool {
+    match value {
+        QueryValuePlan::Array(v) => v.len() == bytes.len() && bytes_starts_with(bytes, QueryValuePlan::Array(v)),
+        _ => {
+            debug_assert!(false, "should never match a wrong type");
+            false
+        }
+    }
+}
+
+fn bytes_starts_with(bytes: &[u8], value: QueryValuePlan) -> bool {
+    match value {
+        QueryValuePlan::Array(v) => {
+            v.len() <= bytes.len()
+                && v.iter()
+                    .zip(bytes.iter())
+                    .all(|(first, second)| matches!(first, SimpleQueryValue::U8(f) if f == second))
+        }
+        _ => {
+            debug_assert!(false, "should never match a wrong type");
+            false
+        }
+    }
+}
+
+impl<const N: usize> ValueCompare for [u8; N] {
+    fn equals(&self, value: QueryValuePlan) -> bool {
+        bytes_equals(self, value)
+    }
+    fn contains_value(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn is(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn starts_with(&self, value: QueryValuePlan) -> bool {
+        bytes_starts_with(self, value)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl ValueCompare for bytes::Bytes {
+    fn equals(&self, value: QueryValuePlan) -> bool {
+        bytes_equals(self, value)
+    }
+    fn contains_value(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn is(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn starts_with(&self, value: QueryValuePlan) -> bool {
+        bytes_starts_with(self, value)
+    }
+}
+
+impl<K: ValueCompare, V: ValueCompare> ValueCompare for BTreeMap<K, V> {
+    fn equals(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn contains_value(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn is(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn contains_key(&self, key: QueryValuePlan) -> bool {
+        self.keys().any(|k| k.equals(key.clone()))
+    }
+    fn key_equals(&self, key: QueryValuePlan, value: QueryValuePlan) -> bool {
+        self.iter()
+            .any(|(k, v)| k.equals(key.clone()) && v.equals(value.clone()))
+    }
+}
+
+impl<K: ValueCompare, V: ValueCompare> ValueCompare for HashMap<K, V> {
+    fn equals(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn contains_value(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn is(&self, _value: QueryValuePlan) -> bool {
+        debug_assert!(false, "should never call wrong action");
+        false
+    }
+    fn contains_key(&self, key: QueryValuePlan) -> bool {
+        self.keys().any(|k| k.equals(key.clone()))
+    }
+    fn key_equals(&self, key: QueryValuePlan, value: QueryValuePlan) -> bool {
+        self.iter()
+            .any(|(k, v)| k.equals(key.clone()) && v.equals(value.clone()))
+    }
 }
 
 impl<T: ValueRange + PartialOrd> ValueRange for Option<T>
@@ -275,7 +420,7 @@ where
     }
 }
 
-fn map_option_vec<T>(
+pub(crate) fn map_option_vec<T>(
     (b0, b1): (Bound<Option<Vec<T>>>, Bound<Option<Vec<T>>>),
 ) -> ((Bound<Vec<T>>, Bound<Vec<T>>), bool) {
     let is_none = match (&b0, &b0) {
@@ -512,6 +657,9 @@ impl<T: EmbeddedDescription + PartialEq + 'static> ValueCompare for T {
         debug_assert!(false, "should never call wrong action");
         false
     }
+    fn is_variant(&self, position: u32) -> bool {
+        self.variant_position() == Some(position)
+    }
 }
 impl<T: EmbeddedDescription + PartialOrd + Clone + 'static> ValueRange for T {
     type RangeType = EmbValue;
@@ -1,6 +1,10 @@
 use crate::index::{Finder, IndexFinder, NoneFinder};
-use crate::{Persistent, Ref, SRes};
+use crate::keyed::invalid_value;
+use crate::{Persistent, Ref, SRes, StructsyError, Sytx};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::hash::Hash;
 use std::io::{Read, Write};
 
 /// Base trait implemented by all types that can be persisted inside a struct.
@@ -15,6 +19,62 @@ pub trait PersistentEmbedded {
     {
         Box::new(NoneFinder::<Self>::default())
     }
+    #[doc(hidden)]
+    fn write_slice(values: &[Self], write: &mut dyn Write) -> SRes<()>
+    where
+        Self: Sized,
+    {
+        for v in values {
+            v.write(write)?;
+        }
+        Ok(())
+    }
+    #[doc(hidden)]
+    fn read_vec(len: usize, read: &mut dyn Read) -> SRes<Vec<Self>>
+    where
+        Self: Sized,
+    {
+        let mut v = Vec::new();
+        for _ in 0..len {
+            v.push(Self::read(read)?);
+        }
+        Ok(v)
+    }
+    /// Declare the indexes of the fields of an embedded struct, `path` is the path of the field
+    /// that holds it in the persistent struct `type_name`
+    #[doc(hidden)]
+    fn declare_nested_indexes(_db: &mut dyn Sytx, _type_name: &str, _path: &[&str]) -> SRes<()>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
+    #[doc(hidden)]
+    fn put_nested_indexes<P: Persistent>(
+        &self,
+        _tx: &mut dyn Sytx,
+        _type_name: &str,
+        _path: &[&str],
+        _id: &Ref<P>,
+    ) -> SRes<()>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
+    #[doc(hidden)]
+    fn remove_nested_indexes<P: Persistent>(
+        &self,
+        _tx: &mut dyn Sytx,
+        _type_name: &str,
+        _path: &[&str],
+        _id: &Ref<P>,
+    ) -> SRes<()>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
 }
 
 impl PersistentEmbedded for u8 {
@@ -31,6 +91,15 @@ impl PersistentEmbedded for u8 {
     {
         Box::new(IndexFinder::<Self>::default())
     }
+    fn write_slice(values: &[u8], write: &mut dyn Write) -> SRes<()> {
+        write.write_all(values)?;
+        Ok(())
+    }
+    fn read_vec(len: usize, read: &mut dyn Read) -> SRes<Vec<u8>> {
+        let mut v = vec![0; len];
+        read.read_exact(&mut v)?;
+        Ok(v)
+    }
 }
 impl PersistentEmbedded for i8 {
     fn write(&self, write: &mut dyn Write) -> SRes<()> {
@@ -61,6 +130,17 @@ impl PersistentEmbedded for bool {
     }
 }
 
+impl PersistentEmbedded for char {
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        WriteBytesExt::write_u32::<BigEndian>(write, *self as u32)?;
+        Ok(())
+    }
+    fn read(read: &mut dyn Read) -> SRes<char> {
+        let code = ReadBytesExt::read_u32::<BigEndian>(read)?;
+        char::from_u32(code).ok_or_else(|| invalid_value("Char", code))
+    }
+}
+
 impl PersistentEmbedded for String {
     fn write(&self, write: &mut dyn Write) -> SRes<()> {
         let b = self.as_bytes();
@@ -99,25 +179,185 @@ impl<T: PersistentEmbedded> PersistentEmbedded for Option<T> {
             Ok(None)
         }
     }
+    fn declare_nested_indexes(db: &mut dyn Sytx, type_name: &str, path: &[&str]) -> SRes<()> {
+        T::declare_nested_indexes(db, type_name, path)
+    }
+    fn put_nested_indexes<P: Persistent>(
+        &self,
+        tx: &mut dyn Sytx,
+        type_name: &str,
+        path: &[&str],
+        id: &Ref<P>,
+    ) -> SRes<()> {
+        if let Some(v) = self {
+            v.put_nested_indexes(tx, type_name, path, id)?;
+        }
+        Ok(())
+    }
+    fn remove_nested_indexes<P: Persistent>(
+        &self,
+        tx: &mut dyn Sytx,
+        type_name: &str,
+        path: &[&str],
+        id: &Ref<P>,
+    ) -> SRes<()> {
+        if let Some(v) = self {
+            v.remove_nested_indexes(tx, type_name, path, id)?;
+        }
+        Ok(())
+    }
 }
 
 impl<T: PersistentEmbedded> PersistentEmbedded for Vec<T> {
     fn write(&self, write: &mut dyn Write) -> SRes<()> {
         WriteBytesExt::write_u32::<BigEndian>(write, self.len() as u32)?;
+        T::write_slice(self, write)
+    }
+    fn read(read: &mut dyn Read) -> SRes<Vec<T>> {
+        let len = ReadBytesExt::read_u32::<BigEndian>(read)?;
+        T::read_vec(len as usize, read)
+    }
+    fn declare_nested_indexes(db: &mut dyn Sytx, type_name: &str, path: &[&str]) -> SRes<()> {
+        T::declare_nested_indexes(db, type_name, path)
+    }
+    fn put_nested_indexes<P: Persistent>(
+        &self,
+        tx: &mut dyn Sytx,
+        type_name: &str,
+        path: &[&str],
+        id: &Ref<P>,
+    ) -> SRes<()> {
         for v in self {
-            T::write(v, write)?;
+            v.put_nested_indexes(tx, type_name, path, id)?;
         }
         Ok(())
     }
-    fn read(read: &mut dyn Read) -> SRes<Vec<T>> {
+    fn remove_nested_indexes<P: Persistent>(
+        &self,
+        tx: &mut dyn Sytx,
+        type_name: &str,
+        path: &[&str],
+        id: &Ref<P>,
+    ) -> SRes<()> {
+        for v in self {
+            v.remove_nested_indexes(tx, type_name, path, id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fixed arrays have the same layout of a `Vec`, the length is checked on read
+impl<T: PersistentEmbedded, const N: usize> PersistentEmbedded for [T; N] {
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        WriteBytesExt::write_u32::<BigEndian>(write, N as u32)?;
+        T::write_slice(self, write)
+    }
+    fn read(read: &mut dyn Read) -> SRes<[T; N]> {
+        let len = ReadBytesExt::read_u32::<BigEndian>(read)? as usize;
+        if len != N {
+            return Err(StructsyError::TypeError(format!("expected {} values found {}", N, len)));
+        }
+        let values = T::read_vec(len, read)?;
+        values
+            .try_into()
+            .map_err(|_| StructsyError::TypeError(format!("expected {} values", N)))
+    }
+}
+
+macro_rules! impl_tuple_embedded {
+    ($($t:ident $pos:tt),+) => {
+        impl<$($t: PersistentEmbedded),+> PersistentEmbedded for ($($t,)+) {
+            fn write(&self, write: &mut dyn Write) -> SRes<()> {
+                $(self.$pos.write(write)?;)+
+                Ok(())
+            }
+            fn read(read: &mut dyn Read) -> SRes<Self> {
+                Ok(($($t::read(read)?,)+))
+            }
+        }
+    };
+}
+impl_tuple_embedded!(A 0, B 1);
+impl_tuple_embedded!(A 0, B 1, C 2);
+impl_tuple_embedded!(A 0, B 1, C 2, D 3);
+impl_tuple_embedded!(A 0, B 1, C 2, D 3, E 4);
+impl_tuple_embedded!(A 0, B 1, C 2, D 3, E 4, F 5);
+
+#[cfg(feature = "uuid")]
+impl PersistentEmbedded for uuid::Uuid {
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        self.as_u128().write(write)
+    }
+    fn read(read: &mut dyn Read) -> SRes<uuid::Uuid> {
+        Ok(uuid::Uuid::from_u128(u128::read(read)?))
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl PersistentEmbedded for rust_decimal::Decimal {
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        self.mantissa().write(write)?;
+        self.scale().write(write)
+    }
+    fn read(read: &mut dyn Read) -> SRes<rust_decimal::Decimal> {
+        let mantissa = i128::read(read)?;
+        let scale = u32::read(read)?;
+        rust_decimal::Decimal::try_from_i128_with_scale(mantissa, scale)
+            .map_err(|_| invalid_value("Decimal", format!("{}e-{}", mantissa, scale)))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl PersistentEmbedded for bytes::Bytes {
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        WriteBytesExt::write_u32::<BigEndian>(write, self.len() as u32)?;
+        write.write_all(self)?;
+        Ok(())
+    }
+    fn read(read: &mut dyn Read) -> SRes<bytes::Bytes> {
+        Ok(bytes::Bytes::from(Vec::<u8>::read(read)?))
+    }
+}
+impl<K: PersistentEmbedded + Ord, V: PersistentEmbedded> PersistentEmbedded for BTreeMap<K, V> {
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        WriteBytesExt::write_u32::<BigEndian>(write, self.len() as u32)?;
+        for (k, v) in self {
+            K::write(k, write)?;
+            V::write(v, write)?;
+        }
+        Ok(())
+    }
+    fn read(read: &mut dyn Read) -> SRes<BTreeMap<K, V>> {
         let len = ReadBytesExt::read_u32::<BigEndian>(read)?;
-        let mut v = Vec::new();
+        let mut m = BTreeMap::new();
         for _ in 0..len {
-            v.push(T::read(read)?);
+            let k = K::read(read)?;
+            m.insert(k, V::read(read)?);
         }
-        Ok(v)
+        Ok(m)
     }
 }
+
+impl<K: PersistentEmbedded + Eq + Hash, V: PersistentEmbedded> PersistentEmbedded for HashMap<K, V> {
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        WriteBytesExt::write_u32::<BigEndian>(write, self.len() as u32)?;
+        for (k, v) in self {
+            K::write(k, write)?;
+            V::write(v, write)?;
+        }
+        Ok(())
+    }
+    fn read(read: &mut dyn Read) -> SRes<HashMap<K, V>> {
+        let len = ReadBytesExt::read_u32::<BigEndian>(read)?;
+        let mut m = HashMap::with_capacity(len as usize);
+        for _ in 0..len {
+            let k = K::read(read)?;
+            m.insert(k, V::read(read)?);
+        }
+        Ok(m)
+    }
+}
+
 impl<T: Persistent> PersistentEmbedded for Ref<T> {
     fn write(&self, write: &mut dyn Write) -> SRes<()> {
         format!("{}", self.raw_id).write(write)?;
@@ -0,0 +1,142 @@
+//! Tokenization and storage of the full-text indexes.
+//!
+//! A full-text index is an inverted index named after the field with a `#fulltext` suffix,
+//! it maps every distinct token of the text to the ids of the records that contain it.
+//! The tokens are the unicode words of the text in lowercase, optionally reduced to
+//! their stem when the `stemming` feature is enabled. The tokenization requires the
+//! `fulltext` feature, without it no full-text index can be created or searched.
+//!
+use crate::{desc::index_name, index::declare_index, SRes, StructsyError, Sytx};
+use persy::ValueMode;
+#[cfg(feature = "fulltext")]
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Suffix of the name of the full-text index of a field
+pub(crate) const FULLTEXT_SUFFIX: &str = "#fulltext";
+
+/// Name of the field used for the full-text index of `field`
+pub fn fulltext_field_name(field: &str) -> String {
+    format!("{}{}", field, FULLTEXT_SUFFIX)
+}
+
+/// Text of a field that can have a full-text index
+pub trait FullTextValue {
+    fn text(&self) -> Option<&str>;
+}
+
+impl FullTextValue for String {
+    fn text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl FullTextValue for Option<String> {
+    fn text(&self) -> Option<&str> {
+        self.as_deref()
+    }
+}
+
+#[cfg(feature = "stemming")]
+fn algorithm(language: &str) -> Option<rust_stemmers::Algorithm> {
+    use rust_stemmers::Algorithm;
+    Some(match language {
+        "arabic" => Algorithm::Arabic,
+        "danish" => Algorithm::Danish,
+        "dutch" => Algorithm::Dutch,
+        "english" => Algorithm::English,
+        "finnish" => Algorithm::Finnish,
+        "french" => Algorithm::French,
+        "german" => Algorithm::German,
+        "greek" => Algorithm::Greek,
+        "hungarian" => Algorithm::Hungarian,
+        "italian" => Algorithm::Italian,
+        "norwegian" => Algorithm::Norwegian,
+        "portuguese" => Algorithm::Portuguese,
+        "romanian" => Algorithm::Romanian,
+        "russian" => Algorithm::Russian,
+        "spanish" => Algorithm::Spanish,
+        "swedish" => Algorithm::Swedish,
+        "tamil" => Algorithm::Tamil,
+        "turkish" => Algorithm::Turkish,
+        _ => return None,
+    })
+}
+
+#[cfg(not(feature = "fulltext"))]
+fn missing_feature() -> StructsyError {
+    StructsyError::TypeError("full-text indexes require the 'fulltext' feature".to_string())
+}
+
+/// Check that the full-text indexes are available, fails without the `fulltext` feature
+#[cfg(not(feature = "fulltext"))]
+pub(crate) fn check_fulltext(_stem: Option<&str>) -> SRes<()> {
+    Err(missing_feature())
+}
+
+/// Check that the full-text indexes are available and the stemming language is supported
+#[cfg(feature = "fulltext")]
+pub(crate) fn check_fulltext(stem: Option<&str>) -> SRes<()> {
+    match stem {
+        #[cfg(feature = "stemming")]
+        Some(language) if algorithm(language).is_none() => Err(StructsyError::TypeError(format!(
+            "stemming language '{}' not supported",
+            language
+        ))),
+        #[cfg(not(feature = "stemming"))]
+        Some(language) => Err(StructsyError::TypeError(format!(
+            "stemming language '{}' requires the 'stemming' feature",
+            language
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(feature = "stemming")]
+fn stem_words(words: Vec<String>, stem: Option<&str>) -> Vec<String> {
+    if let Some(algorithm) = stem.and_then(algorithm) {
+        let stemmer = rust_stemmers::Stemmer::create(algorithm);
+        words.into_iter().map(|w| stemmer.stem(&w).into_owned()).collect()
+    } else {
+        words
+    }
+}
+
+#[cfg(all(feature = "fulltext", not(feature = "stemming")))]
+fn stem_words(words: Vec<String>, _stem: Option<&str>) -> Vec<String> {
+    words
+}
+
+/// Split a text in the tokens used by the full-text indexes, keeping the repeated tokens
+#[cfg(feature = "fulltext")]
+pub fn tokenize(text: &str, stem: Option<&str>) -> SRes<Vec<String>> {
+    let words = text.unicode_words().map(|w| w.to_lowercase()).collect();
+    Ok(stem_words(words, stem))
+}
+
+/// Split a text in the tokens used by the full-text indexes, fails without the `fulltext` feature
+#[cfg(not(feature = "fulltext"))]
+pub fn tokenize(_text: &str, _stem: Option<&str>) -> SRes<Vec<String>> {
+    Err(missing_feature())
+}
+
+/// The distinct tokens of a value, the keys of the value in the full-text index
+pub fn index_tokens<V: FullTextValue>(value: &V, stem: Option<&str>) -> SRes<Vec<String>> {
+    let mut tokens = match value.text() {
+        Some(text) => tokenize(text, stem)?,
+        None => Vec::new(),
+    };
+    tokens.sort();
+    tokens.dedup();
+    Ok(tokens)
+}
+
+/// Number of occurrences of the terms in the tokens, used to rank the search results
+pub(crate) fn term_frequency(tokens: &[String], terms: &[String]) -> usize {
+    tokens.iter().filter(|t| terms.contains(t)).count()
+}
+
+pub fn declare_fulltext_index(db: &mut dyn Sytx, type_name: &str, field: &str, stem: Option<&str>) -> SRes<()> {
+    check_fulltext(stem)?;
+    let name = index_name(type_name, &[&fulltext_field_name(field)]);
+    declare_index::<String>(db, &name, ValueMode::Cluster)
+}
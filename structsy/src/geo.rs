@@ -0,0 +1,338 @@
+//! Geographic points and the cells used by the geo indexes.
+//!
+//! A geo index is named after the field with a `#geo` suffix and maps the cell of every point
+//! to the ids of the records that are in it. The cell is the position of the point on a Z-order
+//! curve: latitude and longitude are quantized to 32 bits and their bits interleaved, so the
+//! points of a cell at any level share the prefix of the key and every cell is a range of keys.
+//! A search covers the area with a few cells and checks the exact position of the found points.
+//!
+use crate::{
+    desc::{Description, FieldDescription, StructDescription},
+    internal::EmbeddedDescription,
+    PersistentEmbedded, SRes,
+};
+use std::io::{Read, Write};
+
+/// Suffix of the name of the geo index of a field
+pub(crate) const GEO_SUFFIX: &str = "#geo";
+/// Mean radius of the earth in meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+/// Bits used for the latitude and for the longitude in a cell key
+const CELL_BITS: u32 = 32;
+/// Max number of cells used to cover a searched area
+const MAX_COVER_CELLS: usize = 16;
+
+/// Name of the field used for the geo index of `field`
+pub fn geo_field_name(field: &str) -> String {
+    format!("{}{}", field, GEO_SUFFIX)
+}
+
+/// A point on the earth, latitude and longitude in degrees
+///
+/// # Example
+/// ```
+/// use structsy::GeoPoint;
+/// let rome = GeoPoint::new(41.9028, 12.4964);
+/// let milan = GeoPoint::new(45.4642, 9.19);
+/// assert!((rome.distance(&milan) - 477_000.0).abs() < 1_000.0);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl GeoPoint {
+    pub fn new(lat: f64, lon: f64) -> GeoPoint {
+        GeoPoint { lat, lon }
+    }
+
+    /// Great circle distance in meters from the other point
+    pub fn distance(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+    }
+
+    /// Key of the cell of this point in the geo index
+    pub(crate) fn cell(&self) -> u64 {
+        interleave(quantize_lat(self.lat), quantize_lon(self.lon))
+    }
+}
+
+impl PersistentEmbedded for GeoPoint {
+    fn write(&self, write: &mut dyn Write) -> SRes<()> {
+        self.lat.write(write)?;
+        self.lon.write(write)?;
+        Ok(())
+    }
+    fn read(read: &mut dyn Read) -> SRes<Self> {
+        let lat = f64::read(read)?;
+        let lon = f64::read(read)?;
+        Ok(GeoPoint { lat, lon })
+    }
+}
+
+impl EmbeddedDescription for GeoPoint {
+    fn get_description() -> Description {
+        let fields = [
+            FieldDescription::new::<f64>(0, "lat", None),
+            FieldDescription::new::<f64>(1, "lon", None),
+        ];
+        Description::Struct(StructDescription::new("GeoPoint", &fields))
+    }
+}
+
+/// A rectangle of latitudes and longitudes, when the longitude of the south west corner is
+/// greater than the one of the north east corner the rectangle crosses the 180th meridian
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeoRect {
+    pub south_west: GeoPoint,
+    pub north_east: GeoPoint,
+}
+
+impl GeoRect {
+    pub fn new(south_west: GeoPoint, north_east: GeoPoint) -> GeoRect {
+        GeoRect { south_west, north_east }
+    }
+
+    pub fn contains(&self, point: &GeoPoint) -> bool {
+        let (west, east) = (self.south_west.lon, self.north_east.lon);
+        let lon = if west <= east {
+            point.lon >= west && point.lon <= east
+        } else {
+            point.lon >= west || point.lon <= east
+        };
+        lon && point.lat >= self.south_west.lat && point.lat <= self.north_east.lat
+    }
+
+    /// Latitude and longitude bounds of the rectangle, split in two at the 180th meridian
+    fn bounds(&self) -> Vec<Bounds> {
+        let (south, north) = (self.south_west.lat, self.north_east.lat);
+        let (west, east) = (self.south_west.lon, self.north_east.lon);
+        if south > north {
+            Vec::new()
+        } else if west <= east {
+            vec![Bounds::new(south, north, west, east)]
+        } else {
+            vec![
+                Bounds::new(south, north, west, 180.0),
+                Bounds::new(south, north, -180.0, east),
+            ]
+        }
+    }
+}
+
+/// Area searched with a geo index
+#[derive(Clone, Debug)]
+pub(crate) enum GeoArea {
+    Rect(GeoRect),
+    /// Points within a distance in meters from the center
+    Radius(GeoPoint, f64),
+}
+
+impl GeoArea {
+    pub(crate) fn contains(&self, point: &GeoPoint) -> bool {
+        match self {
+            GeoArea::Rect(rect) => rect.contains(point),
+            GeoArea::Radius(center, meters) => center.distance(point) <= *meters,
+        }
+    }
+
+    /// Latitude and longitude bounds that contain the area
+    fn bounds(&self) -> Vec<Bounds> {
+        match self {
+            GeoArea::Rect(rect) => rect.bounds(),
+            GeoArea::Radius(center, meters) => {
+                if meters.is_nan() || *meters < 0.0 {
+                    return Vec::new();
+                }
+                let angle = meters / EARTH_RADIUS;
+                let dlat = angle.to_degrees();
+                let (south, north) = (center.lat - dlat, center.lat + dlat);
+                if south <= -90.0 || north >= 90.0 {
+                    // Around a pole all the longitudes are in the area
+                    return vec![Bounds::new(south, north, -180.0, 180.0)];
+                }
+                let ratio = angle.sin() / center.lat.to_radians().cos();
+                if ratio >= 1.0 {
+                    return vec![Bounds::new(south, north, -180.0, 180.0)];
+                }
+                let dlon = ratio.asin().to_degrees();
+                let (west, east) = (center.lon - dlon, center.lon + dlon);
+                if west < -180.0 {
+                    vec![
+                        Bounds::new(south, north, west + 360.0, 180.0),
+                        Bounds::new(south, north, -180.0, east),
+                    ]
+                } else if east > 180.0 {
+                    vec![
+                        Bounds::new(south, north, west, 180.0),
+                        Bounds::new(south, north, -180.0, east - 360.0),
+                    ]
+                } else {
+                    vec![Bounds::new(south, north, west, east)]
+                }
+            }
+        }
+    }
+
+    /// Ranges of cell keys that contain all the points of the area, sorted and not overlapping
+    pub(crate) fn cover(&self) -> Vec<(u64, u64)> {
+        let mut ranges = self
+            .bounds()
+            .iter()
+            .flat_map(|bounds| bounds.cover())
+            .map(|cell| cell.range())
+            .collect::<Vec<_>>();
+        ranges.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::new();
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if last.1 == u64::MAX || start <= last.1 + 1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+}
+
+fn quantize(value: f64, min: f64, span: f64) -> u32 {
+    let scaled = ((value - min) / span * 4_294_967_296.0).floor();
+    if scaled.is_nan() || scaled <= 0.0 {
+        0
+    } else if scaled >= u32::MAX as f64 {
+        u32::MAX
+    } else {
+        scaled as u32
+    }
+}
+
+fn quantize_lat(lat: f64) -> u32 {
+    quantize(lat, -90.0, 180.0)
+}
+
+fn quantize_lon(lon: f64) -> u32 {
+    quantize(lon, -180.0, 360.0)
+}
+
+/// Spread the bits of the value on the even bits of the result
+fn spread(value: u32) -> u64 {
+    let mut x = value as u64;
+    x = (x | (x << 16)) & 0x0000_FFFF_0000_FFFF;
+    x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    x = (x | (x << 1)) & 0x5555_5555_5555_5555;
+    x
+}
+
+fn interleave(lat: u32, lon: u32) -> u64 {
+    (spread(lat) << 1) | spread(lon)
+}
+
+/// Quantized bounds of an area, inclusive
+struct Bounds {
+    south: u64,
+    north: u64,
+    west: u64,
+    east: u64,
+}
+
+impl Bounds {
+    fn new(south: f64, north: f64, west: f64, east: f64) -> Bounds {
+        Bounds {
+            south: quantize_lat(south) as u64,
+            north: quantize_lat(north) as u64,
+            west: quantize_lon(west) as u64,
+            east: quantize_lon(east) as u64,
+        }
+    }
+
+    /// Cells that contain the bounds, refined level by level until the limit of cells
+    fn cover(&self) -> Vec<Cell> {
+        let mut cells = vec![Cell {
+            level: 0,
+            lat: 0,
+            lon: 0,
+        }];
+        loop {
+            let mut next = Vec::new();
+            let mut refined = false;
+            for cell in &cells {
+                if cell.level == CELL_BITS || cell.inside(self) {
+                    next.push(*cell);
+                } else {
+                    refined = true;
+                    next.extend(cell.children().into_iter().filter(|c| c.intersects(self)));
+                }
+            }
+            if !refined || next.len() > MAX_COVER_CELLS {
+                return cells;
+            }
+            cells = next;
+        }
+    }
+}
+
+/// A cell of the Z-order curve, at `level` latitude and longitude have `level` bits
+#[derive(Clone, Copy)]
+struct Cell {
+    level: u32,
+    lat: u64,
+    lon: u64,
+}
+
+impl Cell {
+    fn size(&self) -> u64 {
+        1 << (CELL_BITS - self.level)
+    }
+
+    fn lat_bounds(&self) -> (u64, u64) {
+        let start = self.lat << (CELL_BITS - self.level);
+        (start, start + self.size() - 1)
+    }
+
+    fn lon_bounds(&self) -> (u64, u64) {
+        let start = self.lon << (CELL_BITS - self.level);
+        (start, start + self.size() - 1)
+    }
+
+    fn inside(&self, bounds: &Bounds) -> bool {
+        let (south, north) = self.lat_bounds();
+        let (west, east) = self.lon_bounds();
+        south >= bounds.south && north <= bounds.north && west >= bounds.west && east <= bounds.east
+    }
+
+    fn intersects(&self, bounds: &Bounds) -> bool {
+        let (south, north) = self.lat_bounds();
+        let (west, east) = self.lon_bounds();
+        south <= bounds.north && north >= bounds.south && west <= bounds.east && east >= bounds.west
+    }
+
+    fn children(&self) -> Vec<Cell> {
+        let level = self.level + 1;
+        let mut children = Vec::new();
+        for lat in 0..2 {
+            for lon in 0..2 {
+                children.push(Cell {
+                    level,
+                    lat: (self.lat << 1) | lat,
+                    lon: (self.lon << 1) | lon,
+                });
+            }
+        }
+        children
+    }
+
+    /// First and last key of the points in the cell
+    fn range(&self) -> (u64, u64) {
+        let (south, _) = self.lat_bounds();
+        let (west, _) = self.lon_bounds();
+        let start = interleave(south as u32, west as u32);
+        let span = u64::MAX.checked_shr(2 * self.level).unwrap_or(0);
+        (start, start | span)
+    }
+}
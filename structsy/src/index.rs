@@ -1,7 +1,9 @@
 use crate::desc::index_name;
+use crate::geo::{geo_field_name, GeoPoint};
 use crate::transaction::TxIterator;
 use crate::{
     filter_builder::{Reader, ReaderIterator},
+    projection::QueryProjection,
     Persistent, Ref, RefSytx, SRes, Snapshot, Structsy, StructsyImpl, Sytx,
 };
 use persy::{IndexType, PersyId, ValueIter, ValueMode};
@@ -11,6 +13,8 @@ use std::vec::IntoIter;
 
 /// Trait implemented by all the values that can be directly indexed.
 pub trait IndexableValue {
+    /// The type of the key stored in the index
+    type Key: IndexType;
     fn puts<P: Persistent>(&self, tx: &mut dyn Sytx, name: &str, field_path: &[&str], id: &Ref<P>) -> SRes<()>;
     fn removes<P: Persistent>(&self, tx: &mut dyn Sytx, name: &str, field_path: &[&str], id: &Ref<P>) -> SRes<()>;
 }
@@ -19,6 +23,7 @@ macro_rules! impl_indexable_value {
     ($($t:ty),+) => {
         $(
         impl IndexableValue for $t {
+            type Key = $t;
             fn puts<P: Persistent>(&self, tx: &mut dyn Sytx, name: &str, field_path: &[&str], id: &Ref<P>) -> SRes<()> {
                 put_index(tx, name, field_path, self, id)
             }
@@ -32,6 +37,7 @@ macro_rules! impl_indexable_value {
 impl_indexable_value!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64, String);
 
 impl<T: IndexableValue> IndexableValue for Option<T> {
+    type Key = T::Key;
     fn puts<P: Persistent>(&self, tx: &mut dyn Sytx, name: &str, field_path: &[&str], id: &Ref<P>) -> SRes<()> {
         if let Some(x) = self {
             x.puts(tx, name, field_path, id)?;
@@ -46,6 +52,7 @@ impl<T: IndexableValue> IndexableValue for Option<T> {
     }
 }
 impl<T: IndexableValue> IndexableValue for Vec<T> {
+    type Key = T::Key;
     fn puts<P: Persistent>(&self, tx: &mut dyn Sytx, name: &str, field_path: &[&str], id: &Ref<P>) -> SRes<()> {
         for x in self {
             x.puts(tx, name, field_path, id)?;
@@ -60,6 +67,7 @@ impl<T: IndexableValue> IndexableValue for Vec<T> {
     }
 }
 impl<T: Persistent> IndexableValue for Ref<T> {
+    type Key = PersyId;
     fn puts<P: Persistent>(&self, tx: &mut dyn Sytx, name: &str, field_path: &[&str], id: &Ref<P>) -> SRes<()> {
         put_index(tx, name, field_path, &self.raw_id, id)?;
         Ok(())
@@ -70,7 +78,7 @@ impl<T: Persistent> IndexableValue for Ref<T> {
     }
 }
 
-fn put_index<T: IndexType, P: Persistent>(
+pub(crate) fn put_index<T: IndexType, P: Persistent>(
     tx: &mut dyn Sytx,
     name: &str,
     field_path: &[&str],
@@ -82,7 +90,7 @@ fn put_index<T: IndexType, P: Persistent>(
     Ok(())
 }
 
-fn remove_index<T: IndexType, P: Persistent>(
+pub(crate) fn remove_index<T: IndexType, P: Persistent>(
     tx: &mut dyn Sytx,
     name: &str,
     field_path: &[&str],
@@ -101,10 +109,61 @@ pub fn declare_index<T: IndexType>(db: &mut dyn Sytx, name: &str, mode: ValueMod
     Ok(())
 }
 
+/// Point of a field that can have a geo index, the index keeps the cell of the point
+pub trait GeoValue {
+    fn point(&self) -> Option<&GeoPoint>;
+}
+
+impl GeoValue for GeoPoint {
+    fn point(&self) -> Option<&GeoPoint> {
+        Some(self)
+    }
+}
+
+impl GeoValue for Option<GeoPoint> {
+    fn point(&self) -> Option<&GeoPoint> {
+        self.as_ref()
+    }
+}
+
+pub fn declare_geo_index(db: &mut dyn Sytx, type_name: &str, field: &str) -> SRes<()> {
+    let name = index_name(type_name, &[&geo_field_name(field)]);
+    declare_index::<u64>(db, &name, ValueMode::Cluster)
+}
+
+/// Add the cell of the point of the `field` of a record to its geo index
+pub fn put_geo<V: GeoValue, P: Persistent>(
+    tx: &mut dyn Sytx,
+    type_name: &str,
+    field: &str,
+    value: &V,
+    id: &Ref<P>,
+) -> SRes<()> {
+    if let Some(point) = value.point() {
+        put_index(tx, type_name, &[&geo_field_name(field)], &point.cell(), id)?;
+    }
+    Ok(())
+}
+
+/// Remove the cell of the point of the `field` of a record from its geo index
+pub fn remove_geo<V: GeoValue, P: Persistent>(
+    tx: &mut dyn Sytx,
+    type_name: &str,
+    field: &str,
+    value: &V,
+    id: &Ref<P>,
+) -> SRes<()> {
+    if let Some(point) = value.point() {
+        remove_index(tx, type_name, &[&geo_field_name(field)], &point.cell(), id)?;
+    }
+    Ok(())
+}
+
 /// Iterator implementation for Range of indexed persistent types
 pub struct IdRangeIteratorTx<'a, K: IndexType> {
     structsy: Arc<StructsyImpl>,
     persy_iter: persy::TxIndexIter<'a, K, PersyId>,
+    created_blobs: &'a mut bool,
     front_key: Option<K>,
     iter: Option<IntoIter<(K, PersyId)>>,
     back_key: Option<K>,
@@ -118,10 +177,15 @@ impl<'a, K: IndexType + PartialEq> TxIterator<'a> for IdRangeIteratorTx<'a, K> {
 }
 
 impl<'a, K: IndexType> IdRangeIteratorTx<'a, K> {
-    fn new(structsy: Arc<StructsyImpl>, iter: persy::TxIndexIter<'a, K, PersyId>) -> IdRangeIteratorTx<'a, K> {
+    fn new(
+        structsy: Arc<StructsyImpl>,
+        iter: persy::TxIndexIter<'a, K, PersyId>,
+        created_blobs: &'a mut bool,
+    ) -> IdRangeIteratorTx<'a, K> {
         IdRangeIteratorTx {
             structsy,
             persy_iter: iter,
+            created_blobs,
             front_key: None,
             iter: None,
             back_key: None,
@@ -133,6 +197,7 @@ impl<'a, K: IndexType> IdRangeIteratorTx<'a, K> {
         RefSytx {
             structsy_impl: self.structsy.clone(),
             trans: self.persy_iter.tx(),
+            created_blobs: self.created_blobs,
         }
     }
 }
@@ -312,8 +377,12 @@ pub enum RangeIter<'a, K> {
     Tx(Box<dyn TxItTrait<'a, K> + 'a>),
 }
 impl<'a, K: IndexType + PartialEq + 'static> RangeIter<'a, K> {
-    fn new_tx(structsy: Arc<StructsyImpl>, p: persy::TxIndexIter<'a, K, PersyId>) -> RangeIter<'a, K> {
-        RangeIter::Tx(Box::new(IdRangeIteratorTx::<'a, K>::new(structsy, p)))
+    fn new_tx(
+        structsy: Arc<StructsyImpl>,
+        p: persy::TxIndexIter<'a, K, PersyId>,
+        created_blobs: &'a mut bool,
+    ) -> RangeIter<'a, K> {
+        RangeIter::Tx(Box::new(IdRangeIteratorTx::<'a, K>::new(structsy, p, created_blobs)))
     }
     fn new_snap(snapshot: Snapshot, p: persy::IndexIter<K, PersyId>) -> RangeIter<'a, K> {
         RangeIter::Snapshot((Box::new(IdRangeIterator::new(p)), snapshot))
@@ -406,6 +475,73 @@ impl<'a, K: 'static, P: Persistent + 'static> ReaderIterator for RangeInstanceIt
         self.iter.reader()
     }
 }
+/// Iterator of the projections built from the keys of an index, the record is loaded only when
+/// the projection can not be built from the key
+pub(crate) struct RangeProjectionIter<'a, K, P, T> {
+    iter: RangeIter<'a, K>,
+    marker: std::marker::PhantomData<(P, T)>,
+}
+impl<'a, K, P, T> RangeProjectionIter<'a, K, P, T> {
+    pub(crate) fn new(iter: RangeIter<'a, K>) -> Self {
+        Self {
+            iter,
+            marker: std::marker::PhantomData,
+        }
+    }
+    pub(crate) fn reader_rev(self) -> ReaderRev<Self> {
+        ReaderRev { iter: self }
+    }
+}
+
+impl<'a, K: 'static, P: QueryProjection<T>, T: Persistent + 'static> RangeProjectionIter<'a, K, P, T> {
+    /// Projection of the entry, `None` when the record can not be read, like a record removed in
+    /// the transaction, the entry is skipped as the other index iterators do
+    fn project(&mut self, key: K, id: PersyId) -> Option<P> {
+        let rid = Ref::new(id);
+        if let Some(projection) = P::from_index(&rid, &key) {
+            return Some(projection);
+        }
+        if let Ok(Some(rec)) = self.iter.reader().read(&rid) {
+            Some(P::from_record(&rid, &rec))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, K: 'static, P: QueryProjection<T>, T: Persistent + 'static> Iterator for RangeProjectionIter<'a, K, P, T> {
+    type Item = P;
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((key, id)) = self.iter.next() {
+            if let Some(projection) = self.project(key, id) {
+                return Some(projection);
+            }
+        }
+        None
+    }
+}
+
+impl<'a, K: 'static, P: QueryProjection<T>, T: Persistent + 'static> DoubleEndedIterator
+    for RangeProjectionIter<'a, K, P, T>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some((key, id)) = self.iter.next_back() {
+            if let Some(projection) = self.project(key, id) {
+                return Some(projection);
+            }
+        }
+        None
+    }
+}
+
+impl<'a, K: 'static, P: QueryProjection<T>, T: Persistent + 'static> ReaderIterator
+    for RangeProjectionIter<'a, K, P, T>
+{
+    fn reader<'b>(&'b mut self) -> Reader<'b> {
+        self.iter.reader()
+    }
+}
+
 pub(crate) struct ReaderRev<T> {
     iter: T,
 }
@@ -452,10 +588,7 @@ impl<K: IndexType + PartialEq + 'static> Finder<K> for IndexFinder<K> {
         Ok(match reader {
             Reader::Structsy(st) => st.structsy_impl.persy.range::<K, PersyId, _>(name, range)?.count(),
             Reader::Snapshot(snap) => snap.ps.range::<K, PersyId, _>(name, range)?.count(),
-            Reader::Tx(RefSytx {
-                structsy_impl: _,
-                trans,
-            }) => trans.range::<K, PersyId, _>(name, range)?.count(),
+            Reader::Tx(RefSytx { trans, .. }) => trans.range::<K, PersyId, _>(name, range)?.count(),
         })
     }
     fn find(&self, reader: &mut Reader, name: &str, k: &K) -> SRes<ValueIter<PersyId>> {
@@ -499,9 +632,15 @@ impl<K: IndexType + PartialEq + 'static> Finder<K> for IndexFinder<K> {
                 RangeIter::new(st.clone(), st.structsy_impl.persy.range::<K, PersyId, _>(name, range)?)
             }
             Reader::Snapshot(snap) => RangeIter::new_snap(snap.clone(), snap.ps.range::<K, PersyId, _>(name, range)?),
-            Reader::Tx(RefSytx { structsy_impl, trans }) => {
-                RangeIter::new_tx(structsy_impl.clone(), trans.range::<K, PersyId, _>(name, range)?)
-            }
+            Reader::Tx(RefSytx {
+                structsy_impl,
+                trans,
+                created_blobs,
+            }) => RangeIter::new_tx(
+                structsy_impl.clone(),
+                trans.range::<K, PersyId, _>(name, range)?,
+                created_blobs,
+            ),
         })
     }
 }
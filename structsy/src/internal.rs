@@ -7,17 +7,23 @@ pub use crate::actions::OrderAction;
 pub use crate::actions::QueryAction;
 pub use crate::actions::RangeAction;
 pub use crate::desc::{
-    Description, EnumDescription, EnumDescriptionBuilder, FieldDescription, SimpleValueTypeBuilder, StructDescription,
-    StructDescriptionBuilder, ValueTypeBuilder, VariantDescription,
+    generic_name, index_name, ComputedIndexDescription, Description, EnumDescription, EnumDescriptionBuilder,
+    FieldDescription, FullTextIndex, IndexCondition, SimpleValueTypeBuilder, StructDescription,
+    StructDescriptionBuilder, SupportedType, ValueTypeBuilder, VariantDescription, VectorIndex,
 };
 pub use crate::filter::Filter;
 pub use crate::filter_builder::FilterBuilder;
 pub use crate::format::PersistentEmbedded;
-pub use crate::index::{declare_index, IndexableValue};
+pub use crate::fulltext::{declare_fulltext_index, fulltext_field_name, index_tokens, tokenize, FullTextValue};
+pub use crate::geo::geo_field_name;
+pub use crate::index::{declare_geo_index, declare_index, put_geo, remove_geo, GeoValue, IndexableValue};
 pub use crate::projection::Projection;
 pub use crate::queries::EmbeddedQuery;
 pub use crate::queries::Query;
-use crate::{Ref, SRes, Sytx};
+pub use crate::vector::{declare_vector_index, put_vector, remove_vector, vector_field_name, VectorValue};
+use crate::{GeoPoint, Ref, SRes, Sytx};
+use std::any::Any;
+use std::cmp::Ordering;
 use std::fmt::Debug;
 use std::io::{Read, Write};
 
@@ -27,13 +33,27 @@ pub(crate) trait FieldInfo: std::fmt::Debug {
 
 pub struct Field<T, V> {
     pub(crate) name: &'static str,
-    pub(crate) access: fn(&T) -> &V,
+    access: FieldAccess<T, V>,
 }
+
+enum FieldAccess<T, V> {
+    Value(fn(&T) -> &V),
+    Variant(fn(&T) -> Option<&V>),
+}
+impl<T, V> Clone for FieldAccess<T, V> {
+    fn clone(&self) -> Self {
+        match self {
+            FieldAccess::Value(access) => FieldAccess::Value(*access),
+            FieldAccess::Variant(access) => FieldAccess::Variant(*access),
+        }
+    }
+}
+
 impl<T, V> Clone for Field<T, V> {
     fn clone(&self) -> Self {
         Field {
             name: self.name,
-            access: self.access,
+            access: self.access.clone(),
         }
     }
 }
@@ -46,7 +66,37 @@ impl<T, V> Debug for Field<T, V> {
 impl<T, V> Field<T, V> {
     #[inline]
     pub fn new(name: &'static str, access: fn(&T) -> &V) -> Self {
-        Field { name, access }
+        Field {
+            name,
+            access: FieldAccess::Value(access),
+        }
+    }
+
+    /// Field of an enum variant, the access return `None` when the value is of another variant
+    #[inline]
+    pub fn new_variant(name: &'static str, access: fn(&T) -> Option<&V>) -> Self {
+        Field {
+            name,
+            access: FieldAccess::Variant(access),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get<'a>(&self, t: &'a T) -> Option<&'a V> {
+        match &self.access {
+            FieldAccess::Value(access) => Some(access(t)),
+            FieldAccess::Variant(access) => access(t),
+        }
+    }
+
+    /// Compare the field of two values, the values without the field are sorted last
+    pub(crate) fn compare(&self, first: &T, second: &T, compare: impl FnOnce(&V, &V) -> Ordering) -> Ordering {
+        match (self.get(first), self.get(second)) {
+            (Some(f), Some(s)) => compare(f, s),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
     }
 }
 
@@ -62,6 +112,11 @@ pub trait EmbeddedDescription: PersistentEmbedded + Sized {
         Filter::default()
     }
     fn get_description() -> Description;
+    /// Position of the current variant for enums, `None` for structs
+    #[doc(hidden)]
+    fn variant_position(&self) -> Option<u32> {
+        None
+    }
 }
 
 /// Trait implemented by persistent struct, implementation automatically generated by
@@ -83,4 +138,48 @@ pub trait Persistent: Sized {
     fn remove_indexes(&self, tx: &mut dyn Sytx, id: &Ref<Self>) -> SRes<()>
     where
         Self: std::marker::Sized;
+    /// Value of the computed index `name` for this instance, `None` if there is no such index
+    #[doc(hidden)]
+    fn computed_index_value(&self, _name: &str) -> Option<Box<dyn Any>> {
+        None
+    }
+    /// Tokens of the text of the field `field` with a full-text index for this instance, `None` if the field has no value
+    #[doc(hidden)]
+    fn fulltext_tokens(&self, _field: &str) -> Option<Vec<String>> {
+        None
+    }
+    /// Name of the full-text index of `field` and its stemming language, `None` if the field has no full-text index
+    #[doc(hidden)]
+    fn fulltext_index(_field: &str) -> Option<(&'static str, Option<&'static str>)>
+    where
+        Self: std::marker::Sized,
+    {
+        None
+    }
+    /// Vector of the field `field` with a vector index for this instance, `None` if the field has no value
+    #[doc(hidden)]
+    fn vector_value(&self, _field: &str) -> Option<&[f32]> {
+        None
+    }
+    /// Dimension and metric of the vector index of `field`, `None` if the field has no vector index
+    #[doc(hidden)]
+    fn vector_index(_field: &str) -> Option<(u32, &'static str)>
+    where
+        Self: std::marker::Sized,
+    {
+        None
+    }
+    /// Point of the field `field` with a geo index for this instance, `None` if the field has no value
+    #[doc(hidden)]
+    fn geo_value(&self, _field: &str) -> Option<&GeoPoint> {
+        None
+    }
+    /// Name of the geo index of `field`, `None` if the field has no geo index
+    #[doc(hidden)]
+    fn geo_index(_field: &str) -> Option<&'static str>
+    where
+        Self: std::marker::Sized,
+    {
+        None
+    }
 }
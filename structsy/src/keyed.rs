@@ -0,0 +1,363 @@
+//! Types indexed and queried through an order preserving key of a primitive type, the key is
+//! used for the index entries and for the comparisons of the filters.
+//!
+use crate::StructsyError;
+use persy::IndexType;
+use std::ops::Bound;
+
+/// Conversion of a value to the key that keeps the same order of the values
+pub(crate) trait KeyedValue: Sized {
+    type Key: IndexType + PartialOrd;
+    fn to_key(&self) -> Self::Key;
+}
+
+pub(crate) fn map_bound<T, K>(bound: &Bound<&T>, map: impl Fn(&T) -> K) -> Bound<K> {
+    match bound {
+        Bound::Included(v) => Bound::Included(map(v)),
+        Bound::Excluded(v) => Bound::Excluded(map(v)),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+pub(crate) fn keys<T: KeyedValue>(values: &[T]) -> Vec<T::Key> {
+    values.iter().map(KeyedValue::to_key).collect()
+}
+
+/// Implements the index and query traits of a type delegating to its key,
+/// `$kt` is the key type and `$key` its query variant, the generic parameters
+/// of the implementations can be provided in square brackets before the type
+macro_rules! impl_keyed_type {
+    ([$($gen:tt)*] $t:ty, $kt:ty, $key:ident) => {
+        impl<$($gen)*> $crate::index::IndexableValue for $t {
+            type Key = $kt;
+            fn puts<P: $crate::Persistent>(
+                &self,
+                tx: &mut dyn $crate::Sytx,
+                name: &str,
+                field_path: &[&str],
+                id: &$crate::Ref<P>,
+            ) -> $crate::SRes<()> {
+                $crate::index::put_index(tx, name, field_path, &$crate::keyed::KeyedValue::to_key(self), id)
+            }
+            fn removes<P: $crate::Persistent>(
+                &self,
+                tx: &mut dyn $crate::Sytx,
+                name: &str,
+                field_path: &[&str],
+                id: &$crate::Ref<P>,
+            ) -> $crate::SRes<()> {
+                $crate::index::remove_index(tx, name, field_path, &$crate::keyed::KeyedValue::to_key(self), id)
+            }
+        }
+
+        impl<$($gen)*> $crate::filter_builder::query_model::SolveSimpleQueryValue for $t {
+            fn new(self) -> $crate::SRes<$crate::filter_builder::query_model::SimpleQueryValue> {
+                Ok($crate::filter_builder::query_model::SimpleQueryValue::$key(
+                    $crate::keyed::KeyedValue::to_key(&self),
+                ))
+            }
+        }
+
+        impl<$($gen)*> $crate::filter_builder::query_model::SolveRangeQueryValue for $t {
+            fn range(
+                val: (&std::ops::Bound<&Self>, &std::ops::Bound<&Self>),
+            ) -> $crate::filter_builder::query_model::RangeQueryValue {
+                use $crate::keyed::{map_bound, KeyedValue};
+                $crate::filter_builder::query_model::RangeQueryValue::$key((
+                    map_bound(val.0, Self::to_key),
+                    map_bound(val.1, Self::to_key),
+                ))
+            }
+            fn range_vec(
+                val: (&std::ops::Bound<&Vec<Self>>, &std::ops::Bound<&Vec<Self>>),
+            ) -> $crate::filter_builder::query_model::VecRangeQueryValue {
+                use $crate::keyed::{keys, map_bound};
+                $crate::filter_builder::query_model::VecRangeQueryValue::$key((
+                    map_bound(val.0, |v| keys(v)),
+                    map_bound(val.1, |v| keys(v)),
+                ))
+            }
+            fn range_option(
+                val: (&std::ops::Bound<&Option<Self>>, &std::ops::Bound<&Option<Self>>),
+            ) -> $crate::filter_builder::query_model::OptionRangeQueryValue {
+                use $crate::keyed::{map_bound, KeyedValue};
+                let key = |v: &Option<Self>| v.as_ref().map(Self::to_key);
+                $crate::filter_builder::query_model::OptionRangeQueryValue::$key((
+                    map_bound(val.0, key),
+                    map_bound(val.1, key),
+                ))
+            }
+            fn range_option_vec(
+                val: (
+                    &std::ops::Bound<&Option<Vec<Self>>>,
+                    &std::ops::Bound<&Option<Vec<Self>>>,
+                ),
+            ) -> $crate::filter_builder::query_model::OptionVecRangeQueryValue {
+                use $crate::keyed::{keys, map_bound};
+                let key = |v: &Option<Vec<Self>>| v.as_ref().map(|v| keys(v));
+                $crate::filter_builder::query_model::OptionVecRangeQueryValue::$key((
+                    map_bound(val.0, key),
+                    map_bound(val.1, key),
+                ))
+            }
+        }
+
+        impl<$($gen)*> $crate::filter_builder::ValueCompare for $t {
+            fn equals(&self, value: $crate::filter_builder::QueryValuePlan) -> bool {
+                use $crate::filter_builder::{query_model::SimpleQueryValue, QueryValuePlan};
+                match value {
+                    QueryValuePlan::Single(SimpleQueryValue::$key(v)) => $crate::keyed::KeyedValue::to_key(self) == v,
+                    _ => {
+                        debug_assert!(false, "should never match a wrong type");
+                        false
+                    }
+                }
+            }
+            fn contains_value(&self, _value: $crate::filter_builder::QueryValuePlan) -> bool {
+                debug_assert!(false, "should never call wrong action");
+                false
+            }
+            fn is(&self, _value: $crate::filter_builder::QueryValuePlan) -> bool {
+                debug_assert!(false, "should never call wrong action");
+                false
+            }
+        }
+
+        impl<$($gen)*> $crate::filter_builder::ValueRange for $t {
+            type RangeType = $kt;
+            fn compare(&self, value: $crate::filter_builder::QueryValuePlan) -> Option<std::cmp::Ordering> {
+                use $crate::filter_builder::{query_model::SimpleQueryValue, QueryValuePlan};
+                match value {
+                    QueryValuePlan::Single(SimpleQueryValue::$key(v)) => {
+                        $crate::keyed::KeyedValue::to_key(self).partial_cmp(&v)
+                    }
+                    _ => {
+                        debug_assert!(false, "should never match a wrong type");
+                        None
+                    }
+                }
+            }
+            fn range(&self, value: $crate::filter_builder::query_model::RangeQueryValue) -> bool {
+                use std::ops::RangeBounds;
+                match value {
+                    $crate::filter_builder::query_model::RangeQueryValue::$key(r) => {
+                        r.contains(&$crate::keyed::KeyedValue::to_key(self))
+                    }
+                    _ => false,
+                }
+            }
+            fn range_contains(&self, _value: $crate::filter_builder::query_model::RangeQueryValue) -> bool {
+                debug_assert!(false, "should never call wrong action");
+                false
+            }
+            fn range_is(&self, _value: $crate::filter_builder::query_model::RangeQueryValue) -> bool {
+                debug_assert!(false, "should never call wrong action");
+                false
+            }
+            fn sort_compare(&self, other: &Self) -> std::cmp::Ordering {
+                use $crate::keyed::KeyedValue;
+                self.to_key().partial_cmp(&other.to_key()).unwrap_or(std::cmp::Ordering::Less)
+            }
+            fn extract_range_vec(
+                value: $crate::filter_builder::query_model::VecRangeQueryValue,
+            ) -> (
+                std::ops::Bound<Vec<Self::RangeType>>,
+                std::ops::Bound<Vec<Self::RangeType>>,
+            ) {
+                match value {
+                    $crate::filter_builder::query_model::VecRangeQueryValue::$key(r) => r,
+                    _ => unreachable!(),
+                }
+            }
+            fn extract_range_option(
+                value: $crate::filter_builder::query_model::OptionRangeQueryValue,
+            ) -> (
+                std::ops::Bound<Option<Self::RangeType>>,
+                std::ops::Bound<Option<Self::RangeType>>,
+            ) {
+                match value {
+                    $crate::filter_builder::query_model::OptionRangeQueryValue::$key(r) => r,
+                    _ => unreachable!(),
+                }
+            }
+            fn extract_range_option_vec(
+                value: $crate::filter_builder::query_model::OptionVecRangeQueryValue,
+            ) -> ($crate::filter_builder::query_model::VecRangeQueryValue, bool) {
+                match value {
+                    $crate::filter_builder::query_model::OptionVecRangeQueryValue::$key(r) => {
+                        let (nb, is_none) = $crate::filter_builder::map_option_vec(r);
+                        (
+                            $crate::filter_builder::query_model::VecRangeQueryValue::$key(nb),
+                            is_none,
+                        )
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            fn map_type(&self) -> Self::RangeType {
+                $crate::keyed::KeyedValue::to_key(self)
+            }
+        }
+    };
+    ($t:ty, $kt:ty, $key:ident) => {
+        $crate::keyed::impl_keyed_type!([] $t, $kt, $key);
+    };
+}
+pub(crate) use impl_keyed_type;
+
+impl KeyedValue for char {
+    type Key = u32;
+    fn to_key(&self) -> u32 {
+        *self as u32
+    }
+}
+impl_keyed_type!(char, u32, U32);
+
+/// Value that can be a component of the composite key of a tuple, written in a text
+/// form where the order of the keys is the order of the values
+pub trait KeyPart {
+    fn write_key(&self, key: &mut String);
+}
+
+macro_rules! impl_unsigned_key {
+    ($($t:ty),+) => {
+        $(
+        impl KeyPart for $t {
+            fn write_key(&self, key: &mut String) {
+                key.push_str(&format!("{:01$x}", self, std::mem::size_of::<$t>() * 2));
+            }
+        }
+        )+
+    };
+}
+impl_unsigned_key!(u8, u16, u32, u64, u128);
+
+/// Signed values flip the sign bit, so the negative values sort before the positive ones
+macro_rules! impl_signed_key {
+    ($($t:ty, $ut:ty),+) => {
+        $(
+        impl KeyPart for $t {
+            fn write_key(&self, key: &mut String) {
+                ((*self as $ut) ^ (1 << (<$ut>::BITS - 1))).write_key(key);
+            }
+        }
+        )+
+    };
+}
+impl_signed_key!(i8, u8, i16, u16, i32, u32, i64, u64, i128, u128);
+
+/// Floats flip all the bits of the negative values and the sign bit of the positive ones
+macro_rules! impl_float_key {
+    ($($t:ty, $ut:ty),+) => {
+        $(
+        impl KeyPart for $t {
+            fn write_key(&self, key: &mut String) {
+                let bits = self.to_bits();
+                let sign = 1 << (<$ut>::BITS - 1);
+                if bits & sign == 0 { bits | sign } else { !bits }.write_key(key);
+            }
+        }
+        )+
+    };
+}
+impl_float_key!(f32, u32, f64, u64);
+
+impl KeyPart for bool {
+    fn write_key(&self, key: &mut String) {
+        key.push(if *self { '1' } else { '0' });
+    }
+}
+
+impl<T: KeyedValue> KeyPart for T
+where
+    T::Key: KeyPart,
+{
+    fn write_key(&self, key: &mut String) {
+        self.to_key().write_key(key);
+    }
+}
+
+/// Strings are terminated by `'\0'` so a shorter string sorts first, the `'\0'`
+/// and `'\u{1}'` characters of the string are escaped with a leading `'\u{1}'`
+impl KeyPart for String {
+    fn write_key(&self, key: &mut String) {
+        for c in self.chars() {
+            match c {
+                '\0' => key.push_str("\u{1}\u{1}"),
+                '\u{1}' => key.push_str("\u{1}\u{2}"),
+                c => key.push(c),
+            }
+        }
+        key.push('\0');
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl KeyedValue for uuid::Uuid {
+    type Key = u128;
+    fn to_key(&self) -> u128 {
+        self.as_u128()
+    }
+}
+#[cfg(feature = "uuid")]
+impl_keyed_type!(uuid::Uuid, u128, U128);
+
+#[cfg(feature = "rust_decimal")]
+impl KeyedValue for rust_decimal::Decimal {
+    type Key = String;
+    fn to_key(&self) -> String {
+        decimal_key(self.mantissa(), self.scale())
+    }
+}
+#[cfg(feature = "rust_decimal")]
+impl_keyed_type!(rust_decimal::Decimal, String, String);
+
+/// Tuples are keyed with the concatenation of the keys of their values
+macro_rules! impl_keyed_tuple {
+    ($($t:ident $pos:tt),+) => {
+        impl<$($t: KeyPart),+> KeyedValue for ($($t,)+) {
+            type Key = String;
+            fn to_key(&self) -> String {
+                let mut key = String::new();
+                $(self.$pos.write_key(&mut key);)+
+                key
+            }
+        }
+        impl_keyed_type!([$($t: KeyPart + PartialEq),+] ($($t,)+), String, String);
+    };
+}
+impl_keyed_tuple!(A 0, B 1);
+impl_keyed_tuple!(A 0, B 1, C 2);
+impl_keyed_tuple!(A 0, B 1, C 2, D 3);
+impl_keyed_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_keyed_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+
+/// Offset of the decimal exponent so it is always written with three positive digits
+const EXPONENT_OFFSET: i64 = 500;
+
+/// Order preserving text key of the decimal `mantissa * 10^-scale`, the same number
+/// with a different scale has the same key.
+///
+/// The key is the sign, the exponent and the significant digits, for negative numbers
+/// exponent and digits are complemented and terminated so shorter numbers sort after.
+pub(crate) fn decimal_key(mantissa: i128, scale: u32) -> String {
+    let digits = mantissa.unsigned_abs().to_string();
+    let significant = digits.trim_end_matches('0');
+    if significant.is_empty() {
+        return "1".to_string();
+    }
+    let exponent = (digits.len() as i64 - scale as i64 + EXPONENT_OFFSET).clamp(0, 999);
+    if mantissa > 0 {
+        format!("2{:03}{}", exponent, significant)
+    } else {
+        let complement = significant
+            .bytes()
+            .map(|d| (b'9' - d + b'0') as char)
+            .collect::<String>();
+        format!("0{:03}{}~", 999 - exponent, complement)
+    }
+}
+
+pub(crate) fn invalid_value(type_name: &str, value: impl std::fmt::Display) -> StructsyError {
+    StructsyError::TypeError(format!("value {} out of range for type {}", value, type_name))
+}
@@ -29,12 +29,16 @@
 //!```
 //!
 //!
+use blob::ChunkSource;
 pub use persy::ValueMode;
 use record::Record;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 mod desc;
 mod format;
+mod fulltext;
+mod geo;
+mod vector;
 use desc::{Description, InternalDescription};
 mod filter_builder;
 mod index;
@@ -46,7 +50,7 @@ pub use crate::id::Ref;
 mod error;
 pub use crate::error::{SRes, StructsyError};
 mod queries;
-pub use crate::queries::{Operators, SnapshotQuery, StructsyIter, StructsyQuery, StructsyQueryTx};
+pub use crate::queries::{IndexQuery, Operators, SnapshotQuery, StructsyIter, StructsyQuery, StructsyQueryTx};
 mod transaction;
 pub use crate::transaction::{OwnedSytx, Prepared, RefSytx, StructsyTx, Sytx};
 use filter_builder::FilterBuilder;
@@ -56,6 +60,7 @@ mod filter;
 mod projection;
 pub use filter::Filter;
 mod actions;
+pub use actions::{Prefix, Variant};
 pub mod record;
 
 #[cfg(feature = "derive")]
@@ -66,6 +71,14 @@ pub mod derive {
 }
 mod snapshot;
 pub use snapshot::Snapshot;
+mod schema_diff;
+pub use schema_diff::{SchemaChange, SchemaDiff};
+mod blob;
+#[cfg(any(feature = "chrono", feature = "time"))]
+mod datetime;
+mod keyed;
+pub use blob::{Blob, BlobReader, BlobWriter};
+pub use geo::{GeoPoint, GeoRect};
 
 /// Main API to persist structs with structsy.
 ///
@@ -298,7 +311,42 @@ impl Structsy {
     /// # }
     /// ```
     pub fn undefine<T: Persistent>(&self) -> SRes<()> {
-        self.structsy_impl.undefine::<T>()
+        self.undefine_with::<T>(UndefinePolicy::Ignore)
+    }
+
+    /// Remove a defined struct deleting all the contained data and indexes, handling the other
+    /// defined types that refer to it following the provided policy.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{Structsy, Ref, UndefinePolicy};
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Simple {
+    ///     name:String,
+    /// }
+    /// #[derive(Persistent)]
+    /// struct Referrer {
+    ///     simple:Ref<Simple>,
+    /// }
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// let stry = Structsy::open("path/to/file.stry")?;
+    /// stry.define::<Simple>()?;
+    /// stry.define::<Referrer>()?;
+    /// assert!(stry.undefine_with::<Simple>(UndefinePolicy::Fail).is_err());
+    /// // Remove both Simple and Referrer
+    /// stry.undefine_with::<Simple>(UndefinePolicy::Cascade)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn undefine_with<T: Persistent>(&self, policy: UndefinePolicy) -> SRes<()> {
+        self.raw_undefine(T::get_name(), policy)
+    }
+
+    /// Remove a defined struct or enum by name, without need of the original source code.
+    pub fn raw_undefine(&self, name: &str, policy: UndefinePolicy) -> SRes<()> {
+        self.structsy_impl.undefine(name, policy)
     }
 
     /// Begin a new transaction needed to manipulate data.
@@ -350,6 +398,38 @@ impl Structsy {
         self.structsy_impl.read(sref)
     }
 
+    /// Read in streaming the content of a blob, `None` if the blob does not exists.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{Blob, Structsy, StructsyTx};
+    /// use structsy_derive::Persistent;
+    /// use std::io::{Read, Write};
+    /// #[derive(Persistent)]
+    /// struct Example {
+    ///     content: Blob,
+    /// }
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// # let structsy = Structsy::open("path/to/file.stry")?;
+    /// //.. open structsy etc.
+    /// let mut tx = structsy.begin()?;
+    /// let mut writer = tx.create_blob()?;
+    /// writer.write_all(b"some content")?;
+    /// let content = writer.finish()?;
+    /// let id = tx.insert(&Example { content })?;
+    /// tx.commit()?;
+    /// let read = structsy.read(&id)?.unwrap();
+    /// let mut data = String::new();
+    /// structsy.read_blob(&read.content)?.unwrap().read_to_string(&mut data)?;
+    /// assert_eq!(data, "some content");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_blob(&self, blob: &Blob) -> SRes<Option<BlobReader<'static>>> {
+        BlobReader::open(ChunkSource::Persy(self.structsy_impl.persy.clone()), blob)
+    }
+
     /// Scan records of a specific struct.
     ///
     ///
@@ -501,6 +581,230 @@ impl Structsy {
         self.structsy_impl.list_defined()
     }
 
+    /// Compare the description of a struct or enum stored in the database with the
+    /// description of the current code.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{Structsy, SchemaChange};
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Simple {
+    ///     name:String,
+    /// }
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// let stry = Structsy::open("path/to/file.stry")?;
+    /// let diff = stry.schema_diff::<Simple>()?;
+    /// for change in diff.changes() {
+    ///     if let SchemaChange::FieldAdded(field) = change {
+    ///         println!("field '{}' need a migration", field.name());
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn schema_diff<T: Persistent>(&self) -> SRes<SchemaDiff> {
+        self.raw_schema_diff(&T::get_description())
+    }
+
+    /// Compare the description of a struct or enum stored in the database with the
+    /// provided description.
+    pub fn raw_schema_diff(&self, desc: &Description) -> SRes<SchemaDiff> {
+        let name = desc.get_name();
+        let stored = self.list_defined()?.find(|d| d.get_name() == name);
+        Ok(SchemaDiff::new(&name, stored.as_ref(), Some(desc)))
+    }
+
+    /// Compare all the descriptions stored in the database with the provided descriptions,
+    /// returning only the types that have some change, types defined in the database
+    /// but not provided are reported as removed.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{Structsy, internal::Persistent};
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Simple {
+    ///     name:String,
+    /// }
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// let stry = Structsy::open("path/to/file.stry")?;
+    /// let diffs = stry.schema_diff_all(&[Simple::get_description()])?;
+    /// assert!(diffs.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn schema_diff_all(&self, descs: &[Description]) -> SRes<Vec<SchemaDiff>> {
+        let stored = self.list_defined()?.collect::<Vec<_>>();
+        let mut diffs = Vec::new();
+        for desc in descs {
+            let name = desc.get_name();
+            let found = stored.iter().find(|d| d.get_name() == name);
+            diffs.push(SchemaDiff::new(&name, found, Some(desc)));
+        }
+        for st in &stored {
+            let name = st.get_name();
+            if !descs.iter().any(|d| d.get_name() == name) {
+                diffs.push(SchemaDiff::new(&name, Some(st), None));
+            }
+        }
+        Ok(diffs.into_iter().filter(|d| !d.is_empty()).collect())
+    }
+
+    /// Rename a defined struct or enum keeping all the existing data, the references from other
+    /// types and the indexes are updated to the new name.
+    ///
+    /// To rename only the code type keeping the stored name use the
+    /// `#[persistent(name = "StoredName")]` attribute on the derive.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::Structsy;
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Customer {
+    ///     name:String,
+    /// }
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// let stry = Structsy::open("path/to/file.stry")?;
+    /// stry.rename_type("Client", "Customer")?;
+    /// stry.define::<Customer>()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rename_type(&self, old: &str, new: &str) -> SRes<()> {
+        self.structsy_impl.rename_type(old, new)
+    }
+
+    /// Create the index of a field of an already defined struct, indexing all the existing records.
+    ///
+    /// The index mode is taken from the `#[index]` declaration of the field in the current code,
+    /// `field` can also be the name of a computed index, indexing the values stored in the records.
+    /// If interrupted the indexing can be resumed calling again this method.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::Structsy;
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Simple {
+    ///     #[index(mode = "cluster")]
+    ///     name:String,
+    /// }
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// let stry = Structsy::open("path/to/file.stry")?;
+    /// stry.create_index::<Simple>("name")?;
+    /// stry.define::<Simple>()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_index<T: Persistent>(&self, field: &str) -> SRes<()> {
+        let mode = match T::get_description() {
+            Description::Struct(s) => s
+                .get_field(field)
+                .map(|f| f.indexed().clone())
+                .or_else(|| s.get_computed_index(field).map(|c| c.indexed().clone()))
+                .flatten(),
+            Description::Enum(_) => None,
+        };
+        if let Some(mode) = mode {
+            self.raw_create_index(T::get_name(), field, mode)
+        } else {
+            Err(StructsyError::ValueChangeError(format!(
+                "field '{}' of '{}' is not declared as indexed",
+                field,
+                T::get_name()
+            )))
+        }
+    }
+
+    /// Drop the index of a field of an already defined struct, the field should not be declared
+    /// indexed anymore in the current code.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::Structsy;
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Simple {
+    ///     name:String,
+    /// }
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// let stry = Structsy::open("path/to/file.stry")?;
+    /// stry.drop_index::<Simple>("name")?;
+    /// stry.define::<Simple>()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn drop_index<T: Persistent>(&self, field: &str) -> SRes<()> {
+        let indexed = match T::get_description() {
+            Description::Struct(s) => s.get_field(field).map(|f| f.indexed().is_some()),
+            Description::Enum(_) => None,
+        };
+        if indexed == Some(false) {
+            self.raw_drop_index(T::get_name(), field)
+        } else {
+            Err(StructsyError::ValueChangeError(format!(
+                "field '{}' of '{}' is still declared as indexed",
+                field,
+                T::get_name()
+            )))
+        }
+    }
+
+    /// Create the index of a field of an already defined struct or enum, without need of the
+    /// original source code.
+    pub fn raw_create_index(&self, type_name: &str, field: &str, mode: ValueMode) -> SRes<()> {
+        self.structsy_impl.create_index(type_name, field, mode)
+    }
+
+    /// Drop the index of a field of an already defined struct or enum, without need of the
+    /// original source code.
+    pub fn raw_drop_index(&self, type_name: &str, field: &str) -> SRes<()> {
+        self.structsy_impl.drop_index(type_name, field)
+    }
+
+    /// Migrate all the records of a defined struct or enum to a new description,
+    /// without need of the original source code.
+    ///
+    /// Each existing record is passed to the migration function that should return a
+    /// record matching the new description, the indexes are rebuilt from the new description
+    /// and the blobs not held anymore by the migrated records are removed.
+    /// If interrupted the migration can be resumed calling again this method with the same arguments.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{Structsy, record::{Record, StructBuilder}};
+    /// use structsy::internal::{StructDescriptionBuilder, ValueTypeBuilder, SimpleValueTypeBuilder};
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// let stry = Structsy::open("path/to/file.stry")?;
+    /// let string_type = ValueTypeBuilder::simple(SimpleValueTypeBuilder::from_name("String").build()).build();
+    /// let desc = StructDescriptionBuilder::new("Person")
+    ///     .add_field(0, "name".to_owned(), string_type.clone(), None)
+    ///     .add_field(1, "surname".to_owned(), string_type, None)
+    ///     .build();
+    /// stry.raw_migrate("Person", desc.clone(), |_record| {
+    ///     StructBuilder::new(desc.clone())?
+    ///         .add_field("name", "John".to_owned())?
+    ///         .add_field("surname", "Doe".to_owned())?
+    ///         .finish()
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn raw_migrate<F>(&self, type_name: &str, desc: Description, migration: F) -> SRes<()>
+    where
+        F: Fn(Record) -> SRes<Record>,
+    {
+        self.structsy_impl.raw_migrate(type_name, desc, migration)
+    }
+
     /// Create a new snapshot at this specific moment.
     ///
     /// # Example
@@ -539,6 +843,17 @@ pub enum Order {
     Desc,
 }
 
+/// Handling of the types that refer to a type that is undefined
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum UndefinePolicy {
+    /// Keep the referring types, leaving the references dangling
+    Ignore,
+    /// Fail if any other defined type refer to the undefined type
+    Fail,
+    /// Undefine also all the types that refer to the undefined type
+    Cascade,
+}
+
 pub trait RawRead {
     /// Scan the records of a struct or enum in a raw format
     fn raw_scan(&self, ty_name: &str) -> SRes<RawIter>;
@@ -1,7 +1,39 @@
-use crate::{Persistent, Ref};
+use crate::{Blob, Persistent, Ref};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
 
 pub trait Projection<T> {
     fn projection(source: &T) -> Self;
+
+    /// Build the projection from a record and its id, used by the queries for fill the fields
+    /// that hold the id of the record
+    #[doc(hidden)]
+    fn projection_with_id(_id: &Ref<T>, source: &T) -> Self
+    where
+        Self: Sized,
+    {
+        Self::projection(source)
+    }
+
+    /// Names of the fields of the source read by the projection, `None` if not known
+    #[doc(hidden)]
+    fn projected_fields() -> Option<Vec<&'static str>>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Build the projection from the id of a record and the value of the only projected field,
+    /// as read from the index of the field, `None` if the value is not of the field type
+    #[doc(hidden)]
+    fn from_index(_id: &Ref<T>, _value: &dyn Any) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
 
 macro_rules! projections {
@@ -16,7 +48,11 @@ macro_rules! projections {
     }
 }
 
-projections!(bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+projections!(bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64, char);
+#[cfg(feature = "uuid")]
+projections!(uuid::Uuid);
+#[cfg(feature = "rust_decimal")]
+projections!(rust_decimal::Decimal);
 
 impl Projection<String> for String {
     fn projection(source: &String) -> Self {
@@ -28,6 +64,72 @@ impl<T: Persistent> Projection<Ref<T>> for Ref<T> {
         source.clone()
     }
 }
+/// How the queries build their results from a record and its id, or from the id and the key of
+/// an index without read the record
+pub(crate) trait QueryProjection<T>: Sized {
+    fn projected_fields() -> Option<Vec<&'static str>>;
+    fn from_record(id: &Ref<T>, source: &T) -> Self;
+    fn from_index(id: &Ref<T>, value: &dyn Any) -> Option<Self>;
+}
+
+/// A projection built by the queries
+pub(crate) struct Projected<P>(pub(crate) P);
+
+impl<T, P: Projection<T>> QueryProjection<T> for Projected<P> {
+    fn projected_fields() -> Option<Vec<&'static str>> {
+        P::projected_fields()
+    }
+    fn from_record(id: &Ref<T>, source: &T) -> Self {
+        Projected(P::projection_with_id(id, source))
+    }
+    fn from_index(id: &Ref<T>, value: &dyn Any) -> Option<Self> {
+        P::from_index(id, value).map(Projected)
+    }
+}
+
+/// The id alone of a record, built without read it
+impl<T> QueryProjection<T> for Ref<T> {
+    fn projected_fields() -> Option<Vec<&'static str>> {
+        Some(Vec::new())
+    }
+    fn from_record(id: &Ref<T>, _source: &T) -> Self {
+        id.clone()
+    }
+    fn from_index(id: &Ref<T>, _value: &dyn Any) -> Option<Self> {
+        Some(id.clone())
+    }
+}
+impl Projection<Blob> for Blob {
+    fn projection(source: &Blob) -> Self {
+        source.clone()
+    }
+}
+impl<T: Projection<T>, const N: usize> Projection<[T; N]> for [T; N] {
+    fn projection(source: &[T; N]) -> Self {
+        std::array::from_fn(|i| T::projection(&source[i]))
+    }
+}
+
+macro_rules! tuple_projections {
+    ($($t:ident $pos:tt),+) => {
+        impl<$($t: Projection<$t>),+> Projection<($($t,)+)> for ($($t,)+) {
+            fn projection(source: &($($t,)+)) -> Self {
+                ($($t::projection(&source.$pos),)+)
+            }
+        }
+    };
+}
+tuple_projections!(A 0, B 1);
+tuple_projections!(A 0, B 1, C 2);
+tuple_projections!(A 0, B 1, C 2, D 3);
+tuple_projections!(A 0, B 1, C 2, D 3, E 4);
+tuple_projections!(A 0, B 1, C 2, D 3, E 4, F 5);
+#[cfg(feature = "bytes")]
+impl Projection<bytes::Bytes> for bytes::Bytes {
+    fn projection(source: &bytes::Bytes) -> Self {
+        source.clone()
+    }
+}
 impl<T: Projection<T>> Projection<Vec<T>> for Vec<T> {
     fn projection(source: &Vec<T>) -> Self {
         source.iter().map(Projection::projection).collect()
@@ -39,3 +141,21 @@ impl<T: Projection<T>> Projection<Option<T>> for Option<T> {
         source.as_ref().map(Projection::projection)
     }
 }
+
+impl<K: Projection<K> + Ord, V: Projection<V>> Projection<BTreeMap<K, V>> for BTreeMap<K, V> {
+    fn projection(source: &BTreeMap<K, V>) -> Self {
+        source
+            .iter()
+            .map(|(k, v)| (Projection::projection(k), Projection::projection(v)))
+            .collect()
+    }
+}
+
+impl<K: Projection<K> + Eq + Hash, V: Projection<V>> Projection<HashMap<K, V>> for HashMap<K, V> {
+    fn projection(source: &HashMap<K, V>) -> Self {
+        source
+            .iter()
+            .map(|(k, v)| (Projection::projection(k), Projection::projection(v)))
+            .collect()
+    }
+}
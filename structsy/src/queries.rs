@@ -1,10 +1,12 @@
 #[allow(deprecated)]
 use crate::{
     filter::Filter,
-    filter_builder::Reader,
-    internal::{EmbeddedDescription, Projection},
-    Fetch, FilterBuilder, IntoResult, OwnedSytx, Persistent, PersistentEmbedded, Ref, Snapshot, Structsy,
+    filter_builder::{Reader, SolveQueryRange, ValueRange},
+    internal::{EmbeddedDescription, Projection, SupportedType},
+    Fetch, FilterBuilder, GeoPoint, GeoRect, IntoResult, OwnedSytx, Persistent, PersistentEmbedded, Ref, SRes,
+    Snapshot, Structsy,
 };
+use std::ops::RangeBounds;
 /// Iterator for query results
 pub struct StructsyIter<'a, T> {
     iterator: Box<dyn Iterator<Item = T> + 'a>,
@@ -79,6 +81,200 @@ pub trait Operators<F> {
     fn not<FN: Fn(F) -> F>(self, builder: FN) -> Self;
 }
 
+/// Filters on the computed, full-text, vector and geo indexes declared on a persistent struct
+/// # Example
+/// ```
+/// use structsy::{ Structsy, StructsyTx, StructsyError, IndexQuery};
+/// use structsy_derive::Persistent;
+/// #[derive(Persistent)]
+/// #[persistent(index(name = "email_lower", expr = "self.email.to_lowercase()", ty = "String"))]
+/// struct User {
+///     email: String,
+/// }
+///
+/// fn index_query() -> Result<(), StructsyError> {
+///     let structsy = Structsy::open("file.structsy")?;
+///     structsy.define::<User>()?;
+///     let mut tx = structsy.begin()?;
+///     tx.insert(&User { email: "Some@Mail.com".to_string() })?;
+///     tx.commit()?;
+///     let count = structsy.query::<User>()
+///         .by_index::<String>("email_lower", "some@mail.com".to_string())
+///         .fetch()
+///         .count();
+///     assert_eq!(count, 1);
+///     let count = structsy.query::<User>()
+///         .by_index_range("email_lower", "a".to_string().."t".to_string())
+///         .fetch()
+///         .count();
+///     assert_eq!(count, 1);
+///     Ok(())
+/// }
+/// ```
+pub trait IndexQuery<T> {
+    /// Filter the records that have `value` in the computed index `name`
+    fn by_index<K>(self, name: &'static str, value: K) -> Self
+    where
+        K: ValueRange + SolveQueryRange + SupportedType + Clone + 'static;
+    /// Filter the records that have a value in `range` in the computed index `name`
+    fn by_index_range<K, R>(self, name: &'static str, range: R) -> Self
+    where
+        K: ValueRange + SolveQueryRange + SupportedType + Clone + 'static,
+        R: RangeBounds<K>;
+    /// Filter the records that have all the words of `terms` in the full-text index of `field`,
+    /// ranked by the frequency of the terms. The query fails if the full-text index is missing or
+    /// structsy is built without the `fulltext` feature
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{ Structsy, StructsyTx, StructsyError, IndexQuery};
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Article {
+    ///     #[index(fulltext)]
+    ///     text: String,
+    /// }
+    ///
+    /// fn search() -> Result<(), StructsyError> {
+    ///     let structsy = Structsy::open("file.structsy")?;
+    ///     structsy.define::<Article>()?;
+    ///     let mut tx = structsy.begin()?;
+    ///     tx.insert(&Article { text: "Rust is a language, a fast language".to_string() })?;
+    ///     tx.commit()?;
+    ///     let count = structsy.query::<Article>().search("text", "FAST Language").fetch().count();
+    ///     assert_eq!(count, 1);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn search(self, field: &'static str, terms: &str) -> Self;
+    /// Keep only the `k` records with the vector of `field` nearest to `vector`, from the nearest,
+    /// using the vector index of the field.
+    ///
+    /// The other conditions of the query are applied to the candidates found with the index
+    /// until `k` of them match, when an index on the other conditions selects few records those
+    /// are ranked by their exact distance instead. The nearest limits the whole query, so it can
+    /// be in a group joined with `and` but the query fails with it inside `or`, `not` and embedded
+    /// or referred queries
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{ Structsy, StructsyTx, StructsyError, IndexQuery};
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Doc {
+    ///     #[index(vector(dim = 3, metric = "cosine"))]
+    ///     embedding: Vec<f32>,
+    /// }
+    ///
+    /// fn nearest() -> Result<(), StructsyError> {
+    ///     let structsy = Structsy::open("file.structsy")?;
+    ///     structsy.define::<Doc>()?;
+    ///     let mut tx = structsy.begin()?;
+    ///     tx.insert(&Doc { embedding: vec![1.0, 0.0, 0.0] })?;
+    ///     tx.insert(&Doc { embedding: vec![0.0, 1.0, 0.0] })?;
+    ///     tx.commit()?;
+    ///     let (_, doc) = structsy.query::<Doc>().nearest("embedding", &[0.9, 0.1, 0.0], 1).fetch().next().unwrap();
+    ///     assert_eq!(doc.embedding, vec![1.0, 0.0, 0.0]);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn nearest(self, field: &'static str, vector: &[f32], k: usize) -> Self;
+    /// Filter the records with the point of `field` inside the rectangle, using the geo index of
+    /// the field
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{ Structsy, StructsyTx, StructsyError, IndexQuery, GeoPoint, GeoRect};
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Shop {
+    ///     #[index(geo)]
+    ///     position: GeoPoint,
+    /// }
+    ///
+    /// fn within_bbox() -> Result<(), StructsyError> {
+    ///     let structsy = Structsy::open("file.structsy")?;
+    ///     structsy.define::<Shop>()?;
+    ///     let mut tx = structsy.begin()?;
+    ///     tx.insert(&Shop { position: GeoPoint::new(45.46, 9.19) })?;
+    ///     tx.insert(&Shop { position: GeoPoint::new(41.90, 12.49) })?;
+    ///     tx.commit()?;
+    ///     let north = GeoRect::new(GeoPoint::new(44.0, 7.0), GeoPoint::new(46.5, 13.0));
+    ///     let count = structsy.query::<Shop>().within_bbox("position", north).fetch().count();
+    ///     assert_eq!(count, 1);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn within_bbox(self, field: &'static str, rect: GeoRect) -> Self;
+    /// Filter the records with the point of `field` within `meters` from `center`, using the geo
+    /// index of the field, the records are ranked by their distance from the center
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{ Structsy, StructsyTx, StructsyError, IndexQuery, GeoPoint};
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Shop {
+    ///     #[index(geo)]
+    ///     position: GeoPoint,
+    ///     name: String,
+    /// }
+    ///
+    /// fn within_radius() -> Result<(), StructsyError> {
+    ///     let structsy = Structsy::open("file.structsy")?;
+    ///     structsy.define::<Shop>()?;
+    ///     let mut tx = structsy.begin()?;
+    ///     tx.insert(&Shop { position: GeoPoint::new(45.47, 9.19), name: "far".to_string() })?;
+    ///     tx.insert(&Shop { position: GeoPoint::new(45.465, 9.19), name: "near".to_string() })?;
+    ///     tx.commit()?;
+    ///     let center = GeoPoint::new(45.46, 9.19);
+    ///     let names = structsy
+    ///         .query::<Shop>()
+    ///         .within_radius("position", center, 2_000.0)
+    ///         .fetch()
+    ///         .map(|(_, shop)| shop.name)
+    ///         .collect::<Vec<_>>();
+    ///     assert_eq!(names, vec!["near", "far"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn within_radius(self, field: &'static str, center: GeoPoint, meters: f64) -> Self;
+}
+
+impl<T: Persistent + 'static, Q: Query<T>> IndexQuery<T> for Q {
+    fn by_index<K>(mut self, name: &'static str, value: K) -> Self
+    where
+        K: ValueRange + SolveQueryRange + SupportedType + Clone + 'static,
+    {
+        self.filter_builder().cond_index_range(name, value.clone()..=value);
+        self
+    }
+    fn by_index_range<K, R>(mut self, name: &'static str, range: R) -> Self
+    where
+        K: ValueRange + SolveQueryRange + SupportedType + Clone + 'static,
+        R: RangeBounds<K>,
+    {
+        self.filter_builder().cond_index_range(name, range);
+        self
+    }
+    fn search(mut self, field: &'static str, terms: &str) -> Self {
+        self.filter_builder().cond_search(field, terms);
+        self
+    }
+    fn nearest(mut self, field: &'static str, vector: &[f32], k: usize) -> Self {
+        self.filter_builder().cond_nearest(field, vector, k);
+        self
+    }
+    fn within_bbox(mut self, field: &'static str, rect: GeoRect) -> Self {
+        self.filter_builder().cond_within_bbox(field, rect);
+        self
+    }
+    fn within_radius(mut self, field: &'static str, center: GeoPoint, meters: f64) -> Self {
+        self.filter_builder().cond_within_radius(field, center, meters);
+        self
+    }
+}
+
 pub trait EmbeddedQuery<T: PersistentEmbedded + 'static>: Sized {
     fn filter_builder(&mut self) -> &mut FilterBuilder<T>;
     fn add_group(&mut self, filter: Filter<T>);
@@ -112,7 +308,7 @@ impl<P, T> ProjectionResult<P, T> {
     }
 }
 
-impl<P: Projection<T>, T: Persistent + 'static> Fetch<P> for ProjectionResult<P, T> {
+impl<P: Projection<T> + 'static, T: Persistent + 'static> Fetch<P> for ProjectionResult<P, T> {
     fn into(self, structsy: &Structsy) -> StructsyIter<P> {
         self.fetch(structsy)
     }
@@ -122,23 +318,20 @@ impl<P: Projection<T>, T: Persistent + 'static> Fetch<P> for ProjectionResult<P,
     }
 
     fn fetch(self, structsy: &Structsy) -> StructsyIter<P> {
-        let data = self.filter.finish(Reader::Structsy(structsy.clone()));
-        StructsyIter::new(Box::new(data.map(|(_, r)| Projection::projection(&r))))
+        StructsyIter::new(self.filter.finish_projection(Reader::Structsy(structsy.clone())))
     }
 
     fn fetch_tx(self, tx: &mut OwnedSytx) -> StructsyIter<P> {
-        let data = self.filter.finish(Reader::Tx(tx.reference()));
-        StructsyIter::new(Box::new(data.map(|(_, r)| Projection::projection(&r))))
+        StructsyIter::new(self.filter.finish_projection(Reader::Tx(tx.reference())))
     }
 
     fn fetch_snapshot(self, snapshot: &Snapshot) -> StructsyIter<P> {
-        let data = self.filter.finish(Reader::Snapshot(snapshot.clone()));
-        StructsyIter::new(Box::new(data.map(|(_, r)| Projection::projection(&r))))
+        StructsyIter::new(self.filter.finish_projection(Reader::Snapshot(snapshot.clone())))
     }
 }
 
 #[allow(deprecated)]
-impl<P: Projection<T>, T: Persistent + 'static> IntoResult<P> for ProjectionResult<P, T> {}
+impl<P: Projection<T> + 'static, T: Persistent + 'static> IntoResult<P> for ProjectionResult<P, T> {}
 
 #[allow(deprecated)]
 impl<T: Persistent + 'static> IntoResult<(Ref<T>, T)> for Filter<T> {}
@@ -187,6 +380,28 @@ impl<T: Persistent + 'static> SnapshotQuery<T> {
     pub fn fetch(self) -> StructsyIter<'static, (Ref<T>, T)> {
         StructsyIter::new(self.builder.finish(Reader::Snapshot(self.snapshot)))
     }
+
+    /// Fetch the found structs failing on invalid conditions, see [`StructsyQuery::try_fetch`]
+    pub fn try_fetch(self) -> SRes<StructsyIter<'static, (Ref<T>, T)>> {
+        Ok(StructsyIter::new(
+            self.builder.try_finish(Reader::Snapshot(self.snapshot))?,
+        ))
+    }
+
+    /// Fetch only the ids of the found structs, see [`StructsyQuery::fetch_refs`]
+    pub fn fetch_refs(self) -> StructsyIter<'static, Ref<T>> {
+        StructsyIter::new(self.builder.finish_refs(Reader::Snapshot(self.snapshot)))
+    }
+
+    /// Alias of [`SnapshotQuery::fetch_refs`]
+    pub fn ids(self) -> StructsyIter<'static, Ref<T>> {
+        self.fetch_refs()
+    }
+
+    /// Count the found structs, see [`StructsyQuery::fetch_refs`]
+    pub fn count(self) -> usize {
+        self.fetch_refs().count()
+    }
 }
 
 pub struct ProjectionSnapshotQuery<P, T> {
@@ -195,19 +410,17 @@ pub struct ProjectionSnapshotQuery<P, T> {
     phantom: std::marker::PhantomData<P>,
 }
 
-impl<P: Projection<T>, T: Persistent + 'static> ProjectionSnapshotQuery<P, T> {
+impl<P: Projection<T> + 'static, T: Persistent + 'static> ProjectionSnapshotQuery<P, T> {
     pub fn fetch(self) -> StructsyIter<'static, P> {
-        let data = self.builder.finish(Reader::Snapshot(self.snapshot));
-        StructsyIter::new(Box::new(data.map(|(_, r)| Projection::projection(&r))))
+        StructsyIter::new(self.builder.finish_projection(Reader::Snapshot(self.snapshot)))
     }
 }
 
-impl<P: Projection<T>, T: Persistent + 'static> IntoIterator for ProjectionSnapshotQuery<P, T> {
+impl<P: Projection<T> + 'static, T: Persistent + 'static> IntoIterator for ProjectionSnapshotQuery<P, T> {
     type Item = P;
     type IntoIter = StructsyIter<'static, P>;
     fn into_iter(self) -> Self::IntoIter {
-        let data = self.builder.finish(Reader::Snapshot(self.snapshot));
-        StructsyIter::new(Box::new(data.map(|(_, r)| Projection::projection(&r))))
+        StructsyIter::new(self.builder.finish_projection(Reader::Snapshot(self.snapshot)))
     }
 }
 
@@ -262,6 +475,44 @@ impl<T: Persistent + 'static> StructsyQuery<T> {
     pub(crate) fn builder(self) -> FilterBuilder<T> {
         self.builder
     }
+    /// Make a projection from the found structs, a field marked with `#[projection(id)]` holds
+    /// the id of the struct.
+    ///
+    /// When the only projected field is the indexed field used for the query and the index
+    /// alone solves the conditions and the order, the values are read from the index without
+    /// load the structs.
+    ///
+    /// # Example
+    /// ```rust
+    /// use structsy::{Ref, Structsy, StructsyTx, StructsyError};
+    /// use structsy_derive::{Persistent, Projection};
+    /// #[derive(Persistent)]
+    /// struct Person {
+    ///     #[index(mode = "cluster")]
+    ///     name: String,
+    ///     surname: String,
+    /// }
+    ///
+    /// #[derive(Projection)]
+    /// #[projection = "Person" ]
+    /// struct NameProjection {
+    ///     #[projection(id)]
+    ///     id: Ref<Person>,
+    ///     name: String,
+    /// }
+    ///
+    /// fn main() -> Result<(), StructsyError> {
+    ///     let structsy = Structsy::memory()?;
+    ///     structsy.define::<Person>()?;
+    ///     let mut tx = structsy.begin()?;
+    ///     let id = tx.insert(&Person { name: "a_name".to_string(), surname: "a_surname".to_string() })?;
+    ///     tx.commit()?;
+    ///     let found = structsy.query::<Person>().projection::<NameProjection>().fetch().next().unwrap();
+    ///     assert_eq!(found.name, "a_name");
+    ///     assert_eq!(found.id, id);
+    ///     Ok(())
+    /// }
+    /// ```
     pub fn projection<P: Projection<T>>(self) -> ProjectionQuery<P, T> {
         ProjectionQuery {
             builder: self.builder,
@@ -273,6 +524,82 @@ impl<T: Persistent + 'static> StructsyQuery<T> {
     pub fn fetch(self) -> StructsyIter<'static, (Ref<T>, T)> {
         StructsyIter::new(self.builder.finish(Reader::Structsy(self.structsy.clone())))
     }
+
+    /// Fetch the found structs, failing if a condition of the query is invalid where
+    /// [`StructsyQuery::fetch`] panics, like a nearest inside `or` or a search on a field without
+    /// a full-text index
+    ///
+    /// # Example
+    /// ```rust
+    /// use structsy::{IndexQuery, Operators, Structsy, StructsyError};
+    /// use structsy_derive::Persistent;
+    /// #[derive(Persistent)]
+    /// struct Doc {
+    ///     #[index(vector(dim = 2, metric = "euclidean"))]
+    ///     embedding: Vec<f32>,
+    /// }
+    ///
+    /// fn main() -> Result<(), StructsyError> {
+    ///     let structsy = Structsy::memory()?;
+    ///     structsy.define::<Doc>()?;
+    ///     assert!(structsy.query::<Doc>().nearest("embedding", &[1.0, 0.0], 3).try_fetch().is_ok());
+    ///     let query = structsy.query::<Doc>().or(|f| f.nearest("embedding", &[1.0, 0.0], 3));
+    ///     assert!(query.try_fetch().is_err());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn try_fetch(self) -> SRes<StructsyIter<'static, (Ref<T>, T)>> {
+        Ok(StructsyIter::new(
+            self.builder.try_finish(Reader::Structsy(self.structsy.clone()))?,
+        ))
+    }
+
+    /// Fetch only the ids of the found structs.
+    ///
+    /// When an index solves all the conditions, or there are no conditions, the ids are read
+    /// without load the structs.
+    ///
+    /// # Example
+    /// ```rust
+    /// use structsy::{Structsy, StructsyTx, StructsyError};
+    /// use structsy_derive::{queries, Persistent};
+    /// #[derive(Persistent)]
+    /// struct Person {
+    ///     #[index(mode = "cluster")]
+    ///     name: String,
+    ///     surname: String,
+    /// }
+    ///
+    /// #[queries(Person)]
+    /// trait PersonQuery {
+    ///     fn by_name(self, name: String) -> Self;
+    /// }
+    ///
+    /// fn main() -> Result<(), StructsyError> {
+    ///     let structsy = Structsy::memory()?;
+    ///     structsy.define::<Person>()?;
+    ///     let mut tx = structsy.begin()?;
+    ///     let id = tx.insert(&Person { name: "a_name".to_string(), surname: "a_surname".to_string() })?;
+    ///     tx.commit()?;
+    ///     let found = structsy.query::<Person>().by_name("a_name".to_string()).fetch_refs().collect::<Vec<_>>();
+    ///     assert_eq!(found, vec![id]);
+    ///     assert_eq!(structsy.query::<Person>().fetch_refs().count(), 1);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn fetch_refs(self) -> StructsyIter<'static, Ref<T>> {
+        StructsyIter::new(self.builder.finish_refs(Reader::Structsy(self.structsy.clone())))
+    }
+
+    /// Alias of [`StructsyQuery::fetch_refs`]
+    pub fn ids(self) -> StructsyIter<'static, Ref<T>> {
+        self.fetch_refs()
+    }
+
+    /// Count the found structs, see [`StructsyQuery::fetch_refs`]
+    pub fn count(self) -> usize {
+        self.fetch_refs().count()
+    }
 }
 
 impl<T: Persistent> IntoIterator for StructsyQuery<T> {
@@ -289,19 +616,17 @@ pub struct ProjectionQuery<P: Projection<T>, T> {
     phantom: std::marker::PhantomData<P>,
 }
 
-impl<P: Projection<T>, T: Persistent + 'static> ProjectionQuery<P, T> {
+impl<P: Projection<T> + 'static, T: Persistent + 'static> ProjectionQuery<P, T> {
     pub fn fetch(self) -> StructsyIter<'static, P> {
-        let data = self.builder.finish(Reader::Structsy(self.structsy.clone()));
-        StructsyIter::new(Box::new(data.map(|(_, r)| Projection::projection(&r))))
+        StructsyIter::new(self.builder.finish_projection(Reader::Structsy(self.structsy.clone())))
     }
 }
 
-impl<P: Projection<T>, T: Persistent + 'static> IntoIterator for ProjectionQuery<P, T> {
+impl<P: Projection<T> + 'static, T: Persistent + 'static> IntoIterator for ProjectionQuery<P, T> {
     type Item = P;
     type IntoIter = StructsyIter<'static, P>;
     fn into_iter(self) -> Self::IntoIter {
-        let data = self.builder.finish(Reader::Structsy(self.structsy.clone()));
-        StructsyIter::new(Box::new(data.map(|(_, r)| Projection::projection(&r))))
+        StructsyIter::new(self.builder.finish_projection(Reader::Structsy(self.structsy.clone())))
     }
 }
 
@@ -411,6 +736,40 @@ impl<'a, T: Persistent> StructsyQueryTx<'a, T> {
     pub fn fetch(self) -> StructsyIter<'a, (Ref<T>, T)> {
         StructsyIter::new(self.builder.finish(Reader::Tx(self.tx.reference())))
     }
+
+    /// Fetch the found structs failing on invalid conditions, including the changes of the
+    /// transaction, see [`StructsyQuery::try_fetch`]
+    pub fn try_fetch(self) -> SRes<StructsyIter<'a, (Ref<T>, T)>> {
+        Ok(StructsyIter::new(
+            self.builder.try_finish(Reader::Tx(self.tx.reference()))?,
+        ))
+    }
+
+    /// Fetch only the ids of the found structs, including the changes of the transaction, see
+    /// [`StructsyQuery::fetch_refs`]
+    pub fn fetch_refs(self) -> StructsyIter<'a, Ref<T>>
+    where
+        T: 'static,
+    {
+        StructsyIter::new(self.builder.finish_refs(Reader::Tx(self.tx.reference())))
+    }
+
+    /// Alias of [`StructsyQueryTx::fetch_refs`]
+    pub fn ids(self) -> StructsyIter<'a, Ref<T>>
+    where
+        T: 'static,
+    {
+        self.fetch_refs()
+    }
+
+    /// Count the found structs, including the changes of the transaction, see
+    /// [`StructsyQuery::fetch_refs`]
+    pub fn count(self) -> usize
+    where
+        T: 'static,
+    {
+        self.fetch_refs().count()
+    }
 }
 pub struct ProjectionQueryTx<'a, P, T> {
     tx: &'a mut OwnedSytx,
@@ -418,19 +777,17 @@ pub struct ProjectionQueryTx<'a, P, T> {
     phantom: std::marker::PhantomData<P>,
 }
 
-impl<'a, P: Projection<T>, T: Persistent + 'static> ProjectionQueryTx<'a, P, T> {
+impl<'a, P: Projection<T> + 'static, T: Persistent + 'static> ProjectionQueryTx<'a, P, T> {
     pub fn fetch(self) -> StructsyIter<'a, P> {
-        let data = self.builder.finish(Reader::Tx(self.tx.reference()));
-        StructsyIter::new(Box::new(data.map(|(_, r)| Projection::projection(&r))))
+        StructsyIter::new(self.builder.finish_projection(Reader::Tx(self.tx.reference())))
     }
 }
 
-impl<'a, P: Projection<T>, T: Persistent + 'static> IntoIterator for ProjectionQueryTx<'a, P, T> {
+impl<'a, P: Projection<T> + 'static, T: Persistent + 'static> IntoIterator for ProjectionQueryTx<'a, P, T> {
     type Item = P;
     type IntoIter = StructsyIter<'a, P>;
     fn into_iter(self) -> Self::IntoIter {
-        let data = self.builder.finish(Reader::Tx(self.tx.reference()));
-        StructsyIter::new(Box::new(data.map(|(_, r)| Projection::projection(&r))))
+        StructsyIter::new(self.builder.finish_projection(Reader::Tx(self.tx.reference())))
     }
 }
 
@@ -1,20 +1,26 @@
 //! Raw access structures
 //!
 use crate::{
+    blob::Blob,
     desc::{
-        Description, EnumDescription, FieldDescription, SimpleValueType, StructDescription, SupportedType, ValueType,
-        VariantDescription,
+        index_name, ComputedIndexDescription, Description, EnumDescription, FieldDescription, FullTextIndex,
+        IndexCondition, SimpleValueType, StructDescription, SupportedType, ValueType, VariantDescription, VectorIndex,
     },
     error::SRes,
+    fulltext::{fulltext_field_name, index_tokens},
+    geo::{geo_field_name, GeoPoint},
     internal::PersistentEmbedded,
+    keyed::{decimal_key, KeyPart},
+    vector::{check_dimension, insert_node, remove_node, vector_field_name, Metric},
     StructsyError,
 };
 use persy::{IndexType, PersyId, Transaction, ValueMode};
-use std::io::{Read, Write};
+use std::io::{Cursor, Read, Write};
 /// Builder to generate a struct record that then can be persisted
 pub struct StructBuilder {
     desc: StructDescription,
     fields: Vec<FieldValue>,
+    computed: Vec<FieldValue>,
 }
 
 impl StructBuilder {
@@ -26,43 +32,35 @@ impl StructBuilder {
         Ok(Self {
             desc,
             fields: Vec::new(),
+            computed: Vec::new(),
         })
     }
     pub fn add_field<T: SupportedType>(mut self, name: &str, value: T) -> SRes<Self> {
-        let found = self.desc.fields().find(|field| field.name == name);
-        if let Some(field) = found {
-            if field.field_type == T::resolve() {
-                let fv = FieldValue {
-                    position: field.position(),
-                    name: field.name().to_owned(),
-                    value_type: field.field_type().clone(),
-                    value: value.new()?,
-                    indexed: field.indexed().clone(),
-                };
-                self.fields.push(fv);
-                Ok(self)
-            } else {
-                Err(StructsyError::ValueChangeError(format!(
-                    "value type:'{:?}' do not match expected type:'{:?}'",
-                    T::resolve(),
-                    field.field_type
-                )))
-            }
-        } else {
-            Err(StructsyError::ValueChangeError(format!(
-                "field with name '{}' not found",
-                name
-            )))
-        }
+        let fv = FieldValue::new(self.desc.fields(), name, value)?;
+        self.fields.push(fv);
+        Ok(self)
+    }
+
+    /// Set the value of a computed index, that can't be evaluated without the source code
+    pub fn add_computed_index<T: SupportedType>(mut self, name: &str, value: T) -> SRes<Self> {
+        let fv = FieldValue::new_computed(self.desc.computed_indexes(), name, value)?;
+        self.computed.push(fv);
+        Ok(self)
     }
 
     pub fn finish(self) -> SRes<Record> {
         if self.desc.fields().count() != self.fields.len() {
             return Err(StructsyError::ValueChangeError("Missing fields".to_owned()));
         }
+        if self.desc.computed_indexes().count() != self.computed.len() {
+            return Err(StructsyError::ValueChangeError(
+                "Missing computed index values".to_owned(),
+            ));
+        }
         Ok(Record::Struct(StructRecord {
             struct_name: self.desc.get_name(),
             fields: self.fields,
+            computed: self.computed,
         }))
     }
 }
@@ -102,7 +100,7 @@ impl EnumBuilder {
 
     pub fn set_simple_variant(&mut self, name: &str) -> SRes<()> {
         if let Some(v) = self.desc.variants().find(|v| v.name == name) {
-            if v.value_type() == &None {
+            if v.value_type() == &None && v.fields.is_empty() {
                 self.variant = Some(Box::new(VariantValue::new(v, None)));
                 Ok(())
             } else {
@@ -119,8 +117,45 @@ impl EnumBuilder {
         }
     }
 
+    /// Select a struct-like or multi-field variant, the fields have to be added
+    /// with [`add_variant_field`](EnumBuilder::add_variant_field)
+    pub fn set_fields_variant(&mut self, name: &str) -> SRes<()> {
+        if let Some(v) = self.desc.variants().find(|v| v.name == name) {
+            if !v.fields.is_empty() {
+                self.variant = Some(Box::new(VariantValue::new(v, None)));
+                Ok(())
+            } else {
+                Err(StructsyError::ValueChangeError(format!(
+                    "variant '{}' do not have fields",
+                    name
+                )))
+            }
+        } else {
+            Err(StructsyError::ValueChangeError(format!(
+                "variant with name '{}' not found",
+                name
+            )))
+        }
+    }
+
+    pub fn add_variant_field<T: SupportedType>(&mut self, name: &str, value: T) -> SRes<()> {
+        let variant = self
+            .variant
+            .as_mut()
+            .ok_or_else(|| StructsyError::ValueChangeError("Requited to set the variant".to_owned()))?;
+        let desc = self.desc.variant(variant.position as usize);
+        let fv = FieldValue::new(desc.fields(), name, value)?;
+        variant.fields.retain(|f| f.name != fv.name);
+        variant.fields.push(fv);
+        Ok(())
+    }
+
     pub fn finish(self) -> SRes<Record> {
-        if self.variant.is_none() {
+        if let Some(variant) = &self.variant {
+            if self.desc.variant(variant.position as usize).fields.len() != variant.fields.len() {
+                return Err(StructsyError::ValueChangeError("Missing fields".to_owned()));
+            }
+        } else {
             return Err(StructsyError::ValueChangeError(
                 "Requited to set the variant".to_owned(),
             ));
@@ -194,12 +229,43 @@ impl Record {
         Ok(())
     }
 
+    pub(crate) fn collect_blobs(&self, blobs: &mut Vec<Blob>) -> SRes<()> {
+        match self {
+            Record::Struct(s) => {
+                for field in &s.fields {
+                    field.value.collect_blobs(blobs)?;
+                }
+            }
+            Record::Enum(e) => {
+                if let Some(value) = &e.variant.value {
+                    value.collect_blobs(blobs)?;
+                }
+                for field in &e.variant.fields {
+                    field.value.collect_blobs(blobs)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn type_name(&self) -> &str {
         match self {
             Record::Struct(s) => s.type_name(),
             Record::Enum(e) => e.type_name(),
         }
     }
+
+    pub(crate) fn check_description(&self, desc: &Description) -> SRes<()> {
+        match (self, desc) {
+            (Record::Struct(s), Description::Struct(sd)) => s.check_description(sd),
+            (Record::Enum(e), Description::Enum(ed)) => e.check_description(ed),
+            _ => Err(StructsyError::TypeError(format!(
+                "record '{}' do not match the kind of the description '{}'",
+                self.type_name(),
+                desc.get_name()
+            ))),
+        }
+    }
 }
 
 /// Struct data used for extraction and debug
@@ -208,6 +274,9 @@ impl Record {
 pub struct StructRecord {
     pub(crate) struct_name: String,
     pub(crate) fields: Vec<FieldValue>,
+    /// Values of the computed indexes, stored after the fields
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) computed: Vec<FieldValue>,
 }
 impl StructRecord {
     fn read(read: &mut dyn Read, desc: &StructDescription) -> SRes<StructRecord> {
@@ -215,9 +284,22 @@ impl StructRecord {
         for field in desc.fields() {
             fields.push(FieldValue::read(read, field)?);
         }
+        let mut computed = Vec::new();
+        if desc.computed_indexes().next().is_some() {
+            // The records written before the computed indexes were declared do not have the values
+            let mut trailing = Vec::new();
+            read.read_to_end(&mut trailing)?;
+            if !trailing.is_empty() {
+                let mut trailing = Cursor::new(trailing);
+                for (position, index) in desc.computed_indexes().enumerate() {
+                    computed.push(FieldValue::read_computed(&mut trailing, index, position as u32)?);
+                }
+            }
+        }
         Ok(StructRecord {
             struct_name: desc.get_name(),
             fields,
+            computed,
         })
     }
 
@@ -231,6 +313,11 @@ impl StructRecord {
 
             field.write(write, fd)?;
         }
+        for index in desc.computed_indexes() {
+            if let Some(value) = self.computed_index(index.name()) {
+                value.value.write(write, index.value_type())?;
+            }
+        }
         Ok(())
     }
 
@@ -250,34 +337,69 @@ impl StructRecord {
     }
 
     pub fn set_field<T: SupportedType>(&mut self, name: &str, value: T) -> SRes<()> {
-        if let Some(field) = &mut self.fields.iter_mut().find(|field| field.name == name) {
-            if field.value_type == T::resolve() {
-                field.value = value.new()?;
-                Ok(())
-            } else {
-                Err(StructsyError::ValueChangeError(format!(
-                    "value type:'{:?}' do not match expected type:'{:?}'",
-                    T::resolve(),
-                    field.value_type
-                )))
-            }
+        FieldValue::set(&mut self.fields, name, value)
+    }
+
+    /// The values of the computed indexes, empty for the records written before the
+    /// indexes were declared
+    pub fn computed_indexes(&self) -> impl Iterator<Item = &FieldValue> {
+        self.computed.iter()
+    }
+
+    pub fn computed_index(&self, name: &str) -> Option<&FieldValue> {
+        self.computed.iter().find(|c| c.name == name)
+    }
+
+    /// Set the value of a computed index, the value is not evaluated from the fields so it
+    /// should be set also when changing the fields it is computed from
+    pub fn set_computed_index<T: SupportedType>(&mut self, name: &str, value: T) -> SRes<()> {
+        if self.computed.iter().any(|c| c.name == name) {
+            FieldValue::set(&mut self.computed, name, value)
         } else {
             Err(StructsyError::ValueChangeError(format!(
-                "field with name '{}' not found",
+                "computed index '{}' not found, the record do not have the computed values",
                 name
             )))
         }
     }
 
+    fn check_description(&self, desc: &StructDescription) -> SRes<()> {
+        if self.struct_name != desc.get_name() {
+            return Err(StructsyError::TypeError(format!(
+                "record of '{}' do not match the description of '{}'",
+                self.struct_name,
+                desc.get_name()
+            )));
+        }
+        FieldValue::check_description(&self.fields, &desc.fields)?;
+        if self.computed.len() != desc.computed_indexes.len() {
+            return Err(StructsyError::ValueChangeError(
+                "Missing computed index values".to_owned(),
+            ));
+        }
+        for computed in &self.computed {
+            match desc.get_computed_index(computed.name()) {
+                Some(cd) if cd.value_type() == &computed.value_type && cd.indexed() == &computed.indexed => {}
+                _ => {
+                    return Err(StructsyError::ValueChangeError(format!(
+                        "computed index '{}' do not match the description",
+                        computed.name()
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn put_indexes(&self, tx: &mut persy::Transaction, id: &PersyId) -> SRes<()> {
-        for field in &self.fields {
-            field.put_indexes(tx, self.type_name(), id)?;
+        for field in self.fields.iter().chain(&self.computed) {
+            field.put_indexes(tx, self.type_name(), &self.fields, id)?;
         }
         Ok(())
     }
     pub(crate) fn remove_indexes(&self, tx: &mut persy::Transaction, id: &PersyId) -> SRes<()> {
-        for field in &self.fields {
-            field.remove_indexes(tx, self.type_name(), id)?;
+        for field in self.fields.iter().chain(&self.computed) {
+            field.remove_indexes(tx, self.type_name(), &self.fields, id)?;
         }
         Ok(())
     }
@@ -309,6 +431,26 @@ impl EnumRecord {
         Ok(())
     }
 
+    fn check_description(&self, desc: &EnumDescription) -> SRes<()> {
+        if self.name != desc.get_name() {
+            return Err(StructsyError::TypeError(format!(
+                "record of '{}' do not match the description of '{}'",
+                self.name,
+                desc.get_name()
+            )));
+        }
+        let found = desc.variants().find(|v| v.position() == self.variant.position);
+        match found {
+            Some(v) if v.name() == self.variant.name() && v.value_type().is_some() == self.variant.value.is_some() => {
+                FieldValue::check_description(&self.variant.fields, &v.fields)
+            }
+            _ => Err(StructsyError::ValueChangeError(format!(
+                "variant '{}' do not match the description",
+                self.variant.name()
+            ))),
+        }
+    }
+
     pub fn variant(&self) -> &VariantValue {
         &self.variant
     }
@@ -335,7 +477,7 @@ impl EnumRecord {
 
     pub fn set_simple_variant(&mut self, name: &str) -> SRes<()> {
         if let Some(v) = self.desc.variants().find(|v| v.name == name) {
-            if v.value_type() == &None {
+            if v.value_type() == &None && v.fields.is_empty() {
                 self.variant = Box::new(VariantValue::new(v, None));
                 Ok(())
             } else {
@@ -352,6 +494,11 @@ impl EnumRecord {
         }
     }
 
+    /// Change the value of a field of the current struct-like or multi-field variant
+    pub fn set_variant_field<T: SupportedType>(&mut self, name: &str, value: T) -> SRes<()> {
+        FieldValue::set(&mut self.variant.fields, name, value)
+    }
+
     pub fn type_name(&self) -> &str {
         &self.name
     }
@@ -370,6 +517,7 @@ pub struct VariantValue {
     pub(crate) position: u32,
     pub(crate) name: String,
     pub(crate) value: Option<Value>,
+    pub(crate) fields: Vec<FieldValue>,
 }
 impl VariantValue {
     fn new(desc: &VariantDescription, value: Option<Value>) -> VariantValue {
@@ -377,6 +525,7 @@ impl VariantValue {
             position: desc.position(),
             name: desc.name().to_owned(),
             value,
+            fields: Vec::new(),
         }
     }
     fn read(read: &mut dyn Read, desc: &VariantDescription) -> SRes<VariantValue> {
@@ -385,17 +534,35 @@ impl VariantValue {
         } else {
             None
         };
-        Ok(Self::new(desc, value))
+        let mut variant = Self::new(desc, value);
+        for field in desc.fields() {
+            variant.fields.push(FieldValue::read(read, field)?);
+        }
+        Ok(variant)
     }
     fn write(&self, write: &mut dyn Write, desc: &VariantDescription) -> SRes<()> {
         if let Some(val) = &self.value {
             val.write(write, desc.value_type().as_ref().expect("value and desc match"))?;
         }
+        for fd in desc.fields() {
+            if let Some(field) = self.field(fd.name()) {
+                field.write(write, fd)?;
+            } else {
+                panic!("value do not match the definition");
+            }
+        }
         Ok(())
     }
     pub fn value(&self) -> &Option<Value> {
         &self.value
     }
+    /// The fields of a struct-like or multi-field variant
+    pub fn fields(&self) -> impl Iterator<Item = &FieldValue> {
+        self.fields.iter()
+    }
+    pub fn field(&self, name: &str) -> Option<&FieldValue> {
+        self.fields.iter().find(|f| f.name == name)
+    }
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -423,8 +590,132 @@ pub struct FieldValue {
         )
     )]
     pub(crate) indexed: Option<ValueMode>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) index_condition: Option<IndexCondition>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) fulltext: Option<FullTextIndex>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) vector: Option<VectorIndex>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) geo: bool,
 }
 impl FieldValue {
+    fn new<'a, T: SupportedType>(
+        mut fields: impl Iterator<Item = &'a FieldDescription>,
+        name: &str,
+        value: T,
+    ) -> SRes<FieldValue> {
+        if let Some(field) = fields.find(|field| field.name == name) {
+            Ok(FieldValue {
+                position: field.position(),
+                name: field.name().to_owned(),
+                value_type: field.field_type().clone(),
+                value: FieldValue::typed_value(field.field_type(), value)?,
+                indexed: field.indexed().clone(),
+                index_condition: field.index_condition().cloned(),
+                fulltext: field.fulltext().cloned(),
+                vector: field.vector().cloned(),
+                geo: field.geo(),
+            })
+        } else {
+            Err(StructsyError::ValueChangeError(format!(
+                "field with name '{}' not found",
+                name
+            )))
+        }
+    }
+
+    fn new_computed<'a, T: SupportedType>(
+        indexes: impl Iterator<Item = &'a ComputedIndexDescription>,
+        name: &str,
+        value: T,
+    ) -> SRes<FieldValue> {
+        if let Some((position, index)) = indexes.enumerate().find(|(_, index)| index.name() == name) {
+            let value = FieldValue::typed_value(index.value_type(), value)?;
+            Ok(FieldValue::computed(index, position as u32, value))
+        } else {
+            Err(StructsyError::ValueChangeError(format!(
+                "computed index with name '{}' not found",
+                name
+            )))
+        }
+    }
+
+    fn computed(index: &ComputedIndexDescription, position: u32, value: Value) -> FieldValue {
+        FieldValue {
+            position,
+            name: index.name().to_owned(),
+            value_type: index.value_type().clone(),
+            value,
+            indexed: index.indexed().clone(),
+            index_condition: None,
+            fulltext: None,
+            vector: None,
+            geo: false,
+        }
+    }
+
+    fn read_computed(read: &mut dyn Read, index: &ComputedIndexDescription, position: u32) -> SRes<FieldValue> {
+        let value = Value::read(read, index.value_type())?;
+        Ok(FieldValue::computed(index, position, value))
+    }
+
+    fn set<T: SupportedType>(fields: &mut [FieldValue], name: &str, value: T) -> SRes<()> {
+        if let Some(field) = fields.iter_mut().find(|field| field.name == name) {
+            field.value = FieldValue::typed_value(&field.value_type, value)?;
+            Ok(())
+        } else {
+            Err(StructsyError::ValueChangeError(format!(
+                "field with name '{}' not found",
+                name
+            )))
+        }
+    }
+
+    /// Indexed byte arrays are described as arrays of `U8`, so the `Vec<u8>` values are converted
+    /// to their elements
+    fn typed_value<T: SupportedType>(field_type: &ValueType, value: T) -> SRes<Value> {
+        let resolved = T::resolve();
+        if field_type == &resolved {
+            value.new()
+        } else if field_type == &resolved.clone().indexed_bytes() {
+            Ok(value.new()?.indexed_bytes())
+        } else {
+            Err(StructsyError::ValueChangeError(format!(
+                "value type:'{:?}' do not match expected type:'{:?}'",
+                resolved, field_type
+            )))
+        }
+    }
+
+    fn check_description(fields: &[FieldValue], desc: &[FieldDescription]) -> SRes<()> {
+        if fields.len() != desc.len() {
+            return Err(StructsyError::ValueChangeError("Missing fields".to_owned()));
+        }
+        for field in fields {
+            if let Some(fd) = desc.iter().find(|fd| fd.name() == field.name()) {
+                if fd.field_type() != &field.value_type
+                    || fd.indexed() != &field.indexed
+                    || fd.index_condition() != field.index_condition.as_ref()
+                    || fd.fulltext() != field.fulltext.as_ref()
+                    || fd.vector() != field.vector.as_ref()
+                    || fd.geo() != field.geo
+                {
+                    return Err(StructsyError::ValueChangeError(format!(
+                        "field '{}' do not match the description",
+                        field.name()
+                    )));
+                }
+            } else {
+                return Err(StructsyError::ValueChangeError(format!(
+                    "field with name '{}' not found",
+                    field.name()
+                )));
+            }
+        }
+        Ok(())
+    }
+
     fn read(read: &mut dyn Read, field: &FieldDescription) -> SRes<FieldValue> {
         Ok(FieldValue {
             position: field.position(),
@@ -432,6 +723,10 @@ impl FieldValue {
             value: Value::read(read, field.field_type())?,
             value_type: field.field_type().clone(),
             indexed: field.indexed.clone(),
+            index_condition: field.index_condition.clone(),
+            fulltext: field.fulltext.clone(),
+            vector: field.vector.clone(),
+            geo: field.geo,
         })
     }
     fn write(&self, write: &mut dyn Write, field: &FieldDescription) -> SRes<()> {
@@ -446,15 +741,99 @@ impl FieldValue {
     pub fn value(&self) -> &Value {
         &self.value
     }
-    pub(crate) fn put_indexes(&self, tx: &mut persy::Transaction, type_name: &str, id: &PersyId) -> SRes<()> {
-        if self.indexed.is_some() {
-            self.value.put_index(tx, type_name, &self.name, id)?;
+    /// Check the condition of a partial index against the other fields of the same struct
+    fn index_condition_met(&self, siblings: &[FieldValue]) -> bool {
+        if let Some(condition) = &self.index_condition {
+            siblings
+                .iter()
+                .find(|f| f.name == condition.field)
+                .is_some_and(|f| f.value == Value::Value(SimpleValue::Bool(condition.value)))
+        } else {
+            true
+        }
+    }
+    pub(crate) fn put_indexes(
+        &self,
+        tx: &mut persy::Transaction,
+        type_name: &str,
+        siblings: &[FieldValue],
+        id: &PersyId,
+    ) -> SRes<()> {
+        self.put_indexes_path(tx, type_name, &self.name, siblings, id)
+    }
+    fn put_indexes_path(
+        &self,
+        tx: &mut persy::Transaction,
+        type_name: &str,
+        path: &str,
+        siblings: &[FieldValue],
+        id: &PersyId,
+    ) -> SRes<()> {
+        if self.indexed.is_some() && self.index_condition_met(siblings) {
+            self.value.put_index(tx, type_name, path, id)?;
+        }
+        if let Some(fulltext) = &self.fulltext {
+            let name = index_name(type_name, &[&fulltext_field_name(path)]);
+            for token in index_tokens(&self.value.text(), fulltext.stem())? {
+                tx.put::<String, PersyId>(&name, token, *id)?;
+            }
+        }
+        if let (Some(index), Some(vector)) = (&self.vector, self.value.vector()) {
+            check_dimension(index.dim(), &vector)?;
+            let name = index_name(type_name, &[&vector_field_name(path)]);
+            insert_node(tx, &name, Metric::from_name(index.metric())?, *id, &vector)?;
+        }
+        if let (true, Some(point)) = (self.geo, self.value.geo_point()) {
+            let name = index_name(type_name, &[&geo_field_name(path)]);
+            tx.put::<u64, PersyId>(&name, point.cell(), *id)?;
+        }
+        for embedded in self.value.embedded_structs() {
+            for field in &embedded.fields {
+                let field_path = format!("{}.{}", path, field.name);
+                field.put_indexes_path(tx, type_name, &field_path, &embedded.fields, id)?;
+            }
         }
         Ok(())
     }
-    pub(crate) fn remove_indexes(&self, tx: &mut persy::Transaction, type_name: &str, id: &PersyId) -> SRes<()> {
-        if self.indexed.is_some() {
-            self.value.remove_index(tx, type_name, &self.name, id)?;
+    pub(crate) fn remove_indexes(
+        &self,
+        tx: &mut persy::Transaction,
+        type_name: &str,
+        siblings: &[FieldValue],
+        id: &PersyId,
+    ) -> SRes<()> {
+        self.remove_indexes_path(tx, type_name, &self.name, siblings, id)
+    }
+    fn remove_indexes_path(
+        &self,
+        tx: &mut persy::Transaction,
+        type_name: &str,
+        path: &str,
+        siblings: &[FieldValue],
+        id: &PersyId,
+    ) -> SRes<()> {
+        if self.indexed.is_some() && self.index_condition_met(siblings) {
+            self.value.remove_index(tx, type_name, path, id)?;
+        }
+        if let Some(fulltext) = &self.fulltext {
+            let name = index_name(type_name, &[&fulltext_field_name(path)]);
+            for token in index_tokens(&self.value.text(), fulltext.stem())? {
+                tx.remove::<String, PersyId>(&name, token, Some(*id))?;
+            }
+        }
+        if let Some(index) = &self.vector {
+            let name = index_name(type_name, &[&vector_field_name(path)]);
+            remove_node(tx, &name, Metric::from_name(index.metric())?, *id)?;
+        }
+        if let (true, Some(point)) = (self.geo, self.value.geo_point()) {
+            let name = index_name(type_name, &[&geo_field_name(path)]);
+            tx.remove::<u64, PersyId>(&name, point.cell(), Some(*id))?;
+        }
+        for embedded in self.value.embedded_structs() {
+            for field in &embedded.fields {
+                let field_path = format!("{}.{}", path, field.name);
+                field.remove_indexes_path(tx, type_name, &field_path, &embedded.fields, id)?;
+            }
         }
         Ok(())
     }
@@ -465,21 +844,31 @@ impl FieldValue {
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     Value(SimpleValue),
-    Option(Option<SimpleValue>),
-    Array(Vec<SimpleValue>),
-    OptionArray(Option<Vec<SimpleValue>>),
+    Option(Option<Box<Value>>),
+    Array(Vec<Value>),
+    Map(Vec<(SimpleValue, SimpleValue)>),
 }
 impl Value {
     pub fn new<T: SupportedType>(value: T) -> SRes<Value> {
         value.new()
     }
 
+    fn indexed_bytes(self) -> Value {
+        match self {
+            Value::Value(SimpleValue::Bytes(bytes)) => {
+                Value::Array(bytes.into_iter().map(|b| Value::Value(SimpleValue::U8(b))).collect())
+            }
+            Value::Option(Some(v)) => Value::Option(Some(Box::new(v.indexed_bytes()))),
+            other => other,
+        }
+    }
+
     fn read(read: &mut dyn Read, field_type: &ValueType) -> SRes<Value> {
         Ok(match field_type {
             ValueType::Value(t) => Value::Value(SimpleValue::read(read, t)?),
             ValueType::Option(t) => {
                 if u8::read(read)? == 1 {
-                    Value::Option(Some(SimpleValue::read(read, t)?))
+                    Value::Option(Some(Box::new(Value::read(read, t)?)))
                 } else {
                     Value::Option(None)
                 }
@@ -488,21 +877,18 @@ impl Value {
                 let len = u32::read(read)?;
                 let mut v = Vec::new();
                 for _ in 0..len {
-                    v.push(SimpleValue::read(read, t)?);
+                    v.push(Value::read(read, t)?);
                 }
                 Value::Array(v)
             }
-            ValueType::OptionArray(t) => {
-                if u8::read(read)? == 1 {
-                    let len = u32::read(read)?;
-                    let mut v = Vec::new();
-                    for _ in 0..len {
-                        v.push(SimpleValue::read(read, t)?);
-                    }
-                    Value::OptionArray(Some(v))
-                } else {
-                    Value::OptionArray(None)
+            ValueType::Map(k, v) => {
+                let len = u32::read(read)?;
+                let mut m = Vec::new();
+                for _ in 0..len {
+                    let key = SimpleValue::read(read, k)?;
+                    m.push((key, SimpleValue::read(read, v)?));
                 }
+                Value::Map(m)
             }
         })
     }
@@ -538,25 +924,95 @@ impl Value {
                     sv.write(write, vt)?;
                 }
             }
-            Value::OptionArray(v) => {
-                let vt = match field_type {
-                    ValueType::OptionArray(vt) => vt,
+            Value::Map(m) => {
+                let (kt, vt) = match field_type {
+                    ValueType::Map(kt, vt) => (kt, vt),
                     _ => panic!("desc do not match field type"),
                 };
+                u32::write(&(m.len() as u32), write)?;
+                for (k, v) in m {
+                    k.write(write, kt)?;
+                    v.write(write, vt)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_blobs(&self, blobs: &mut Vec<Blob>) -> SRes<()> {
+        match self {
+            Value::Value(v) => v.collect_blobs(blobs)?,
+            Value::Option(v) => {
                 if let Some(sv) = v {
-                    u8::write(&1, write)?;
-                    u32::write(&(sv.len() as u32), write)?;
-                    for ssv in sv {
-                        ssv.write(write, vt)?;
-                    }
-                } else {
-                    u8::write(&0, write)?;
+                    sv.collect_blobs(blobs)?;
+                }
+            }
+            Value::Array(v) => {
+                for sv in v {
+                    sv.collect_blobs(blobs)?;
+                }
+            }
+            Value::Map(v) => {
+                for (k, sv) in v {
+                    k.collect_blobs(blobs)?;
+                    sv.collect_blobs(blobs)?;
                 }
             }
         }
         Ok(())
     }
 
+    /// The text of a string or optional string value
+    fn text(&self) -> Option<String> {
+        match self {
+            Value::Value(SimpleValue::String(s)) => Some(s.clone()),
+            Value::Option(Some(v)) => v.text(),
+            _ => None,
+        }
+    }
+
+    /// The vector of an array or optional array of f32 values
+    fn vector(&self) -> Option<Vec<f32>> {
+        match self {
+            Value::Array(values) => values
+                .iter()
+                .map(|v| match v {
+                    Value::Value(SimpleValue::F32(x)) => Some(*x),
+                    _ => None,
+                })
+                .collect(),
+            Value::Option(Some(v)) => v.vector(),
+            _ => None,
+        }
+    }
+
+    /// The point of a geo point or optional geo point value
+    fn geo_point(&self) -> Option<GeoPoint> {
+        match self {
+            Value::Value(SimpleValue::Embedded(Record::Struct(s))) => {
+                match (s.field("lat").map(|f| f.value()), s.field("lon").map(|f| f.value())) {
+                    (Some(Value::Value(SimpleValue::F64(lat))), Some(Value::Value(SimpleValue::F64(lon)))) => {
+                        Some(GeoPoint::new(*lat, *lon))
+                    }
+                    _ => None,
+                }
+            }
+            Value::Option(Some(v)) => v.geo_point(),
+            _ => None,
+        }
+    }
+
+    /// The embedded struct records held by this value, also inside options and arrays
+    fn embedded_structs(&self) -> Vec<&StructRecord> {
+        match self {
+            Value::Value(SimpleValue::Embedded(Record::Struct(s))) => vec![s],
+            Value::Value(_) => Vec::new(),
+            Value::Option(v) => v.iter().flat_map(|sv| sv.embedded_structs()).collect(),
+            Value::Array(v) => v.iter().flat_map(|sv| sv.embedded_structs()).collect(),
+            Value::Map(_) => Vec::new(),
+        }
+    }
+
     pub(crate) fn put_index(&self, tx: &mut persy::Transaction, type_name: &str, name: &str, id: &PersyId) -> SRes<()> {
         match self {
             Value::Value(v) => {
@@ -572,13 +1028,7 @@ impl Value {
                     sv.put_index(tx, type_name, name, id)?;
                 }
             }
-            Value::OptionArray(v) => {
-                if let Some(sv) = v {
-                    for ssv in sv {
-                        ssv.put_index(tx, type_name, name, id)?;
-                    }
-                }
-            }
+            Value::Map(_) => {}
         }
         Ok(())
     }
@@ -603,13 +1053,7 @@ impl Value {
                     sv.remove_index(tx, type_name, name, id)?;
                 }
             }
-            Value::OptionArray(v) => {
-                if let Some(sv) = v {
-                    for ssv in sv {
-                        ssv.remove_index(tx, type_name, name, id)?;
-                    }
-                }
-            }
+            Value::Map(_) => {}
         }
         Ok(())
     }
@@ -635,6 +1079,21 @@ pub enum SimpleValue {
     String(String),
     Ref(String),
     Embedded(Record),
+    Bytes(Vec<u8>),
+    Blob(String),
+    /// Nanoseconds since the unix epoch in UTC
+    DateTime(i128),
+    /// Days since the unix epoch
+    Date(i32),
+    /// Nanoseconds of a signed duration
+    Duration(i128),
+    Char(char),
+    /// The uuid as a big endian number
+    Uuid(u128),
+    /// Mantissa and scale of the decimal number
+    Decimal(i128, u32),
+    Tuple(Vec<SimpleValue>),
+    FixedArray(Vec<SimpleValue>),
 }
 
 impl SimpleValue {
@@ -657,6 +1116,28 @@ impl SimpleValue {
             String => SimpleValue::String(std::string::String::read(read)?),
             Ref(t) => SimpleValue::Ref(format!("{}@{}", t, std::string::String::read(read)?)),
             Embedded(desc) => SimpleValue::Embedded(Record::read(read, desc)?),
+            Bytes => SimpleValue::Bytes(Vec::<u8>::read(read)?),
+            Blob => SimpleValue::Blob(std::string::String::read(read)?),
+            DateTime => SimpleValue::DateTime(i128::read(read)?),
+            Date => SimpleValue::Date(i32::read(read)?),
+            Duration => SimpleValue::Duration(i128::read(read)?),
+            Char => SimpleValue::Char(char::read(read)?),
+            Uuid => SimpleValue::Uuid(u128::read(read)?),
+            Decimal => SimpleValue::Decimal(i128::read(read)?, u32::read(read)?),
+            Tuple(types) => SimpleValue::Tuple(
+                types
+                    .iter()
+                    .map(|t| SimpleValue::read(read, t))
+                    .collect::<SRes<Vec<SimpleValue>>>()?,
+            ),
+            FixedArray(t, _) => {
+                let len = u32::read(read)?;
+                let mut v = Vec::new();
+                for _ in 0..len {
+                    v.push(SimpleValue::read(read, t)?);
+                }
+                SimpleValue::FixedArray(v)
+            }
         })
     }
     fn write(&self, write: &mut dyn Write, value_type: &SimpleValueType) -> SRes<()> {
@@ -689,6 +1170,50 @@ impl SimpleValue {
                 };
                 v.write(write, desc)?;
             }
+            SimpleValue::Bytes(v) => PersistentEmbedded::write(v, write)?,
+            SimpleValue::Blob(v) => std::string::String::write(v, write)?,
+            SimpleValue::DateTime(v) => i128::write(v, write)?,
+            SimpleValue::Date(v) => i32::write(v, write)?,
+            SimpleValue::Duration(v) => i128::write(v, write)?,
+            SimpleValue::Char(v) => char::write(v, write)?,
+            SimpleValue::Uuid(v) => u128::write(v, write)?,
+            SimpleValue::Decimal(m, s) => {
+                i128::write(m, write)?;
+                u32::write(s, write)?;
+            }
+            SimpleValue::Tuple(values) => {
+                let types = match value_type {
+                    SimpleValueType::Tuple(types) if types.len() == values.len() => types,
+                    _ => panic!("type do not mach desc"),
+                };
+                for (v, t) in values.iter().zip(types) {
+                    v.write(write, t)?;
+                }
+            }
+            SimpleValue::FixedArray(values) => {
+                let t = match value_type {
+                    SimpleValueType::FixedArray(t, len) if *len as usize == values.len() => t,
+                    _ => panic!("type do not mach desc"),
+                };
+                u32::write(&(values.len() as u32), write)?;
+                for v in values {
+                    v.write(write, t)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_blobs(&self, blobs: &mut Vec<Blob>) -> SRes<()> {
+        match self {
+            SimpleValue::Blob(v) => blobs.push(Blob { id: v.parse()? }),
+            SimpleValue::Embedded(r) => r.collect_blobs(blobs)?,
+            SimpleValue::Tuple(values) | SimpleValue::FixedArray(values) => {
+                for v in values {
+                    v.collect_blobs(blobs)?;
+                }
+            }
+            _ => {}
         }
         Ok(())
     }
@@ -716,7 +1241,18 @@ impl SimpleValue {
                 }
                 put_index(tx, type_name, name, &values[1].parse::<PersyId>()?, id)?;
             }
+            SimpleValue::Embedded(Record::Enum(e)) => put_index(tx, type_name, name, &e.variant.position, id)?,
             SimpleValue::Embedded(_v) => {}
+            SimpleValue::Bytes(_v) => {}
+            SimpleValue::Blob(_v) => {}
+            SimpleValue::DateTime(v) => put_index(tx, type_name, name, v, id)?,
+            SimpleValue::Date(v) => put_index(tx, type_name, name, v, id)?,
+            SimpleValue::Duration(v) => put_index(tx, type_name, name, v, id)?,
+            SimpleValue::Char(v) => put_index(tx, type_name, name, &(*v as u32), id)?,
+            SimpleValue::Uuid(v) => put_index(tx, type_name, name, v, id)?,
+            SimpleValue::Decimal(m, s) => put_index(tx, type_name, name, &decimal_key(*m, *s), id)?,
+            SimpleValue::Tuple(values) => put_index(tx, type_name, name, &tuple_key(values)?, id)?,
+            SimpleValue::FixedArray(_v) => {}
         }
         Ok(())
     }
@@ -750,10 +1286,67 @@ impl SimpleValue {
                 }
                 remove_index(tx, type_name, name, &values[1].parse::<PersyId>()?, id)?;
             }
+            SimpleValue::Embedded(Record::Enum(e)) => remove_index(tx, type_name, name, &e.variant.position, id)?,
             SimpleValue::Embedded(_v) => {}
+            SimpleValue::Bytes(_v) => {}
+            SimpleValue::Blob(_v) => {}
+            SimpleValue::DateTime(v) => remove_index(tx, type_name, name, v, id)?,
+            SimpleValue::Date(v) => remove_index(tx, type_name, name, v, id)?,
+            SimpleValue::Duration(v) => remove_index(tx, type_name, name, v, id)?,
+            SimpleValue::Char(v) => remove_index(tx, type_name, name, &(*v as u32), id)?,
+            SimpleValue::Uuid(v) => remove_index(tx, type_name, name, v, id)?,
+            SimpleValue::Decimal(m, s) => remove_index(tx, type_name, name, &decimal_key(*m, *s), id)?,
+            SimpleValue::Tuple(values) => remove_index(tx, type_name, name, &tuple_key(values)?, id)?,
+            SimpleValue::FixedArray(_v) => {}
         }
         Ok(())
     }
+
+    /// Write the value as part of a composite key, matching the key of `KeyPart`
+    fn write_key(&self, key: &mut String) -> SRes<()> {
+        match self {
+            SimpleValue::U8(v) => v.write_key(key),
+            SimpleValue::U16(v) => v.write_key(key),
+            SimpleValue::U32(v) => v.write_key(key),
+            SimpleValue::U64(v) => v.write_key(key),
+            SimpleValue::U128(v) => v.write_key(key),
+            SimpleValue::I8(v) => v.write_key(key),
+            SimpleValue::I16(v) => v.write_key(key),
+            SimpleValue::I32(v) => v.write_key(key),
+            SimpleValue::I64(v) => v.write_key(key),
+            SimpleValue::I128(v) => v.write_key(key),
+            SimpleValue::F32(v) => v.write_key(key),
+            SimpleValue::F64(v) => v.write_key(key),
+            SimpleValue::Bool(v) => v.write_key(key),
+            SimpleValue::String(v) => v.write_key(key),
+            SimpleValue::DateTime(v) => v.write_key(key),
+            SimpleValue::Date(v) => v.write_key(key),
+            SimpleValue::Duration(v) => v.write_key(key),
+            SimpleValue::Char(v) => v.write_key(key),
+            SimpleValue::Uuid(v) => v.write_key(key),
+            SimpleValue::Decimal(m, s) => decimal_key(*m, *s).write_key(key),
+            SimpleValue::Tuple(values) => tuple_key(values)?.write_key(key),
+            SimpleValue::Ref(_)
+            | SimpleValue::Embedded(_)
+            | SimpleValue::Bytes(_)
+            | SimpleValue::Blob(_)
+            | SimpleValue::FixedArray(_) => {
+                return Err(StructsyError::TypeError(format!(
+                    "value {:?} can not be part of a tuple key",
+                    self
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn tuple_key(values: &[SimpleValue]) -> SRes<String> {
+    let mut key = String::new();
+    for v in values {
+        v.write_key(&mut key)?;
+    }
+    Ok(key)
 }
 
 fn put_index<T: IndexType>(tx: &mut Transaction, type_name: &str, name: &str, k: &T, id: &PersyId) -> SRes<()> {
@@ -0,0 +1,239 @@
+//! Differences between the descriptions stored in a database and the ones of the code
+//!
+use crate::desc::{
+    ComputedIndexDescription, Description, EnumDescription, FieldDescription, FullTextIndex, IndexCondition,
+    StructDescription, ValueType, VariantDescription, VectorIndex,
+};
+use persy::ValueMode;
+
+/// Single change between the stored description of a type and the current one
+#[derive(Debug, PartialEq, Clone)]
+pub enum SchemaChange {
+    /// The type is described in the code but is not defined in the database
+    TypeAdded,
+    /// The type is defined in the database but is not described in the code
+    TypeRemoved,
+    /// The type changed from struct to enum or from enum to struct
+    KindChanged,
+    /// A new field that is not present in the stored description
+    FieldAdded(FieldDescription),
+    /// A stored field that is not present anymore
+    FieldRemoved(FieldDescription),
+    /// A field that changed type
+    FieldRetyped {
+        name: String,
+        stored: ValueType,
+        current: ValueType,
+    },
+    /// A field that changed position
+    FieldMoved { name: String, stored: u32, current: u32 },
+    /// A field that changed index mode, `None` means not indexed
+    IndexChanged {
+        name: String,
+        stored: Option<ValueMode>,
+        current: Option<ValueMode>,
+    },
+    /// A field index that changed the condition of the partial index, `None` means not partial
+    IndexConditionChanged {
+        name: String,
+        stored: Option<IndexCondition>,
+        current: Option<IndexCondition>,
+    },
+    /// A field that changed full-text index, `None` means without full-text index
+    FullTextIndexChanged {
+        name: String,
+        stored: Option<FullTextIndex>,
+        current: Option<FullTextIndex>,
+    },
+    /// A field that changed vector index, `None` means without vector index
+    VectorIndexChanged {
+        name: String,
+        stored: Option<VectorIndex>,
+        current: Option<VectorIndex>,
+    },
+    /// A field that added or removed the geo index
+    GeoIndexChanged { name: String, stored: bool, current: bool },
+    /// A new computed index that is not present in the stored description
+    ComputedIndexAdded(ComputedIndexDescription),
+    /// A stored computed index that is not present anymore
+    ComputedIndexRemoved(ComputedIndexDescription),
+    /// A computed index that changed type or index mode
+    ComputedIndexChanged {
+        stored: ComputedIndexDescription,
+        current: ComputedIndexDescription,
+    },
+    /// A new variant that is not present in the stored description
+    VariantAdded(VariantDescription),
+    /// A stored variant that is not present anymore
+    VariantRemoved(VariantDescription),
+    /// A variant that changed the type of the value
+    VariantRetyped {
+        name: String,
+        stored: Option<ValueType>,
+        current: Option<ValueType>,
+    },
+    /// A variant that changed position
+    VariantMoved { name: String, stored: u32, current: u32 },
+    /// A struct-like or multi-field variant that changed its fields
+    VariantFieldsChanged {
+        name: String,
+        stored: Vec<FieldDescription>,
+        current: Vec<FieldDescription>,
+    },
+}
+
+/// All the changes between the stored description of a type and the current one
+#[derive(Debug, PartialEq, Clone)]
+pub struct SchemaDiff {
+    name: String,
+    changes: Vec<SchemaChange>,
+}
+
+impl SchemaDiff {
+    pub(crate) fn new(name: &str, stored: Option<&Description>, current: Option<&Description>) -> Self {
+        let changes = match (stored, current) {
+            (None, None) => Vec::new(),
+            (None, Some(_)) => vec![SchemaChange::TypeAdded],
+            (Some(_), None) => vec![SchemaChange::TypeRemoved],
+            (Some(Description::Struct(stored)), Some(Description::Struct(current))) => struct_changes(stored, current),
+            (Some(Description::Enum(stored)), Some(Description::Enum(current))) => enum_changes(stored, current),
+            (Some(_), Some(_)) => vec![SchemaChange::KindChanged],
+        };
+        SchemaDiff {
+            name: name.to_owned(),
+            changes,
+        }
+    }
+
+    /// The name of the compared type
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Iterate the changes found for the type
+    pub fn changes(&self) -> impl std::iter::Iterator<Item = &SchemaChange> {
+        self.changes.iter()
+    }
+
+    /// True if the stored description match the current one
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+fn struct_changes(stored: &StructDescription, current: &StructDescription) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+    for field in stored.fields() {
+        if let Some(cur) = current.get_field(field.name()) {
+            if cur.position() != field.position() {
+                changes.push(SchemaChange::FieldMoved {
+                    name: field.name().to_owned(),
+                    stored: field.position(),
+                    current: cur.position(),
+                });
+            }
+            if cur.field_type() != field.field_type() {
+                changes.push(SchemaChange::FieldRetyped {
+                    name: field.name().to_owned(),
+                    stored: field.field_type().clone(),
+                    current: cur.field_type().clone(),
+                });
+            }
+            if cur.indexed() != field.indexed() {
+                changes.push(SchemaChange::IndexChanged {
+                    name: field.name().to_owned(),
+                    stored: field.indexed().clone(),
+                    current: cur.indexed().clone(),
+                });
+            }
+            if cur.index_condition() != field.index_condition() {
+                changes.push(SchemaChange::IndexConditionChanged {
+                    name: field.name().to_owned(),
+                    stored: field.index_condition().cloned(),
+                    current: cur.index_condition().cloned(),
+                });
+            }
+            if cur.fulltext() != field.fulltext() {
+                changes.push(SchemaChange::FullTextIndexChanged {
+                    name: field.name().to_owned(),
+                    stored: field.fulltext().cloned(),
+                    current: cur.fulltext().cloned(),
+                });
+            }
+            if cur.vector() != field.vector() {
+                changes.push(SchemaChange::VectorIndexChanged {
+                    name: field.name().to_owned(),
+                    stored: field.vector().cloned(),
+                    current: cur.vector().cloned(),
+                });
+            }
+            if cur.geo() != field.geo() {
+                changes.push(SchemaChange::GeoIndexChanged {
+                    name: field.name().to_owned(),
+                    stored: field.geo(),
+                    current: cur.geo(),
+                });
+            }
+        } else {
+            changes.push(SchemaChange::FieldRemoved(field.clone()));
+        }
+    }
+    for field in current.fields() {
+        if stored.get_field(field.name()).is_none() {
+            changes.push(SchemaChange::FieldAdded(field.clone()));
+        }
+    }
+    for computed in stored.computed_indexes() {
+        match current.get_computed_index(computed.name()) {
+            Some(cur) if cur != computed => changes.push(SchemaChange::ComputedIndexChanged {
+                stored: computed.clone(),
+                current: cur.clone(),
+            }),
+            Some(_) => {}
+            None => changes.push(SchemaChange::ComputedIndexRemoved(computed.clone())),
+        }
+    }
+    for computed in current.computed_indexes() {
+        if stored.get_computed_index(computed.name()).is_none() {
+            changes.push(SchemaChange::ComputedIndexAdded(computed.clone()));
+        }
+    }
+    changes
+}
+
+fn enum_changes(stored: &EnumDescription, current: &EnumDescription) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+    for variant in stored.variants() {
+        if let Some(cur) = current.variants().find(|v| v.name() == variant.name()) {
+            if cur.position() != variant.position() {
+                changes.push(SchemaChange::VariantMoved {
+                    name: variant.name().to_owned(),
+                    stored: variant.position(),
+                    current: cur.position(),
+                });
+            }
+            if cur.value_type() != variant.value_type() {
+                changes.push(SchemaChange::VariantRetyped {
+                    name: variant.name().to_owned(),
+                    stored: variant.value_type().clone(),
+                    current: cur.value_type().clone(),
+                });
+            }
+            if cur.fields().ne(variant.fields()) {
+                changes.push(SchemaChange::VariantFieldsChanged {
+                    name: variant.name().to_owned(),
+                    stored: variant.fields().cloned().collect(),
+                    current: cur.fields().cloned().collect(),
+                });
+            }
+        } else {
+            changes.push(SchemaChange::VariantRemoved(variant.clone()));
+        }
+    }
+    for variant in current.variants() {
+        if !stored.variants().any(|v| v.name() == variant.name()) {
+            changes.push(SchemaChange::VariantAdded(variant.clone()));
+        }
+    }
+    changes
+}
@@ -1,3 +1,4 @@
+use crate::blob::{Blob, BlobReader, ChunkSource};
 use crate::error::{SRes, StructsyError};
 use crate::filter_builder::FilterBuilder;
 use crate::id::raw_parse;
@@ -167,6 +168,12 @@ impl Snapshot {
         }
     }
 
+    /// Read in streaming the content of a blob as it was when the snapshot was taken,
+    /// `None` if the blob does not exists.
+    pub fn read_blob(&self, blob: &Blob) -> SRes<Option<BlobReader<'static>>> {
+        BlobReader::open(ChunkSource::Snapshot(self.ps.clone()), blob)
+    }
+
     pub fn list_defined(&self) -> SRes<impl std::iter::Iterator<Item = crate::desc::Description>> {
         self.structsy_impl.list_defined()
     }
@@ -1,4 +1,5 @@
 use crate::{
+    blob::{remove_dropped_blobs, remove_pending_blobs, remove_unused_blobs},
     desc::DefinitionInfo,
     id::{raw_format, raw_parse},
     internal::Description,
@@ -6,9 +7,9 @@ use crate::{
     snapshot::SnapshotRecordIter,
     transaction::OwnedSytx,
     InternalDescription, Persistent, PersistentEmbedded, RawAccess, RawRead, Ref, SRes, Snapshot, Structsy,
-    StructsyConfig, StructsyError, StructsyTx,
+    StructsyConfig, StructsyError, StructsyTx, UndefinePolicy,
 };
-use persy::{Config, Persy, PersyId, Transaction};
+use persy::{Config, Persy, PersyId, Transaction, ValueMode};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::io::Cursor;
@@ -38,7 +39,7 @@ impl Definitions {
             } else {
                 let desc = T::get_description();
                 if x.desc != desc {
-                    Err(StructsyError::StructNotDefined(desc.get_name()))
+                    Err(StructsyError::StructDescriptionMismatch(desc.get_name()))
                 } else {
                     x.checked = true;
                     Ok(x.info())
@@ -81,19 +82,53 @@ impl Definitions {
         self.define_raw(T::get_description(), create)
     }
 
-    pub fn drop_defined<T: Persistent>(&self) -> SRes<InternalDescription> {
-        let desc = T::get_description();
+    pub fn undefine(&self, name: &str, policy: UndefinePolicy, persy: &Persy) -> SRes<()> {
         let mut lock = self.definitions.lock()?;
-        let removed = lock.remove(&desc.get_name());
-        if let Some(rem) = removed {
-            Ok(rem)
-        } else {
-            Err(StructsyError::StructNotDefined(desc.get_name()))
+        if !lock.contains_key(name) {
+            return Err(StructsyError::StructNotDefined(name.to_owned()));
+        }
+        let mut to_drop = vec![name.to_owned()];
+        let mut i = 0;
+        while i < to_drop.len() {
+            let current = to_drop[i].clone();
+            for (other, def) in lock.iter() {
+                if !to_drop.contains(other) && def.desc.refers(&current) {
+                    match policy {
+                        UndefinePolicy::Ignore => {}
+                        UndefinePolicy::Fail => {
+                            return Err(StructsyError::StructReferenced(current, other.clone()));
+                        }
+                        UndefinePolicy::Cascade => to_drop.push(other.clone()),
+                    }
+                }
+            }
+            i += 1;
+        }
+        let mut tx = persy.begin()?;
+        for drop_name in &to_drop {
+            if let Some(def) = lock.get(drop_name) {
+                if def.desc.has_blobs() {
+                    let records = tx
+                        .scan(def.info().segment_name())?
+                        .map(|(_, data)| Record::read(&mut Cursor::new(data), &def.desc))
+                        .collect::<SRes<Vec<_>>>()?;
+                    for record in records {
+                        remove_unused_blobs(&mut tx, &record, None)?;
+                    }
+                }
+                def.desc.drop_indexes(&mut tx)?;
+                tx.delete(INTERNAL_SEGMENT_NAME, &def.id)?;
+                tx.drop_segment(def.info().segment_name())?;
+            }
+        }
+        tx.prepare()?.commit()?;
+        for drop_name in &to_drop {
+            lock.remove(drop_name);
         }
+        Ok(())
     }
 
-    pub fn is_migration_started<T: Persistent>(&self) -> SRes<bool> {
-        let name = T::get_name();
+    pub fn is_migration_started_raw(&self, name: &str) -> SRes<bool> {
         let lock = self.definitions.lock()?;
         Ok(if let Some(to_check) = lock.get(name) {
             to_check.is_migration_started()
@@ -102,27 +137,29 @@ impl Definitions {
         })
     }
 
-    pub fn start_migration<T: Persistent>(&self, st: &Arc<StructsyImpl>) -> SRes<()> {
-        let name = T::get_name();
+    pub fn start_migration_tx(&self, name: &str, tx: &mut OwnedSytx) -> SRes<()> {
         let mut lock = self.definitions.lock()?;
         if let Some(to_change) = lock.get_mut(name) {
             to_change.start_migration();
-            to_change.update(st)?;
+            to_change.update_tx(tx)?;
         }
-
         Ok(())
     }
 
     pub fn finish_migration<S: Persistent, D: Persistent>(&self, st: &Arc<StructsyImpl>) -> SRes<()> {
-        let name = S::get_name();
+        self.finish_migration_raw(S::get_name(), D::get_description(), st)
+    }
+
+    pub fn finish_migration_raw(&self, name: &str, desc: Description, st: &Arc<StructsyImpl>) -> SRes<()> {
+        let new_name = desc.get_name();
         let mut tx = st.begin()?;
         let mut lock = self.definitions.lock()?;
         if let Some(mut to_change) = lock.remove(name) {
-            to_change.migrate::<D>(&mut tx)?;
-            lock.insert(D::get_name().to_string(), to_change);
+            to_change.migrate(desc, &mut tx)?;
+            lock.insert(new_name.clone(), to_change);
         }
         for (_, v) in lock.iter_mut() {
-            if v.remap_refer(name, D::get_name()) {
+            if v.remap_refer(name, &new_name) {
                 v.update_tx(&mut tx)?;
             }
         }
@@ -130,6 +167,52 @@ impl Definitions {
         Ok(())
     }
 
+    pub fn update_description<F>(&self, name: &str, tx: &mut OwnedSytx, update: F) -> SRes<()>
+    where
+        F: FnOnce(&mut InternalDescription) -> SRes<()>,
+    {
+        let mut lock = self.definitions.lock()?;
+        if let Some(to_change) = lock.get_mut(name) {
+            update(to_change)?;
+            to_change.checked = false;
+            to_change.update_tx(tx)?;
+            Ok(())
+        } else {
+            Err(StructsyError::StructNotDefined(name.to_owned()))
+        }
+    }
+
+    pub fn rename(&self, old: &str, new: &str, st: &Arc<StructsyImpl>) -> SRes<()> {
+        let mut lock = self.definitions.lock()?;
+        if lock.contains_key(new) {
+            return Err(StructsyError::StructAlreadyDefined(new.to_owned()));
+        }
+        let mut renamed = if let Some(def) = lock.get(old) {
+            def.clone()
+        } else {
+            return Err(StructsyError::StructNotDefined(old.to_owned()));
+        };
+        let mut tx = st.begin()?;
+        renamed.rename(&mut tx.trans, new)?;
+        renamed.remap_refer(old, new);
+        renamed.update_tx(&mut tx)?;
+        let mut remapped = Vec::new();
+        for (name, v) in lock.iter().filter(|(name, _)| *name != old) {
+            let mut def = v.clone();
+            if def.remap_refer(old, new) {
+                def.update_tx(&mut tx)?;
+                remapped.push((name.clone(), def));
+            }
+        }
+        tx.commit()?;
+        lock.remove(old);
+        lock.insert(new.to_owned(), renamed);
+        for (name, def) in remapped {
+            lock.insert(name, def);
+        }
+        Ok(())
+    }
+
     pub fn list(&self) -> SRes<impl std::iter::Iterator<Item = Description>> {
         let values = {
             self.definitions
@@ -167,28 +250,159 @@ impl StructsyImpl {
             return Ok(());
         }
         let info = self.check_defined::<S>()?;
+        let definition = self.definitions.full_definition_by_name(S::get_name())?;
+        check_no_index_build(&definition)?;
         let migration_batches = format!("--migration-{}-{}", S::get_name(), D::get_name());
-        if self.persy.exists_segment(&migration_batches)? && !self.definitions.is_migration_started::<S>()? {
+        let resume = definition.is_migration_started();
+        self.prepare_batches(&migration_batches, resume, || {
+            Ok(self.scan::<S>()?.map(|(id, _)| id.raw_id.to_string()))
+        })?;
+        let new_desc = D::get_description();
+        let kept_indexes = kept_indexes(&definition.desc, &new_desc);
+        let computed = has_computed_indexes(&definition.desc) || has_computed_indexes(&new_desc);
+        if !resume {
+            let mut tx = self.begin()?;
+            switch_computed_indexes(&mut tx.trans, &definition.desc, &new_desc, &kept_indexes)?;
+            self.definitions.start_migration_tx(S::get_name(), &mut tx)?;
+            tx.commit()?;
+        }
+        let mut tx = self.begin()?;
+        for (batch_id, record) in self.persy.scan(&migration_batches)? {
+            let batch: Vec<Ref<S>> = PersistentEmbedded::read(&mut Cursor::new(record))?;
+            for id in batch {
+                let mut buff = Vec::new();
+                D::from(tx.read(&id)?.unwrap()).write(&mut buff)?;
+                if computed {
+                    if let Some(data) = tx.trans.read(info.segment_name(), &id.raw_id)? {
+                        let old = Record::read(&mut Cursor::new(data), &definition.desc)?;
+                        let new = Record::read(&mut Cursor::new(&buff), &new_desc)?;
+                        if let (Record::Struct(old), Record::Struct(new)) = (&old, &new) {
+                            for value in old.computed_indexes().filter(|c| kept_indexes.contains(&c.name)) {
+                                value.remove_indexes(&mut tx.trans, old.type_name(), &old.fields, &id.raw_id)?;
+                            }
+                            for value in new.computed_indexes() {
+                                value.put_indexes(&mut tx.trans, new.type_name(), &new.fields, &id.raw_id)?;
+                            }
+                        }
+                    }
+                }
+                if let Some(desc) = info.blobs_desc() {
+                    // The blobs of the fields dropped by the migration are removed
+                    if let Some(data) = tx.trans.read(info.segment_name(), &id.raw_id)? {
+                        let old = Record::read(&mut Cursor::new(data), desc)?;
+                        let new = Record::read(&mut Cursor::new(&buff), &D::get_description())?;
+                        remove_unused_blobs(&mut tx.trans, &old, Some(&new))?;
+                    }
+                }
+                tx.trans.update(info.segment_name(), &id.raw_id, &buff)?;
+            }
+            tx.trans.delete(&migration_batches, &batch_id)?;
+            tx.commit()?;
+            tx = self.begin()?;
+        }
+        tx.commit()?;
+        self.definitions.finish_migration::<S, D>(self)?;
+        Ok(())
+    }
+
+    pub fn raw_migrate<F>(self: &Arc<Self>, type_name: &str, desc: Description, migration: F) -> SRes<()>
+    where
+        F: Fn(Record) -> SRes<Record>,
+    {
+        let definition = self.definitions.full_definition_by_name(type_name)?;
+        check_no_index_build(&definition)?;
+        let new_name = desc.get_name();
+        let migration_batches = format!("--raw-migration-{}-{}", type_name, new_name);
+        let resume = definition.is_migration_started();
+        self.prepare_batches(&migration_batches, resume, || {
+            Ok(self
+                .persy
+                .scan(definition.info().segment_name())?
+                .map(|(id, _)| raw_format(type_name, &id)))
+        })?;
+        let kept_indexes = kept_indexes(&definition.desc, &desc);
+        if !self.definitions.is_migration_started_raw(type_name)? {
+            // Indexes are switched to the new description together with the migration start,
+            // so a resumed migration will find them already in place.
+            let mut tx = self.begin()?;
+            if let Description::Struct(s) = &definition.desc {
+                for field in s.fields().filter(|f| !kept_indexes.contains(&f.name)) {
+                    field.drop_index(&mut tx.trans, &s.get_name())?;
+                }
+            }
+            if let Description::Struct(s) = &desc {
+                for field in s.fields().filter(|f| !kept_indexes.contains(&f.name)) {
+                    field.create_index(&mut tx.trans, &s.get_name())?;
+                }
+            }
+            switch_computed_indexes(&mut tx.trans, &definition.desc, &desc, &kept_indexes)?;
+            self.definitions.start_migration_tx(type_name, &mut tx)?;
+            tx.commit()?;
+        }
+        let info = definition.info();
+        let segment = info.segment_name();
+        let mut tx = self.begin()?;
+        for (batch_id, record) in self.persy.scan(&migration_batches)? {
+            let batch: Vec<String> = PersistentEmbedded::read(&mut Cursor::new(record))?;
+            for id in batch {
+                let (_, pid) = raw_parse(&id)?;
+                let id: PersyId = pid.parse()?;
+                if let Some(data) = tx.trans.read(segment, &id)? {
+                    let old = Record::read(&mut Cursor::new(data), &definition.desc)?;
+                    if let Record::Struct(s) = &old {
+                        let kept = s.fields().chain(s.computed_indexes());
+                        for field in kept.filter(|f| kept_indexes.contains(&f.name)) {
+                            field.remove_indexes(&mut tx.trans, s.type_name(), &s.fields, &id)?;
+                        }
+                    }
+                    let mut old_blobs = Vec::new();
+                    old.collect_blobs(&mut old_blobs)?;
+                    let new = migration(old)?;
+                    new.check_description(&desc)?;
+                    remove_dropped_blobs(&mut tx.trans, &old_blobs, Some(&new))?;
+                    let mut buff = Vec::new();
+                    new.write(&mut buff, &desc)?;
+                    tx.trans.update(segment, &id, &buff)?;
+                    new.put_indexes(&mut tx.trans, &id)?;
+                }
+            }
+            tx.trans.delete(&migration_batches, &batch_id)?;
+            tx.commit()?;
+            tx = self.begin()?;
+        }
+        tx.commit()?;
+        self.definitions.finish_migration_raw(type_name, desc, self)?;
+        Ok(())
+    }
+
+    /// Collect the ids of all the records of a type in batches stored in a dedicated segment,
+    /// if the batches are from an already started operation are kept for resume it.
+    fn prepare_batches<I, F>(self: &Arc<Self>, batches: &str, resume: bool, ids: F) -> SRes<()>
+    where
+        I: Iterator<Item = String>,
+        F: FnOnce() -> SRes<I>,
+    {
+        if self.persy.exists_segment(batches)? && !resume {
             let mut tx = self.begin()?;
-            tx.trans.drop_segment(&migration_batches)?;
+            tx.trans.drop_segment(batches)?;
             tx.commit()?;
         }
-        if !self.persy.exists_segment(&migration_batches)? {
+        if !self.persy.exists_segment(batches)? {
             let mut tx = self.begin()?;
-            tx.trans.create_segment(&migration_batches)?;
+            tx.trans.create_segment(batches)?;
             tx.commit()?;
             let batch_size = 1000;
             let batch_commit_size = batch_size * 1000;
             let mut count = 0;
             tx = self.begin()?;
             let mut mig_batch = Vec::new();
-            for (id, _record) in self.scan::<S>()? {
+            for id in ids()? {
                 mig_batch.push(id);
                 count += 1;
                 if count % batch_size == 0 {
                     let mut buff = Vec::new();
                     PersistentEmbedded::write(&mig_batch, &mut buff)?;
-                    tx.trans.insert(&migration_batches, &buff)?;
+                    tx.trans.insert(batches, &buff)?;
                     mig_batch.clear();
                 }
                 if count % batch_commit_size == 0 {
@@ -199,29 +413,109 @@ impl StructsyImpl {
             if !mig_batch.is_empty() {
                 let mut buff = Vec::new();
                 PersistentEmbedded::write(&mig_batch, &mut buff)?;
-                tx.trans.insert(&migration_batches, &buff)?;
+                tx.trans.insert(batches, &buff)?;
                 mig_batch.clear();
             }
             tx.commit()?;
         }
-        self.definitions.start_migration::<S>(self)?;
+        Ok(())
+    }
+
+    pub fn create_index(self: &Arc<Self>, type_name: &str, field: &str, mode: ValueMode) -> SRes<()> {
+        let definition = self.definitions.full_definition_by_name(type_name)?;
+        check_no_migration(&definition, type_name)?;
+        let stored_mode = definition.field_index(field)?;
+        // An interrupted build of the same index is resumed
+        let resume = definition.index_build() == Some(field);
+        if !resume {
+            check_no_index_build(&definition)?;
+            match stored_mode {
+                Some(m) if m == mode => return Ok(()),
+                Some(_) => {
+                    return Err(StructsyError::ValueChangeError(format!(
+                        "field '{}' of '{}' is already indexed with a different mode",
+                        field, type_name
+                    )))
+                }
+                None => {}
+            }
+        }
+        let index_batches = format!("--index-{}-{}", type_name, field);
+        self.prepare_batches(&index_batches, resume, || {
+            Ok(self
+                .persy
+                .scan(definition.info().segment_name())?
+                .map(|(id, _)| id.to_string()))
+        })?;
+        if !resume {
+            let mut tx = self.begin()?;
+            self.definitions.update_description(type_name, &mut tx, |def| {
+                def.set_field_index(field, Some(mode.clone()))?;
+                def.start_index_build(field);
+                Ok(())
+            })?;
+            if let Description::Struct(s) = &self.definitions.full_definition_by_name(type_name)?.desc {
+                if let Some(fd) = s.get_field(field) {
+                    fd.create_index(&mut tx.trans, type_name)?;
+                } else if let Some(cd) = s.get_computed_index(field) {
+                    cd.create_index(&mut tx.trans, type_name)?;
+                }
+            }
+            tx.commit()?;
+        }
+        let definition = self.definitions.full_definition_by_name(type_name)?;
+        let info = definition.info();
+        let segment = info.segment_name();
         let mut tx = self.begin()?;
-        for (batch_id, record) in self.persy.scan(&migration_batches)? {
-            let batch: Vec<Ref<S>> = PersistentEmbedded::read(&mut Cursor::new(record))?;
+        for (batch_id, record) in self.persy.scan(&index_batches)? {
+            let batch: Vec<String> = PersistentEmbedded::read(&mut Cursor::new(record))?;
             for id in batch {
-                let mut buff = Vec::new();
-                D::from(tx.read(&id)?.unwrap()).write(&mut buff)?;
-                tx.trans.update(info.segment_name(), &id.raw_id, &buff)?;
+                let id: PersyId = id.parse()?;
+                if let Some(data) = tx.trans.read(segment, &id)? {
+                    if let Record::Struct(s) = Record::read(&mut Cursor::new(data), &definition.desc)? {
+                        if let Some(fv) = s.field(field).or_else(|| s.computed_index(field)) {
+                            fv.put_indexes(&mut tx.trans, type_name, &s.fields, &id)?;
+                        }
+                    }
+                }
             }
-            tx.trans.delete(&migration_batches, &batch_id)?;
+            tx.trans.delete(&index_batches, &batch_id)?;
             tx.commit()?;
             tx = self.begin()?;
         }
+        self.definitions.update_description(type_name, &mut tx, |def| {
+            def.finish_index_build();
+            Ok(())
+        })?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn drop_index(self: &Arc<Self>, type_name: &str, field: &str) -> SRes<()> {
+        let definition = self.definitions.full_definition_by_name(type_name)?;
+        check_no_migration(&definition, type_name)?;
+        check_no_index_build(&definition)?;
+        if definition.field_index(field)?.is_none() {
+            return Ok(());
+        }
+        let mut tx = self.begin()?;
+        if let Description::Struct(s) = &definition.desc {
+            if let Some(fd) = s.get_field(field) {
+                fd.drop_index(&mut tx.trans, type_name)?;
+            } else if let Some(cd) = s.get_computed_index(field) {
+                cd.drop_index(&mut tx.trans, type_name)?;
+            }
+        }
+        self.definitions
+            .update_description(type_name, &mut tx, |def| def.set_field_index(field, None))?;
         tx.commit()?;
-        self.definitions.finish_migration::<S, D>(self)?;
         Ok(())
     }
 
+    pub fn rename_type(self: &Arc<Self>, old: &str, new: &str) -> SRes<()> {
+        self.definitions.rename(old, new, self)
+    }
+
     fn init_segment<P: AsRef<Path>>(path: P) -> SRes<()> {
         let persy = Persy::open(path, Config::new())?;
         let mut tx = persy.begin()?;
@@ -272,26 +566,15 @@ impl StructsyImpl {
             .define::<T, _>(|desc| InternalDescription::create::<T>(desc, self))
     }
 
-    pub fn undefine<T: Persistent>(&self) -> SRes<()> {
-        let int_def = self.definitions.drop_defined::<T>()?;
-        let mut tx = self.persy.begin()?;
-        if let Description::Struct(s) = &int_def.desc {
-            for field in &s.fields {
-                if field.indexed.is_some() {
-                    tx.drop_index(&format!("{}.{}", s.get_name(), field.name))?;
-                }
-            }
-        }
-        tx.delete(INTERNAL_SEGMENT_NAME, &int_def.id)?;
-        tx.drop_segment(int_def.info().segment_name())?;
-        tx.prepare()?.commit()?;
-        Ok(())
+    pub fn undefine(&self, name: &str, policy: UndefinePolicy) -> SRes<()> {
+        self.definitions.undefine(name, policy, &self.persy)
     }
 
     pub fn begin(self: &Arc<Self>) -> SRes<OwnedSytx> {
         Ok(OwnedSytx {
             structsy_impl: self.clone(),
             trans: self.persy.begin()?,
+            created_blobs: false,
         })
     }
 
@@ -304,7 +587,10 @@ impl StructsyImpl {
         }
     }
 
-    pub fn commit(&self, tx: OwnedSytx) -> SRes<()> {
+    pub fn commit(&self, mut tx: OwnedSytx) -> SRes<()> {
+        if tx.created_blobs {
+            remove_pending_blobs(&mut tx.trans)?;
+        }
         let to_finalize = tx.trans.prepare()?;
         to_finalize.commit()?;
         Ok(())
@@ -397,8 +683,9 @@ impl RawTransaction {
         let type_name = record.type_name();
         let definition = self.structsy_impl.definitions.full_definition_by_name(type_name)?;
         let ppid = pid.parse()?;
-        if let Some(record) = self.raw_read(id)? {
-            record.remove_indexes(&mut self.tx, &ppid)?;
+        if let Some(old) = self.raw_read(id)? {
+            old.remove_indexes(&mut self.tx, &ppid)?;
+            remove_unused_blobs(&mut self.tx, &old, Some(record))?;
         }
         let mut data = Vec::new();
         record.write(&mut data, &definition.desc)?;
@@ -412,6 +699,7 @@ impl RawTransaction {
         let ppid = pid.parse()?;
         if let Some(record) = self.raw_read(id)? {
             record.remove_indexes(&mut self.tx, &ppid)?;
+            remove_unused_blobs(&mut self.tx, &record, None)?;
         }
         self.tx.delete(definition.info().segment_name(), &ppid)?;
         Ok(())
@@ -467,6 +755,71 @@ impl Iterator for RawIter {
         }
     }
 }
+fn check_no_migration(definition: &InternalDescription, type_name: &str) -> SRes<()> {
+    if definition.is_migration_started() {
+        return Err(StructsyError::ValueChangeError(format!(
+            "'{}' has a migration in progress",
+            type_name
+        )));
+    }
+    Ok(())
+}
+
+fn check_no_index_build(definition: &InternalDescription) -> SRes<()> {
+    if let Some(field) = definition.index_build() {
+        return Err(StructsyError::ValueChangeError(format!(
+            "'{}' has the build of the index of field '{}' in progress",
+            definition.desc.get_name(),
+            field
+        )));
+    }
+    Ok(())
+}
+
+/// Names of the indexed fields that have the same name, type and indexes in both descriptions,
+/// the indexes of this fields can be reused as they are.
+fn kept_indexes(old: &Description, new: &Description) -> Vec<String> {
+    match (old, new) {
+        (Description::Struct(old), Description::Struct(new)) if old.get_name() == new.get_name() => {
+            let fields = old
+                .fields()
+                .filter(|f| f.has_indexes())
+                .filter(|f| {
+                    new.get_field(f.name())
+                        .map(|nf| nf.field_type() == f.field_type() && nf.same_indexes(f))
+                        .unwrap_or(false)
+                })
+                .map(|f| f.name().to_owned());
+            let computed = old
+                .computed_indexes()
+                .filter(|c| c.indexed().is_some() && new.get_computed_index(c.name()) == Some(c))
+                .map(|c| c.name().to_owned());
+            fields.chain(computed).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn has_computed_indexes(desc: &Description) -> bool {
+    matches!(desc, Description::Struct(s) if s.computed_indexes().next().is_some())
+}
+
+/// Drop the computed indexes of the old description and create the ones of the new description,
+/// except the ones kept by the migration
+fn switch_computed_indexes(tx: &mut Transaction, old: &Description, new: &Description, kept: &[String]) -> SRes<()> {
+    if let Description::Struct(s) = old {
+        for computed in s.computed_indexes().filter(|c| !kept.contains(&c.name)) {
+            computed.drop_index(tx, &s.get_name())?;
+        }
+    }
+    if let Description::Struct(s) = new {
+        for computed in s.computed_indexes().filter(|c| !kept.contains(&c.name)) {
+            computed.create_index(tx, &s.get_name())?;
+        }
+    }
+    Ok(())
+}
+
 pub(crate) fn tx_read<T: Persistent>(name: &str, tx: &mut Transaction, id: &PersyId) -> SRes<Option<T>> {
     if let Some(buff) = tx.read(name, id)? {
         Ok(Some(T::read(&mut Cursor::new(buff))?))
@@ -1,4 +1,9 @@
-use crate::{Fetch, FilterBuilder, Persistent, Ref, SRes, StructsyImpl, StructsyIter, StructsyQueryTx};
+use crate::blob::{
+    attach_blobs_raw, init_blob_segment, remove_pending_blobs, remove_unused_blobs_raw, Blob, BlobReader, BlobWriter,
+    ChunkSource,
+};
+use crate::filter_builder::Reader;
+use crate::{Fetch, Filter, FilterBuilder, Persistent, Ref, SRes, StructsyImpl, StructsyIter, StructsyQueryTx};
 use persy::Transaction;
 use std::{io::Cursor, marker::PhantomData, sync::Arc};
 
@@ -8,6 +13,8 @@ use std::{io::Cursor, marker::PhantomData, sync::Arc};
 pub struct OwnedSytx {
     pub(crate) structsy_impl: Arc<StructsyImpl>,
     pub(crate) trans: Transaction,
+    /// True if blobs were created in the transaction, so the ones not stored are removed on commit
+    pub(crate) created_blobs: bool,
 }
 
 impl OwnedSytx {
@@ -59,6 +66,7 @@ impl OwnedSytx {
         RefSytx {
             trans: &mut self.trans,
             structsy_impl: self.structsy_impl.clone(),
+            created_blobs: &mut self.created_blobs,
         }
     }
 }
@@ -69,11 +77,13 @@ impl OwnedSytx {
 pub struct RefSytx<'a> {
     pub(crate) structsy_impl: Arc<StructsyImpl>,
     pub(crate) trans: &'a mut Transaction,
+    pub(crate) created_blobs: &'a mut bool,
 }
 
 /// Internal use transaction reference
 pub struct TxRef<'a> {
     pub(crate) trans: &'a mut Transaction,
+    pub(crate) created_blobs: &'a mut bool,
 }
 
 /// Internal use implementation reference
@@ -94,7 +104,10 @@ pub trait Sytx {
 
 impl Sytx for OwnedSytx {
     fn tx(&mut self) -> TxRef {
-        TxRef { trans: &mut self.trans }
+        TxRef {
+            trans: &mut self.trans,
+            created_blobs: &mut self.created_blobs,
+        }
     }
     fn structsy(&self) -> ImplRef {
         ImplRef {
@@ -103,13 +116,19 @@ impl Sytx for OwnedSytx {
     }
 }
 impl StructsyTx for OwnedSytx {
-    fn commit(self) -> SRes<()> {
+    fn commit(mut self) -> SRes<()> {
+        if self.created_blobs {
+            remove_pending_blobs(&mut self.trans)?;
+        }
         let prepared = self.trans.prepare()?;
         prepared.commit()?;
         Ok(())
     }
 
-    fn prepare_commit(self) -> SRes<Prepared> {
+    fn prepare_commit(mut self) -> SRes<Prepared> {
+        if self.created_blobs {
+            remove_pending_blobs(&mut self.trans)?;
+        }
         Ok(Prepared {
             prepared: self.trans.prepare()?,
         })
@@ -118,7 +137,10 @@ impl StructsyTx for OwnedSytx {
 
 impl<'a> Sytx for RefSytx<'a> {
     fn tx(&mut self) -> TxRef {
-        TxRef { trans: self.trans }
+        TxRef {
+            trans: self.trans,
+            created_blobs: self.created_blobs,
+        }
     }
     fn structsy(&self) -> ImplRef {
         ImplRef {
@@ -181,6 +203,9 @@ pub trait StructsyTx: Sytx + Sized {
         let mut buff = Vec::new();
         sct.write(&mut buff)?;
         let id = self.tx().trans.insert(def.segment_name(), &buff)?;
+        if let Some(desc) = def.blobs_desc() {
+            attach_blobs_raw(self.tx().trans, desc, &buff)?;
+        }
         let id_ref = Ref::new(id);
         sct.put_indexes(self, &id_ref)?;
         Ok(id_ref)
@@ -215,6 +240,10 @@ pub trait StructsyTx: Sytx + Sized {
         if let Some(old_rec) = old {
             old_rec.remove_indexes(self, sref)?;
         }
+        if let Some(desc) = def.blobs_desc() {
+            remove_unused_blobs_raw(self.tx().trans, desc, def.segment_name(), &sref.raw_id, Some(&buff))?;
+            attach_blobs_raw(self.tx().trans, desc, &buff)?;
+        }
         self.tx().trans.update(def.segment_name(), &sref.raw_id, &buff)?;
         sct.put_indexes(self, sref)?;
         Ok(())
@@ -247,6 +276,9 @@ pub trait StructsyTx: Sytx + Sized {
         if let Some(old_rec) = old {
             old_rec.remove_indexes(self, sref)?;
         }
+        if let Some(desc) = def.blobs_desc() {
+            remove_unused_blobs_raw(self.tx().trans, desc, def.segment_name(), &sref.raw_id, None)?;
+        }
         self.tx().trans.delete(def.segment_name(), &sref.raw_id)?;
         Ok(())
     }
@@ -278,6 +310,65 @@ pub trait StructsyTx: Sytx + Sized {
         crate::structsy::tx_read(def.segment_name(), &mut self.tx().trans, &sref.raw_id)
     }
 
+    /// Create a new blob writing the content in streaming, the returned handle
+    /// should be stored in a record field, blobs not stored in any record are removed on commit.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{Blob, Structsy, StructsyTx};
+    /// use structsy_derive::Persistent;
+    /// use std::io::Write;
+    /// #[derive(Persistent)]
+    /// struct Example {
+    ///     content: Blob,
+    /// }
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// # let structsy = Structsy::open("path/to/file.stry")?;
+    /// //.. open structsy etc.
+    /// let mut tx = structsy.begin()?;
+    /// let mut writer = tx.create_blob()?;
+    /// writer.write_all(b"some content")?;
+    /// let content = writer.finish()?;
+    /// tx.insert(&Example { content })?;
+    /// tx.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn create_blob(&mut self) -> SRes<BlobWriter<'_>> {
+        init_blob_segment(&self.structsy().structsy_impl.persy)?;
+        let TxRef { trans, created_blobs } = self.tx();
+        *created_blobs = true;
+        Ok(BlobWriter::new(trans))
+    }
+
+    /// Read in streaming the content of a blob considering changes in transaction,
+    /// `None` if the blob does not exists.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{Blob, Structsy, StructsyTx};
+    /// use structsy_derive::Persistent;
+    /// use std::io::{Read, Write};
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// # let structsy = Structsy::open("path/to/file.stry")?;
+    /// //.. open structsy etc.
+    /// let mut tx = structsy.begin()?;
+    /// let mut writer = tx.create_blob()?;
+    /// writer.write_all(b"some content")?;
+    /// let content = writer.finish()?;
+    /// let mut data = Vec::new();
+    /// tx.read_blob(&content)?.unwrap().read_to_end(&mut data)?;
+    /// tx.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn read_blob(&mut self, blob: &Blob) -> SRes<Option<BlobReader<'_>>> {
+        let TxRef { trans, .. } = self.tx();
+        BlobReader::open(ChunkSource::Tx(trans), blob)
+    }
+
     /// Scan persistent instances of a struct considering changes in transaction.
     ///
     /// # Example
@@ -301,7 +392,131 @@ pub trait StructsyTx: Sytx + Sized {
     /// # }
     /// ```
     fn scan<T: Persistent>(&mut self) -> SRes<TxRecordIter<T>> {
-        raw_tx_scan(self.structsy().structsy_impl, self.tx().trans)
+        let structsy_impl = self.structsy().structsy_impl;
+        let TxRef { trans, created_blobs } = self.tx();
+        raw_tx_scan(structsy_impl, trans, created_blobs)
+    }
+
+    /// Delete all the persistent instances found by a filter considering changes in transaction,
+    /// returning the number of deleted instances.
+    ///
+    /// The filter runs once and the found instances are deleted in batches while the query goes on,
+    /// so only the ids of a batch are kept in memory, a nearest filter deletes at most its `k`
+    /// instances.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{Filter, Structsy, StructsyTx};
+    /// use structsy_derive::{queries, Persistent};
+    /// #[derive(Persistent)]
+    /// struct Example {
+    ///     #[index(mode = "cluster")]
+    ///     value: u8,
+    /// }
+    /// #[queries(Example)]
+    /// trait ExampleQuery {
+    ///     fn by_value(self, value: u8) -> Self;
+    /// }
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// # let structsy = Structsy::open("path/to/file.stry")?;
+    /// //.. open structsy etc.
+    /// let mut tx = structsy.begin()?;
+    /// tx.insert(&Example { value: 10 })?;
+    /// tx.insert(&Example { value: 20 })?;
+    /// let deleted = tx.delete_where(Filter::<Example>::new().by_value(10))?;
+    /// assert_eq!(deleted, 1);
+    /// tx.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn delete_where<T: Persistent + 'static>(&mut self, filter: Filter<T>) -> SRes<usize> {
+        let structsy_impl = self.structsy().structsy_impl;
+        let TxRef { trans, created_blobs } = self.tx();
+        let reader = Reader::Tx(RefSytx {
+            structsy_impl,
+            trans,
+            created_blobs,
+        });
+        let mut found = filter.extract_filter().try_finish_refs(reader)?;
+        let mut count = 0;
+        loop {
+            let ids = (&mut found).take(BULK_BATCH_SIZE).collect::<Vec<_>>();
+            // The reader of a query in a transaction is the transaction itself
+            if let Reader::Tx(mut tx) = found.reader() {
+                for id in &ids {
+                    tx.delete(id)?;
+                }
+            }
+            count += ids.len();
+            if ids.len() < BULK_BATCH_SIZE {
+                return Ok(count);
+            }
+        }
+    }
+
+    /// Update all the persistent instances found by a filter considering changes in transaction,
+    /// returning the number of updated instances.
+    ///
+    /// The filter runs once and the found instances are updated in batches while the query goes on,
+    /// so only the ids of a batch are kept in memory, an updated instance that still matches the
+    /// filter is not updated again and a nearest filter updates at most its `k` instances.
+    ///
+    /// # Example
+    /// ```
+    /// use structsy::{Filter, Structsy, StructsyTx};
+    /// use structsy_derive::{queries, Persistent};
+    /// #[derive(Persistent)]
+    /// struct Example {
+    ///     #[index(mode = "cluster")]
+    ///     value: u8,
+    /// }
+    /// #[queries(Example)]
+    /// trait ExampleQuery {
+    ///     fn by_value(self, value: u8) -> Self;
+    /// }
+    /// # use structsy::SRes;
+    /// # fn example() -> SRes<()> {
+    /// # let structsy = Structsy::open("path/to/file.stry")?;
+    /// //.. open structsy etc.
+    /// let mut tx = structsy.begin()?;
+    /// tx.insert(&Example { value: 10 })?;
+    /// let updated = tx.update_where(Filter::<Example>::new().by_value(10), |rec| rec.value = 11)?;
+    /// assert_eq!(updated, 1);
+    /// tx.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn update_where<T, F>(&mut self, filter: Filter<T>, mut update: F) -> SRes<usize>
+    where
+        T: Persistent + 'static,
+        F: FnMut(&mut T),
+    {
+        let structsy_impl = self.structsy().structsy_impl;
+        let TxRef { trans, created_blobs } = self.tx();
+        let reader = Reader::Tx(RefSytx {
+            structsy_impl,
+            trans,
+            created_blobs,
+        });
+        let mut found = filter.extract_filter().try_finish_refs(reader)?;
+        let mut count = 0;
+        loop {
+            let ids = (&mut found).take(BULK_BATCH_SIZE).collect::<Vec<_>>();
+            // The reader of a query in a transaction is the transaction itself
+            if let Reader::Tx(mut tx) = found.reader() {
+                for id in &ids {
+                    if let Some(mut rec) = tx.read(id)? {
+                        update(&mut rec);
+                        tx.update(id, &rec)?;
+                        count += 1;
+                    }
+                }
+            }
+            if ids.len() < BULK_BATCH_SIZE {
+                return Ok(count);
+            }
+        }
     }
 
     /// Commit a transaction
@@ -342,13 +557,17 @@ pub trait StructsyTx: Sytx + Sized {
     fn prepare_commit(self) -> SRes<Prepared>;
 }
 
+/// Number of records handled for each batch of a bulk delete or update
+const BULK_BATCH_SIZE: usize = 1000;
+
 pub(crate) fn raw_tx_scan<'a, T: Persistent>(
     structsy: Arc<StructsyImpl>,
     trans: &'a mut Transaction,
+    created_blobs: &'a mut bool,
 ) -> SRes<TxRecordIter<'a, T>> {
     let def = structsy.check_defined::<T>()?;
     let iter = trans.scan(def.segment_name())?;
-    Ok(TxRecordIter::new(iter, structsy))
+    Ok(TxRecordIter::new(iter, structsy, created_blobs))
 }
 
 pub trait TxIterator<'a>: Iterator {
@@ -366,14 +585,20 @@ pub struct TxRecordIter<'a, T> {
     iter: persy::TxSegmentIter<'a>,
     marker: PhantomData<T>,
     structsy_impl: Arc<StructsyImpl>,
+    created_blobs: &'a mut bool,
 }
 
 impl<'a, T> TxRecordIter<'a, T> {
-    fn new(iter: persy::TxSegmentIter<'a>, structsy_impl: Arc<StructsyImpl>) -> TxRecordIter<'a, T> {
+    fn new(
+        iter: persy::TxSegmentIter<'a>,
+        structsy_impl: Arc<StructsyImpl>,
+        created_blobs: &'a mut bool,
+    ) -> TxRecordIter<'a, T> {
         TxRecordIter {
             iter,
             marker: PhantomData,
             structsy_impl,
+            created_blobs,
         }
     }
 
@@ -381,6 +606,7 @@ impl<'a, T> TxRecordIter<'a, T> {
         RefSytx {
             trans: self.iter.tx(),
             structsy_impl: self.structsy_impl.clone(),
+            created_blobs: self.created_blobs,
         }
     }
 }
@@ -392,6 +618,7 @@ impl<'a, T: Persistent> TxRecordIter<'a, T> {
                 let stx = RefSytx {
                     trans: tx,
                     structsy_impl: self.structsy_impl.clone(),
+                    created_blobs: self.created_blobs,
                 };
                 Some((Ref::new(id), x, stx))
             } else {
@@ -0,0 +1,629 @@
+//! Storage and search of the approximate nearest neighbour indexes of vector fields.
+//!
+//! A vector index is a HNSW graph named after the field with a `#vector` suffix, every indexed
+//! record has a node in the segment with the index name holding the vector and, for every level
+//! of the node, the ids of the records that are its neighbours. The index with the same name maps
+//! the record ids to their node and the index with the `_entry` suffix keeps the record used as
+//! entry point of the searches.
+//!
+use crate::{
+    desc::index_name, filter_builder::Reader, format::PersistentEmbedded, Persistent, Ref, RefSytx, SRes,
+    StructsyError, Sytx,
+};
+use persy::{PersyId, Transaction, ValueMode};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+    io::Cursor,
+    rc::Rc,
+};
+
+/// Suffix of the name of the vector index of a field
+pub(crate) const VECTOR_SUFFIX: &str = "#vector";
+/// Max number of neighbours of a node in the levels above the first
+const MAX_NEIGHBOURS: usize = 16;
+/// Max number of neighbours of a node in the first level
+const MAX_NEIGHBOURS_BASE: usize = 32;
+/// Number of candidates considered when searching the neighbours of a new node
+const EF_CONSTRUCTION: usize = 100;
+/// Min number of candidates considered by a search
+pub(crate) const EF_SEARCH: usize = 64;
+
+/// Name of the field used for the vector index of `field`
+pub fn vector_field_name(field: &str) -> String {
+    format!("{}{}", field, VECTOR_SUFFIX)
+}
+
+fn entry_index_name(name: &str) -> String {
+    format!("{}_entry", name)
+}
+
+/// Distance function used to compare the vectors of an index
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// One minus the cosine of the angle between the vectors
+    Cosine,
+    /// Euclidean distance between the vectors
+    Euclidean,
+    /// Negated dot product of the vectors
+    Dot,
+}
+
+impl Metric {
+    pub fn from_name(name: &str) -> SRes<Metric> {
+        match name {
+            "cosine" => Ok(Metric::Cosine),
+            "euclidean" => Ok(Metric::Euclidean),
+            "dot" => Ok(Metric::Dot),
+            _ => Err(StructsyError::TypeError(format!(
+                "vector metric '{}' not supported",
+                name
+            ))),
+        }
+    }
+
+    /// Distance between two vectors, smaller is nearer
+    pub fn distance(&self, first: &[f32], second: &[f32]) -> f32 {
+        let dot = || first.iter().zip(second).map(|(a, b)| a * b).sum::<f32>();
+        match self {
+            Metric::Cosine => {
+                let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let norms = norm(first) * norm(second);
+                if norms == 0.0 {
+                    1.0
+                } else {
+                    1.0 - dot() / norms
+                }
+            }
+            Metric::Euclidean => first
+                .iter()
+                .zip(second)
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum::<f32>()
+                .sqrt(),
+            Metric::Dot => -dot(),
+        }
+    }
+}
+
+/// Vector of a field that can have a vector index
+pub trait VectorValue {
+    fn vector(&self) -> Option<&[f32]>;
+}
+
+impl VectorValue for Vec<f32> {
+    fn vector(&self) -> Option<&[f32]> {
+        Some(self)
+    }
+}
+
+impl VectorValue for Option<Vec<f32>> {
+    fn vector(&self) -> Option<&[f32]> {
+        self.as_deref()
+    }
+}
+
+/// Node of the graph, the neighbours are the ids of the records of the nearby nodes
+#[derive(Clone)]
+struct Node {
+    record: PersyId,
+    vector: Vec<f32>,
+    neighbours: Vec<Vec<PersyId>>,
+}
+
+impl Node {
+    fn level(&self) -> usize {
+        self.neighbours.len() - 1
+    }
+
+    fn write(&self) -> SRes<Vec<u8>> {
+        let mut buff = Vec::new();
+        self.record.to_string().write(&mut buff)?;
+        self.vector.write(&mut buff)?;
+        let neighbours = self
+            .neighbours
+            .iter()
+            .map(|level| level.iter().map(|id| id.to_string()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        neighbours.write(&mut buff)?;
+        Ok(buff)
+    }
+
+    fn read(data: Vec<u8>) -> SRes<Node> {
+        let mut read = Cursor::new(data);
+        let record = String::read(&mut read)?.parse()?;
+        let vector = Vec::<f32>::read(&mut read)?;
+        let mut neighbours = Vec::new();
+        for level in Vec::<Vec<String>>::read(&mut read)? {
+            let mut ids = Vec::new();
+            for id in level {
+                ids.push(id.parse()?);
+            }
+            neighbours.push(ids);
+        }
+        Ok(Node {
+            record,
+            vector,
+            neighbours,
+        })
+    }
+}
+
+/// Read access to the graph, from the transaction that changes it or from the query readers
+pub(crate) trait GraphRead {
+    fn node_id(&mut self, name: &str, record: &PersyId) -> SRes<Option<PersyId>>;
+    fn node_data(&mut self, name: &str, node: &PersyId) -> SRes<Option<Vec<u8>>>;
+    fn entry(&mut self, name: &str) -> SRes<Option<PersyId>>;
+}
+
+impl GraphRead for Transaction {
+    fn node_id(&mut self, name: &str, record: &PersyId) -> SRes<Option<PersyId>> {
+        Ok(self.one::<PersyId, PersyId>(name, record)?)
+    }
+    fn node_data(&mut self, name: &str, node: &PersyId) -> SRes<Option<Vec<u8>>> {
+        Ok(self.read(name, node)?)
+    }
+    fn entry(&mut self, name: &str) -> SRes<Option<PersyId>> {
+        Ok(self.one::<u8, PersyId>(&entry_index_name(name), &0)?)
+    }
+}
+
+impl<'a> GraphRead for Reader<'a> {
+    fn node_id(&mut self, name: &str, record: &PersyId) -> SRes<Option<PersyId>> {
+        match self {
+            Reader::Structsy(st) => Ok(st.structsy_impl.persy.one::<PersyId, PersyId>(name, record)?),
+            Reader::Snapshot(snap) => Ok(snap.ps.one::<PersyId, PersyId>(name, record)?),
+            Reader::Tx(RefSytx { trans, .. }) => trans.node_id(name, record),
+        }
+    }
+    fn node_data(&mut self, name: &str, node: &PersyId) -> SRes<Option<Vec<u8>>> {
+        match self {
+            Reader::Structsy(st) => Ok(st.structsy_impl.persy.read(name, node)?),
+            Reader::Snapshot(snap) => Ok(snap.ps.read(name, node)?),
+            Reader::Tx(RefSytx { trans, .. }) => trans.node_data(name, node),
+        }
+    }
+    fn entry(&mut self, name: &str) -> SRes<Option<PersyId>> {
+        let entry = entry_index_name(name);
+        match self {
+            Reader::Structsy(st) => Ok(st.structsy_impl.persy.one::<u8, PersyId>(&entry, &0)?),
+            Reader::Snapshot(snap) => Ok(snap.ps.one::<u8, PersyId>(&entry, &0)?),
+            Reader::Tx(RefSytx { trans, .. }) => trans.entry(name),
+        }
+    }
+}
+
+/// A record with its distance from the searched vector
+#[derive(Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    record: PersyId,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .partial_cmp(&other.distance)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.record.cmp(&other.record))
+    }
+}
+
+fn max_neighbours(level: usize) -> usize {
+    if level == 0 {
+        MAX_NEIGHBOURS_BASE
+    } else {
+        MAX_NEIGHBOURS
+    }
+}
+
+fn random_level() -> usize {
+    let factor = 1.0 / (MAX_NEIGHBOURS as f64).ln();
+    let uniform = 1.0 - rand::random::<f64>();
+    (-uniform.ln() * factor).floor() as usize
+}
+
+/// The graph of an index, keeping in memory the nodes already read by an operation
+struct Graph<'a, R: ?Sized> {
+    store: &'a mut R,
+    name: &'a str,
+    metric: Metric,
+    nodes: HashMap<PersyId, Option<(PersyId, Rc<Node>)>>,
+}
+
+impl<'a, R: GraphRead + ?Sized> Graph<'a, R> {
+    fn new(store: &'a mut R, name: &'a str, metric: Metric) -> Self {
+        Graph {
+            store,
+            name,
+            metric,
+            nodes: HashMap::new(),
+        }
+    }
+
+    /// The node of a record with its id, `None` if the record is not in the graph anymore
+    fn node(&mut self, record: &PersyId) -> SRes<Option<(PersyId, Rc<Node>)>> {
+        if let Some(found) = self.nodes.get(record) {
+            return Ok(found.clone());
+        }
+        let mut found = None;
+        if let Some(node_id) = self.store.node_id(self.name, record)? {
+            if let Some(data) = self.store.node_data(self.name, &node_id)? {
+                found = Some((node_id, Rc::new(Node::read(data)?)));
+            }
+        }
+        self.nodes.insert(*record, found.clone());
+        Ok(found)
+    }
+
+    fn distance(&self, first: &[f32], second: &[f32]) -> f32 {
+        self.metric.distance(first, second)
+    }
+
+    /// Greedy search of the `ef` nearest records to `query` in a level of the graph, starting
+    /// from the `entries` and returning the found records from the nearest
+    fn search_level(
+        &mut self,
+        query: &[f32],
+        entries: Vec<Candidate>,
+        ef: usize,
+        level: usize,
+    ) -> SRes<Vec<Candidate>> {
+        let mut visited = entries.iter().map(|c| c.record).collect::<HashSet<_>>();
+        let mut candidates = entries.iter().copied().map(Reverse).collect::<BinaryHeap<_>>();
+        let mut found = entries.into_iter().collect::<BinaryHeap<_>>();
+        while let Some(Reverse(candidate)) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|f| candidate.distance > f.distance) {
+                break;
+            }
+            let node = match self.node(&candidate.record)? {
+                Some((_, node)) if node.neighbours.len() > level => node,
+                _ => continue,
+            };
+            for record in &node.neighbours[level] {
+                if !visited.insert(*record) {
+                    continue;
+                }
+                if let Some((_, neighbour)) = self.node(record)? {
+                    let near = Candidate {
+                        distance: self.distance(query, &neighbour.vector),
+                        record: *record,
+                    };
+                    if found.len() < ef || found.peek().is_some_and(|f| near.distance < f.distance) {
+                        candidates.push(Reverse(near));
+                        found.push(near);
+                        if found.len() > ef {
+                            found.pop();
+                        }
+                    }
+                }
+            }
+        }
+        Ok(found.into_sorted_vec())
+    }
+
+    /// Descend the graph from the entry point down to `level`, keeping only the nearest record,
+    /// returns also the top level of the graph
+    fn descend(&mut self, query: &[f32], level: usize) -> SRes<Option<(Vec<Candidate>, usize)>> {
+        let entry = match self.store.entry(self.name)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let node = match self.node(&entry)? {
+            Some((_, node)) => node,
+            None => return Ok(None),
+        };
+        let top = node.level();
+        let mut entries = vec![Candidate {
+            distance: self.distance(query, &node.vector),
+            record: entry,
+        }];
+        for l in (level + 1..=top).rev() {
+            entries = self.search_level(query, entries, 1, l)?;
+        }
+        Ok(Some((entries, top)))
+    }
+
+    /// Select the neighbours of `vector` between the `candidates`, at most the max allowed in `level`.
+    ///
+    /// A candidate is preferred when it is nearer to `vector` than to the neighbours already
+    /// selected, keeping the links toward the isolated records so they stay reachable, the
+    /// remaining places are filled with the nearest of the other candidates.
+    fn select_neighbours(&mut self, vector: &[f32], candidates: &[PersyId], level: usize) -> SRes<Vec<PersyId>> {
+        let mut near = Vec::new();
+        for record in candidates {
+            if let Some((_, node)) = self.node(record)? {
+                let distance = self.distance(vector, &node.vector);
+                near.push((
+                    Candidate {
+                        distance,
+                        record: *record,
+                    },
+                    node,
+                ));
+            }
+        }
+        near.sort_by_key(|(candidate, _)| *candidate);
+        let max = max_neighbours(level);
+        let mut selected: Vec<(Candidate, Rc<Node>)> = Vec::new();
+        let mut discarded = Vec::new();
+        for (candidate, node) in near {
+            if selected.len() >= max {
+                break;
+            }
+            let diverse = selected
+                .iter()
+                .all(|(_, other)| candidate.distance < self.distance(&node.vector, &other.vector));
+            if diverse {
+                selected.push((candidate, node));
+            } else {
+                discarded.push(candidate.record);
+            }
+        }
+        let mut neighbours = selected.into_iter().map(|(c, _)| c.record).collect::<Vec<_>>();
+        let missing = max.saturating_sub(neighbours.len());
+        neighbours.extend(discarded.into_iter().take(missing));
+        Ok(neighbours)
+    }
+}
+
+impl<'a> Graph<'a, Transaction> {
+    fn write(&mut self, node_id: PersyId, node: Node) -> SRes<()> {
+        self.store.update(self.name, &node_id, &node.write()?)?;
+        self.nodes.insert(node.record, Some((node_id, Rc::new(node))));
+        Ok(())
+    }
+
+    fn set_entry(&mut self, record: Option<PersyId>) -> SRes<()> {
+        set_entry(self.store, self.name, record)
+    }
+
+    fn insert(&mut self, record: PersyId, vector: &[f32]) -> SRes<()> {
+        let level = random_level();
+        let found = self.descend(vector, level)?;
+        let mut node = Node {
+            record,
+            vector: vector.to_vec(),
+            neighbours: vec![Vec::new(); level + 1],
+        };
+        let node_id = self.store.insert(self.name, &node.write()?)?;
+        self.store.put::<PersyId, PersyId>(self.name, record, node_id)?;
+        let (mut entries, top) = match found {
+            Some(found) => found,
+            None => {
+                self.nodes.insert(record, Some((node_id, Rc::new(node))));
+                return self.set_entry(Some(record));
+            }
+        };
+        for l in (0..=level.min(top)).rev() {
+            entries = self.search_level(vector, entries, EF_CONSTRUCTION, l)?;
+            let candidates = entries
+                .iter()
+                .map(|c| c.record)
+                .filter(|r| *r != record)
+                .collect::<Vec<_>>();
+            let neighbours = self.select_neighbours(vector, &candidates, l)?;
+            for neighbour in &neighbours {
+                if let Some((neighbour_id, neighbour_node)) = self.node(neighbour)? {
+                    if neighbour_node.neighbours.len() <= l || neighbour_node.neighbours[l].contains(&record) {
+                        continue;
+                    }
+                    let mut links = neighbour_node.neighbours[l].clone();
+                    links.push(record);
+                    if links.len() > max_neighbours(l) {
+                        links = self.select_neighbours(&neighbour_node.vector, &links, l)?;
+                    }
+                    let mut changed = Node::clone(&neighbour_node);
+                    changed.neighbours[l] = links;
+                    self.write(neighbour_id, changed)?;
+                }
+            }
+            node.neighbours[l] = neighbours;
+        }
+        self.write(node_id, node)?;
+        if level > top {
+            self.set_entry(Some(record))?;
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, record: PersyId) -> SRes<()> {
+        let (node_id, node) = match self.node(&record)? {
+            Some(found) => found,
+            None => return Ok(()),
+        };
+        self.store.delete(self.name, &node_id)?;
+        self.store
+            .remove::<PersyId, PersyId>(self.name, record, Some(node_id))?;
+        self.nodes.insert(record, None);
+        for (level, neighbours) in node.neighbours.iter().enumerate() {
+            for neighbour in neighbours {
+                if let Some((neighbour_id, neighbour_node)) = self.node(neighbour)? {
+                    if neighbour_node.neighbours.len() <= level {
+                        continue;
+                    }
+                    // Reconnect the neighbour with the other neighbours of the removed node
+                    let mut links = neighbour_node.neighbours[level].clone();
+                    links.retain(|r| *r != record);
+                    for other in neighbours {
+                        if other != neighbour && !links.contains(other) {
+                            links.push(*other);
+                        }
+                    }
+                    let mut changed = Node::clone(&neighbour_node);
+                    changed.neighbours[level] = self.select_neighbours(&neighbour_node.vector, &links, level)?;
+                    self.write(neighbour_id, changed)?;
+                }
+            }
+        }
+        if self.store.entry(self.name)? == Some(record) {
+            // The new entry point is the neighbour of the removed node in the highest level
+            let mut entry: Option<Rc<Node>> = None;
+            for neighbour in node.neighbours.iter().rev().flatten() {
+                if let Some((_, neighbour_node)) = self.node(neighbour)? {
+                    if entry.as_ref().map_or(true, |e| neighbour_node.level() > e.level()) {
+                        entry = Some(neighbour_node);
+                    }
+                }
+            }
+            if entry.is_none() {
+                entry = self.any_node()?;
+            }
+            self.set_entry(entry.map(|e| e.record))?;
+        }
+        Ok(())
+    }
+
+    /// The first node of the graph, used as entry point when the removed entry had no neighbours
+    /// left, that happens only when it was the last node of the graph or of a part disconnected
+    /// by earlier removals
+    fn any_node(&mut self) -> SRes<Option<Rc<Node>>> {
+        let found = self.store.scan(self.name)?.next();
+        Ok(match found {
+            Some((_, data)) => Some(Rc::new(Node::read(data)?)),
+            None => None,
+        })
+    }
+}
+
+/// Approximate search of the `ef` nearest records to `query`, from the nearest
+pub(crate) fn search<R: GraphRead + ?Sized>(
+    store: &mut R,
+    name: &str,
+    metric: Metric,
+    query: &[f32],
+    ef: usize,
+) -> SRes<Vec<(PersyId, f32)>> {
+    let mut graph = Graph::new(store, name, metric);
+    if let Some((entries, _)) = graph.descend(query, 0)? {
+        let found = graph.search_level(query, entries, ef, 0)?;
+        Ok(found.into_iter().map(|c| (c.record, c.distance)).collect())
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+fn set_entry(tx: &mut Transaction, name: &str, record: Option<PersyId>) -> SRes<()> {
+    let entry = entry_index_name(name);
+    if let Some(record) = record {
+        tx.put::<u8, PersyId>(&entry, 0, record)?;
+    } else {
+        tx.remove::<u8, PersyId>(&entry, 0, None)?;
+    }
+    Ok(())
+}
+
+/// Add the vector of a record to the graph linking it to its nearest records
+pub(crate) fn insert_node(
+    tx: &mut Transaction,
+    name: &str,
+    metric: Metric,
+    record: PersyId,
+    vector: &[f32],
+) -> SRes<()> {
+    Graph::new(tx, name, metric).insert(record, vector)
+}
+
+/// Remove the vector of a record from the graph, reconnecting its neighbours between them
+pub(crate) fn remove_node(tx: &mut Transaction, name: &str, metric: Metric, record: PersyId) -> SRes<()> {
+    Graph::new(tx, name, metric).remove(record)
+}
+
+/// Check the definition of a vector index, returning its metric
+pub(crate) fn check_vector_index(dim: u32, metric: &str) -> SRes<Metric> {
+    if dim == 0 {
+        return Err(StructsyError::TypeError(
+            "vector index dimension must be greater than zero".to_owned(),
+        ));
+    }
+    Metric::from_name(metric)
+}
+
+/// Check that the vector has the dimension of the index
+pub(crate) fn check_dimension(dim: u32, vector: &[f32]) -> SRes<()> {
+    if vector.len() != dim as usize {
+        Err(StructsyError::TypeError(format!(
+            "vector of dimension {} in a vector index of dimension {}",
+            vector.len(),
+            dim
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn create_vector_index(tx: &mut Transaction, name: &str) -> SRes<()> {
+    tx.create_segment(name)?;
+    tx.create_index::<PersyId, PersyId>(name, ValueMode::Replace)?;
+    tx.create_index::<u8, PersyId>(&entry_index_name(name), ValueMode::Replace)?;
+    Ok(())
+}
+
+pub(crate) fn drop_vector_index(tx: &mut Transaction, name: &str) -> SRes<()> {
+    tx.drop_segment(name)?;
+    tx.drop_index(name)?;
+    tx.drop_index(&entry_index_name(name))?;
+    Ok(())
+}
+
+/// Move the graph to the index of another name, the nodes refer to the records so they are
+/// copied as they are
+pub(crate) fn rename_vector_index(tx: &mut Transaction, old_name: &str, new_name: &str) -> SRes<()> {
+    create_vector_index(tx, new_name)?;
+    let nodes = tx.scan(old_name)?.map(|(_, data)| data).collect::<Vec<_>>();
+    for data in nodes {
+        let node_id = tx.insert(new_name, &data)?;
+        let record = Node::read(data)?.record;
+        tx.put::<PersyId, PersyId>(new_name, record, node_id)?;
+    }
+    let entry = tx.entry(old_name)?;
+    set_entry(tx, new_name, entry)?;
+    drop_vector_index(tx, old_name)
+}
+
+pub fn declare_vector_index(db: &mut dyn Sytx, type_name: &str, field: &str, dim: u32, metric: &str) -> SRes<()> {
+    check_vector_index(dim, metric)?;
+    create_vector_index(db.tx().trans, &index_name(type_name, &[&vector_field_name(field)]))
+}
+
+/// Add the vector of the `field` of a record to its vector index
+pub fn put_vector<V: VectorValue, P: Persistent>(
+    tx: &mut dyn Sytx,
+    type_name: &str,
+    field: &str,
+    dim: u32,
+    metric: &str,
+    value: &V,
+    id: &Ref<P>,
+) -> SRes<()> {
+    if let Some(vector) = value.vector() {
+        check_dimension(dim, vector)?;
+        let name = index_name(type_name, &[&vector_field_name(field)]);
+        insert_node(tx.tx().trans, &name, Metric::from_name(metric)?, id.raw_id, vector)?;
+    }
+    Ok(())
+}
+
+/// Remove the vector of the `field` of a record from its vector index
+pub fn remove_vector<V: VectorValue, P: Persistent>(
+    tx: &mut dyn Sytx,
+    type_name: &str,
+    field: &str,
+    metric: &str,
+    value: &V,
+    id: &Ref<P>,
+) -> SRes<()> {
+    if value.vector().is_some() {
+        let name = index_name(type_name, &[&vector_field_name(field)]);
+        remove_node(tx.tx().trans, &name, Metric::from_name(metric)?, id.raw_id)?;
+    }
+    Ok(())
+}
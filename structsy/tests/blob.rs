@@ -0,0 +1,360 @@
+use std::io::{Read, Write};
+use structsy::{
+    internal::{SimpleValueTypeBuilder, StructDescriptionBuilder, ValueTypeBuilder},
+    record::{Record, SimpleValue, StructBuilder, Value},
+    Blob, RawAccess, SRes, Structsy, StructsyTx,
+};
+use structsy_derive::{Persistent, PersistentEmbedded};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent)]
+struct Picture {
+    name: String,
+    content: Blob,
+}
+
+#[derive(Persistent)]
+struct PictureV1 {
+    name: String,
+}
+
+impl From<Picture> for PictureV1 {
+    fn from(pic: Picture) -> PictureV1 {
+        PictureV1 { name: pic.name }
+    }
+}
+
+#[derive(PersistentEmbedded)]
+struct Attachment {
+    content: Blob,
+}
+
+#[derive(Persistent)]
+struct Mail {
+    attachments: Vec<Attachment>,
+    preview: Option<Blob>,
+}
+
+fn content(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i % 251) as u8).collect()
+}
+
+fn write_blob<T: StructsyTx>(tx: &mut T, data: &[u8]) -> SRes<Blob> {
+    let mut writer = tx.create_blob()?;
+    for chunk in data.chunks(1000) {
+        writer.write_all(chunk)?;
+    }
+    writer.finish()
+}
+
+fn read_all(db: &Structsy, blob: &Blob) -> SRes<Option<Vec<u8>>> {
+    if let Some(mut reader) = db.read_blob(blob)? {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        assert_eq!(reader.len(), data.len() as u64);
+        Ok(Some(data))
+    } else {
+        Ok(None)
+    }
+}
+
+#[test]
+fn test_blob_write_read() {
+    structsy_inst("blob_write_read", |db| {
+        db.define::<Picture>()?;
+        let data = content(200_000);
+        let mut tx = db.begin()?;
+        let blob = write_blob(&mut tx, &data)?;
+        let mut read = Vec::new();
+        tx.read_blob(&blob)?.unwrap().read_to_end(&mut read)?;
+        assert_eq!(read, data);
+        let id = tx.insert(&Picture {
+            name: "big".to_string(),
+            content: blob,
+        })?;
+        tx.commit()?;
+
+        let pic = db.read(&id)?.unwrap();
+        assert_eq!(read_all(db, &pic.content)?, Some(data.clone()));
+
+        let snapshot = db.snapshot()?;
+        let mut read = Vec::new();
+        snapshot.read_blob(&pic.content)?.unwrap().read_to_end(&mut read)?;
+        assert_eq!(read, data);
+
+        let mut tx = db.begin()?;
+        let empty = write_blob(&mut tx, &[])?;
+        tx.insert(&Picture {
+            name: "empty".to_string(),
+            content: empty.clone(),
+        })?;
+        tx.commit()?;
+        let reader = db.read_blob(&empty)?.unwrap();
+        assert!(reader.is_empty());
+        Ok(())
+    });
+}
+
+#[test]
+fn test_blob_removed_on_delete_and_update() {
+    structsy_inst("blob_removed", |db| {
+        db.define::<Picture>()?;
+        let mut tx = db.begin()?;
+        let first = write_blob(&mut tx, &content(100_000))?;
+        let id = tx.insert(&Picture {
+            name: "first".to_string(),
+            content: first.clone(),
+        })?;
+        tx.commit()?;
+
+        let mut tx = db.begin()?;
+        tx.update(
+            &id,
+            &Picture {
+                name: "renamed".to_string(),
+                content: first.clone(),
+            },
+        )?;
+        tx.commit()?;
+        assert!(read_all(db, &first)?.is_some());
+
+        let mut tx = db.begin()?;
+        let second = write_blob(&mut tx, &content(10))?;
+        tx.update(
+            &id,
+            &Picture {
+                name: "second".to_string(),
+                content: second.clone(),
+            },
+        )?;
+        tx.commit()?;
+        assert!(read_all(db, &first)?.is_none());
+        assert_eq!(read_all(db, &second)?, Some(content(10)));
+
+        let snapshot = db.snapshot()?;
+        let mut tx = db.begin()?;
+        tx.delete(&id)?;
+        tx.commit()?;
+        assert!(read_all(db, &second)?.is_none());
+        assert!(snapshot.read_blob(&second)?.is_some());
+        Ok(())
+    });
+}
+
+#[test]
+fn test_nested_blob_removed() {
+    structsy_inst("nested_blob_removed", |db| {
+        db.define::<Mail>()?;
+        let mut tx = db.begin()?;
+        let attached = write_blob(&mut tx, &content(1000))?;
+        let preview = write_blob(&mut tx, &content(100))?;
+        let id = tx.insert(&Mail {
+            attachments: vec![Attachment {
+                content: attached.clone(),
+            }],
+            preview: Some(preview.clone()),
+        })?;
+        tx.commit()?;
+        assert!(read_all(db, &attached)?.is_some());
+
+        let mut raw = db.raw_begin()?;
+        let raw_id = db.raw_scan("Mail")?.next().unwrap().0;
+        raw.raw_delete(&raw_id)?;
+        raw.prepare()?.commit()?;
+        assert!(db.read(&id)?.is_none());
+        assert!(read_all(db, &attached)?.is_none());
+        assert!(read_all(db, &preview)?.is_none());
+        Ok(())
+    });
+}
+
+#[test]
+fn test_blob_removed_on_undefine() {
+    structsy_inst("blob_undefine", |db| {
+        db.define::<Picture>()?;
+        let mut tx = db.begin()?;
+        let blob = write_blob(&mut tx, &content(1000))?;
+        tx.insert(&Picture {
+            name: "pic".to_string(),
+            content: blob.clone(),
+        })?;
+        tx.commit()?;
+        db.undefine::<Picture>()?;
+        assert!(read_all(db, &blob)?.is_none());
+        Ok(())
+    });
+}
+
+#[test]
+fn test_unattached_blob_removed() {
+    structsy_inst("unattached_blob_removed", |db| {
+        db.define::<Picture>()?;
+        let mut tx = db.begin()?;
+        let unattached = write_blob(&mut tx, &content(100_000))?;
+        let mut dropped = tx.create_blob()?;
+        dropped.write_all(&content(200_000))?;
+        drop(dropped);
+        let mut aborted = tx.create_blob()?;
+        aborted.write_all(&content(200_000))?;
+        aborted.abort()?;
+        let attached = write_blob(&mut tx, &content(100_000))?;
+        tx.insert(&Picture {
+            name: "attached".to_string(),
+            content: attached.clone(),
+        })?;
+        assert!(tx.read_blob(&unattached)?.is_some());
+        tx.commit()?;
+        assert!(read_all(db, &unattached)?.is_none());
+        assert_eq!(read_all(db, &attached)?, Some(content(100_000)));
+
+        let mut tx = db.begin()?;
+        let replaced = write_blob(&mut tx, &content(10))?;
+        let id = tx.insert(&Picture {
+            name: "replaced".to_string(),
+            content: replaced.clone(),
+        })?;
+        let updated = write_blob(&mut tx, &content(20))?;
+        tx.update(
+            &id,
+            &Picture {
+                name: "updated".to_string(),
+                content: updated.clone(),
+            },
+        )?;
+        tx.prepare_commit()?.commit()?;
+        assert!(read_all(db, &replaced)?.is_none());
+        assert_eq!(read_all(db, &updated)?, Some(content(20)));
+        Ok(())
+    });
+}
+
+#[test]
+fn test_blob_segment_created_lazily() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("blob_segment_lazy.stry");
+    let blobs_exist = |file: &std::path::Path| {
+        let persy = persy::Persy::open(file, persy::Config::new()).unwrap();
+        persy.exists_segment("__#blobs").unwrap()
+    };
+    {
+        let db = Structsy::open(&file).unwrap();
+        db.define::<PictureV1>().unwrap();
+        let mut tx = db.begin().unwrap();
+        tx.insert(&PictureV1 {
+            name: "plain".to_string(),
+        })
+        .unwrap();
+        tx.commit().unwrap();
+    }
+    assert!(!blobs_exist(&file));
+    {
+        let db = Structsy::open(&file).unwrap();
+        let mut tx = db.begin().unwrap();
+        let unattached = {
+            // A blob created from the transaction of an iterator is removed on commit as well
+            let mut iter = tx.scan::<PictureV1>().unwrap();
+            write_blob(&mut iter.tx(), &content(10)).unwrap()
+        };
+        tx.commit().unwrap();
+        assert!(read_all(&db, &unattached).unwrap().is_none());
+    }
+    assert!(blobs_exist(&file));
+
+    let file = dir.path().join("blob_segment_define.stry");
+    Structsy::open(&file).unwrap().define::<Picture>().unwrap();
+    assert!(blobs_exist(&file));
+}
+
+#[test]
+fn test_blob_removed_on_migrate() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("blob_migrate.stry");
+    let blob = {
+        let db = Structsy::open(file.clone()).unwrap();
+        db.define::<Picture>().unwrap();
+        let mut tx = db.begin().unwrap();
+        let blob = write_blob(&mut tx, &content(1000)).unwrap();
+        tx.insert(&Picture {
+            name: "pic".to_string(),
+            content: blob.clone(),
+        })
+        .unwrap();
+        tx.commit().unwrap();
+        blob
+    };
+    let prep = Structsy::prepare_open(file).unwrap();
+    prep.migrate::<Picture, PictureV1>().unwrap();
+    let db = prep.open().unwrap();
+    db.define::<PictureV1>().unwrap();
+    assert_eq!(db.scan::<PictureV1>().unwrap().count(), 1);
+    assert!(read_all(&db, &blob).unwrap().is_none());
+}
+
+#[test]
+fn test_blob_removed_on_raw_migrate() {
+    structsy_inst("blob_raw_migrate", |db| {
+        db.define::<Picture>()?;
+        let mut tx = db.begin()?;
+        let blob = write_blob(&mut tx, &content(1000))?;
+        tx.insert(&Picture {
+            name: "pic".to_string(),
+            content: blob.clone(),
+        })?;
+        tx.commit()?;
+        let desc = StructDescriptionBuilder::new("Picture")
+            .add_field(
+                0,
+                "name".to_owned(),
+                ValueTypeBuilder::simple(SimpleValueTypeBuilder::from_name("String").build()).build(),
+                None,
+            )
+            .build();
+        db.raw_migrate("Picture", desc.clone(), |rec| {
+            let name = match rec {
+                Record::Struct(s) => s.field("name").map(|f| f.value().clone()),
+                _ => None,
+            };
+            let mut rec = StructBuilder::new(desc.clone())?;
+            if let Some(Value::Value(SimpleValue::String(name))) = name {
+                rec = rec.add_field("name", name)?;
+            }
+            rec.finish()
+        })?;
+        assert_eq!(db.raw_scan("Picture")?.count(), 1);
+        assert!(read_all(db, &blob)?.is_none());
+        Ok(())
+    });
+}
+
+#[test]
+fn test_blob_description() {
+    structsy_inst("blob_description", |db| {
+        db.define::<Picture>()?;
+        let od = db.list_defined()?.find(|d| d.get_name() == "Picture").unwrap();
+        let desc = StructDescriptionBuilder::new("Picture")
+            .add_field(
+                0,
+                "name".to_owned(),
+                ValueTypeBuilder::simple(SimpleValueTypeBuilder::from_name("String").build()).build(),
+                None,
+            )
+            .add_field(
+                1,
+                "content".to_owned(),
+                ValueTypeBuilder::simple(SimpleValueTypeBuilder::from_name("Blob").build()).build(),
+                None,
+            )
+            .build();
+        assert_eq!(od, desc);
+        assert!(od.has_blobs());
+        Ok(())
+    });
+}
@@ -0,0 +1,183 @@
+use structsy::{Filter, SRes, Structsy, StructsyTx};
+use structsy_derive::{queries, Persistent};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Item {
+    #[index(mode = "exclusive")]
+    code: String,
+    #[index(mode = "cluster")]
+    stock: u32,
+    shelf: String,
+}
+
+#[queries(Item)]
+trait ItemQuery {
+    fn by_code(self, code: String) -> Self;
+    fn by_stock(self, stock: u32) -> Self;
+    fn by_stock_range<R: std::ops::RangeBounds<u32>>(self, stock: R) -> Self;
+    fn by_shelf(self, shelf: String) -> Self;
+}
+
+fn item(n: u32) -> Item {
+    Item {
+        code: format!("code {}", n),
+        stock: n % 10,
+        shelf: format!("shelf {}", n % 3),
+    }
+}
+
+fn fill(db: &Structsy, count: u32) -> SRes<()> {
+    db.define::<Item>()?;
+    let mut tx = db.begin()?;
+    for n in 0..count {
+        tx.insert(&item(n))?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[test]
+fn delete_where_indexed() {
+    structsy_inst("delete_where_indexed", |db| {
+        fill(db, 30)?;
+        let mut tx = db.begin()?;
+        let deleted = tx.delete_where(Filter::<Item>::new().by_stock(3))?;
+        assert_eq!(deleted, 3);
+        assert_eq!(tx.query::<Item>().by_stock(3).fetch().count(), 0);
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().by_stock(3).fetch().count(), 0);
+        assert_eq!(db.query::<Item>().fetch().count(), 27);
+        // The exclusive index does not hold the deleted keys anymore
+        let mut tx = db.begin()?;
+        tx.insert(&item(3))?;
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().by_code("code 3".to_string()).fetch().count(), 1);
+
+        let mut tx = db.begin()?;
+        assert_eq!(tx.delete_where(Filter::<Item>::new().by_stock(42))?, 0);
+        assert_eq!(tx.delete_where(Filter::<Item>::new())?, 28);
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().fetch().count(), 0);
+        Ok(())
+    });
+}
+
+#[test]
+fn delete_where_residual_filter() {
+    structsy_inst("delete_where_residual_filter", |db| {
+        fill(db, 30)?;
+        let mut tx = db.begin()?;
+        // Also the instances inserted in the transaction are found
+        tx.insert(&item(30))?;
+        let filter = Filter::<Item>::new()
+            .by_stock_range(..5)
+            .by_shelf("shelf 0".to_string());
+        let deleted = tx.delete_where(filter)?;
+        let expected = (0..=30).filter(|n| n % 10 < 5 && n % 3 == 0).count();
+        assert_eq!(deleted, expected);
+        tx.commit()?;
+        let left = db.query::<Item>().fetch().map(|(_, i)| i).collect::<Vec<_>>();
+        assert_eq!(left.len(), 31 - expected);
+        assert!(left.iter().all(|i| !(i.stock < 5 && i.shelf == "shelf 0")));
+        Ok(())
+    });
+}
+
+#[test]
+fn delete_where_batches() {
+    structsy_inst("delete_where_batches", |db| {
+        fill(db, 2500)?;
+        let mut tx = db.begin()?;
+        let deleted = tx.delete_where(Filter::<Item>::new().by_stock_range(1..))?;
+        assert_eq!(deleted, 2250);
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().fetch().count(), 250);
+        assert_eq!(db.query::<Item>().by_stock_range(1..).fetch().count(), 0);
+        Ok(())
+    });
+}
+
+#[test]
+fn update_where() {
+    structsy_inst("update_where", |db| {
+        fill(db, 30)?;
+        let mut tx = db.begin()?;
+        // The updated instances still match the filter, but are updated once
+        let updated = tx.update_where(Filter::<Item>::new().by_stock_range(..5), |i| i.stock += 1)?;
+        assert_eq!(updated, 15);
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().by_stock(0).fetch().count(), 0);
+        assert_eq!(db.query::<Item>().by_stock(1).fetch().count(), 3);
+        assert_eq!(db.query::<Item>().by_stock(5).fetch().count(), 6);
+        let (_, found) = db
+            .query::<Item>()
+            .by_code("code 14".to_string())
+            .fetch()
+            .next()
+            .unwrap();
+        assert_eq!(found.stock, 5);
+
+        let mut tx = db.begin()?;
+        let filter = Filter::<Item>::new().by_shelf("shelf 1".to_string());
+        let updated = tx.update_where(filter, |i| i.code = format!("moved {}", i.code))?;
+        assert_eq!(updated, 10);
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().by_code("code 1".to_string()).fetch().count(), 0);
+        let (_, found) = db
+            .query::<Item>()
+            .by_code("moved code 1".to_string())
+            .fetch()
+            .next()
+            .unwrap();
+        assert_eq!(found.shelf, "shelf 1");
+        Ok(())
+    });
+}
+
+#[test]
+fn bulk_where_residual_batches() {
+    structsy_inst("bulk_where_residual_batches", |db| {
+        fill(db, 3500)?;
+        let shelf = || Filter::<Item>::new().by_shelf("shelf 0".to_string());
+        let expected = (0..3500).filter(|n| n % 3 == 0).count();
+        let mut tx = db.begin()?;
+        // More than a batch of instances that still match the filter after the update
+        let updated = tx.update_where(shelf(), |i| i.code = format!("moved {}", i.code))?;
+        assert_eq!(updated, expected);
+        let deleted = tx.delete_where(shelf())?;
+        assert_eq!(deleted, expected);
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().fetch().count(), 3500 - expected);
+        assert!(db.query::<Item>().fetch().all(|(_, i)| !i.code.starts_with("moved")));
+        Ok(())
+    });
+}
+
+#[test]
+fn update_where_touched_keys() {
+    structsy_inst("update_where_touched_keys", |db| {
+        fill(db, 30)?;
+        let mut tx = db.begin()?;
+        // The keys changed in the transaction before the update are found while the query runs
+        for n in 30..35 {
+            tx.insert(&item(n))?;
+        }
+        let updated = tx.update_where(Filter::<Item>::new().by_stock_range(..5), |i| i.stock += 1)?;
+        assert_eq!(updated, 20);
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().by_stock(0).fetch().count(), 0);
+        assert_eq!(db.query::<Item>().by_stock(1).fetch().count(), 4);
+        assert_eq!(db.query::<Item>().by_stock(5).fetch().count(), 7);
+        assert_eq!(db.query::<Item>().by_stock_range(6..).fetch().count(), 12);
+        Ok(())
+    });
+}
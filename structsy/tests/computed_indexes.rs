@@ -0,0 +1,322 @@
+use structsy::{
+    internal::{ComputedIndexDescription, Description},
+    record::{Record, SimpleValue, StructBuilder, Value},
+    IndexQuery, Persistent, RawAccess, SRes, Structsy, StructsyTx, ValueMode,
+};
+use structsy_derive::{queries, Persistent};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+#[persistent(index(
+    name = "email_lower",
+    expr = "self.email.to_lowercase()",
+    ty = "String",
+    mode = "exclusive"
+))]
+#[persistent(index(name = "total", expr = "self.price * self.quantity", ty = "u32"))]
+struct Item {
+    email: String,
+    price: u32,
+    quantity: u32,
+}
+
+#[queries(Item)]
+trait ItemQuery {
+    fn by_price(self, price: u32) -> Self;
+}
+
+fn item(email: &str, price: u32, quantity: u32) -> Item {
+    Item {
+        email: email.to_string(),
+        price,
+        quantity,
+    }
+}
+
+fn emails<I: Iterator<Item = (structsy::Ref<Item>, Item)>>(iter: I) -> Vec<String> {
+    iter.map(|(_, i)| i.email).collect()
+}
+
+#[test]
+fn computed_index_query() {
+    structsy_inst("computed_index_query", |db| {
+        db.define::<Item>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&item("One@Mail.com", 10, 2))?;
+        tx.insert(&item("two@mail.com", 5, 1))?;
+        tx.insert(&item("THREE@mail.com", 10, 1))?;
+        tx.commit()?;
+
+        let found = db
+            .query::<Item>()
+            .by_index::<String>("email_lower", "one@mail.com".to_string())
+            .fetch();
+        assert_eq!(emails(found), vec!["One@Mail.com".to_string()]);
+        let found = db.query::<Item>().by_index("total", 10u32).fetch();
+        assert_eq!(emails(found), vec!["THREE@mail.com".to_string()]);
+        let found = db.query::<Item>().by_index_range("total", 6u32..).fetch();
+        assert_eq!(
+            emails(found),
+            vec!["THREE@mail.com".to_string(), "One@Mail.com".to_string()]
+        );
+        let found = db
+            .query::<Item>()
+            .by_index_range("total", 6u32..)
+            .by_price(10)
+            .by_index::<String>("email_lower", "one@mail.com".to_string())
+            .fetch();
+        assert_eq!(emails(found), vec!["One@Mail.com".to_string()]);
+        assert_eq!(db.query::<Item>().by_index("missing", 10u32).fetch().count(), 0);
+        Ok(())
+    });
+}
+
+#[test]
+fn computed_index_maintenance() {
+    structsy_inst("computed_index_maintenance", |db| {
+        db.define::<Item>()?;
+        let mut tx = db.begin()?;
+        let id = tx.insert(&item("One@Mail.com", 10, 2))?;
+        tx.commit()?;
+
+        let mut tx = db.begin()?;
+        tx.update(&id, &item("Other@Mail.com", 10, 3))?;
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().by_index("total", 20u32).fetch().count(), 0);
+        assert_eq!(db.query::<Item>().by_index("total", 30u32).fetch().count(), 1);
+
+        let mut tx = db.begin()?;
+        tx.insert(&item("one@mail.com", 1, 1))?;
+        tx.commit()?;
+        let count = db
+            .query::<Item>()
+            .by_index::<String>("email_lower", "one@mail.com".to_string())
+            .fetch()
+            .count();
+        assert_eq!(count, 1);
+
+        let mut tx = db.begin()?;
+        let count = tx
+            .query::<Item>()
+            .by_index::<String>("email_lower", "other@mail.com".to_string())
+            .fetch()
+            .count();
+        assert_eq!(count, 1);
+        tx.delete(&id)?;
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().by_index("total", 30u32).fetch().count(), 0);
+
+        // The exclusive computed index rejects values that differ only in case
+        let mut tx = db.begin()?;
+        tx.insert(&item("ONE@MAIL.COM", 2, 2))?;
+        assert!(tx.commit().is_err());
+        Ok(())
+    });
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+#[persistent(name = "Item")]
+struct OldItem {
+    email: String,
+    price: u32,
+    quantity: u32,
+}
+
+fn raw_value(record: &Record, name: &str) -> Option<Value> {
+    match record {
+        Record::Struct(s) => s.computed_index(name).map(|c| c.value().clone()),
+        _ => None,
+    }
+}
+
+#[test]
+fn computed_index_description() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("computed_index_description.stry");
+    {
+        let db = Structsy::open(&file).unwrap();
+        db.define::<Item>().unwrap();
+    }
+    let db = Structsy::open(&file).unwrap();
+    let desc = db.list_defined().unwrap().find(|d| d.get_name() == "Item").unwrap();
+    assert_eq!(desc, Item::get_description());
+    if let Description::Struct(s) = &desc {
+        let computed = s.computed_indexes().collect::<Vec<_>>();
+        assert_eq!(
+            computed,
+            vec![
+                &ComputedIndexDescription::new::<String>("email_lower", Some(ValueMode::Exclusive)),
+                &ComputedIndexDescription::new::<u32>("total", Some(ValueMode::Cluster)),
+            ]
+        );
+    } else {
+        panic!("expected a struct description");
+    }
+    assert!(db.schema_diff::<Item>().unwrap().is_empty());
+    assert!(!db.define::<Item>().unwrap());
+}
+
+#[test]
+fn computed_index_raw_records() {
+    structsy_inst("computed_index_raw_records", |db| {
+        db.define::<Item>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&item("One@Mail.com", 10, 2))?;
+        tx.commit()?;
+
+        let (id, mut record) = db.raw_scan("Item")?.next().unwrap();
+        let lower = Value::Value(SimpleValue::String("one@mail.com".to_string()));
+        assert_eq!(raw_value(&record, "email_lower"), Some(lower));
+        assert_eq!(raw_value(&record, "total"), Some(Value::Value(SimpleValue::U32(20))));
+
+        if let Record::Struct(s) = &mut record {
+            s.set_field("quantity", 3u32)?;
+            s.set_computed_index("total", 30u32)?;
+        }
+        let mut raw = db.raw_begin()?;
+        raw.raw_update(&id, &record)?;
+        raw.prepare()?.commit()?;
+        assert_eq!(db.query::<Item>().by_index("total", 20u32).fetch().count(), 0);
+        assert_eq!(db.query::<Item>().by_index("total", 30u32).fetch().count(), 1);
+
+        let desc = db.list_defined()?.find(|d| d.get_name() == "Item").unwrap();
+        let missing = StructBuilder::new(desc.clone())?
+            .add_field("email", "two@mail.com".to_string())?
+            .add_field("price", 5u32)?
+            .add_field("quantity", 1u32)?
+            .finish();
+        assert!(missing.is_err());
+        let record = StructBuilder::new(desc)?
+            .add_field("email", "Two@Mail.com".to_string())?
+            .add_field("price", 5u32)?
+            .add_field("quantity", 1u32)?
+            .add_computed_index("email_lower", "two@mail.com".to_string())?
+            .add_computed_index("total", 5u32)?
+            .finish()?;
+        let mut raw = db.raw_begin()?;
+        let new_id = raw.raw_insert(&record)?;
+        raw.prepare()?.commit()?;
+        let found = db
+            .query::<Item>()
+            .by_index::<String>("email_lower", "two@mail.com".to_string())
+            .fetch();
+        assert_eq!(emails(found), vec!["Two@Mail.com".to_string()]);
+
+        let mut raw = db.raw_begin()?;
+        raw.raw_delete(&new_id)?;
+        raw.prepare()?.commit()?;
+        // The exclusive index does not hold the deleted value anymore
+        let mut tx = db.begin()?;
+        tx.insert(&item("TWO@mail.com", 1, 1))?;
+        tx.commit()?;
+        Ok(())
+    });
+}
+
+#[test]
+fn computed_index_drop_create() {
+    structsy_inst("computed_index_drop_create", |db| {
+        db.define::<Item>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&item("One@Mail.com", 10, 2))?;
+        tx.insert(&item("two@mail.com", 5, 1))?;
+        tx.commit()?;
+
+        db.raw_drop_index("Item", "email_lower")?;
+        let desc = db.list_defined()?.find(|d| d.get_name() == "Item").unwrap();
+        if let Description::Struct(s) = &desc {
+            let computed = s.computed_indexes().find(|c| c.name() == "email_lower").unwrap();
+            assert_eq!(computed.indexed(), &None);
+        }
+        assert!(!db.schema_diff::<Item>()?.is_empty());
+
+        db.create_index::<Item>("email_lower")?;
+        assert!(db.schema_diff::<Item>()?.is_empty());
+        db.define::<Item>()?;
+        let found = db
+            .query::<Item>()
+            .by_index::<String>("email_lower", "one@mail.com".to_string())
+            .fetch();
+        assert_eq!(emails(found), vec!["One@Mail.com".to_string()]);
+        // The values of the existing records are back in the exclusive index
+        let mut tx = db.begin()?;
+        tx.insert(&item("ONE@MAIL.COM", 2, 2))?;
+        assert!(tx.commit().is_err());
+        Ok(())
+    });
+}
+
+#[test]
+fn computed_index_migrate() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("computed_index_migrate.stry");
+    {
+        let db = Structsy::open(&file).unwrap();
+        db.define::<OldItem>().unwrap();
+        let mut tx = db.begin().unwrap();
+        tx.insert(&OldItem {
+            email: "One@Mail.com".to_string(),
+            price: 10,
+            quantity: 2,
+        })
+        .unwrap();
+        tx.commit().unwrap();
+        assert!(db.define::<Item>().is_err());
+    }
+    let prep = Structsy::prepare_open(&file).unwrap();
+    prep.migrate::<OldItem, Item>().unwrap();
+    let db = prep.open().unwrap();
+    assert!(!db.define::<Item>().unwrap());
+    let found = db.query::<Item>().by_index("total", 20u32).fetch();
+    assert_eq!(emails(found), vec!["One@Mail.com".to_string()]);
+
+    // The raw migration keeps the indexes and the values of the records
+    let desc = db.list_defined().unwrap().find(|d| d.get_name() == "Item").unwrap();
+    db.raw_migrate("Item", desc, Ok).unwrap();
+    let found = db.query::<Item>().by_index("total", 20u32).fetch();
+    assert_eq!(emails(found), vec!["One@Mail.com".to_string()]);
+    let mut tx = db.begin().unwrap();
+    tx.insert(&item("ONE@MAIL.COM", 2, 2)).unwrap();
+    assert!(tx.commit().is_err());
+}
+
+impl From<OldItem> for Item {
+    fn from(old: OldItem) -> Item {
+        Item {
+            email: old.email,
+            price: old.price,
+            quantity: old.quantity,
+        }
+    }
+}
+
+#[test]
+fn computed_index_rename_undefine() {
+    structsy_inst("computed_index_rename_undefine", |db| {
+        db.define::<Item>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&item("One@Mail.com", 10, 2))?;
+        tx.commit()?;
+        db.rename_type("Item", "Renamed")?;
+        db.rename_type("Renamed", "Item")?;
+        db.define::<Item>()?;
+        let found = db.query::<Item>().by_index("total", 20u32).fetch();
+        assert_eq!(emails(found), vec!["One@Mail.com".to_string()]);
+
+        db.undefine::<Item>()?;
+        db.define::<Item>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&item("ONE@MAIL.COM", 2, 2))?;
+        tx.commit()?;
+        assert_eq!(db.query::<Item>().by_index("total", 20u32).fetch().count(), 0);
+        Ok(())
+    });
+}
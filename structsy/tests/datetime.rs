@@ -0,0 +1,193 @@
+#![cfg(any(feature = "chrono", feature = "time"))]
+use structsy::{SRes, Structsy};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_types {
+    use super::structsy_inst;
+    use chrono::{DateTime, Duration, NaiveDate, Utc};
+    use std::ops::RangeBounds;
+    use structsy::{
+        internal::{SimpleValueTypeBuilder, StructDescriptionBuilder, ValueTypeBuilder},
+        record::{Record, SimpleValue, Value},
+        RawAccess, StructsyTx,
+    };
+    use structsy_derive::{queries, Persistent};
+
+    #[derive(Persistent, Debug, PartialEq)]
+    struct Event {
+        #[index(mode = "cluster")]
+        at: DateTime<Utc>,
+        day: NaiveDate,
+        took: Duration,
+        ends: Option<DateTime<Utc>>,
+    }
+
+    impl Event {
+        fn new(secs: i64, took: i64) -> Event {
+            let at = DateTime::from_timestamp(secs, 500).unwrap();
+            Event {
+                at,
+                day: at.date_naive(),
+                took: Duration::milliseconds(took),
+                ends: Some(at + Duration::milliseconds(took)),
+            }
+        }
+    }
+
+    #[queries(Event)]
+    trait EventQuery {
+        fn by_at(self, at: DateTime<Utc>) -> Self;
+        fn by_at_range<R: RangeBounds<DateTime<Utc>>>(self, at: R) -> Self;
+        fn by_day_range<R: RangeBounds<NaiveDate>>(self, day: R) -> Self;
+        fn by_took_range<R: RangeBounds<Duration>>(self, took: R) -> Self;
+        fn by_ends_range<R: RangeBounds<DateTime<Utc>>>(self, ends: R) -> Self;
+    }
+
+    #[test]
+    fn chrono_values_query() {
+        structsy_inst("chrono_values_query", |db| {
+            db.define::<Event>()?;
+            let mut tx = db.begin()?;
+            tx.insert(&Event::new(-86_400, -10))?;
+            tx.insert(&Event::new(0, 100))?;
+            tx.insert(&Event::new(1_000_000_000, 2000))?;
+            tx.insert(&Event::new(2_000_000_000, 30_000))?;
+            tx.commit()?;
+
+            let first = Event::new(-86_400, -10);
+            let found = db.query::<Event>().by_at(first.at).fetch().next().unwrap().1;
+            assert_eq!(found, first);
+
+            let from = DateTime::from_timestamp(0, 0).unwrap();
+            let to = DateTime::from_timestamp(1_500_000_000, 0).unwrap();
+            assert_eq!(db.query::<Event>().by_at_range(from..to).fetch().count(), 2);
+            assert_eq!(db.query::<Event>().by_at_range(..from).fetch().count(), 1);
+            assert_eq!(db.query::<Event>().by_ends_range(from..to).fetch().count(), 2);
+
+            let day = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+            assert_eq!(db.query::<Event>().by_day_range(day..).fetch().count(), 3);
+            assert_eq!(db.query::<Event>().by_day_range(..day).fetch().count(), 1);
+
+            let second = Duration::seconds(1);
+            assert_eq!(db.query::<Event>().by_took_range(..Duration::zero()).fetch().count(), 1);
+            assert_eq!(db.query::<Event>().by_took_range(second..).fetch().count(), 2);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn chrono_raw_values() {
+        structsy_inst("chrono_raw_values", |db| {
+            db.define::<Event>()?;
+            let mut tx = db.begin()?;
+            tx.insert(&Event::new(-1, 1500))?;
+            tx.commit()?;
+
+            let od = db.list_defined()?.find(|d| d.get_name() == "Event").unwrap();
+            let simple = |name| ValueTypeBuilder::simple(SimpleValueTypeBuilder::from_name(name).build()).build();
+            let date_time = SimpleValueTypeBuilder::from_name("DateTime").build();
+            let desc = StructDescriptionBuilder::new("Event")
+                .add_field(
+                    0,
+                    "at".to_owned(),
+                    simple("DateTime"),
+                    Some(structsy::ValueMode::Cluster),
+                )
+                .add_field(1, "day".to_owned(), simple("Date"), None)
+                .add_field(2, "took".to_owned(), simple("Duration"), None)
+                .add_field(3, "ends".to_owned(), ValueTypeBuilder::option(date_time).build(), None)
+                .build();
+            assert_eq!(od, desc);
+
+            let (_, record) = db.raw_scan("Event")?.next().unwrap();
+            match &record {
+                Record::Struct(st) => {
+                    match st.field("at").unwrap().value() {
+                        Value::Value(SimpleValue::DateTime(v)) => assert_eq!(*v, -999_999_500),
+                        _ => panic!("wrong value"),
+                    }
+                    match st.field("day").unwrap().value() {
+                        Value::Value(SimpleValue::Date(v)) => assert_eq!(*v, -1),
+                        _ => panic!("wrong value"),
+                    }
+                    match st.field("took").unwrap().value() {
+                        Value::Value(SimpleValue::Duration(v)) => assert_eq!(*v, 1_500_000_000),
+                        _ => panic!("wrong value"),
+                    }
+                }
+                _ => panic!("wrong record"),
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(feature = "time")]
+mod time_types {
+    use super::structsy_inst;
+    use std::ops::RangeBounds;
+    use structsy::StructsyTx;
+    use structsy_derive::{queries, Persistent};
+    use time::{Date, Duration, Month, OffsetDateTime};
+
+    #[derive(Persistent, Debug, PartialEq)]
+    struct Booking {
+        at: OffsetDateTime,
+        #[index(mode = "cluster")]
+        day: Date,
+        #[index(mode = "cluster")]
+        stay: Duration,
+    }
+
+    impl Booking {
+        fn new(secs: i64, hours: i64) -> Booking {
+            let at = OffsetDateTime::from_unix_timestamp(secs).unwrap();
+            Booking {
+                at,
+                day: at.date(),
+                stay: Duration::hours(hours),
+            }
+        }
+    }
+
+    #[queries(Booking)]
+    trait BookingQuery {
+        fn by_at_range<R: RangeBounds<OffsetDateTime>>(self, at: R) -> Self;
+        fn by_day(self, day: Date) -> Self;
+        fn by_day_range<R: RangeBounds<Date>>(self, day: R) -> Self;
+        fn by_stay_range<R: RangeBounds<Duration>>(self, stay: R) -> Self;
+    }
+
+    #[test]
+    fn time_values_query() {
+        structsy_inst("time_values_query", |db| {
+            db.define::<Booking>()?;
+            let mut tx = db.begin()?;
+            tx.insert(&Booking::new(-100_000_000, 1))?;
+            tx.insert(&Booking::new(0, 24))?;
+            tx.insert(&Booking::new(1_600_000_000, 48))?;
+            tx.commit()?;
+
+            let epoch = OffsetDateTime::UNIX_EPOCH;
+            assert_eq!(db.query::<Booking>().by_at_range(epoch..).fetch().count(), 2);
+            assert_eq!(db.query::<Booking>().by_at_range(..epoch).fetch().count(), 1);
+
+            let day = Date::from_calendar_date(2020, Month::September, 13).unwrap();
+            let found = db.query::<Booking>().by_day(day).fetch().next().unwrap().1;
+            assert_eq!(found, Booking::new(1_600_000_000, 48));
+            assert_eq!(db.query::<Booking>().by_day_range(epoch.date()..day).fetch().count(), 1);
+
+            assert_eq!(db.query::<Booking>().by_stay_range(Duration::DAY..).fetch().count(), 2);
+            Ok(())
+        });
+    }
+}
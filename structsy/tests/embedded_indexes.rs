@@ -0,0 +1,155 @@
+use structsy::{record::Record, Filter, RawAccess, SRes, Structsy, StructsyTx};
+use structsy_derive::{embedded_queries, queries, Persistent, PersistentEmbedded};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(PersistentEmbedded, Debug, PartialEq, Clone)]
+struct Address {
+    #[index(mode = "cluster")]
+    city: String,
+    #[index(mode = "exclusive")]
+    code: u32,
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Person {
+    name: String,
+    address: Address,
+    previous: Vec<Address>,
+}
+
+#[queries(Person)]
+trait PersonQuery {
+    fn address(self, address: Filter<Address>) -> Self;
+}
+
+#[embedded_queries(Address)]
+trait AddressQuery {
+    fn by_city(self, city: String) -> Self;
+}
+
+fn person(name: &str, city: &str, code: u32, previous: Vec<Address>) -> Person {
+    Person {
+        name: name.to_string(),
+        address: Address {
+            city: city.to_string(),
+            code,
+        },
+        previous,
+    }
+}
+
+fn names(db: &Structsy, filter: Filter<Address>) -> Vec<String> {
+    db.query::<Person>()
+        .address(filter)
+        .fetch()
+        .map(|(_, p)| p.name)
+        .collect()
+}
+
+#[test]
+fn embedded_field_index() {
+    structsy_inst("embedded_field_index", |db| {
+        db.define::<Person>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&person("one", "Rome", 1, Vec::new()))?;
+        let two = tx.insert(&person("two", "Milan", 2, Vec::new()))?;
+        tx.insert(&person("three", "Rome", 3, Vec::new()))?;
+        tx.commit()?;
+
+        let rome = Filter::<Address>::new().by_city("Rome".to_string());
+        assert_eq!(names(db, rome), vec!["one".to_string(), "three".to_string()]);
+
+        let mut tx = db.begin()?;
+        tx.update(&two, &person("two", "Rome", 2, Vec::new()))?;
+        tx.commit()?;
+        assert_eq!(names(db, Filter::<Address>::new().by_city("Rome".to_string())).len(), 3);
+        assert_eq!(
+            names(db, Filter::<Address>::new().by_city("Milan".to_string())).len(),
+            0
+        );
+
+        let mut tx = db.begin()?;
+        tx.delete(&two)?;
+        tx.commit()?;
+        let mut tx = db.begin()?;
+        tx.insert(&person("four", "Turin", 2, Vec::new()))?;
+        tx.commit()?;
+
+        // The exclusive index on the embedded field rejects a duplicate code
+        let mut tx = db.begin()?;
+        tx.insert(&person("five", "Turin", 1, Vec::new()))?;
+        assert!(tx.commit().is_err());
+        Ok(())
+    });
+}
+
+#[test]
+fn embedded_vec_field_index() {
+    structsy_inst("embedded_vec_field_index", |db| {
+        db.define::<Person>()?;
+        let old = |city: &str, code: u32| Address {
+            city: city.to_string(),
+            code,
+        };
+        let mut tx = db.begin()?;
+        let one = tx.insert(&person("one", "Rome", 1, vec![old("Paris", 10), old("Berlin", 11)]))?;
+        tx.commit()?;
+
+        // The update removes the index entries of the dropped element
+        let mut tx = db.begin()?;
+        tx.update(&one, &person("one", "Rome", 1, vec![old("Paris", 10)]))?;
+        tx.commit()?;
+        let mut tx = db.begin()?;
+        tx.insert(&person("two", "Milan", 2, vec![old("Paris", 11)]))?;
+        tx.commit()?;
+
+        // Each element of the vector is indexed under the same path
+        let mut tx = db.begin()?;
+        tx.insert(&person("three", "Turin", 3, vec![old("Paris", 10)]))?;
+        assert!(tx.commit().is_err());
+        Ok(())
+    });
+}
+
+#[test]
+fn embedded_field_index_raw() {
+    structsy_inst("embedded_field_index_raw", |db| {
+        db.define::<Person>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&person("one", "Rome", 1, Vec::new()))?;
+        tx.commit()?;
+        let desc = db.list_defined()?.find(|d| d.get_name() == "Person").unwrap();
+
+        let dir = tempdir().expect("can make a tempdir");
+        let other = Structsy::open(dir.path().join("other.stry"))?;
+        other.raw_define(desc)?;
+        let (_, record) = db.raw_scan("Person")?.next().unwrap();
+        let mut raw_tx = other.raw_begin()?;
+        raw_tx.raw_insert(&record)?;
+        raw_tx.prepare()?.commit()?;
+
+        // The raw insert fills the embedded index declared by the raw description
+        let mut duplicate = record.clone();
+        if let Record::Struct(s) = &mut duplicate {
+            s.set_field("name", "two".to_string())?;
+        }
+        let mut raw_tx = other.raw_begin()?;
+        raw_tx.raw_insert(&duplicate)?;
+        assert!(raw_tx.prepare().is_err());
+
+        other.define::<Person>()?;
+        assert_eq!(
+            names(&other, Filter::<Address>::new().by_city("Rome".to_string())).len(),
+            1
+        );
+        Ok(())
+    });
+}
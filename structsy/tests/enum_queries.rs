@@ -0,0 +1,238 @@
+use structsy::{internal::EmbeddedDescription, record::Record, Filter, RawAccess, SRes, Structsy, StructsyTx, Variant};
+use structsy_derive::{embedded_queries, queries, Persistent, PersistentEmbedded};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(PersistentEmbedded, Debug, PartialEq, Clone)]
+struct ShipInfo {
+    carrier: String,
+    days: u32,
+}
+
+#[derive(PersistentEmbedded, Debug, PartialEq, Clone)]
+enum Status {
+    Pending,
+    Shipped(ShipInfo),
+    Cancelled { reason: String },
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Order {
+    #[index(mode = "cluster")]
+    status: Status,
+    code: u32,
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Ticket {
+    status: Status,
+    title: String,
+}
+
+#[queries(Order)]
+trait OrderQuery {
+    fn by_status(self, status: Variant<Status>) -> Self;
+    fn status(self, status: Filter<Status>) -> Self;
+    fn by_code(self, code: u32) -> Self;
+    fn status_is(self, status: Status) -> Self;
+}
+
+#[queries(Ticket)]
+trait TicketQuery {
+    fn by_status(self, status: Variant<Status>) -> Self;
+}
+
+#[embedded_queries(Status)]
+trait StatusQuery {
+    fn shipped(self, shipped: Filter<ShipInfo>) -> Self;
+}
+
+#[embedded_queries(ShipInfo)]
+trait ShipInfoQuery {
+    fn by_carrier(self, carrier: String) -> Self;
+}
+
+fn shipped(carrier: &str, days: u32) -> Status {
+    Status::Shipped(ShipInfo {
+        carrier: carrier.to_string(),
+        days,
+    })
+}
+
+fn fill(db: &Structsy) -> SRes<()> {
+    db.define::<Order>()?;
+    let mut tx = db.begin()?;
+    tx.insert(&Order {
+        status: Status::Pending,
+        code: 1,
+    })?;
+    tx.insert(&Order {
+        status: shipped("post", 3),
+        code: 2,
+    })?;
+    tx.insert(&Order {
+        status: shipped("courier", 1),
+        code: 3,
+    })?;
+    tx.insert(&Order {
+        status: Status::Cancelled {
+            reason: "late".to_string(),
+        },
+        code: 4,
+    })?;
+    tx.commit()?;
+    Ok(())
+}
+
+#[test]
+fn variant_position() {
+    assert_eq!(Status::Pending.variant_position(), Some(0));
+    assert_eq!(shipped("post", 1).variant_position(), Some(1));
+    let variant = Status::variant_cancelled();
+    assert_eq!(variant.position(), 2);
+    assert_eq!(variant.name(), "Cancelled");
+}
+
+#[test]
+fn query_by_variant() {
+    structsy_inst("query_by_variant", |db| {
+        fill(db)?;
+        let codes = db
+            .query::<Order>()
+            .by_status(Status::variant_shipped())
+            .fetch()
+            .map(|(_, o)| o.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec![2, 3]);
+        let codes = db
+            .query::<Order>()
+            .by_status(Status::variant_pending())
+            .fetch()
+            .map(|(_, o)| o.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec![1]);
+        let count = db
+            .query::<Order>()
+            .by_status(Status::variant_cancelled())
+            .by_code(1)
+            .fetch()
+            .count();
+        assert_eq!(count, 0);
+
+        db.define::<Ticket>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&Ticket {
+            status: Status::Pending,
+            title: "one".to_string(),
+        })?;
+        tx.insert(&Ticket {
+            status: shipped("post", 2),
+            title: "two".to_string(),
+        })?;
+        tx.commit()?;
+        let titles = db
+            .query::<Ticket>()
+            .by_status(Status::variant_shipped())
+            .fetch()
+            .map(|(_, t)| t.title)
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["two".to_string()]);
+        Ok(())
+    });
+}
+
+#[test]
+fn query_variant_payload() {
+    structsy_inst("query_variant_payload", |db| {
+        fill(db)?;
+        let info = Filter::<ShipInfo>::new().by_carrier("courier".to_string());
+        let status = Filter::<Status>::new().shipped(info);
+        let codes = db
+            .query::<Order>()
+            .status(status)
+            .fetch()
+            .map(|(_, o)| o.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec![3]);
+        Ok(())
+    });
+}
+
+#[test]
+fn variant_index_follows_updates() {
+    structsy_inst("variant_index_follows_updates", |db| {
+        fill(db)?;
+        let (id, mut order) = db.query::<Order>().by_code(1).fetch().next().unwrap();
+        order.status = shipped("post", 5);
+        let mut tx = db.begin()?;
+        tx.update(&id, &order)?;
+        tx.commit()?;
+        assert_eq!(
+            db.query::<Order>().by_status(Status::variant_pending()).fetch().count(),
+            0
+        );
+        assert_eq!(
+            db.query::<Order>().by_status(Status::variant_shipped()).fetch().count(),
+            3
+        );
+
+        let (_, mut record) = db.raw_scan("Order")?.next().unwrap();
+        match &mut record {
+            Record::Struct(s) => s.set_field("code", 10u32)?,
+            _ => panic!("wrong record"),
+        }
+        let mut raw_tx = db.raw_begin()?;
+        raw_tx.raw_insert(&record)?;
+        raw_tx.prepare()?.commit()?;
+        let total = [
+            Status::variant_pending(),
+            Status::variant_shipped(),
+            Status::variant_cancelled(),
+        ]
+        .iter()
+        .map(|v| db.query::<Order>().by_status(v.clone()).fetch().count())
+        .sum::<usize>();
+        assert_eq!(total, 5);
+        Ok(())
+    });
+}
+
+#[test]
+fn query_indexed_enum_equality() {
+    structsy_inst("query_indexed_enum_equality", |db| {
+        fill(db)?;
+        let codes = db
+            .query::<Order>()
+            .status_is(shipped("courier", 1))
+            .fetch()
+            .map(|(_, o)| o.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec![3]);
+        let codes = db
+            .query::<Order>()
+            .status_is(Status::Pending)
+            .fetch()
+            .map(|(_, o)| o.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec![1]);
+        let count = db.query::<Order>().status_is(shipped("courier", 2)).fetch().count();
+        assert_eq!(count, 0);
+
+        let (id, _) = db.query::<Order>().by_code(4).fetch().next().unwrap();
+        let mut tx = db.begin()?;
+        tx.delete(&id)?;
+        tx.commit()?;
+        let cancelled = Status::Cancelled {
+            reason: "late".to_string(),
+        };
+        assert_eq!(db.query::<Order>().status_is(cancelled).fetch().count(), 0);
+        Ok(())
+    });
+}
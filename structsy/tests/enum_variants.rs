@@ -0,0 +1,188 @@
+use std::io::Cursor;
+use structsy::{
+    internal::{Description, EnumDescriptionBuilder, FieldDescription, SimpleValueTypeBuilder, ValueTypeBuilder},
+    record::{EnumBuilder, Record, SimpleValue, Value},
+    Persistent, RawAccess, SRes, Structsy, StructsyTx,
+};
+use structsy_derive::{queries, Persistent, PersistentEmbedded};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+enum Event {
+    Click { x: i32, y: i32 },
+    Key(char, bool),
+    Text(String),
+    Close,
+}
+
+#[derive(PersistentEmbedded, Debug, PartialEq, Clone)]
+enum Shape {
+    Circle { radius: u32 },
+    Rect(u32, u32),
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Drawing {
+    shapes: Vec<Shape>,
+}
+
+#[queries(Event)]
+trait EventQuery {
+    fn by_click_x(self, click_x: i32) -> Self;
+    fn by_click(self, click_x: i32, click_y: i32) -> Self;
+    fn by_key_0(self, key_0: char) -> Self;
+}
+
+fn fill(db: &Structsy) -> SRes<()> {
+    db.define::<Event>()?;
+    let mut tx = db.begin()?;
+    tx.insert(&Event::Click { x: 10, y: 20 })?;
+    tx.insert(&Event::Click { x: 10, y: 30 })?;
+    tx.insert(&Event::Key('a', true))?;
+    tx.insert(&Event::Text("hello".to_string()))?;
+    tx.insert(&Event::Close)?;
+    tx.commit()?;
+    Ok(())
+}
+
+#[test]
+fn variant_fields_persistence() {
+    structsy_inst("variant_fields_persistence", |db| {
+        db.define::<Event>()?;
+        db.define::<Drawing>()?;
+        let mut tx = db.begin()?;
+        let click = tx.insert(&Event::Click { x: -1, y: 2 })?;
+        let key = tx.insert(&Event::Key('z', false))?;
+        let close = tx.insert(&Event::Close)?;
+        let drawing = Drawing {
+            shapes: vec![Shape::Circle { radius: 3 }, Shape::Rect(4, 5)],
+        };
+        let drawing_id = tx.insert(&drawing)?;
+        tx.commit()?;
+
+        assert_eq!(db.read(&click)?, Some(Event::Click { x: -1, y: 2 }));
+        assert_eq!(db.read(&key)?, Some(Event::Key('z', false)));
+        assert_eq!(db.read(&close)?, Some(Event::Close));
+        assert_eq!(db.read(&drawing_id)?, Some(drawing));
+        Ok(())
+    });
+}
+
+#[test]
+fn variant_fields_query() {
+    structsy_inst("variant_fields_query", |db| {
+        fill(db)?;
+        let found = db
+            .query::<Event>()
+            .by_click_x(10)
+            .fetch()
+            .map(|(_, e)| e)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![Event::Click { x: 10, y: 20 }, Event::Click { x: 10, y: 30 }]
+        );
+        let found = db
+            .query::<Event>()
+            .by_click(10, 30)
+            .fetch()
+            .map(|(_, e)| e)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![Event::Click { x: 10, y: 30 }]);
+        assert_eq!(db.query::<Event>().by_click_x(11).fetch().count(), 0);
+        let found = db
+            .query::<Event>()
+            .by_key_0('a')
+            .fetch()
+            .map(|(_, e)| e)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![Event::Key('a', true)]);
+        Ok(())
+    });
+}
+
+#[test]
+fn variant_fields_raw() {
+    structsy_inst("variant_fields_raw", |db| {
+        fill(db)?;
+        let desc = db.list_defined()?.find(|d| d.get_name() == "Event").unwrap();
+        let expected = EnumDescriptionBuilder::new("Event")
+            .add_fields_variant(
+                0,
+                "Click",
+                vec![
+                    FieldDescription::new::<i32>(0, "x", None),
+                    FieldDescription::new::<i32>(1, "y", None),
+                ],
+            )
+            .add_fields_variant(
+                1,
+                "Key",
+                vec![
+                    FieldDescription::new::<char>(0, "0", None),
+                    FieldDescription::new::<bool>(1, "1", None),
+                ],
+            )
+            .add_variant(
+                2,
+                "Text",
+                Some(ValueTypeBuilder::simple(SimpleValueTypeBuilder::from_name("String").build()).build()),
+            )
+            .add_variant(3, "Close", None)
+            .build();
+        assert_eq!(desc, expected);
+
+        let (_, record) = db.raw_scan("Event")?.next().unwrap();
+        match &record {
+            Record::Enum(en) => {
+                let variant = en.variant();
+                assert_eq!(variant.name(), "Click");
+                assert_eq!(variant.value(), &None);
+                assert_eq!(variant.field("y").unwrap().value(), &Value::Value(SimpleValue::I32(20)));
+            }
+            _ => panic!("wrong record"),
+        }
+
+        let mut builder = EnumBuilder::new(desc.clone())?;
+        assert!(builder.add_variant_field("x", 1i32).is_err());
+        assert!(builder.set_fields_variant("Close").is_err());
+        builder.set_fields_variant("Click")?;
+        assert!(builder.add_variant_field("x", 1u32).is_err());
+        builder.add_variant_field("x", 10i32)?;
+        let mut partial = EnumBuilder::new(desc.clone())?;
+        partial.set_fields_variant("Click")?;
+        partial.add_variant_field("y", 1i32)?;
+        assert!(partial.finish().is_err());
+        builder.add_variant_field("y", 40i32)?;
+        let mut raw = builder.finish()?;
+        if let Record::Enum(en) = &mut raw {
+            assert!(en.set_variant_field("y", "wrong".to_string()).is_err());
+            en.set_variant_field("y", 50i32)?;
+        }
+
+        let mut raw_tx = db.raw_begin()?;
+        raw_tx.raw_insert(&raw)?;
+        raw_tx.prepare()?.commit()?;
+        let found = db.query::<Event>().by_click(10, 50).fetch().count();
+        assert_eq!(found, 1);
+        assert_eq!(db.query::<Event>().by_click_x(10).fetch().count(), 3);
+        Ok(())
+    });
+}
+
+#[test]
+fn variant_fields_desc_read_write() {
+    let desc = Event::get_description();
+    let mut buff = Vec::new();
+    desc.write(&mut buff).unwrap();
+    let read_desc = Description::read(&mut Cursor::new(buff)).unwrap();
+    assert_eq!(desc, read_desc);
+}
@@ -0,0 +1,195 @@
+use structsy::{Order, Ref, SRes, Structsy, StructsyTx};
+use structsy_derive::{queries, Persistent, Projection};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Purchase {
+    #[index(mode = "cluster")]
+    customer: String,
+    #[index(mode = "cluster")]
+    total: u32,
+    note: String,
+}
+
+#[derive(Projection, Debug, PartialEq)]
+#[projection = "Purchase"]
+struct PurchaseId {
+    #[projection(id)]
+    id: Ref<Purchase>,
+}
+
+#[queries(Purchase)]
+trait PurchaseQuery {
+    fn by_customer(self, customer: String) -> Self;
+    fn by_total_range<R: std::ops::RangeBounds<u32>>(self, total: R) -> Self;
+    fn by_note(self, note: String) -> Self;
+    fn order_by_total(self, total: Order) -> Self;
+}
+
+fn purchase(customer: &str, total: u32) -> Purchase {
+    Purchase {
+        customer: customer.to_string(),
+        total,
+        note: format!("note {}", total % 2),
+    }
+}
+
+fn fill(db: &Structsy) -> SRes<Vec<(Ref<Purchase>, Purchase)>> {
+    db.define::<Purchase>()?;
+    let mut tx = db.begin()?;
+    let mut purchases = Vec::new();
+    for (customer, total) in [("ann", 30), ("bob", 10), ("ann", 20), ("carl", 40), ("bob", 25)] {
+        let o = purchase(customer, total);
+        purchases.push((tx.insert(&o)?, o));
+    }
+    tx.commit()?;
+    Ok(purchases)
+}
+
+fn sorted(mut ids: Vec<Ref<Purchase>>) -> Vec<Ref<Purchase>> {
+    ids.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    ids
+}
+
+fn expected(purchases: &[(Ref<Purchase>, Purchase)], filter: impl Fn(&Purchase) -> bool) -> Vec<Ref<Purchase>> {
+    sorted(
+        purchases
+            .iter()
+            .filter(|(_, o)| filter(o))
+            .map(|(id, _)| id.clone())
+            .collect(),
+    )
+}
+
+#[test]
+fn fetch_refs_scan() {
+    structsy_inst("fetch_refs_scan", |db| {
+        let purchases = fill(db)?;
+        let found = sorted(db.query::<Purchase>().fetch_refs().collect());
+        assert_eq!(found, expected(&purchases, |_| true));
+        assert_eq!(db.query::<Purchase>().count(), 5);
+        assert_eq!(
+            sorted(db.query::<Purchase>().ids().collect()),
+            expected(&purchases, |_| true)
+        );
+        let found = sorted(
+            db.query::<Purchase>()
+                .projection::<PurchaseId>()
+                .fetch()
+                .map(|o| o.id)
+                .collect(),
+        );
+        assert_eq!(found, expected(&purchases, |_| true));
+        Ok(())
+    });
+}
+
+#[test]
+fn fetch_refs_indexed() {
+    structsy_inst("fetch_refs_indexed", |db| {
+        let purchases = fill(db)?;
+        let found = sorted(
+            db.query::<Purchase>()
+                .by_customer("ann".to_string())
+                .fetch_refs()
+                .collect(),
+        );
+        assert_eq!(found, expected(&purchases, |o| o.customer == "ann"));
+        let found = db
+            .query::<Purchase>()
+            .by_total_range(15..=30)
+            .order_by_total(Order::Desc)
+            .fetch_refs()
+            .collect::<Vec<_>>();
+        let totals = purchases
+            .iter()
+            .map(|(id, o)| (id.clone(), o.total))
+            .collect::<Vec<_>>();
+        let found = found
+            .iter()
+            .map(|id| totals.iter().find(|(t, _)| t == id).unwrap().1)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![30, 25, 20]);
+        let count = db.query::<Purchase>().by_customer("dan".to_string()).count();
+        assert_eq!(count, 0);
+        assert_eq!(db.query::<Purchase>().by_customer("ann".to_string()).count(), 2);
+        Ok(())
+    });
+}
+
+#[test]
+fn fetch_refs_residual_filter() {
+    structsy_inst("fetch_refs_residual_filter", |db| {
+        let purchases = fill(db)?;
+        // Conditions not solved by an index check the loaded records
+        let found = sorted(
+            db.query::<Purchase>()
+                .by_note("note 0".to_string())
+                .fetch_refs()
+                .collect(),
+        );
+        assert_eq!(found, expected(&purchases, |o| o.note == "note 0"));
+        let found = sorted(
+            db.query::<Purchase>()
+                .by_customer("bob".to_string())
+                .by_note("note 1".to_string())
+                .fetch_refs()
+                .collect(),
+        );
+        assert_eq!(
+            found,
+            expected(&purchases, |o| o.customer == "bob" && o.note == "note 1")
+        );
+        Ok(())
+    });
+}
+
+#[test]
+fn fetch_refs_tx() {
+    structsy_inst("fetch_refs_tx", |db| {
+        let purchases = fill(db)?;
+        let snapshot = db.snapshot()?;
+        let mut tx = db.begin()?;
+        let new_id = tx.insert(&purchase("ann", 50))?;
+        let (bob_id, _) = purchases.iter().find(|(_, o)| o.customer == "bob").unwrap();
+        tx.delete(bob_id)?;
+        let mut all = expected(&purchases, |_| true);
+        all.retain(|id| id != bob_id);
+        all.push(new_id.clone());
+        let all = sorted(all);
+        assert_eq!(sorted(tx.query::<Purchase>().fetch_refs().collect()), all);
+        let mut ann = expected(&purchases, |o| o.customer == "ann");
+        ann.push(new_id);
+        let found = sorted(
+            tx.query::<Purchase>()
+                .by_customer("ann".to_string())
+                .fetch_refs()
+                .collect(),
+        );
+        assert_eq!(found, sorted(ann));
+        assert_eq!(tx.query::<Purchase>().by_customer("ann".to_string()).count(), 3);
+        assert_eq!(sorted(tx.query::<Purchase>().ids().collect()), all);
+        tx.commit()?;
+
+        assert_eq!(
+            sorted(snapshot.query::<Purchase>().fetch_refs().collect()),
+            expected(&purchases, |_| true)
+        );
+        let found = snapshot.query::<Purchase>().by_customer("bob".to_string()).count();
+        assert_eq!(found, 2);
+        assert_eq!(
+            sorted(snapshot.query::<Purchase>().ids().collect()),
+            expected(&purchases, |_| true)
+        );
+        assert_eq!(sorted(db.query::<Purchase>().fetch_refs().collect()), all);
+        Ok(())
+    });
+}
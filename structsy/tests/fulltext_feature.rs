@@ -0,0 +1,37 @@
+#![cfg(not(feature = "fulltext"))]
+use structsy::{IndexQuery, SRes, Structsy, StructsyError};
+use structsy_derive::Persistent;
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Article {
+    #[index(fulltext)]
+    body: String,
+}
+
+#[test]
+fn fulltext_requires_feature() {
+    structsy_inst("fulltext_requires_feature", |db| {
+        assert!(db.define::<Article>().is_err());
+        Ok(())
+    });
+}
+
+#[test]
+fn fulltext_search_requires_feature() {
+    structsy_inst("fulltext_search_requires_feature", |db| {
+        match db.query::<Article>().search("body", "some words").try_fetch() {
+            Err(StructsyError::TypeError(message)) => assert!(message.contains("'fulltext' feature")),
+            _ => panic!("the search is expected to fail"),
+        }
+        Ok(())
+    });
+}
@@ -0,0 +1,164 @@
+#![cfg(feature = "fulltext")]
+use structsy::{IndexQuery, RawAccess, SRes, Structsy, StructsyTx};
+use structsy_derive::{queries, Persistent};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Article {
+    #[index(fulltext)]
+    body: String,
+    #[index(mode = "cluster")]
+    author: String,
+    #[index(fulltext)]
+    title: Option<String>,
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Stemmed {
+    #[index(fulltext, stem = "english")]
+    text: String,
+}
+
+#[queries(Article)]
+trait ArticleQuery {
+    fn by_author(self, author: String) -> Self;
+}
+
+fn article(body: &str, author: &str, title: Option<&str>) -> Article {
+    Article {
+        body: body.to_string(),
+        author: author.to_string(),
+        title: title.map(|t| t.to_string()),
+    }
+}
+
+fn fill(db: &Structsy) -> SRes<()> {
+    db.define::<Article>()?;
+    let mut tx = db.begin()?;
+    tx.insert(&article("Rust is fast. Rust is safe.", "ann", Some("About Rust")))?;
+    tx.insert(&article("Fast cars are fast and loud", "bob", None))?;
+    tx.insert(&article("Safe and sound, Über café", "ann", Some("Sound")))?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn authors(db: &Structsy, field: &'static str, terms: &str) -> Vec<String> {
+    db.query::<Article>()
+        .search(field, terms)
+        .fetch()
+        .map(|(_, a)| a.author)
+        .collect()
+}
+
+#[test]
+fn fulltext_search() {
+    structsy_inst("fulltext_search", |db| {
+        fill(db)?;
+        // Ranked by the frequency of the terms
+        assert_eq!(authors(db, "body", "fast"), vec!["bob", "ann"]);
+        assert_eq!(authors(db, "body", "RUST fast"), vec!["ann"]);
+        assert_eq!(authors(db, "body", "über"), vec!["ann"]);
+        assert_eq!(authors(db, "body", "slow"), Vec::<String>::new());
+        assert_eq!(authors(db, "body", " , "), Vec::<String>::new());
+        assert_eq!(authors(db, "title", "rust"), vec!["ann"]);
+
+        let bodies = db
+            .query::<Article>()
+            .by_author("ann".to_string())
+            .search("body", "safe")
+            .fetch()
+            .map(|(_, a)| a.body)
+            .collect::<Vec<_>>();
+        assert_eq!(bodies.len(), 2);
+        let count = db
+            .query::<Article>()
+            .by_author("bob".to_string())
+            .search("body", "safe")
+            .fetch()
+            .count();
+        assert_eq!(count, 0);
+        Ok(())
+    });
+}
+
+#[test]
+fn fulltext_index_maintenance() {
+    structsy_inst("fulltext_index_maintenance", |db| {
+        fill(db)?;
+        let (id, _) = db.query::<Article>().search("body", "loud").fetch().next().unwrap();
+        let mut tx = db.begin()?;
+        tx.update(&id, &article("Quiet bikes", "bob", Some("Bikes")))?;
+        tx.commit()?;
+        assert_eq!(authors(db, "body", "loud").len(), 0);
+        assert_eq!(authors(db, "body", "fast"), vec!["ann"]);
+        assert_eq!(authors(db, "title", "bikes"), vec!["bob"]);
+
+        let mut tx = db.begin()?;
+        tx.delete(&id)?;
+        tx.commit()?;
+        assert_eq!(authors(db, "body", "quiet").len(), 0);
+        Ok(())
+    });
+}
+
+#[test]
+fn fulltext_index_raw() {
+    structsy_inst("fulltext_index_raw", |db| {
+        fill(db)?;
+        let desc = db.list_defined()?.find(|d| d.get_name() == "Article").unwrap();
+
+        let dir = tempdir().expect("can make a tempdir");
+        let other = Structsy::open(dir.path().join("other.stry"))?;
+        other.raw_define(desc)?;
+        other.define::<Article>()?;
+        let mut raw_tx = other.raw_begin()?;
+        for (_, record) in db.raw_scan("Article")? {
+            raw_tx.raw_insert(&record)?;
+        }
+        raw_tx.prepare()?.commit()?;
+        assert_eq!(authors(&other, "body", "fast"), vec!["bob", "ann"]);
+        Ok(())
+    });
+}
+
+#[cfg(feature = "stemming")]
+#[test]
+fn fulltext_stemming() {
+    structsy_inst("fulltext_stemming", |db| {
+        db.define::<Stemmed>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&Stemmed {
+            text: "She runs every day".to_string(),
+        })?;
+        tx.commit()?;
+        assert_eq!(db.query::<Stemmed>().search("text", "running").fetch().count(), 1);
+        Ok(())
+    });
+}
+
+#[cfg(not(feature = "stemming"))]
+#[test]
+fn fulltext_stemming_requires_feature() {
+    structsy_inst("fulltext_stemming_requires_feature", |db| {
+        assert!(db.define::<Stemmed>().is_err());
+        Ok(())
+    });
+}
+
+#[test]
+fn fulltext_search_without_index() {
+    structsy_inst("fulltext_search_without_index", |db| {
+        fill(db)?;
+        let found = db.query::<Article>().search("author", "ann").try_fetch();
+        assert!(found.is_err());
+        Ok(())
+    });
+}
@@ -0,0 +1,147 @@
+use structsy::{
+    internal::{Description, EqualAction, Query},
+    Persistent, SRes, Structsy, StructsyTx,
+};
+use structsy_derive::{Persistent, PersistentEmbedded};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(PersistentEmbedded, Debug, PartialEq, Clone)]
+struct Customer {
+    name: String,
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Wrapper<T> {
+    inner: T,
+    #[index(mode = "cluster")]
+    tag: String,
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+enum Either<T> {
+    Left(T),
+    Nothing,
+}
+
+#[derive(PersistentEmbedded, Debug, PartialEq, Clone)]
+struct Pair<T> {
+    first: T,
+    second: T,
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Holder {
+    pair: Pair<u8>,
+    names: Pair<Option<String>>,
+}
+
+trait WrapperQuery {
+    fn by_tag(self, tag: &str) -> Self;
+}
+
+impl<Q: Query<Wrapper<Customer>>> WrapperQuery for Q {
+    fn by_tag(mut self, tag: &str) -> Self {
+        EqualAction::equal((Wrapper::<Customer>::field_tag(), self.filter_builder()), tag);
+        self
+    }
+}
+
+fn customer(name: &str) -> Wrapper<Customer> {
+    Wrapper {
+        inner: Customer { name: name.to_string() },
+        tag: format!("{}_tag", name),
+    }
+}
+
+#[test]
+fn generic_names() {
+    assert_eq!(Wrapper::<Customer>::get_name(), "Wrapper<Customer>");
+    assert_eq!(Wrapper::<u32>::get_name(), "Wrapper<U32>");
+    assert_eq!(Wrapper::<Vec<Option<i64>>>::get_name(), "Wrapper<Vec<Option<I64>>>");
+    assert_eq!(Either::<Customer>::get_name(), "Either<Customer>");
+    assert_eq!(Wrapper::<u32>::get_description().get_name(), "Wrapper<U32>");
+}
+
+#[test]
+fn generic_struct_persistence() {
+    structsy_inst("generic_struct_persistence", |db| {
+        db.define::<Wrapper<Customer>>()?;
+        db.define::<Wrapper<u32>>()?;
+        let mut tx = db.begin()?;
+        let id = tx.insert(&customer("one"))?;
+        tx.insert(&customer("two"))?;
+        let num_id = tx.insert(&Wrapper {
+            inner: 10u32,
+            tag: "one_tag".to_string(),
+        })?;
+        tx.commit()?;
+
+        assert_eq!(db.read(&id)?, Some(customer("one")));
+        assert_eq!(db.read(&num_id)?.map(|w| w.inner), Some(10));
+        assert_eq!(db.scan::<Wrapper<Customer>>()?.count(), 2);
+        assert_eq!(db.scan::<Wrapper<u32>>()?.count(), 1);
+
+        let found = db
+            .query::<Wrapper<Customer>>()
+            .by_tag("one_tag")
+            .fetch()
+            .collect::<Vec<_>>();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1, customer("one"));
+
+        let mut names = db.list_defined()?.map(|d| d.get_name()).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["Wrapper<Customer>", "Wrapper<U32>"]);
+        Ok(())
+    });
+}
+
+#[test]
+fn generic_enum_and_embedded() {
+    structsy_inst("generic_enum_and_embedded", |db| {
+        db.define::<Either<Customer>>()?;
+        db.define::<Holder>()?;
+        let mut tx = db.begin()?;
+        let left = tx.insert(&Either::Left(Customer {
+            name: "left".to_string(),
+        }))?;
+        let nothing = tx.insert(&Either::<Customer>::Nothing)?;
+        let holder = Holder {
+            pair: Pair { first: 1, second: 2 },
+            names: Pair {
+                first: Some("a".to_string()),
+                second: None,
+            },
+        };
+        let holder_id = tx.insert(&holder)?;
+        tx.commit()?;
+
+        assert_eq!(
+            db.read(&left)?,
+            Some(Either::Left(Customer {
+                name: "left".to_string()
+            }))
+        );
+        assert_eq!(db.read(&nothing)?, Some(Either::Nothing));
+        assert_eq!(db.read(&holder_id)?, Some(holder));
+
+        let desc = db.list_defined()?.find(|d| d.get_name() == "Holder").unwrap();
+        let embedded = match desc {
+            Description::Struct(st) => st
+                .fields()
+                .filter_map(|f| f.get_field_type_description().map(|d| d.get_name()))
+                .collect::<Vec<_>>(),
+            _ => panic!("wrong description"),
+        };
+        assert_eq!(embedded, vec!["Pair<U8>", "Pair<Option<String>>"]);
+        Ok(())
+    });
+}
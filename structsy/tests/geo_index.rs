@@ -0,0 +1,287 @@
+use structsy::{GeoPoint, GeoRect, IndexQuery, RawAccess, SRes, Structsy, StructsyTx};
+use structsy_derive::{queries, Persistent};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Delivery {
+    #[index(geo)]
+    position: GeoPoint,
+    #[index(mode = "cluster")]
+    courier: String,
+    weight: u32,
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Place {
+    #[index(geo)]
+    location: Option<GeoPoint>,
+    name: String,
+}
+
+#[queries(Delivery)]
+trait DeliveryQuery {
+    fn by_courier(self, courier: String) -> Self;
+    fn by_weight(self, weight: u32) -> Self;
+}
+
+fn delivery(lat: i32, lon: i32) -> Delivery {
+    Delivery {
+        position: GeoPoint::new(lat as f64 / 10.0, lon as f64 / 10.0),
+        courier: if (lat + lon) % 2 == 0 { "ann" } else { "bob" }.to_string(),
+        weight: (lat.unsigned_abs() + lon.unsigned_abs()) % 3,
+    }
+}
+
+/// A grid of deliveries every 0.1 degrees around Milan
+fn fill(db: &Structsy) -> SRes<Vec<Delivery>> {
+    db.define::<Delivery>()?;
+    let mut deliveries = Vec::new();
+    let mut tx = db.begin()?;
+    for lat in 450..460 {
+        for lon in 90..100 {
+            let d = delivery(lat, lon);
+            tx.insert(&d)?;
+            deliveries.push(d);
+        }
+    }
+    tx.commit()?;
+    Ok(deliveries)
+}
+
+/// The distances of the deliveries inside the radius, as expected from the index
+fn exact(deliveries: &[Delivery], center: &GeoPoint, meters: f64, filter: impl Fn(&Delivery) -> bool) -> Vec<f64> {
+    let mut found = deliveries
+        .iter()
+        .filter(|d| filter(d))
+        .map(|d| d.position.distance(center))
+        .filter(|distance| *distance <= meters)
+        .collect::<Vec<_>>();
+    found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    found
+}
+
+fn distances<I: Iterator<Item = (structsy::Ref<Delivery>, Delivery)>>(iter: I, center: &GeoPoint) -> Vec<f64> {
+    iter.map(|(_, d)| d.position.distance(center)).collect()
+}
+
+#[test]
+fn geo_within_radius() {
+    structsy_inst("geo_within_radius", |db| {
+        let deliveries = fill(db)?;
+        let center = GeoPoint::new(45.43, 9.52);
+        let found = db
+            .query::<Delivery>()
+            .within_radius("position", center, 25_000.0)
+            .fetch();
+        let found = distances(found, &center);
+        assert!(found.len() > 10);
+        assert_eq!(found, exact(&deliveries, &center, 25_000.0, |_| true));
+
+        let found = db
+            .query::<Delivery>()
+            .within_radius("position", center, 1_000.0)
+            .fetch();
+        assert_eq!(found.count(), 0);
+        let far = GeoPoint::new(-33.87, 151.21);
+        let found = db.query::<Delivery>().within_radius("position", far, 100_000.0).fetch();
+        assert_eq!(found.count(), 0);
+        let found = db
+            .query::<Delivery>()
+            .within_radius("position", far, 20_100_000.0)
+            .fetch();
+        assert_eq!(found.count(), deliveries.len());
+        Ok(())
+    });
+}
+
+#[test]
+fn geo_within_bbox() {
+    structsy_inst("geo_within_bbox", |db| {
+        let deliveries = fill(db)?;
+        let rect = GeoRect::new(GeoPoint::new(45.25, 9.05), GeoPoint::new(45.55, 9.35));
+        let found = db
+            .query::<Delivery>()
+            .within_bbox("position", rect)
+            .fetch()
+            .map(|(_, d)| d)
+            .collect::<Vec<_>>();
+        assert_eq!(found.len(), 9);
+        let expected = deliveries.iter().filter(|d| rect.contains(&d.position)).count();
+        assert_eq!(found.len(), expected);
+        assert!(found.iter().all(|d| rect.contains(&d.position)));
+
+        // Across the 180th meridian
+        let mut tx = db.begin()?;
+        tx.insert(&delivery(0, 1795))?;
+        tx.insert(&delivery(0, -1795))?;
+        tx.insert(&delivery(0, 1700))?;
+        tx.commit()?;
+        let rect = GeoRect::new(GeoPoint::new(-1.0, 179.0), GeoPoint::new(1.0, -179.0));
+        let count = db.query::<Delivery>().within_bbox("position", rect).fetch().count();
+        assert_eq!(count, 2);
+        Ok(())
+    });
+}
+
+#[test]
+fn geo_without_index() {
+    structsy_inst("geo_without_index", |db| {
+        fill(db)?;
+        let rect = GeoRect::new(GeoPoint::new(45.25, 9.05), GeoPoint::new(45.55, 9.35));
+        let found = db.query::<Delivery>().within_bbox("courier", rect).try_fetch();
+        assert!(found.is_err());
+        let center = GeoPoint::new(45.4, 9.2);
+        let found = db
+            .query::<Delivery>()
+            .within_radius("weight", center, 100.0)
+            .try_fetch();
+        assert!(found.is_err());
+        Ok(())
+    });
+}
+
+#[test]
+fn geo_filtered() {
+    structsy_inst("geo_filtered", |db| {
+        let deliveries = fill(db)?;
+        let center = GeoPoint::new(45.5, 9.5);
+        let found = db
+            .query::<Delivery>()
+            .by_courier("ann".to_string())
+            .within_radius("position", center, 20_000.0)
+            .fetch();
+        let expected = exact(&deliveries, &center, 20_000.0, |d| d.courier == "ann");
+        assert_eq!(distances(found, &center), expected);
+        let found = db
+            .query::<Delivery>()
+            .by_weight(1)
+            .within_radius("position", center, 20_000.0)
+            .fetch();
+        let expected = exact(&deliveries, &center, 20_000.0, |d| d.weight == 1);
+        assert_eq!(distances(found, &center), expected);
+        let count = db
+            .query::<Delivery>()
+            .by_courier("carl".to_string())
+            .within_radius("position", center, 20_000.0)
+            .fetch()
+            .count();
+        assert_eq!(count, 0);
+        Ok(())
+    });
+}
+
+#[test]
+fn geo_index_maintenance() {
+    structsy_inst("geo_index_maintenance", |db| {
+        fill(db)?;
+        let center = GeoPoint::new(45.0, 9.0);
+        let found = db
+            .query::<Delivery>()
+            .within_radius("position", center, 30_000.0)
+            .fetch()
+            .collect::<Vec<_>>();
+        assert!(found.len() > 1);
+        let mut tx = db.begin()?;
+        tx.update(&found[0].0, &delivery(410, 120))?;
+        for (id, _) in &found[1..] {
+            tx.delete(id)?;
+        }
+        tx.commit()?;
+        assert_eq!(
+            db.query::<Delivery>()
+                .within_radius("position", center, 30_000.0)
+                .fetch()
+                .count(),
+            0
+        );
+        let rome = GeoPoint::new(41.0, 12.0);
+        let (_, moved) = db
+            .query::<Delivery>()
+            .within_radius("position", rome, 1_000.0)
+            .fetch()
+            .next()
+            .unwrap();
+        assert_eq!(moved.position, rome);
+
+        // The query in a transaction finds also the not committed changes
+        let mut tx = db.begin()?;
+        tx.insert(&delivery(411, 120))?;
+        let found = tx
+            .query::<Delivery>()
+            .within_radius("position", rome, 20_000.0)
+            .fetch()
+            .map(|(_, d)| d.position)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![rome, GeoPoint::new(41.1, 12.0)]);
+        tx.commit()?;
+        Ok(())
+    });
+}
+
+#[test]
+fn geo_index_optional() {
+    structsy_inst("geo_index_optional", |db| {
+        db.define::<Place>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&Place {
+            location: Some(GeoPoint::new(45.46, 9.19)),
+            name: "milan".to_string(),
+        })?;
+        tx.insert(&Place {
+            location: None,
+            name: "nowhere".to_string(),
+        })?;
+        tx.insert(&Place {
+            location: Some(GeoPoint::new(45.07, 7.69)),
+            name: "turin".to_string(),
+        })?;
+        tx.commit()?;
+        let names = db
+            .query::<Place>()
+            .within_radius("location", GeoPoint::new(45.1, 7.7), 200_000.0)
+            .fetch()
+            .map(|(_, p)| p.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["turin", "milan"]);
+        let all = GeoRect::new(GeoPoint::new(-90.0, -180.0), GeoPoint::new(90.0, 180.0));
+        let count = db.query::<Place>().within_bbox("location", all).fetch().count();
+        assert_eq!(count, 2);
+        Ok(())
+    });
+}
+
+#[test]
+fn geo_index_raw() {
+    structsy_inst("geo_index_raw", |db| {
+        let deliveries = fill(db)?;
+        let desc = db.list_defined()?.find(|d| d.get_name() == "Delivery").unwrap();
+
+        let dir = tempdir().expect("can make a tempdir");
+        let other = Structsy::open(dir.path().join("other.stry"))?;
+        other.raw_define(desc)?;
+        other.define::<Delivery>()?;
+        let mut raw_tx = other.raw_begin()?;
+        for (_, record) in db.raw_scan("Delivery")? {
+            raw_tx.raw_insert(&record)?;
+        }
+        raw_tx.prepare()?.commit()?;
+        let center = GeoPoint::new(45.71, 9.33);
+        let found = other
+            .query::<Delivery>()
+            .within_radius("position", center, 15_000.0)
+            .fetch();
+        assert_eq!(
+            distances(found, &center),
+            exact(&deliveries, &center, 15_000.0, |_| true)
+        );
+        Ok(())
+    });
+}
@@ -0,0 +1,127 @@
+use structsy::{Structsy, StructsyError, StructsyTx};
+use tempfile::tempdir;
+
+mod plain {
+    use structsy_derive::Persistent;
+
+    #[derive(Persistent)]
+    pub struct Item {
+        pub name: String,
+        pub size: u32,
+    }
+}
+
+mod indexed {
+    use structsy_derive::{queries, Persistent};
+
+    #[derive(Persistent)]
+    pub struct Item {
+        #[index(mode = "cluster")]
+        pub name: String,
+        pub size: u32,
+    }
+
+    #[queries(Item)]
+    pub trait ItemQuery {
+        fn by_name(self, name: String) -> Self;
+    }
+}
+
+use indexed::ItemQuery;
+
+#[test]
+fn test_create_drop_index() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("index_management.stry");
+    {
+        let db = Structsy::open(&file).unwrap();
+        db.define::<plain::Item>().unwrap();
+        let mut tx = db.begin().unwrap();
+        for (name, size) in &[("aaa", 1), ("bbb", 2), ("aaa", 3)] {
+            tx.insert(&plain::Item {
+                name: name.to_string(),
+                size: *size,
+            })
+            .unwrap();
+        }
+        tx.commit().unwrap();
+    }
+    {
+        let db = Structsy::open(&file).unwrap();
+        assert!(db.define::<indexed::Item>().is_err());
+        db.create_index::<indexed::Item>("name").unwrap();
+        // Creating again an existing index do nothing
+        db.create_index::<indexed::Item>("name").unwrap();
+        db.define::<indexed::Item>().unwrap();
+        let mut tx = db.begin().unwrap();
+        tx.insert(&indexed::Item {
+            name: "aaa".to_string(),
+            size: 4,
+        })
+        .unwrap();
+        tx.commit().unwrap();
+        let mut sizes = db
+            .query::<indexed::Item>()
+            .by_name("aaa".to_string())
+            .fetch()
+            .map(|(_, item)| item.size)
+            .collect::<Vec<_>>();
+        sizes.sort();
+        assert_eq!(sizes, vec![1, 3, 4]);
+        assert!(db.schema_diff::<indexed::Item>().unwrap().is_empty());
+    }
+    {
+        let db = Structsy::open(&file).unwrap();
+        assert!(db.drop_index::<indexed::Item>("name").is_err());
+        db.drop_index::<plain::Item>("name").unwrap();
+        db.define::<plain::Item>().unwrap();
+        let mut tx = db.begin().unwrap();
+        tx.insert(&plain::Item {
+            name: "ccc".to_string(),
+            size: 5,
+        })
+        .unwrap();
+        tx.commit().unwrap();
+        assert_eq!(db.scan::<plain::Item>().unwrap().count(), 5);
+    }
+    {
+        let db = Structsy::open(&file).unwrap();
+        db.create_index::<indexed::Item>("name").unwrap();
+        db.define::<indexed::Item>().unwrap();
+        let found = db
+            .query::<indexed::Item>()
+            .by_name("ccc".to_string())
+            .fetch()
+            .map(|(_, item)| item.size)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![5]);
+    }
+}
+
+#[test]
+fn test_create_index_during_migration() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("index_during_migration.stry");
+    let db = Structsy::open(&file).unwrap();
+    db.define::<plain::Item>().unwrap();
+    let mut tx = db.begin().unwrap();
+    for size in 0..3 {
+        tx.insert(&plain::Item {
+            name: "aaa".to_string(),
+            size,
+        })
+        .unwrap();
+    }
+    tx.commit().unwrap();
+    let desc = db.list_defined().unwrap().find(|d| d.get_name() == "Item").unwrap();
+    // A failing migration is left in progress to be resumed
+    let failed = db.raw_migrate("Item", desc.clone(), |_| Err(StructsyError::InvalidId));
+    assert!(failed.is_err());
+    assert!(db.create_index::<indexed::Item>("name").is_err());
+    assert!(db.define::<indexed::Item>().is_err());
+    db.raw_migrate("Item", desc, Ok).unwrap();
+    db.create_index::<indexed::Item>("name").unwrap();
+    db.define::<indexed::Item>().unwrap();
+    let count = db.query::<indexed::Item>().by_name("aaa".to_string()).fetch().count();
+    assert_eq!(count, 3);
+}
@@ -0,0 +1,242 @@
+use structsy::{Filter, Order, Ref, SRes, Structsy, StructsyTx};
+use structsy_derive::{queries, Persistent, Projection};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Product {
+    #[index(mode = "exclusive")]
+    code: String,
+    #[index(mode = "cluster")]
+    price: u32,
+    #[index(mode = "cluster")]
+    discount: Option<u32>,
+    label: String,
+}
+
+#[derive(Projection, Debug, PartialEq)]
+#[projection = "Product"]
+struct CodeProjection {
+    code: String,
+}
+
+#[derive(Projection, Debug, PartialEq)]
+#[projection = "Product"]
+struct PriceProjection {
+    #[projection(id)]
+    id: Ref<Product>,
+    price: u32,
+}
+
+#[derive(Projection, Debug, PartialEq)]
+#[projection = "Product"]
+struct LabelProjection {
+    #[projection(id)]
+    id: Ref<Product>,
+    label: String,
+}
+
+#[derive(Projection, Debug, PartialEq)]
+#[projection = "Product"]
+struct DiscountProjection {
+    discount: Option<u32>,
+}
+
+#[queries(Product)]
+trait ProductQuery {
+    fn by_code(self, code: String) -> Self;
+    fn by_price(self, price: u32) -> Self;
+    fn by_price_range<R: std::ops::RangeBounds<u32>>(self, price: R) -> Self;
+    fn by_label(self, label: String) -> Self;
+    fn order_by_price(self, price: Order) -> Self;
+    fn order_by_label(self, label: Order) -> Self;
+}
+
+fn product(code: &str, price: u32, discount: Option<u32>) -> Product {
+    Product {
+        code: code.to_string(),
+        price,
+        discount,
+        label: format!("label {}", code),
+    }
+}
+
+fn fill(db: &Structsy) -> SRes<Vec<(Ref<Product>, Product)>> {
+    db.define::<Product>()?;
+    let mut tx = db.begin()?;
+    let mut products = Vec::new();
+    for (code, price, discount) in [("d", 30, None), ("a", 10, Some(1)), ("c", 20, None), ("b", 20, Some(2))] {
+        let p = product(code, price, discount);
+        products.push((tx.insert(&p)?, p));
+    }
+    tx.commit()?;
+    Ok(products)
+}
+
+fn codes<I: Iterator<Item = CodeProjection>>(iter: I) -> Vec<String> {
+    iter.map(|p| p.code).collect()
+}
+
+#[test]
+fn index_projection_scan() {
+    structsy_inst("index_projection_scan", |db| {
+        let products = fill(db)?;
+        // Without filter all the index is read, in the order of the keys
+        let found = codes(db.query::<Product>().projection::<CodeProjection>().fetch());
+        assert_eq!(found, vec!["a", "b", "c", "d"]);
+        let found = db
+            .query::<Product>()
+            .order_by_price(Order::Desc)
+            .projection::<PriceProjection>()
+            .fetch()
+            .map(|p| p.price)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![30, 20, 20, 10]);
+        let found = db.query::<Product>().projection::<PriceProjection>().fetch();
+        for prj in found {
+            let (_, p) = products.iter().find(|(id, _)| *id == prj.id).unwrap();
+            assert_eq!(p.price, prj.price);
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn index_projection_filtered() {
+    structsy_inst("index_projection_filtered", |db| {
+        let products = fill(db)?;
+        let found = codes(
+            db.query::<Product>()
+                .by_code("c".to_string())
+                .projection::<CodeProjection>()
+                .fetch(),
+        );
+        assert_eq!(found, vec!["c"]);
+        let mut found = db
+            .query::<Product>()
+            .by_price(20)
+            .projection::<PriceProjection>()
+            .fetch()
+            .collect::<Vec<_>>();
+        found.sort_by(|a, b| a.id.partial_cmp(&b.id).unwrap());
+        let mut expected = products
+            .iter()
+            .filter(|(_, p)| p.price == 20)
+            .map(|(id, p)| PriceProjection {
+                id: id.clone(),
+                price: p.price,
+            })
+            .collect::<Vec<_>>();
+        expected.sort_by(|a, b| a.id.partial_cmp(&b.id).unwrap());
+        assert_eq!(found, expected);
+        let found = db
+            .query::<Product>()
+            .by_price_range(15..)
+            .order_by_price(Order::Desc)
+            .projection::<PriceProjection>()
+            .fetch()
+            .map(|p| p.price)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![30, 20, 20]);
+        let found = db
+            .query::<Product>()
+            .by_price_range(15..)
+            .order_by_price(Order::Desc)
+            .fetch()
+            .map(|(_, p)| p.price)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![30, 20, 20]);
+        let found = db.fetch(
+            Filter::<Product>::new()
+                .by_code("a".to_string())
+                .projection::<CodeProjection>(),
+        );
+        assert_eq!(codes(found), vec!["a"]);
+        Ok(())
+    });
+}
+
+#[test]
+fn index_projection_not_covered() {
+    structsy_inst("index_projection_not_covered", |db| {
+        let products = fill(db)?;
+        // Conditions and orders not solved by the index need the records
+        let found = codes(
+            db.query::<Product>()
+                .by_code("b".to_string())
+                .by_label("label b".to_string())
+                .projection::<CodeProjection>()
+                .fetch(),
+        );
+        assert_eq!(found, vec!["b"]);
+        let found = codes(
+            db.query::<Product>()
+                .by_price(20)
+                .order_by_label(Order::Desc)
+                .projection::<CodeProjection>()
+                .fetch(),
+        );
+        assert_eq!(found, vec!["c", "b"]);
+        // Not indexed fields and optional fields are read from the records
+        let (b_id, _) = products.iter().find(|(_, p)| p.code == "b").unwrap();
+        let found = db
+            .query::<Product>()
+            .by_code("b".to_string())
+            .projection::<LabelProjection>()
+            .fetch()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![LabelProjection {
+                id: b_id.clone(),
+                label: "label b".to_string(),
+            }]
+        );
+        let found = db.query::<Product>().projection::<DiscountProjection>().fetch().count();
+        assert_eq!(found, 4);
+        Ok(())
+    });
+}
+
+#[test]
+fn index_projection_tx() {
+    structsy_inst("index_projection_tx", |db| {
+        let products = fill(db)?;
+        let snapshot = db.snapshot()?;
+        let mut tx = db.begin()?;
+        tx.insert(&product("e", 5, None))?;
+        let (a_id, _) = products.iter().find(|(_, p)| p.code == "a").unwrap();
+        tx.delete(a_id)?;
+        let found = codes(tx.query::<Product>().projection::<CodeProjection>().fetch());
+        assert_eq!(found, vec!["b", "c", "d", "e"]);
+        let found = tx
+            .query::<Product>()
+            .by_price_range(..=20)
+            .projection::<PriceProjection>()
+            .fetch()
+            .map(|p| p.price)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![5, 20, 20]);
+        tx.commit()?;
+
+        let found = codes(snapshot.query::<Product>().projection::<CodeProjection>().fetch());
+        assert_eq!(found, vec!["a", "b", "c", "d"]);
+        let found = codes(db.query::<Product>().projection::<CodeProjection>().fetch());
+        assert_eq!(found, vec!["b", "c", "d", "e"]);
+        Ok(())
+    });
+}
+
+#[test]
+#[should_panic(expected = "holds the id of the record")]
+fn projection_with_id_requires_query() {
+    use structsy::internal::Projection;
+    PriceProjection::projection(&product("a", 1, None));
+}
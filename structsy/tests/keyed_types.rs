@@ -0,0 +1,166 @@
+use std::ops::RangeBounds;
+use structsy::{SRes, Structsy, StructsyTx};
+use structsy_derive::{queries, Persistent};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq)]
+struct Letter {
+    #[index(mode = "cluster")]
+    value: char,
+    other: Option<char>,
+}
+
+#[queries(Letter)]
+trait LetterQuery {
+    fn by_value(self, value: char) -> Self;
+    fn by_value_range<R: RangeBounds<char>>(self, value: R) -> Self;
+    fn by_other_range<R: RangeBounds<Option<char>>>(self, other: R) -> Self;
+}
+
+#[test]
+fn char_values_query() {
+    structsy_inst("char_values_query", |db| {
+        db.define::<Letter>()?;
+        let mut tx = db.begin()?;
+        for (value, other) in [('a', None), ('b', Some('z')), ('è', Some('y')), ('🦀', Some('x'))] {
+            tx.insert(&Letter { value, other })?;
+        }
+        tx.commit()?;
+
+        let found = db.query::<Letter>().by_value('🦀').fetch().next().unwrap().1;
+        assert_eq!(found.other, Some('x'));
+        assert_eq!(db.query::<Letter>().by_value_range('b'..).fetch().count(), 3);
+        assert_eq!(db.query::<Letter>().by_value_range('a'..='b').fetch().count(), 2);
+        assert_eq!(
+            db.query::<Letter>()
+                .by_other_range(Some('y')..=Some('z'))
+                .fetch()
+                .count(),
+            2
+        );
+        Ok(())
+    });
+}
+
+#[cfg(feature = "uuid")]
+mod uuid_types {
+    use super::structsy_inst;
+    use std::ops::RangeBounds;
+    use structsy::StructsyTx;
+    use structsy_derive::{queries, Persistent};
+    use uuid::Uuid;
+
+    #[derive(Persistent, Debug, PartialEq)]
+    struct Device {
+        #[index(mode = "exclusive")]
+        id: Uuid,
+        parent: Option<Uuid>,
+    }
+
+    #[queries(Device)]
+    trait DeviceQuery {
+        fn by_id(self, id: Uuid) -> Self;
+        fn by_id_range<R: RangeBounds<Uuid>>(self, id: R) -> Self;
+        fn by_parent(self, parent: Option<Uuid>) -> Self;
+    }
+
+    #[test]
+    fn uuid_values_query() {
+        structsy_inst("uuid_values_query", |db| {
+            db.define::<Device>()?;
+            let root = Uuid::from_u128(1);
+            let mut tx = db.begin()?;
+            tx.insert(&Device { id: root, parent: None })?;
+            tx.insert(&Device {
+                id: Uuid::from_u128(u128::MAX),
+                parent: Some(root),
+            })?;
+            tx.insert(&Device {
+                id: Uuid::from_u128(1 << 100),
+                parent: Some(root),
+            })?;
+            tx.commit()?;
+
+            let found = db.query::<Device>().by_id(root).fetch().next().unwrap().1;
+            assert_eq!(found, Device { id: root, parent: None });
+            let from = Uuid::from_u128(2);
+            assert_eq!(db.query::<Device>().by_id_range(from..).fetch().count(), 2);
+            assert_eq!(db.query::<Device>().by_parent(Some(root)).fetch().count(), 2);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+mod decimal_types {
+    use super::structsy_inst;
+    use rust_decimal::Decimal;
+    use std::ops::RangeBounds;
+    use structsy::StructsyTx;
+    use structsy_derive::{queries, Persistent};
+
+    #[derive(Persistent, Debug, PartialEq)]
+    struct Price {
+        #[index(mode = "cluster")]
+        amount: Decimal,
+    }
+
+    #[queries(Price)]
+    trait PriceQuery {
+        fn by_amount(self, amount: Decimal) -> Self;
+        fn by_amount_range<R: RangeBounds<Decimal>>(self, amount: R) -> Self;
+    }
+
+    fn dec(mantissa: i64, scale: u32) -> Decimal {
+        Decimal::new(mantissa, scale)
+    }
+
+    #[test]
+    fn decimal_values_query() {
+        structsy_inst("decimal_values_query", |db| {
+            db.define::<Price>()?;
+            let values = [
+                dec(-1050, 1),
+                dec(-1, 0),
+                dec(-5, 2),
+                dec(0, 0),
+                dec(5, 2),
+                dec(15, 1),
+                dec(1000, 0),
+            ];
+            let mut tx = db.begin()?;
+            for amount in values.iter() {
+                tx.insert(&Price { amount: *amount })?;
+            }
+            tx.commit()?;
+
+            assert_eq!(db.query::<Price>().by_amount(dec(150, 2)).fetch().count(), 1);
+            assert_eq!(db.query::<Price>().by_amount_range(dec(0, 0)..).fetch().count(), 4);
+            assert_eq!(db.query::<Price>().by_amount_range(..dec(-1, 1)).fetch().count(), 2);
+            assert_eq!(
+                db.query::<Price>()
+                    .by_amount_range(dec(-1, 0)..=dec(1, 1))
+                    .fetch()
+                    .count(),
+                4
+            );
+            let mut found = db
+                .query::<Price>()
+                .by_amount_range(..)
+                .fetch()
+                .map(|(_, p)| p.amount)
+                .collect::<Vec<_>>();
+            found.sort();
+            assert_eq!(found, values);
+            Ok(())
+        });
+    }
+}
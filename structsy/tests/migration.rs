@@ -229,3 +229,104 @@ fn test_ref_migration() {
     second::second_operation(file.clone()).unwrap();
     third::third_operation(file).unwrap();
 }
+
+mod raw_before {
+    use std::path::PathBuf;
+    use structsy::{SRes, Structsy, StructsyTx};
+    use structsy_derive::Persistent;
+
+    #[derive(Persistent)]
+    struct Item {
+        #[index(mode = "cluster")]
+        name: String,
+        size: u32,
+    }
+
+    pub fn fill(file: PathBuf) -> SRes<()> {
+        let db = Structsy::open(file)?;
+        db.define::<Item>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&Item {
+            name: "aaa".to_string(),
+            size: 10,
+        })?;
+        tx.insert(&Item {
+            name: "bbb".to_string(),
+            size: 20,
+        })?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+mod raw_after {
+    use std::path::PathBuf;
+    use structsy::{
+        internal::{SimpleValueTypeBuilder, StructDescriptionBuilder, ValueTypeBuilder},
+        record::{Record, SimpleValue, StructBuilder, Value},
+        SRes, Structsy, StructsyError, ValueMode,
+    };
+    use structsy_derive::{queries, Persistent};
+
+    #[derive(Persistent)]
+    struct Item {
+        #[index(mode = "cluster")]
+        name: String,
+        #[index(mode = "cluster")]
+        size: u64,
+        surname: String,
+    }
+
+    #[queries(Item)]
+    trait ItemQuery {
+        fn by_name(self, name: String) -> Self;
+        fn by_size(self, size: u64) -> Self;
+    }
+
+    pub fn raw_migrate(file: PathBuf) -> SRes<()> {
+        let db = Structsy::open(file)?;
+        let simple = |name| ValueTypeBuilder::simple(SimpleValueTypeBuilder::from_name(name).build()).build();
+        let desc = StructDescriptionBuilder::new("Item")
+            .add_field(0, "name".to_owned(), simple("String"), Some(ValueMode::Cluster))
+            .add_field(1, "size".to_owned(), simple("U64"), Some(ValueMode::Cluster))
+            .add_field(2, "surname".to_owned(), simple("String"), None)
+            .build();
+        db.raw_migrate("Item", desc.clone(), |record| {
+            let st = match record {
+                Record::Struct(st) => st,
+                _ => return Err(StructsyError::TypeError("expected a struct".to_owned())),
+            };
+            let name = match st.field("name").map(|f| f.value()) {
+                Some(Value::Value(SimpleValue::String(s))) => s.clone(),
+                _ => return Err(StructsyError::TypeError("expected a string".to_owned())),
+            };
+            let size = match st.field("size").map(|f| f.value()) {
+                Some(Value::Value(SimpleValue::U32(s))) => *s as u64,
+                _ => return Err(StructsyError::TypeError("expected a u32".to_owned())),
+            };
+            StructBuilder::new(desc.clone())?
+                .add_field("name", format!("{}_new", name))?
+                .add_field("size", size)?
+                .add_field("surname", "Doe".to_owned())?
+                .finish()
+        })?;
+
+        db.define::<Item>()?;
+        assert_eq!(db.scan::<Item>()?.count(), 2);
+        assert_eq!(db.query::<Item>().by_name("aaa".to_owned()).fetch().count(), 0);
+        let (_, found) = db.query::<Item>().by_name("aaa_new".to_owned()).fetch().next().unwrap();
+        assert_eq!(found.size, 10);
+        assert_eq!(found.surname, "Doe");
+        let (_, found) = db.query::<Item>().by_size(20).fetch().next().unwrap();
+        assert_eq!(found.name, "bbb_new");
+        Ok(())
+    }
+}
+
+#[test]
+fn test_raw_migration() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("test_raw_migration.stry");
+    raw_before::fill(file.clone()).unwrap();
+    raw_after::raw_migrate(file).unwrap();
+}
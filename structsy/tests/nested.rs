@@ -0,0 +1,217 @@
+use std::io::Cursor;
+use structsy::{
+    internal::{Description, SimpleValueTypeBuilder, StructDescriptionBuilder, ValueTypeBuilder},
+    record::{Record, SimpleValue, Value},
+    Persistent, RawAccess, SRes, Structsy, StructsyTx,
+};
+use structsy_derive::{queries, Persistent, PersistentEmbedded};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(PersistentEmbedded, Debug, PartialEq, Clone)]
+struct Cell {
+    value: u32,
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Grid {
+    matrix: Vec<Vec<f64>>,
+    #[index(mode = "cluster")]
+    labels: Vec<Option<String>>,
+    limit: Option<Option<u32>>,
+    data: Option<Vec<u8>>,
+    rows: Vec<Vec<Cell>>,
+    chunks: Vec<Vec<u8>>,
+}
+
+impl Grid {
+    fn new(base: f64, label: Option<&str>, limit: Option<Option<u32>>) -> Grid {
+        Grid {
+            matrix: vec![vec![base, base + 1.0], vec![], vec![base * 2.0]],
+            labels: vec![label.map(|l| l.to_string()), None],
+            limit,
+            data: Some(vec![1, 2, 3]),
+            rows: vec![vec![Cell { value: 1 }, Cell { value: 2 }]],
+            chunks: vec![vec![0xFF], vec![]],
+        }
+    }
+}
+
+#[queries(Grid)]
+trait GridQuery {
+    fn by_matrix(self, matrix: Vec<Vec<f64>>) -> Self;
+    fn by_row(self, matrix: Vec<f64>) -> Self;
+    fn by_labels(self, labels: Option<String>) -> Self;
+    fn by_limit(self, limit: Option<Option<u32>>) -> Self;
+    fn by_limit_value(self, limit: Option<u32>) -> Self;
+}
+
+#[test]
+fn nested_persistence() {
+    structsy_inst("nested_persistence", |db| {
+        db.define::<Grid>()?;
+        let grid = Grid::new(1.5, Some("one"), Some(None));
+        let mut tx = db.begin()?;
+        let id = tx.insert(&grid)?;
+        tx.commit()?;
+        assert_eq!(db.read(&id)?, Some(grid));
+        Ok(())
+    });
+}
+
+#[test]
+fn nested_query() {
+    structsy_inst("nested_query", |db| {
+        db.define::<Grid>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&Grid::new(1.0, Some("one"), None))?;
+        tx.insert(&Grid::new(2.0, Some("two"), Some(None)))?;
+        tx.insert(&Grid::new(3.0, None, Some(Some(3))))?;
+        tx.commit()?;
+
+        let matrix = vec![vec![2.0, 3.0], vec![], vec![4.0]];
+        let found = db.query::<Grid>().by_matrix(matrix).fetch().collect::<Vec<_>>();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1, Grid::new(2.0, Some("two"), Some(None)));
+        assert_eq!(db.query::<Grid>().by_row(vec![6.0]).fetch().count(), 1);
+        assert_eq!(db.query::<Grid>().by_row(vec![]).fetch().count(), 3);
+
+        assert_eq!(db.query::<Grid>().by_labels(Some("one".to_string())).fetch().count(), 1);
+        assert_eq!(db.query::<Grid>().by_labels(None).fetch().count(), 3);
+
+        assert_eq!(db.query::<Grid>().by_limit(None).fetch().count(), 1);
+        assert_eq!(db.query::<Grid>().by_limit(Some(None)).fetch().count(), 1);
+        assert_eq!(db.query::<Grid>().by_limit(Some(Some(3))).fetch().count(), 1);
+        assert_eq!(db.query::<Grid>().by_limit_value(Some(3)).fetch().count(), 1);
+        assert_eq!(db.query::<Grid>().by_limit_value(None).fetch().count(), 1);
+        Ok(())
+    });
+}
+
+#[test]
+fn nested_raw() {
+    structsy_inst("nested_raw", |db| {
+        db.define::<Grid>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&Grid::new(1.0, Some("one"), Some(None)))?;
+        tx.commit()?;
+
+        let od = db.list_defined()?.find(|d| d.get_name() == "Grid").unwrap();
+        let simple = |name| ValueTypeBuilder::simple(SimpleValueTypeBuilder::from_name(name).build()).build();
+        let cell = SimpleValueTypeBuilder::embedded(
+            StructDescriptionBuilder::new("Cell")
+                .add_field(0, "value".to_owned(), simple("U32"), None)
+                .build(),
+        )
+        .build();
+        let desc = StructDescriptionBuilder::new("Grid")
+            .add_field(
+                0,
+                "matrix".to_owned(),
+                ValueTypeBuilder::array_of(ValueTypeBuilder::array_of(simple("F64")).build()).build(),
+                None,
+            )
+            .add_field(
+                1,
+                "labels".to_owned(),
+                ValueTypeBuilder::array_of(ValueTypeBuilder::option_of(simple("String")).build()).build(),
+                Some(structsy::ValueMode::Cluster),
+            )
+            .add_field(
+                2,
+                "limit".to_owned(),
+                ValueTypeBuilder::option_of(ValueTypeBuilder::option_of(simple("U32")).build()).build(),
+                None,
+            )
+            .add_field(
+                3,
+                "data".to_owned(),
+                ValueTypeBuilder::option_of(simple("Bytes")).build(),
+                None,
+            )
+            .add_field(
+                4,
+                "rows".to_owned(),
+                ValueTypeBuilder::array_of(ValueTypeBuilder::array(cell).build()).build(),
+                None,
+            )
+            .add_field(
+                5,
+                "chunks".to_owned(),
+                ValueTypeBuilder::array_of(simple("Bytes")).build(),
+                None,
+            )
+            .build();
+        assert_eq!(od, desc);
+
+        let (_, record) = db.raw_scan("Grid")?.next().unwrap();
+        match &record {
+            Record::Struct(st) => {
+                assert_eq!(
+                    st.field("limit").unwrap().value(),
+                    &Value::Option(Some(Box::new(Value::Option(None))))
+                );
+                assert_eq!(
+                    st.field("labels").unwrap().value(),
+                    &Value::Array(vec![
+                        Value::Option(Some(Box::new(Value::Value(SimpleValue::String("one".to_string()))))),
+                        Value::Option(None),
+                    ])
+                );
+            }
+            _ => panic!("wrong record"),
+        }
+
+        // The raw insert has to write the same layout and index keys of the typed insert
+        let mut raw_tx = db.raw_begin()?;
+        raw_tx.raw_insert(&record)?;
+        raw_tx.prepare()?.commit()?;
+        assert_eq!(db.query::<Grid>().by_labels(Some("one".to_string())).fetch().count(), 2);
+        let all = db.scan::<Grid>()?.map(|(_, g)| g).collect::<Vec<_>>();
+        assert_eq!(all, vec![Grid::new(1.0, Some("one"), Some(None)); 2]);
+        Ok(())
+    });
+}
+
+#[test]
+fn nested_desc_read_write() {
+    let desc = Grid::get_description();
+    let mut buff = Vec::new();
+    desc.write(&mut buff).unwrap();
+    let read_desc = Description::read(&mut Cursor::new(buff)).unwrap();
+    assert_eq!(desc, read_desc);
+}
+
+#[test]
+fn single_level_desc_compatible() {
+    // Struct "Old" with the field "opt" described with the single level option of array tag
+    let mut buff = vec![1];
+    buff.extend_from_slice(&3u32.to_be_bytes());
+    buff.extend_from_slice(b"Old");
+    buff.extend_from_slice(&1u32.to_be_bytes());
+    buff.extend_from_slice(&0u32.to_be_bytes());
+    buff.extend_from_slice(&3u32.to_be_bytes());
+    buff.extend_from_slice(b"opt");
+    buff.extend_from_slice(&[4, 3, 0]);
+
+    let desc = Description::read(&mut Cursor::new(buff.clone())).unwrap();
+    let expected = StructDescriptionBuilder::new("Old")
+        .add_field(
+            0,
+            "opt".to_owned(),
+            ValueTypeBuilder::option_array(SimpleValueTypeBuilder::from_name("U32").build()).build(),
+            None,
+        )
+        .build();
+    assert_eq!(desc, expected);
+    let mut written = Vec::new();
+    desc.write(&mut written).unwrap();
+    assert_eq!(written, buff);
+}
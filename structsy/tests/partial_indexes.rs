@@ -0,0 +1,188 @@
+use structsy::{record::Record, Order, RawAccess, SRes, Structsy, StructsyTx};
+use structsy_derive::{queries, Persistent};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct User {
+    #[index(mode = "exclusive", when = "self.active")]
+    email: String,
+    active: bool,
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Account {
+    #[index(mode = "cluster", when = "!self.closed")]
+    owner: String,
+    closed: bool,
+}
+
+#[queries(User)]
+trait UserQuery {
+    fn by_email(self, email: String) -> Self;
+    fn by_active(self, active: bool) -> Self;
+    fn order_by_email(self, email: Order) -> Self;
+}
+
+#[queries(Account)]
+trait AccountQuery {
+    fn by_owner(self, owner: String) -> Self;
+    fn by_closed(self, closed: bool) -> Self;
+}
+
+fn user(email: &str, active: bool) -> User {
+    User {
+        email: email.to_string(),
+        active,
+    }
+}
+
+#[test]
+fn partial_index_query() {
+    structsy_inst("partial_index_query", |db| {
+        db.define::<User>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&user("carol", true))?;
+        tx.insert(&user("bob", false))?;
+        tx.insert(&user("alice", true))?;
+        tx.commit()?;
+
+        // Without the condition of the index the query has to find also the not indexed records
+        assert_eq!(db.query::<User>().by_email("bob".to_string()).fetch().count(), 1);
+        let count = db
+            .query::<User>()
+            .by_active(true)
+            .by_email("bob".to_string())
+            .fetch()
+            .count();
+        assert_eq!(count, 0);
+        let count = db
+            .query::<User>()
+            .by_active(true)
+            .by_email("alice".to_string())
+            .fetch()
+            .count();
+        assert_eq!(count, 1);
+        let count = db
+            .query::<User>()
+            .by_active(false)
+            .by_email("bob".to_string())
+            .fetch()
+            .count();
+        assert_eq!(count, 1);
+
+        let emails = db
+            .query::<User>()
+            .order_by_email(Order::Asc)
+            .fetch()
+            .map(|(_, u)| u.email)
+            .collect::<Vec<_>>();
+        assert_eq!(emails, vec!["alice", "bob", "carol"]);
+        let emails = db
+            .query::<User>()
+            .by_active(true)
+            .order_by_email(Order::Desc)
+            .fetch()
+            .map(|(_, u)| u.email)
+            .collect::<Vec<_>>();
+        assert_eq!(emails, vec!["carol", "alice"]);
+        Ok(())
+    });
+}
+
+#[test]
+fn partial_index_negated_condition() {
+    structsy_inst("partial_index_negated_condition", |db| {
+        db.define::<Account>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&Account {
+            owner: "one".to_string(),
+            closed: false,
+        })?;
+        tx.insert(&Account {
+            owner: "one".to_string(),
+            closed: true,
+        })?;
+        tx.commit()?;
+        let count = db
+            .query::<Account>()
+            .by_closed(false)
+            .by_owner("one".to_string())
+            .fetch()
+            .count();
+        assert_eq!(count, 1);
+        assert_eq!(db.query::<Account>().by_owner("one".to_string()).fetch().count(), 2);
+        Ok(())
+    });
+}
+
+#[test]
+fn partial_index_maintenance() {
+    structsy_inst("partial_index_maintenance", |db| {
+        db.define::<User>()?;
+        let mut tx = db.begin()?;
+        let alice = tx.insert(&user("alice", true))?;
+        // Only active users are in the exclusive index
+        tx.insert(&user("bob", false))?;
+        tx.insert(&user("bob", false))?;
+        tx.commit()?;
+
+        let mut tx = db.begin()?;
+        tx.update(&alice, &user("alice", false))?;
+        tx.commit()?;
+        let mut tx = db.begin()?;
+        let other = tx.insert(&user("alice", true))?;
+        tx.commit()?;
+        let mut tx = db.begin()?;
+        tx.delete(&other)?;
+        tx.insert(&user("bob", true))?;
+        tx.commit()?;
+
+        let mut tx = db.begin()?;
+        tx.insert(&user("bob", true))?;
+        assert!(tx.commit().is_err());
+        Ok(())
+    });
+}
+
+#[test]
+fn partial_index_raw() {
+    structsy_inst("partial_index_raw", |db| {
+        db.define::<User>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&user("alice", true))?;
+        tx.commit()?;
+        let desc = db.list_defined()?.find(|d| d.get_name() == "User").unwrap();
+
+        let dir = tempdir().expect("can make a tempdir");
+        let other = Structsy::open(dir.path().join("other.stry"))?;
+        other.raw_define(desc)?;
+        other.define::<User>()?;
+        let (_, record) = db.raw_scan("User")?.next().unwrap();
+        let mut raw_tx = other.raw_begin()?;
+        raw_tx.raw_insert(&record)?;
+        raw_tx.prepare()?.commit()?;
+
+        // The raw insert follows the condition stored in the description
+        let mut inactive = record.clone();
+        if let Record::Struct(s) = &mut inactive {
+            s.set_field("active", false)?;
+        }
+        let mut raw_tx = other.raw_begin()?;
+        raw_tx.raw_insert(&inactive)?;
+        raw_tx.prepare()?.commit()?;
+        assert_eq!(other.query::<User>().by_email("alice".to_string()).fetch().count(), 2);
+
+        let mut raw_tx = other.raw_begin()?;
+        raw_tx.raw_insert(&record)?;
+        assert!(raw_tx.prepare().is_err());
+        Ok(())
+    });
+}
@@ -1,5 +1,6 @@
+use std::collections::{BTreeMap, HashMap};
 use std::ops::RangeBounds;
-use structsy::{Filter, Operators, SRes, Structsy, StructsyTx};
+use structsy::{Filter, Operators, Prefix, SRes, Structsy, StructsyTx};
 use structsy_derive::{embedded_queries, queries, Persistent, PersistentEmbedded};
 use tempfile::tempdir;
 
@@ -763,3 +764,188 @@ pub fn basic_not_query() {
         Ok(())
     });
 }
+
+#[derive(Persistent)]
+struct BasicMap {
+    tags: BTreeMap<String, u32>,
+    scores: HashMap<u32, String>,
+}
+impl BasicMap {
+    fn new(tags: &[(&str, u32)]) -> BasicMap {
+        BasicMap {
+            tags: tags.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+            scores: tags.iter().map(|(k, v)| (*v, k.to_string())).collect(),
+        }
+    }
+}
+
+#[queries(BasicMap)]
+trait BasicMapQuery {
+    fn by_tag(self, tags: String) -> Self;
+    fn by_tag_str(self, tags: &str) -> Self;
+    fn by_tag_value(self, tags: (String, u32)) -> Self;
+    fn by_score(self, scores: u32) -> Self;
+    fn by_score_value(self, scores: (u32, String)) -> Self;
+}
+
+#[test]
+pub fn basic_map_query() {
+    structsy_inst("basic_map_query", |db| {
+        db.define::<BasicMap>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&BasicMap::new(&[("aaa", 1), ("bbb", 2)]))?;
+        tx.insert(&BasicMap::new(&[("aaa", 3)]))?;
+        tx.insert(&BasicMap::new(&[]))?;
+        tx.commit()?;
+        let count = db.query::<BasicMap>().by_tag("aaa".to_string()).fetch().count();
+        assert_eq!(count, 2);
+        let count = db.query::<BasicMap>().by_tag_str("bbb").fetch().count();
+        assert_eq!(count, 1);
+        let count = db.query::<BasicMap>().by_tag_str("ccc").fetch().count();
+        assert_eq!(count, 0);
+        let count = db
+            .query::<BasicMap>()
+            .by_tag_value(("aaa".to_string(), 3))
+            .fetch()
+            .count();
+        assert_eq!(count, 1);
+        let count = db
+            .query::<BasicMap>()
+            .by_tag_value(("bbb".to_string(), 3))
+            .fetch()
+            .count();
+        assert_eq!(count, 0);
+        let count = db.query::<BasicMap>().by_score(2).fetch().count();
+        assert_eq!(count, 1);
+        let count = db
+            .query::<BasicMap>()
+            .by_score_value((1, "aaa".to_string()))
+            .fetch()
+            .count();
+        assert_eq!(count, 1);
+        let count = db.query::<BasicMap>().not(|not| not.by_tag_str("aaa")).fetch().count();
+        assert_eq!(count, 1);
+        Ok(())
+    });
+}
+
+#[derive(Persistent)]
+struct BasicBytes {
+    data: Vec<u8>,
+    hash: [u8; 4],
+}
+impl BasicBytes {
+    fn new(data: &[u8]) -> BasicBytes {
+        let mut hash = [0; 4];
+        for (h, d) in hash.iter_mut().zip(data.iter()) {
+            *h = *d;
+        }
+        BasicBytes {
+            data: data.to_vec(),
+            hash,
+        }
+    }
+}
+
+#[queries(BasicBytes)]
+trait BasicBytesQuery {
+    fn by_data(self, data: Vec<u8>) -> Self;
+    fn by_data_prefix(self, data: Prefix<Vec<u8>>) -> Self;
+    fn by_hash(self, hash: [u8; 4]) -> Self;
+    fn by_hash_prefix(self, hash: Prefix<Vec<u8>>) -> Self;
+}
+
+#[test]
+pub fn basic_bytes_query() {
+    structsy_inst("basic_bytes_query", |db| {
+        db.define::<BasicBytes>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&BasicBytes::new(&[1, 2, 3]))?;
+        tx.insert(&BasicBytes::new(&[1, 2, 4, 5, 6]))?;
+        tx.insert(&BasicBytes::new(&[2]))?;
+        tx.commit()?;
+        let count = db.query::<BasicBytes>().by_data(vec![1, 2, 3]).fetch().count();
+        assert_eq!(count, 1);
+        let count = db.query::<BasicBytes>().by_data(vec![1, 2]).fetch().count();
+        assert_eq!(count, 0);
+        let count = db
+            .query::<BasicBytes>()
+            .by_data_prefix(Prefix(vec![1, 2]))
+            .fetch()
+            .count();
+        assert_eq!(count, 2);
+        let count = db
+            .query::<BasicBytes>()
+            .by_data_prefix(Prefix(vec![2, 1]))
+            .fetch()
+            .count();
+        assert_eq!(count, 0);
+        let count = db.query::<BasicBytes>().by_data_prefix(Prefix(vec![])).fetch().count();
+        assert_eq!(count, 3);
+        let count = db.query::<BasicBytes>().by_hash([1, 2, 4, 5]).fetch().count();
+        assert_eq!(count, 1);
+        let count = db.query::<BasicBytes>().by_hash_prefix(Prefix(vec![1])).fetch().count();
+        assert_eq!(count, 2);
+        let count = db
+            .query::<BasicBytes>()
+            .not(|not| not.by_hash_prefix(Prefix(vec![1, 2, 3])))
+            .fetch()
+            .count();
+        assert_eq!(count, 2);
+        Ok(())
+    });
+}
+
+#[derive(Persistent)]
+struct IndexedBytes {
+    #[index(mode = "cluster")]
+    data: Vec<u8>,
+    #[index(mode = "cluster")]
+    thumb: Option<Vec<u8>>,
+}
+
+#[queries(IndexedBytes)]
+trait IndexedBytesQuery {
+    fn by_data(self, data: Vec<u8>) -> Self;
+    fn by_data_contains(self, data: u8) -> Self;
+    fn by_thumb(self, thumb: Option<Vec<u8>>) -> Self;
+}
+
+#[test]
+pub fn indexed_bytes_query() {
+    structsy_inst("indexed_bytes_query", |db| {
+        db.define::<IndexedBytes>()?;
+        let mut tx = db.begin()?;
+        let id = tx.insert(&IndexedBytes {
+            data: vec![1, 2, 3],
+            thumb: Some(vec![7]),
+        })?;
+        tx.insert(&IndexedBytes {
+            data: vec![3, 4],
+            thumb: None,
+        })?;
+        tx.commit()?;
+        let count = db.query::<IndexedBytes>().by_data(vec![1, 2, 3]).fetch().count();
+        assert_eq!(count, 1);
+        let count = db.query::<IndexedBytes>().by_data_contains(3).fetch().count();
+        assert_eq!(count, 2);
+        let count = db.query::<IndexedBytes>().by_data_contains(4).fetch().count();
+        assert_eq!(count, 1);
+        let count = db.query::<IndexedBytes>().by_thumb(Some(vec![7])).fetch().count();
+        assert_eq!(count, 1);
+        let mut tx = db.begin()?;
+        tx.update(
+            &id,
+            &IndexedBytes {
+                data: vec![5],
+                thumb: None,
+            },
+        )?;
+        tx.commit()?;
+        let count = db.query::<IndexedBytes>().by_data_contains(1).fetch().count();
+        assert_eq!(count, 0);
+        let count = db.query::<IndexedBytes>().by_data_contains(5).fetch().count();
+        assert_eq!(count, 1);
+        Ok(())
+    });
+}
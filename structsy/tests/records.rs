@@ -1,3 +1,4 @@
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
 
 use structsy::{
@@ -283,3 +284,174 @@ fn test_description_mapping_enum() {
         Ok(())
     });
 }
+
+#[derive(Persistent)]
+struct WithMap {
+    values: BTreeMap<String, u32>,
+}
+
+#[test]
+fn test_raw_map_field() {
+    structsy_inst("raw_map_field", |db| {
+        db.define::<WithMap>()?;
+        let mut tx = db.begin()?;
+        let mut values = BTreeMap::new();
+        values.insert("aaa".to_string(), 1);
+        tx.insert(&WithMap { values })?;
+        tx.commit()?;
+
+        let od = db.list_defined()?.find(|d| d.get_name() == "WithMap").unwrap();
+        let field_type = ValueTypeBuilder::map(
+            SimpleValueTypeBuilder::from_name("String").build(),
+            SimpleValueTypeBuilder::from_name("U32").build(),
+        )
+        .build();
+        let desc = StructDescriptionBuilder::new("WithMap")
+            .add_field(0, "values".to_owned(), field_type, None)
+            .build();
+        assert_eq!(od, desc);
+
+        let (id, mut record) = db.raw_scan("WithMap")?.next().unwrap();
+        match &record {
+            Record::Struct(st) => match st.field("values").unwrap().value() {
+                Value::Map(m) => assert_eq!(m, &vec![(SimpleValue::String("aaa".to_string()), SimpleValue::U32(1))]),
+                _ => panic!("wrong value"),
+            },
+            _ => panic!("wrong record"),
+        }
+        let mut values = HashMap::new();
+        values.insert("bbb".to_string(), 2u32);
+        match &mut record {
+            Record::Struct(rec) => rec.set_field("values", values)?,
+            _ => panic!("wrong record"),
+        }
+        let mut tx = db.raw_begin()?;
+        tx.raw_update(&id, &record)?;
+        tx.prepare()?.commit()?;
+        let (_, read) = db.scan::<WithMap>()?.next().unwrap();
+        assert_eq!(read.values.get("bbb"), Some(&2));
+        assert_eq!(read.values.len(), 1);
+        Ok(())
+    });
+}
+
+#[derive(Persistent)]
+struct WithBytes {
+    data: Vec<u8>,
+    hash: [u8; 4],
+    thumb: Option<Vec<u8>>,
+}
+
+#[test]
+fn test_raw_bytes_field() {
+    structsy_inst("raw_bytes_field", |db| {
+        db.define::<WithBytes>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&WithBytes {
+            data: vec![1, 2, 3],
+            hash: [4, 5, 6, 7],
+            thumb: None,
+        })?;
+        tx.commit()?;
+
+        let od = db.list_defined()?.find(|d| d.get_name() == "WithBytes").unwrap();
+        let bytes = || SimpleValueTypeBuilder::from_name("Bytes").build();
+        let desc = StructDescriptionBuilder::new("WithBytes")
+            .add_field(0, "data".to_owned(), ValueTypeBuilder::simple(bytes()).build(), None)
+            .add_field(1, "hash".to_owned(), ValueTypeBuilder::simple(bytes()).build(), None)
+            .add_field(2, "thumb".to_owned(), ValueTypeBuilder::option(bytes()).build(), None)
+            .build();
+        assert_eq!(od, desc);
+
+        let (id, mut record) = db.raw_scan("WithBytes")?.next().unwrap();
+        match &record {
+            Record::Struct(st) => {
+                match st.field("data").unwrap().value() {
+                    Value::Value(SimpleValue::Bytes(b)) => assert_eq!(b, &vec![1, 2, 3]),
+                    _ => panic!("wrong value"),
+                }
+                match st.field("hash").unwrap().value() {
+                    Value::Value(SimpleValue::Bytes(b)) => assert_eq!(b, &vec![4, 5, 6, 7]),
+                    _ => panic!("wrong value"),
+                }
+            }
+            _ => panic!("wrong record"),
+        }
+        match &mut record {
+            Record::Struct(rec) => {
+                rec.set_field("thumb", Some(vec![8u8, 9]))?;
+                rec.set_field("hash", [0u8, 1, 2, 3])?;
+            }
+            _ => panic!("wrong record"),
+        }
+        let mut tx = db.raw_begin()?;
+        tx.raw_update(&id, &record)?;
+        tx.prepare()?.commit()?;
+        let (_, read) = db.scan::<WithBytes>()?.next().unwrap();
+        assert_eq!(read.data, vec![1, 2, 3]);
+        assert_eq!(read.hash, [0, 1, 2, 3]);
+        assert_eq!(read.thumb, Some(vec![8, 9]));
+        Ok(())
+    });
+}
+
+#[derive(Persistent)]
+struct WithIndexedBytes {
+    #[index(mode = "cluster")]
+    data: Vec<u8>,
+    #[index(mode = "cluster")]
+    thumb: Option<Vec<u8>>,
+}
+
+#[test]
+fn test_indexed_bytes_description() {
+    structsy_inst("indexed_bytes_description", |db| {
+        db.define::<WithIndexedBytes>()?;
+        // Indexed byte arrays keep the description of the arrays of u8 indexed by element
+        let od = db.list_defined()?.find(|d| d.get_name() == "WithIndexedBytes").unwrap();
+        let u8_type = || SimpleValueTypeBuilder::from_name("U8").build();
+        let mode = Some(structsy::ValueMode::Cluster);
+        let desc = StructDescriptionBuilder::new("WithIndexedBytes")
+            .add_field(
+                0,
+                "data".to_owned(),
+                ValueTypeBuilder::array(u8_type()).build(),
+                mode.clone(),
+            )
+            .add_field(
+                1,
+                "thumb".to_owned(),
+                ValueTypeBuilder::option_array(u8_type()).build(),
+                mode,
+            )
+            .build();
+        assert_eq!(od, desc);
+        let mut tx = db.begin()?;
+        tx.insert(&WithIndexedBytes {
+            data: vec![1, 2],
+            thumb: Some(vec![3]),
+        })?;
+        tx.commit()?;
+        let (_, record) = db.raw_scan("WithIndexedBytes")?.next().unwrap();
+        match &record {
+            Record::Struct(st) => match st.field("data").unwrap().value() {
+                Value::Array(values) => assert_eq!(
+                    values,
+                    &vec![Value::Value(SimpleValue::U8(1)), Value::Value(SimpleValue::U8(2))]
+                ),
+                _ => panic!("wrong value"),
+            },
+            _ => panic!("wrong record"),
+        }
+        let (id, mut record) = db.raw_scan("WithIndexedBytes")?.next().unwrap();
+        if let Record::Struct(rec) = &mut record {
+            rec.set_field("data", vec![9u8])?;
+        }
+        let mut tx = db.raw_begin()?;
+        tx.raw_update(&id, &record)?;
+        tx.prepare()?.commit()?;
+        let (_, read) = db.scan::<WithIndexedBytes>()?.next().unwrap();
+        assert_eq!(read.data, vec![9]);
+        Ok(())
+    });
+}
@@ -0,0 +1,156 @@
+use structsy::{Structsy, StructsyTx};
+use tempfile::tempdir;
+
+mod old {
+    use structsy::Ref;
+    use structsy_derive::{Persistent, PersistentEmbedded};
+
+    #[derive(Persistent)]
+    pub struct Client {
+        #[index(mode = "cluster")]
+        pub name: String,
+    }
+
+    #[derive(Persistent)]
+    pub struct Order {
+        pub client: Ref<Client>,
+    }
+
+    #[derive(Persistent)]
+    pub enum Holder {
+        Client(ClientRef),
+        Nobody,
+    }
+
+    #[derive(PersistentEmbedded)]
+    pub struct ClientRef {
+        pub client: Ref<Client>,
+    }
+}
+
+mod new {
+    use structsy::Ref;
+    use structsy_derive::{queries, Persistent, PersistentEmbedded};
+
+    #[derive(Persistent)]
+    pub struct Customer {
+        #[index(mode = "cluster")]
+        pub name: String,
+    }
+
+    #[derive(Persistent)]
+    pub struct Order {
+        pub client: Ref<Customer>,
+    }
+
+    #[derive(Persistent)]
+    pub enum Holder {
+        Client(ClientRef),
+        Nobody,
+    }
+
+    #[derive(PersistentEmbedded)]
+    pub struct ClientRef {
+        pub client: Ref<Customer>,
+    }
+
+    #[queries(Customer)]
+    pub trait CustomerQuery {
+        fn by_name(self, name: String) -> Self;
+    }
+}
+
+mod legacy {
+    use structsy_derive::Persistent;
+
+    #[derive(Persistent)]
+    #[persistent(name = "Client")]
+    pub struct Customer {
+        #[index(mode = "cluster")]
+        pub name: String,
+    }
+}
+
+fn fill(db: &Structsy) {
+    db.define::<old::Client>().unwrap();
+    db.define::<old::Order>().unwrap();
+    db.define::<old::Holder>().unwrap();
+    let mut tx = db.begin().unwrap();
+    let client = tx
+        .insert(&old::Client {
+            name: "aaa".to_string(),
+        })
+        .unwrap();
+    tx.insert(&old::Order { client: client.clone() }).unwrap();
+    tx.insert(&old::Holder::Client(old::ClientRef { client })).unwrap();
+    tx.commit().unwrap();
+}
+
+#[test]
+fn test_rename_type() {
+    use new::CustomerQuery;
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("rename.stry");
+    {
+        let db = Structsy::open(&file).unwrap();
+        fill(&db);
+    }
+    {
+        let db = Structsy::open(&file).unwrap();
+        db.rename_type("Client", "Customer").unwrap();
+        db.define::<new::Customer>().unwrap();
+        db.define::<new::Order>().unwrap();
+        db.define::<new::Holder>().unwrap();
+    }
+    let db = Structsy::open(&file).unwrap();
+    db.define::<new::Customer>().unwrap();
+    db.define::<new::Order>().unwrap();
+    db.define::<new::Holder>().unwrap();
+    let (id, customer) = db
+        .query::<new::Customer>()
+        .by_name("aaa".to_string())
+        .fetch()
+        .next()
+        .unwrap();
+    assert_eq!(customer.name, "aaa");
+    let (_, order) = db.scan::<new::Order>().unwrap().next().unwrap();
+    assert_eq!(order.client, id);
+    let (_, holder) = db.scan::<new::Holder>().unwrap().next().unwrap();
+    match holder {
+        new::Holder::Client(r) => assert_eq!(r.client, id),
+        new::Holder::Nobody => panic!("wrong variant"),
+    }
+    let mut tx = db.begin().unwrap();
+    tx.insert(&new::Customer {
+        name: "bbb".to_string(),
+    })
+    .unwrap();
+    tx.commit().unwrap();
+    assert_eq!(
+        db.query::<new::Customer>().by_name("bbb".to_string()).fetch().count(),
+        1
+    );
+    assert!(db.rename_type("Client", "Other").is_err());
+    assert!(db.rename_type("Order", "Customer").is_err());
+}
+
+#[test]
+fn test_persistent_name_attribute() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("rename_attribute.stry");
+    {
+        let db = Structsy::open(&file).unwrap();
+        fill(&db);
+    }
+    let db = Structsy::open(&file).unwrap();
+    db.define::<legacy::Customer>().unwrap();
+    let (_, customer) = db.scan::<legacy::Customer>().unwrap().next().unwrap();
+    assert_eq!(customer.name, "aaa");
+    let mut tx = db.begin().unwrap();
+    tx.insert(&legacy::Customer {
+        name: "bbb".to_string(),
+    })
+    .unwrap();
+    tx.commit().unwrap();
+    assert_eq!(db.scan::<old::Client>().unwrap().count(), 2);
+}
@@ -0,0 +1,186 @@
+use structsy::{internal::Persistent, SchemaChange, Structsy, StructsyError, ValueMode};
+use tempfile::tempdir;
+
+mod stored {
+    use structsy_derive::{Persistent, PersistentEmbedded};
+
+    #[derive(Persistent)]
+    pub struct Data {
+        pub name: String,
+        pub size: u32,
+        pub old: bool,
+    }
+
+    #[derive(Persistent)]
+    pub enum Status {
+        Active,
+        Removed(String),
+    }
+
+    #[derive(Persistent)]
+    pub struct Other {
+        pub emb: Emb,
+    }
+
+    #[derive(PersistentEmbedded)]
+    pub struct Emb {
+        pub name: String,
+    }
+
+    #[derive(Persistent)]
+    pub struct Place {
+        #[index(mode = "exclusive")]
+        pub code: String,
+        pub active: bool,
+        pub embedding: Vec<f32>,
+        pub point: structsy::GeoPoint,
+    }
+}
+
+mod current {
+    use structsy_derive::Persistent;
+
+    #[derive(Persistent)]
+    pub struct Data {
+        #[index(mode = "cluster")]
+        pub name: String,
+        pub size: u64,
+        pub surname: String,
+    }
+
+    #[derive(Persistent)]
+    pub enum Status {
+        Removed(u32),
+        Active,
+        Suspended,
+    }
+
+    #[derive(Persistent)]
+    pub struct Place {
+        #[index(mode = "exclusive", when = "self.active")]
+        pub code: String,
+        pub active: bool,
+        #[index(vector(dim = 2, metric = "euclidean"))]
+        pub embedding: Vec<f32>,
+        #[index(geo)]
+        pub point: structsy::GeoPoint,
+    }
+}
+
+#[test]
+fn test_schema_diff() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("schema_diff.stry");
+    let db = Structsy::open(&file).unwrap();
+    db.define::<stored::Data>().unwrap();
+    db.define::<stored::Status>().unwrap();
+    db.define::<stored::Other>().unwrap();
+
+    assert!(db.schema_diff::<stored::Data>().unwrap().is_empty());
+
+    let diff = db.schema_diff::<current::Data>().unwrap();
+    assert_eq!(diff.name(), "Data");
+    let changes = diff.changes().cloned().collect::<Vec<_>>();
+    assert_eq!(changes.len(), 4);
+    assert!(changes.contains(&SchemaChange::IndexChanged {
+        name: "name".to_owned(),
+        stored: None,
+        current: Some(ValueMode::Cluster),
+    }));
+    assert!(changes
+        .iter()
+        .any(|c| matches!(c, SchemaChange::FieldRetyped { name, .. } if name == "size")));
+    assert!(changes
+        .iter()
+        .any(|c| matches!(c, SchemaChange::FieldRemoved(f) if f.name() == "old")));
+    assert!(changes
+        .iter()
+        .any(|c| matches!(c, SchemaChange::FieldAdded(f) if f.name() == "surname")));
+
+    let diff = db.schema_diff::<current::Status>().unwrap();
+    let changes = diff.changes().cloned().collect::<Vec<_>>();
+    assert_eq!(changes.len(), 4);
+    assert!(changes.contains(&SchemaChange::VariantMoved {
+        name: "Active".to_owned(),
+        stored: 0,
+        current: 1,
+    }));
+    assert!(changes.contains(&SchemaChange::VariantMoved {
+        name: "Removed".to_owned(),
+        stored: 1,
+        current: 0,
+    }));
+    assert!(changes
+        .iter()
+        .any(|c| matches!(c, SchemaChange::VariantRetyped { name, .. } if name == "Removed")));
+    assert!(changes
+        .iter()
+        .any(|c| matches!(c, SchemaChange::VariantAdded(v) if v.name() == "Suspended")));
+}
+
+#[test]
+fn test_schema_diff_index_kinds() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("schema_diff_index_kinds.stry");
+    let db = Structsy::open(&file).unwrap();
+    db.define::<stored::Place>().unwrap();
+
+    let diff = db.schema_diff::<current::Place>().unwrap();
+    let changes = diff.changes().cloned().collect::<Vec<_>>();
+    assert_eq!(changes.len(), 3);
+    assert!(changes.iter().any(|c| matches!(
+        c,
+        SchemaChange::IndexConditionChanged { name, stored: None, current: Some(cond) }
+            if name == "code" && cond.field() == "active" && cond.value()
+    )));
+    assert!(changes.iter().any(|c| matches!(
+        c,
+        SchemaChange::VectorIndexChanged { name, stored: None, current: Some(vector) }
+            if name == "embedding" && vector.dim() == 2 && vector.metric() == "euclidean"
+    )));
+    assert!(changes.contains(&SchemaChange::GeoIndexChanged {
+        name: "point".to_owned(),
+        stored: false,
+        current: true,
+    }));
+    assert!(db.define::<current::Place>().is_err());
+}
+
+#[test]
+fn test_schema_diff_all() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("schema_diff_all.stry");
+    let db = Structsy::open(&file).unwrap();
+    db.define::<stored::Data>().unwrap();
+    db.define::<stored::Other>().unwrap();
+
+    let diffs = db
+        .schema_diff_all(&[current::Data::get_description(), current::Status::get_description()])
+        .unwrap();
+    assert_eq!(diffs.len(), 3);
+    let other = diffs.iter().find(|d| d.name() == "Other").unwrap();
+    assert_eq!(other.changes().collect::<Vec<_>>(), vec![&SchemaChange::TypeRemoved]);
+    let status = diffs.iter().find(|d| d.name() == "Status").unwrap();
+    assert_eq!(status.changes().collect::<Vec<_>>(), vec![&SchemaChange::TypeAdded]);
+    assert!(!diffs.iter().find(|d| d.name() == "Data").unwrap().is_empty());
+
+    let diffs = db
+        .schema_diff_all(&[stored::Data::get_description(), stored::Other::get_description()])
+        .unwrap();
+    assert!(diffs.is_empty());
+}
+
+#[test]
+fn test_description_mismatch_error() {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join("schema_mismatch.stry");
+    {
+        let db = Structsy::open(&file).unwrap();
+        db.define::<stored::Data>().unwrap();
+    }
+    let db = Structsy::open(&file).unwrap();
+    match db.scan::<current::Data>() {
+        Err(StructsyError::StructDescriptionMismatch(name)) => assert_eq!(name, "Data"),
+        _ => panic!("expected a mismatch error"),
+    }
+}
@@ -0,0 +1,196 @@
+use std::ops::RangeBounds;
+use structsy::{
+    internal::{SimpleValueTypeBuilder, StructDescriptionBuilder, ValueTypeBuilder},
+    record::{Record, SimpleValue, Value},
+    RawAccess, SRes, Structsy, StructsyTx,
+};
+use structsy_derive::{queries, Persistent, PersistentEmbedded};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(PersistentEmbedded, Debug, PartialEq, Clone)]
+struct Label {
+    text: String,
+}
+
+#[derive(Persistent, Debug, PartialEq)]
+struct Shape {
+    #[index(mode = "cluster")]
+    pos: (i32, String),
+    color: [f32; 3],
+    tag: [u8; 2],
+    names: [String; 2],
+    steps: Vec<(u8, bool)>,
+    label: Option<(Label, u64)>,
+}
+
+impl Shape {
+    fn new(x: i32, name: &str) -> Shape {
+        Shape {
+            pos: (x, name.to_string()),
+            color: [x as f32, 0.5, -1.0],
+            tag: [1, 2],
+            names: [name.to_string(), format!("{}{}", name, name)],
+            steps: vec![(1, true), (2, false)],
+            label: Some((Label { text: name.to_string() }, 10)),
+        }
+    }
+}
+
+#[queries(Shape)]
+trait ShapeQuery {
+    fn by_pos(self, pos: (i32, String)) -> Self;
+    fn by_pos_range<R: RangeBounds<(i32, String)>>(self, pos: R) -> Self;
+}
+
+#[test]
+fn tuple_array_persistence() {
+    structsy_inst("tuple_array_persistence", |db| {
+        db.define::<Shape>()?;
+        let mut tx = db.begin()?;
+        let id = tx.insert(&Shape::new(-3, "a"))?;
+        tx.commit()?;
+        assert_eq!(db.read(&id)?, Some(Shape::new(-3, "a")));
+        Ok(())
+    });
+}
+
+#[test]
+fn tuple_index_query() {
+    structsy_inst("tuple_index_query", |db| {
+        db.define::<Shape>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&Shape::new(-1, "b"))?;
+        tx.insert(&Shape::new(-1, "ab"))?;
+        tx.insert(&Shape::new(-1, ""))?;
+        tx.insert(&Shape::new(0, "a\0b"))?;
+        tx.insert(&Shape::new(2, "a"))?;
+        tx.commit()?;
+
+        let found = db
+            .query::<Shape>()
+            .by_pos((-1, "ab".to_string()))
+            .fetch()
+            .collect::<Vec<_>>();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1, Shape::new(-1, "ab"));
+
+        let from = (-1, "ab".to_string());
+        let to = (2, "a".to_string());
+        assert_eq!(
+            db.query::<Shape>()
+                .by_pos_range(from.clone()..to.clone())
+                .fetch()
+                .count(),
+            3
+        );
+        assert_eq!(db.query::<Shape>().by_pos_range(..from).fetch().count(), 1);
+        assert_eq!(db.query::<Shape>().by_pos_range(to..).fetch().count(), 1);
+        let zero = (0, String::new());
+        assert_eq!(db.query::<Shape>().by_pos_range(..zero).fetch().count(), 3);
+        Ok(())
+    });
+}
+
+#[test]
+fn tuple_array_raw() {
+    structsy_inst("tuple_array_raw", |db| {
+        db.define::<Shape>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&Shape::new(5, "x"))?;
+        tx.commit()?;
+
+        let od = db.list_defined()?.find(|d| d.get_name() == "Shape").unwrap();
+        let simple = |name| SimpleValueTypeBuilder::from_name(name).build();
+        let label = SimpleValueTypeBuilder::embedded(
+            StructDescriptionBuilder::new("Label")
+                .add_field(
+                    0,
+                    "text".to_owned(),
+                    ValueTypeBuilder::simple(simple("String")).build(),
+                    None,
+                )
+                .build(),
+        )
+        .build();
+        let desc = StructDescriptionBuilder::new("Shape")
+            .add_field(
+                0,
+                "pos".to_owned(),
+                ValueTypeBuilder::simple(SimpleValueTypeBuilder::tuple(vec![simple("I32"), simple("String")]).build())
+                    .build(),
+                Some(structsy::ValueMode::Cluster),
+            )
+            .add_field(
+                1,
+                "color".to_owned(),
+                ValueTypeBuilder::simple(SimpleValueTypeBuilder::fixed_array(simple("F32"), 3).build()).build(),
+                None,
+            )
+            .add_field(
+                2,
+                "tag".to_owned(),
+                ValueTypeBuilder::simple(simple("Bytes")).build(),
+                None,
+            )
+            .add_field(
+                3,
+                "names".to_owned(),
+                ValueTypeBuilder::simple(SimpleValueTypeBuilder::fixed_array(simple("String"), 2).build()).build(),
+                None,
+            )
+            .add_field(
+                4,
+                "steps".to_owned(),
+                ValueTypeBuilder::array(SimpleValueTypeBuilder::tuple(vec![simple("U8"), simple("Bool")]).build())
+                    .build(),
+                None,
+            )
+            .add_field(
+                5,
+                "label".to_owned(),
+                ValueTypeBuilder::option(SimpleValueTypeBuilder::tuple(vec![label, simple("U64")]).build()).build(),
+                None,
+            )
+            .build();
+        assert_eq!(od, desc);
+
+        let (_, record) = db.raw_scan("Shape")?.next().unwrap();
+        match &record {
+            Record::Struct(st) => {
+                match st.field("pos").unwrap().value() {
+                    Value::Value(SimpleValue::Tuple(v)) => {
+                        assert_eq!(v, &vec![SimpleValue::I32(5), SimpleValue::String("x".to_string())])
+                    }
+                    _ => panic!("wrong value"),
+                }
+                match st.field("color").unwrap().value() {
+                    Value::Value(SimpleValue::FixedArray(v)) => {
+                        assert_eq!(
+                            v,
+                            &vec![SimpleValue::F32(5.0), SimpleValue::F32(0.5), SimpleValue::F32(-1.0)]
+                        )
+                    }
+                    _ => panic!("wrong value"),
+                }
+            }
+            _ => panic!("wrong record"),
+        }
+
+        // The raw insert has to produce the same composite key of the typed insert
+        let mut raw_tx = db.raw_begin()?;
+        raw_tx.raw_insert(&record)?;
+        raw_tx.prepare()?.commit()?;
+        assert_eq!(db.query::<Shape>().by_pos((5, "x".to_string())).fetch().count(), 2);
+        let all = db.scan::<Shape>()?.map(|(_, s)| s).collect::<Vec<_>>();
+        assert_eq!(all, vec![Shape::new(5, "x"), Shape::new(5, "x")]);
+        Ok(())
+    });
+}
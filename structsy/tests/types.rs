@@ -1,3 +1,4 @@
+use std::collections::{BTreeMap, HashMap};
 use structsy::{Ref, Structsy, StructsyTx};
 use structsy_derive::{Persistent, PersistentEmbedded};
 use tempfile::tempdir;
@@ -19,6 +20,8 @@ struct AllTypes {
     val_f64: f64,
     val_ref: Ref<Other>,
     val_emb: Embed,
+    val_map: BTreeMap<String, Embed>,
+    val_hash_map: HashMap<u32, Ref<Other>>,
 }
 #[derive(Persistent, PartialEq, Debug)]
 struct Other {
@@ -67,8 +70,10 @@ fn test_persist_all_values() {
         val_i128: 19,
         val_f32: 20.0,
         val_f64: 21.0,
-        val_ref: ref_id,
+        val_ref: ref_id.clone(),
         val_emb: Embed { val_u8: 50 },
+        val_map: vec![("aa".to_string(), Embed { val_u8: 51 })].into_iter().collect(),
+        val_hash_map: vec![(1, ref_id.clone())].into_iter().collect(),
     };
     let id = tx.insert(&all).expect("inserted correctly");
     tx.commit().expect("committed correctly");
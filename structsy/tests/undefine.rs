@@ -0,0 +1,85 @@
+use structsy::{Ref, Structsy, StructsyError, StructsyTx, UndefinePolicy};
+use structsy_derive::Persistent;
+use tempfile::tempdir;
+
+#[derive(Persistent)]
+struct Target {
+    #[index(mode = "cluster")]
+    name: String,
+}
+
+#[derive(Persistent)]
+struct Referrer {
+    target: Ref<Target>,
+}
+
+#[derive(Persistent)]
+struct Indirect {
+    referrer: Ref<Referrer>,
+}
+
+fn open(name: &str) -> (tempfile::TempDir, Structsy) {
+    let dir = tempdir().expect("can make a tempdir");
+    let db = Structsy::open(dir.path().join(name)).unwrap();
+    db.define::<Target>().unwrap();
+    db.define::<Referrer>().unwrap();
+    db.define::<Indirect>().unwrap();
+    let mut tx = db.begin().unwrap();
+    let target = tx
+        .insert(&Target {
+            name: "one".to_string(),
+        })
+        .unwrap();
+    let referrer = tx.insert(&Referrer { target }).unwrap();
+    tx.insert(&Indirect { referrer }).unwrap();
+    tx.commit().unwrap();
+    (dir, db)
+}
+
+#[test]
+fn test_undefine_fail() {
+    let (_dir, db) = open("undefine_fail.stry");
+    match db.undefine_with::<Target>(UndefinePolicy::Fail) {
+        Err(StructsyError::StructReferenced(name, other)) => {
+            assert_eq!(name, "Target");
+            assert_eq!(other, "Referrer");
+        }
+        _ => panic!("expected a referenced error"),
+    }
+    assert!(db.is_defined::<Target>().unwrap());
+    assert_eq!(db.scan::<Target>().unwrap().count(), 1);
+    db.undefine_with::<Indirect>(UndefinePolicy::Fail).unwrap();
+    assert!(!db.is_defined::<Indirect>().unwrap());
+}
+
+#[test]
+fn test_undefine_cascade() {
+    let (_dir, db) = open("undefine_cascade.stry");
+    db.undefine_with::<Target>(UndefinePolicy::Cascade).unwrap();
+    assert!(!db.is_defined::<Target>().unwrap());
+    assert!(!db.is_defined::<Referrer>().unwrap());
+    assert!(!db.is_defined::<Indirect>().unwrap());
+    assert_eq!(db.list_defined().unwrap().count(), 0);
+
+    // Indexes are dropped as well, so the type can be defined again
+    db.define::<Target>().unwrap();
+    let mut tx = db.begin().unwrap();
+    tx.insert(&Target {
+        name: "one".to_string(),
+    })
+    .unwrap();
+    tx.commit().unwrap();
+    assert_eq!(db.scan::<Target>().unwrap().count(), 1);
+}
+
+#[test]
+fn test_undefine_ignore_by_name() {
+    let (_dir, db) = open("undefine_ignore.stry");
+    db.raw_undefine("Target", UndefinePolicy::Ignore).unwrap();
+    assert!(!db.is_defined::<Target>().unwrap());
+    assert!(db.is_defined::<Referrer>().unwrap());
+    assert!(db.is_defined::<Indirect>().unwrap());
+    assert!(db.raw_undefine("Target", UndefinePolicy::Ignore).is_err());
+    db.define::<Target>().unwrap();
+    assert_eq!(db.scan::<Target>().unwrap().count(), 0);
+}
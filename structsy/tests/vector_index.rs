@@ -0,0 +1,336 @@
+use structsy::{Filter, IndexQuery, Operators, RawAccess, SRes, Structsy, StructsyError, StructsyTx};
+use structsy_derive::{queries, Persistent};
+use tempfile::tempdir;
+
+fn structsy_inst(name: &str, test: fn(db: &Structsy) -> SRes<()>) {
+    let dir = tempdir().expect("can make a tempdir");
+    let file = dir.path().join(format!("{}.stry", name));
+
+    let db = Structsy::open(&file).expect("can open just create");
+    test(&db).expect("test is fine");
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Point {
+    #[index(vector(dim = 2, metric = "euclidean"))]
+    position: Vec<f32>,
+    #[index(mode = "cluster")]
+    color: String,
+    size: u32,
+}
+
+#[derive(Persistent, Debug, PartialEq, Clone)]
+struct Doc {
+    #[index(vector(dim = 3, metric = "cosine"))]
+    embedding: Option<Vec<f32>>,
+    title: String,
+}
+
+#[queries(Point)]
+trait PointQuery {
+    fn by_color(self, color: String) -> Self;
+    fn by_size(self, size: u32) -> Self;
+}
+
+fn point(x: u32, y: u32) -> Point {
+    Point {
+        position: vec![x as f32, y as f32],
+        color: if (x + y) % 2 == 0 { "red" } else { "blue" }.to_string(),
+        size: x % 3,
+    }
+}
+
+fn fill(db: &Structsy) -> SRes<Vec<Point>> {
+    db.define::<Point>()?;
+    let mut points = Vec::new();
+    let mut tx = db.begin()?;
+    for x in 0..15 {
+        for y in 0..15 {
+            let p = point(x, y);
+            tx.insert(&p)?;
+            points.push(p);
+        }
+    }
+    tx.commit()?;
+    Ok(points)
+}
+
+fn distance(p: &Point, target: &[f32]) -> f32 {
+    p.position
+        .iter()
+        .zip(target)
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// The distances of the exact nearest points, as expected from the index
+fn exact(points: &[Point], target: &[f32], k: usize, filter: impl Fn(&Point) -> bool) -> Vec<f32> {
+    let mut found = points
+        .iter()
+        .filter(|p| filter(p))
+        .map(|p| distance(p, target))
+        .collect::<Vec<_>>();
+    found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    found.truncate(k);
+    found
+}
+
+fn distances<I: Iterator<Item = (structsy::Ref<Point>, Point)>>(iter: I, target: &[f32]) -> Vec<f32> {
+    iter.map(|(_, p)| distance(&p, target)).collect()
+}
+
+#[test]
+fn vector_nearest() {
+    structsy_inst("vector_nearest", |db| {
+        let points = fill(db)?;
+        let target = [3.2, 7.1];
+        let found = distances(db.query::<Point>().nearest("position", &target, 3).fetch(), &target);
+        assert_eq!(found, exact(&points, &target, 3, |_| true));
+
+        let target = [14.6, 0.3];
+        let found = distances(db.query::<Point>().nearest("position", &target, 5).fetch(), &target);
+        assert_eq!(found, exact(&points, &target, 5, |_| true));
+
+        let all = db.query::<Point>().nearest("position", &target, 1000).fetch().count();
+        assert_eq!(all, points.len());
+        Ok(())
+    });
+}
+
+#[test]
+fn vector_nearest_filtered() {
+    structsy_inst("vector_nearest_filtered", |db| {
+        let points = fill(db)?;
+        let target = [6.4, 6.7];
+        // Not indexed condition, the candidates of the vector index are filtered
+        let found = db.query::<Point>().by_size(2).nearest("position", &target, 4).fetch();
+        assert_eq!(distances(found, &target), exact(&points, &target, 4, |p| p.size == 2));
+        // Indexed condition, the filtered records are ranked by the exact distance
+        let found = db
+            .query::<Point>()
+            .by_color("blue".to_string())
+            .nearest("position", &target, 4)
+            .fetch();
+        let found = distances(found, &target);
+        assert_eq!(found, exact(&points, &target, 4, |p| p.color == "blue"));
+        let count = db
+            .query::<Point>()
+            .by_color("green".to_string())
+            .nearest("position", &target, 4)
+            .fetch()
+            .count();
+        assert_eq!(count, 0);
+        Ok(())
+    });
+}
+
+#[test]
+fn vector_nearest_nested() {
+    structsy_inst("vector_nearest_nested", |db| {
+        let points = fill(db)?;
+        let target = [6.4, 6.7];
+        // The nearest of a group in and limits the whole query
+        let found = db
+            .query::<Point>()
+            .by_size(2)
+            .and(|f| f.nearest("position", &target, 4))
+            .fetch();
+        assert_eq!(distances(found, &target), exact(&points, &target, 4, |p| p.size == 2));
+        let found = db
+            .query::<Point>()
+            .and(|f| f.by_color("red".to_string()).nearest("position", &target, 3))
+            .fetch();
+        let found = distances(found, &target);
+        assert_eq!(found, exact(&points, &target, 3, |p| p.color == "red"));
+        Ok(())
+    });
+}
+
+fn type_error<T>(result: SRes<T>) -> String {
+    match result {
+        Err(StructsyError::TypeError(message)) => message,
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("the query is expected to fail"),
+    }
+}
+
+#[test]
+fn vector_nearest_invalid() {
+    structsy_inst("vector_nearest_invalid", |db| {
+        fill(db)?;
+        let target = [6.4, 6.7];
+        let query = db.query::<Point>().or(|f| f.nearest("position", &target, 4));
+        assert!(type_error(query.try_fetch()).contains("is not supported inside 'or'"));
+        let query = db.query::<Point>().not(|f| f.nearest("position", &target, 4));
+        assert!(type_error(query.try_fetch()).contains("is not supported inside 'not'"));
+        let query = db
+            .query::<Point>()
+            .nearest("position", &target, 4)
+            .and(|f| f.nearest("position", &target, 2));
+        assert!(type_error(query.try_fetch()).contains("conflicts with the nearest"));
+        let query = db.query::<Point>().nearest("position", &[1.0, 2.0, 3.0], 4);
+        assert!(type_error(query.try_fetch()).contains("has dimension 2 not 3"));
+        let query = db.query::<Point>().nearest("color", &target, 4);
+        assert!(type_error(query.try_fetch()).contains("does not have a vector index"));
+        // The refs of bulk operations fail in the same way
+        let mut tx = db.begin()?;
+        let filter = Filter::<Point>::new().or(|f| f.nearest("position", &target, 4));
+        assert!(type_error(tx.delete_where(filter)).contains("is not supported inside 'or'"));
+        Ok(())
+    });
+}
+
+#[test]
+fn vector_nearest_bulk() {
+    structsy_inst("vector_nearest_bulk", |db| {
+        fill(db)?;
+        let target = vec![7.0, 7.0];
+        let mut tx = db.begin()?;
+        // Only the k nearest instances are changed
+        let filter = Filter::<Point>::new().nearest("position", &target, 5);
+        assert_eq!(tx.update_where(filter, |p| p.size = 10)?, 5);
+        let filter = Filter::<Point>::new().nearest("position", &target, 4);
+        assert_eq!(tx.delete_where(filter)?, 4);
+        tx.commit()?;
+        assert_eq!(db.query::<Point>().fetch().count(), 221);
+        assert_eq!(db.query::<Point>().by_size(10).fetch().count(), 1);
+        Ok(())
+    });
+}
+
+#[test]
+fn vector_index_remove_most() {
+    structsy_inst("vector_index_remove_most", |db| {
+        let mut points = fill(db)?;
+        let ids = db.query::<Point>().fetch().map(|(id, _)| id).collect::<Vec<_>>();
+        // The entry point is removed many times, the graph keeps reachable all the other nodes
+        let mut tx = db.begin()?;
+        for id in &ids[..205] {
+            tx.delete(id)?;
+        }
+        tx.commit()?;
+        points.drain(..205);
+        let origin = [0.0, 0.0];
+        let found = distances(db.query::<Point>().nearest("position", &origin, 20).fetch(), &origin);
+        assert_eq!(found, exact(&points, &origin, 20, |_| true));
+        Ok(())
+    });
+}
+
+#[test]
+fn vector_index_maintenance() {
+    structsy_inst("vector_index_maintenance", |db| {
+        let mut points = fill(db)?;
+        let target = [20.0, 20.0];
+        let nearest = db
+            .query::<Point>()
+            .nearest("position", &target, 20)
+            .fetch()
+            .collect::<Vec<_>>();
+        let mut tx = db.begin()?;
+        tx.update(&nearest[0].0, &point(30, 30))?;
+        for (id, _) in &nearest[1..] {
+            tx.delete(id)?;
+        }
+        tx.commit()?;
+        points.retain(|p| !nearest.iter().any(|(_, n)| n.position == p.position));
+        points.push(point(30, 30));
+
+        let found = distances(db.query::<Point>().nearest("position", &target, 3).fetch(), &target);
+        assert_eq!(found, exact(&points, &target, 3, |_| true));
+        let origin = [0.0, 0.0];
+        let found = distances(db.query::<Point>().nearest("position", &origin, 3).fetch(), &origin);
+        assert_eq!(found, exact(&points, &origin, 3, |_| true));
+
+        let (_, far) = db
+            .query::<Point>()
+            .nearest("position", &[39.0, 39.0], 1)
+            .fetch()
+            .next()
+            .unwrap();
+        assert_eq!(far.position, vec![30.0, 30.0]);
+        // The query in a transaction finds also the not committed changes
+        let mut tx = db.begin()?;
+        tx.insert(&point(40, 40))?;
+        let (_, found) = tx
+            .query::<Point>()
+            .nearest("position", &[39.0, 39.0], 1)
+            .fetch()
+            .next()
+            .unwrap();
+        assert_eq!(found.position, vec![40.0, 40.0]);
+        tx.commit()?;
+
+        let ids = db.query::<Point>().fetch().map(|(id, _)| id).collect::<Vec<_>>();
+        let mut tx = db.begin()?;
+        for id in &ids {
+            tx.delete(id)?;
+        }
+        tx.commit()?;
+        assert_eq!(db.query::<Point>().nearest("position", &target, 3).fetch().count(), 0);
+        Ok(())
+    });
+}
+
+#[test]
+fn vector_index_cosine_optional() {
+    structsy_inst("vector_index_cosine_optional", |db| {
+        db.define::<Doc>()?;
+        let mut tx = db.begin()?;
+        tx.insert(&Doc {
+            embedding: Some(vec![1.0, 0.0, 0.0]),
+            title: "x".to_string(),
+        })?;
+        tx.insert(&Doc {
+            embedding: Some(vec![0.0, 2.0, 0.0]),
+            title: "y".to_string(),
+        })?;
+        tx.insert(&Doc {
+            embedding: None,
+            title: "none".to_string(),
+        })?;
+        tx.insert(&Doc {
+            embedding: Some(vec![0.0, 0.5, 0.5]),
+            title: "yz".to_string(),
+        })?;
+        tx.commit()?;
+        let titles = db
+            .query::<Doc>()
+            .nearest("embedding", &[0.0, 10.0, 1.0], 5)
+            .fetch()
+            .map(|(_, d)| d.title)
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["y", "yz", "x"]);
+
+        let mut tx = db.begin()?;
+        let wrong = tx.insert(&Doc {
+            embedding: Some(vec![1.0, 0.0]),
+            title: "wrong".to_string(),
+        });
+        assert!(wrong.is_err());
+        Ok(())
+    });
+}
+
+#[test]
+fn vector_index_raw() {
+    structsy_inst("vector_index_raw", |db| {
+        let points = fill(db)?;
+        let desc = db.list_defined()?.find(|d| d.get_name() == "Point").unwrap();
+
+        let dir = tempdir().expect("can make a tempdir");
+        let other = Structsy::open(dir.path().join("other.stry"))?;
+        other.raw_define(desc)?;
+        other.define::<Point>()?;
+        let mut raw_tx = other.raw_begin()?;
+        for (_, record) in db.raw_scan("Point")? {
+            raw_tx.raw_insert(&record)?;
+        }
+        raw_tx.prepare()?.commit()?;
+        let target = [9.9, 1.2];
+        let found = distances(other.query::<Point>().nearest("position", &target, 4).fetch(), &target);
+        assert_eq!(found, exact(&points, &target, 4, |_| true));
+        Ok(())
+    });
+}